@@ -0,0 +1,588 @@
+//! Automating the parameters of a **Node** over time.
+
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::param::Param;
+use crate::{Frame, Panning, Sample, Volume};
+use std::fmt;
+
+/// A function mapping the time in seconds to a parameter value.
+///
+/// The function must be `Send` so that the **Automation** may be moved to the audio thread along
+/// with the rest of the **Graph**. It is only ever called from the thread that is rendering the
+/// **Graph**, so it need not be `Sync`.
+pub type AutomationFn = Box<dyn FnMut(f64) -> f32 + Send>;
+
+/// Selects the parameter of the wrapped **Node** that is driven by the automation, e.g.
+/// `|osc: &mut Oscillator, hz| osc.set_frequency(hz as f64)`.
+pub type ParamSetter<N> = fn(&mut N, f32);
+
+/// A **Node** that drives a parameter of the wrapped **Node** with a function of time.
+///
+/// The function is evaluated once per buffer, at the time of the buffer's first frame, and the
+/// result is passed to the parameter setter before delegating to the wrapped node's
+/// `audio_requested`. Time starts at `0.0` and advances by `frames / sample_hz` with each buffer.
+///
+/// All other **Node** methods are delegated to the wrapped node.
+pub struct Automation<N> {
+    node: N,
+    function: AutomationFn,
+    setter: ParamSetter<N>,
+    /// The time of the next buffer's first frame in seconds.
+    time_secs: f64,
+}
+
+impl<N> Automation<N> {
+    /// Construct an **Automation** driving the parameter of `node` selected by `setter` with the
+    /// given function of time.
+    pub fn new<A>(node: N, setter: ParamSetter<N>, function: A) -> Self
+    where
+        A: FnMut(f64) -> f32 + Send + 'static,
+    {
+        Automation {
+            node,
+            function: Box::new(function),
+            setter,
+            time_secs: 0.0,
+        }
+    }
+
+    /// The time at which the function will next be evaluated, in seconds.
+    pub fn time_secs(&self) -> f64 {
+        self.time_secs
+    }
+
+    /// Move the automation to the given time in seconds.
+    pub fn set_time_secs(&mut self, secs: f64) {
+        self.time_secs = secs;
+    }
+
+    /// Replace the function of time driving the parameter.
+    pub fn set_function<A>(&mut self, function: A)
+    where
+        A: FnMut(f64) -> f32 + Send + 'static,
+    {
+        self.function = Box::new(function);
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the **Automation**, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<N> fmt::Debug for Automation<N>
+where
+    N: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Automation")
+            .field("node", &self.node)
+            .field("time_secs", &self.time_secs)
+            .finish()
+    }
+}
+
+impl<F, N> Node<F> for Automation<N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let value = (self.function)(self.time_secs);
+        (self.setter)(&mut self.node, value);
+        self.node.audio_requested(buffer, sample_hz);
+        if sample_hz > 0.0 {
+            self.time_secs += buffer.len() as f64 / sample_hz;
+        }
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.node.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.node.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        self.node.vol_per_channel()
+    }
+
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        self.node.vols_per_channel(vols)
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    /// Prepares the wrapped node and returns the automation to time `0.0`.
+    fn prepare(&mut self, settings: Settings) {
+        self.time_secs = 0.0;
+        self.node.prepare(settings);
+    }
+
+    /// Resets the wrapped node and returns the automation to time `0.0`.
+    fn reset(&mut self) {
+        self.time_secs = 0.0;
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+}
+
+/// A **Node** that glides the pan position of the wrapped **Node** smoothly between targets,
+/// frame by frame.
+///
+/// The pan is held within a [**Param**](../param/struct.Param.html), so it follows that
+/// **Param**'s smoothing curve and time, which may be configured via `pan_param_mut`. While the pan
+/// is gliding, the pan of every frame is reported via `Node::pan_per_frame`, so the `Graph` applies
+/// it per frame rather than stepping once per buffer. Once the target is reached, the `Graph`
+/// returns to its cheaper per-buffer path.
+///
+/// The wrapped node's own `pan` is replaced. All other **Node** methods are delegated to the
+/// wrapped node.
+#[derive(Clone, Debug)]
+pub struct SmoothedPan<N> {
+    node: N,
+    pan: Param,
+    /// The pan of each frame of the last buffer, if the pan was gliding.
+    pans: Vec<Panning>,
+    gliding: bool,
+}
+
+impl<N> SmoothedPan<N> {
+    /// Construct a centered **SmoothedPan** that glides between pan targets over the given number
+    /// of frames.
+    pub fn new(node: N, smoothing_samples: usize) -> Self {
+        let mut pan = Param::new(0.0);
+        pan.set_smoothing_samples(smoothing_samples);
+        SmoothedPan {
+            node,
+            pan,
+            pans: Vec::new(),
+            gliding: false,
+        }
+    }
+
+    /// The pan position towards which the wrapped node is gliding.
+    pub fn pan(&self) -> Panning {
+        self.pan.target()
+    }
+
+    /// Begin gliding towards the given pan position, clamped to `-1.0 ... 1.0`.
+    pub fn set_pan(&mut self, pan: Panning) {
+        self.pan.set_target(pan.clamp(-1.0, 1.0));
+    }
+
+    /// The **Param** holding the pan position.
+    pub fn pan_param(&self) -> &Param {
+        &self.pan
+    }
+
+    /// A mutable reference to the **Param** holding the pan position, e.g. for changing its
+    /// smoothing curve or time.
+    pub fn pan_param_mut(&mut self) -> &mut Param {
+        &mut self.pan
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the **SmoothedPan**, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<F, N> Node<F> for SmoothedPan<N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.node.audio_requested(buffer, sample_hz);
+        self.gliding = self.pan.is_smoothing();
+        if self.gliding {
+            let pan = &mut self.pan;
+            self.pans.clear();
+            self.pans.extend(buffer.iter().map(|_| pan.next_value()));
+        }
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.pan.value()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        if self.gliding {
+            Some(&self.pans)
+        } else {
+            None
+        }
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    /// Prepares the wrapped node and jumps straight to the target pan position.
+    fn prepare(&mut self, settings: Settings) {
+        let target = self.pan.target();
+        self.pan.set_value(target);
+        self.gliding = false;
+        self.node.prepare(settings);
+    }
+
+    /// Resets the wrapped node and jumps straight to the target pan position.
+    fn reset(&mut self) {
+        let target = self.pan.target();
+        self.pan.set_value(target);
+        self.gliding = false;
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A source rendering its `value` and recording each value rendered.
+    #[derive(Debug, Default)]
+    struct Recorder {
+        value: f32,
+        rendered: Vec<f32>,
+    }
+
+    impl Node<[f32; 1]> for Recorder {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            self.rendered.push(self.value);
+            for frame in buffer.iter_mut() {
+                *frame = [self.value];
+            }
+        }
+    }
+
+    #[test]
+    fn a_linear_ramp_is_evaluated_once_per_buffer() {
+        let setter: ParamSetter<Recorder> = |node, value| node.value = value;
+        let mut automation =
+            Automation::new(Recorder::default(), setter, |secs| secs as f32 * 10.0);
+        let mut buffer = [[0.0f32]; 100];
+        for _ in 0..4 {
+            automation.audio_requested(&mut buffer, 1_000.0);
+        }
+        // Each buffer is a tenth of a second long.
+        let rendered = &automation.node().rendered;
+        assert_eq!(rendered.len(), 4);
+        for (i, &value) in rendered.iter().enumerate() {
+            assert!((value - i as f32).abs() < 1e-5, "{} != {}", value, i);
+        }
+        assert!(buffer.iter().all(|frame| (frame[0] - 3.0).abs() < 1e-5));
+        assert!((automation.time_secs() - 0.4).abs() < 1e-12);
+    }
+
+    #[test]
+    fn the_automation_time_may_be_moved() {
+        let setter: ParamSetter<Recorder> = |node, value| node.value = value;
+        let mut automation = Automation::new(Recorder::default(), setter, |secs| secs as f32);
+        automation.set_time_secs(2.0);
+        let mut buffer = [[0.0f32]; 10];
+        automation.audio_requested(&mut buffer, 1_000.0);
+        assert_eq!(automation.node().rendered, [2.0]);
+        automation.set_function(|secs| -(secs as f32));
+        automation.audio_requested(&mut buffer, 1_000.0);
+        assert!((automation.node().rendered[1] + 2.01).abs() < 1e-5);
+    }
+
+    /// A stereo source at full scale.
+    struct Stereo;
+
+    impl Node<[f32; 2]> for Stereo {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 2]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [1.0; 2];
+            }
+        }
+
+        fn pan_law(&self) -> PanLaw {
+            PanLaw::ConstantPower
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn a_pan_sweep_shifts_energy_monotonically_from_left_to_right() {
+        let mut pan = SmoothedPan::new(Stereo, 1_000);
+        pan.pan_param_mut().set_value(-1.0);
+        pan.set_pan(1.0);
+        let mut graph = crate::Graph::new();
+        let idx = graph.add_node(pan);
+        graph.set_master(Some(idx));
+        let mut buffer = [[0.0f32; 2]; 1_000];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer
+            .windows(2)
+            .all(|w| w[1][0] < w[0][0] && w[1][1] > w[0][1]));
+        assert!(buffer[0][0] > 0.99 && buffer[0][1] < 0.01);
+        assert_eq!(
+            buffer[999],
+            [PanLaw::ConstantPower.vol_per_channel(1.0, 1.0)[0], 1.0]
+        );
+        // Constant power throughout.
+        assert!(buffer
+            .iter()
+            .all(|frame| (frame[0] * frame[0] + frame[1] * frame[1] - 1.0).abs() < 1e-5));
+
+        // Once the target is reached, the constant pan applies to the whole buffer.
+        let mut buffer = [[0.0f32; 2]; 64];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(Node::<[f32; 2]>::pan_per_frame(graph.node(idx).unwrap()).is_none());
+        assert!(buffer.iter().all(|&frame| frame == buffer[0]));
+        assert!(buffer[0][0].abs() < 1e-6);
+    }
+}
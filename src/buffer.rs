@@ -0,0 +1,856 @@
+//! Re-usable working buffers for use within the audio thread, conversion between the
+//! interleaved buffers of frames used throughout the crate and planar (one buffer per channel)
+//! layouts, and reading delay lines between their stored frames.
+
+use crate::delay::{DelayInterpolation, DelayLine};
+use crate::node::{Node, Settings};
+use crate::util;
+use sample::{Frame, Sample};
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "simd")]
+use std::simd::Simd;
+
+/// A pool of working buffers, allowing custom nodes to use temporary buffers without allocating
+/// upon every call to `audio_requested`.
+///
+/// Buffers are acquired with `acquire` and returned to the pool automatically when the
+/// **PooledBuffer** is dropped. Memory is re-used between acquisitions, so once the pool holds a
+/// buffer of sufficient size, acquiring performs no heap allocation. Use `with_capacity` to
+/// allocate up front.
+///
+/// Every acquired buffer is filled with `F::equilibrium()`, ready to be summed onto.
+#[derive(Debug, Default)]
+pub struct BufferPool<F> {
+    free: RefCell<Vec<Vec<F>>>,
+}
+
+/// A buffer acquired from a **BufferPool**, dereferencing to a slice of frames.
+///
+/// The buffer is returned to the pool when dropped.
+#[derive(Debug)]
+pub struct PooledBuffer<'a, F> {
+    buffer: Vec<F>,
+    pool: &'a BufferPool<F>,
+}
+
+impl<F> BufferPool<F>
+where
+    F: Frame,
+{
+    /// Construct an empty **BufferPool**.
+    pub fn new() -> Self {
+        BufferPool {
+            free: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Construct a **BufferPool** holding the given number of buffers, each with capacity for the
+    /// given number of frames.
+    pub fn with_capacity(buffers: usize, frames: usize) -> Self {
+        let free = (0..buffers).map(|_| Vec::with_capacity(frames)).collect();
+        BufferPool {
+            free: RefCell::new(free),
+        }
+    }
+
+    /// The number of buffers within the pool that are not currently acquired.
+    pub fn available(&self) -> usize {
+        self.free.borrow().len()
+    }
+
+    /// Acquire a buffer of `frames` frames, each set to `F::equilibrium()`.
+    ///
+    /// A pooled buffer with sufficient capacity is used if there is one. Otherwise, a pooled
+    /// buffer is grown to the required size, or a new buffer is allocated if the pool is empty.
+    pub fn acquire(&self, frames: usize) -> PooledBuffer<'_, F> {
+        let mut free = self.free.borrow_mut();
+        let mut buffer = match free.iter().position(|b| b.capacity() >= frames) {
+            Some(idx) => free.swap_remove(idx),
+            None => free.pop().unwrap_or_default(),
+        };
+        buffer.clear();
+        buffer.resize(frames, F::equilibrium());
+        PooledBuffer { buffer, pool: self }
+    }
+}
+
+impl<'a, F> Deref for PooledBuffer<'a, F> {
+    type Target = [F];
+    #[inline]
+    fn deref(&self) -> &[F] {
+        &self.buffer
+    }
+}
+
+impl<'a, F> DerefMut for PooledBuffer<'a, F> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [F] {
+        &mut self.buffer
+    }
+}
+
+impl<'a, F> Drop for PooledBuffer<'a, F> {
+    fn drop(&mut self) {
+        let buffer = ::std::mem::take(&mut self.buffer);
+        self.pool.free.borrow_mut().push(buffer);
+    }
+}
+
+/// The number of samples mixed at once by `mix_into_f32`, chosen to fill the widest vector
+/// registers commonly available (e.g. eight `f32` lanes of AVX).
+const MIX_LANES: usize = 8;
+
+/// The greatest number of samples of `f32` frames gathered into a block by `mix_into`, so that
+/// they may be mixed `MIX_LANES` at a time whatever the number of channels.
+const MIX_BLOCK: usize = 64;
+
+/// Mix the frames of `src` scaled by `gain` into the frames of `dst`.
+///
+/// Each sample is summed as by the default `Node::combine`, so the sum is exact for every sample
+/// format and integer formats saturate at full scale rather than overflowing. A `gain` of `1.0`
+/// leaves `src` unscaled, so the result is identical to summing the buffers sample by sample.
+/// Frames beyond the end of the shorter buffer are left as they are.
+///
+/// Frames of `f32` samples, which never saturate, are instead mixed in blocks via
+/// `mix_into_f32`, with an identical result.
+pub fn mix_into<F>(dst: &mut [F], src: &[F], gain: f32)
+where
+    F: Frame,
+{
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+    if is_f32::<F::Sample>() && F::n_channels() <= MIX_BLOCK {
+        let channels = F::n_channels();
+        let frames_per_block = MIX_BLOCK / channels;
+        let mut dst_block = [0.0; MIX_BLOCK];
+        let mut src_block = [0.0; MIX_BLOCK];
+        for (dst, src) in dst
+            .chunks_mut(frames_per_block)
+            .zip(src.chunks(frames_per_block))
+        {
+            let n = dst.len() * channels;
+            gather_f32(dst, &mut dst_block[..n]);
+            gather_f32(src, &mut src_block[..n]);
+            mix_into_f32(&mut dst_block[..n], &src_block[..n], gain);
+            scatter_f32(&dst_block[..n], dst);
+        }
+    } else if gain == 1.0 {
+        sample::slice::zip_map_in_place(dst, src, |acc, frame| {
+            acc.zip_map(frame, util::add_samples)
+        });
+    } else {
+        let gain = <F::Sample as Sample>::Float::from_sample(gain);
+        sample::slice::zip_map_in_place(dst, src, |acc, frame| {
+            acc.zip_map(frame, |a, s| util::add_samples(a, s.mul_amp(gain)))
+        });
+    }
+}
+
+/// Mix the samples of `src` scaled by `gain` into the samples of `dst`, as `mix_into` does for
+/// frames of `f32`.
+///
+/// The samples are mixed `MIX_LANES` at a time with no dependency between lanes, which the
+/// compiler turns into vector instructions where the target supports them. With the `simd`
+/// feature enabled, each group of lanes is mixed explicitly via `std::simd`. The result is
+/// identical to that of `mix_into` either way. Samples beyond the end of the shorter buffer are
+/// left as they are.
+pub fn mix_into_f32(dst: &mut [f32], src: &[f32], gain: f32) {
+    let len = dst.len().min(src.len());
+    let (dst, src) = (&mut dst[..len], &src[..len]);
+    let mut dst_lanes = dst.chunks_exact_mut(MIX_LANES);
+    let mut src_lanes = src.chunks_exact(MIX_LANES);
+    for (d, s) in (&mut dst_lanes).zip(&mut src_lanes) {
+        mix_lanes(d, s, gain);
+    }
+    let rest = dst_lanes.into_remainder().iter_mut();
+    for (d, &s) in rest.zip(src_lanes.remainder()) {
+        *d += s * gain;
+    }
+}
+
+/// Scale every frame of `buffer` by `gain`.
+///
+/// Frames of `f32` samples are scaled in blocks via `apply_gain_f32`, with a result identical to
+/// scaling each sample in turn.
+pub fn apply_gain<F>(buffer: &mut [F], gain: f32)
+where
+    F: Frame,
+{
+    if is_f32::<F::Sample>() && F::n_channels() <= MIX_BLOCK {
+        let channels = F::n_channels();
+        let mut block = [0.0; MIX_BLOCK];
+        for frames in buffer.chunks_mut(MIX_BLOCK / channels) {
+            let n = frames.len() * channels;
+            gather_f32(frames, &mut block[..n]);
+            apply_gain_f32(&mut block[..n], gain);
+            scatter_f32(&block[..n], frames);
+        }
+    } else {
+        let gain = <F::Sample as Sample>::Float::from_sample(gain);
+        sample::slice::map_in_place(buffer, |frame| frame.map(|s| s.mul_amp(gain)));
+    }
+}
+
+/// Scale every sample of `buffer` by `gain`.
+///
+/// The result is identical to scaling each sample in turn, as the **Gain** node does. Like
+/// `mix_into_f32`, the samples are scaled `MIX_LANES` at a time, explicitly via `std::simd` when
+/// the `simd` feature is enabled.
+pub fn apply_gain_f32(buffer: &mut [f32], gain: f32) {
+    let mut lanes = buffer.chunks_exact_mut(MIX_LANES);
+    for x in &mut lanes {
+        gain_lanes(x, gain);
+    }
+    for x in lanes.into_remainder() {
+        *x *= gain;
+    }
+}
+
+/// Mix `MIX_LANES` samples of `src` scaled by `gain` into `dst`.
+#[cfg(not(feature = "simd"))]
+#[inline]
+fn mix_lanes(dst: &mut [f32], src: &[f32], gain: f32) {
+    for (d, &s) in dst.iter_mut().zip(src) {
+        *d += s * gain;
+    }
+}
+
+/// Mix `MIX_LANES` samples of `src` scaled by `gain` into `dst`.
+#[cfg(feature = "simd")]
+#[inline]
+fn mix_lanes(dst: &mut [f32], src: &[f32], gain: f32) {
+    let d = Simd::<f32, MIX_LANES>::from_slice(dst);
+    let s = Simd::<f32, MIX_LANES>::from_slice(src);
+    (d + s * Simd::splat(gain)).copy_to_slice(dst);
+}
+
+/// Scale `MIX_LANES` samples of `buffer` by `gain`.
+#[cfg(not(feature = "simd"))]
+#[inline]
+fn gain_lanes(buffer: &mut [f32], gain: f32) {
+    for x in buffer.iter_mut() {
+        *x *= gain;
+    }
+}
+
+/// Scale `MIX_LANES` samples of `buffer` by `gain`.
+#[cfg(feature = "simd")]
+#[inline]
+fn gain_lanes(buffer: &mut [f32], gain: f32) {
+    let x = Simd::<f32, MIX_LANES>::from_slice(buffer);
+    (x * Simd::splat(gain)).copy_to_slice(buffer);
+}
+
+/// Whether samples of type `S` are `f32`, i.e. the size of an `f32` and able to hold the
+/// smallest normal `f32` exactly, which no integer format can.
+#[inline]
+fn is_f32<S>() -> bool
+where
+    S: Sample,
+{
+    let tiny = f32::MIN_POSITIVE;
+    let sample: S = <S::Float as Sample>::from_sample(tiny).to_sample();
+    ::std::mem::size_of::<S>() == ::std::mem::size_of::<f32>()
+        && sample.to_float_sample().to_sample::<f32>() == tiny
+}
+
+/// Write the samples of `frames` into `samples`, one frame after another. For `f32` frames, the
+/// conversions leave each sample as it is.
+#[inline]
+fn gather_f32<F>(frames: &[F], samples: &mut [f32])
+where
+    F: Frame,
+{
+    for (frame, out) in frames.iter().zip(samples.chunks_mut(F::n_channels())) {
+        for (x, s) in out.iter_mut().zip(frame.channels()) {
+            *x = s.to_float_sample().to_sample();
+        }
+    }
+}
+
+/// Write `samples`, as gathered by `gather_f32`, back into `frames`.
+#[inline]
+fn scatter_f32<F>(samples: &[f32], frames: &mut [F])
+where
+    F: Frame,
+{
+    for (frame, samples) in frames.iter_mut().zip(samples.chunks(F::n_channels())) {
+        *frame = F::from_fn(|channel| {
+            <F::Sample as Sample>::Float::from_sample(samples[channel]).to_sample()
+        });
+    }
+}
+
+/// Replace every NaN or infinite sample of `buffer` with silence, returning the number of samples
+/// replaced.
+///
+/// A single non-finite sample, e.g. from an unstable filter, would otherwise poison every sum
+/// downstream of it. Finite samples are left untouched, and integer samples are always finite.
+pub fn sanitize<F>(buffer: &mut [F]) -> usize
+where
+    F: Frame,
+{
+    let mut replaced = 0;
+    sample::slice::map_in_place(buffer, |frame| {
+        frame.map(|s| {
+            if util::to_f64(s).is_finite() {
+                s
+            } else {
+                replaced += 1;
+                F::Sample::equilibrium()
+            }
+        })
+    });
+    replaced
+}
+
+/// Split a buffer of interleaved frames into one buffer of samples per channel.
+pub fn to_planar<F>(frames: &[F]) -> Vec<Vec<F::Sample>>
+where
+    F: Frame,
+{
+    let mut planar = vec![Vec::with_capacity(frames.len()); F::n_channels()];
+    for frame in frames {
+        for (channel, s) in planar.iter_mut().zip(frame.channels()) {
+            channel.push(s);
+        }
+    }
+    planar
+}
+
+/// Interleave one buffer of samples per channel into a buffer of frames.
+///
+/// The number of frames is the length of the longest channel. Channels that are missing or
+/// shorter than the longest are padded with equilibrium.
+pub fn to_interleaved<F>(planar: &[Vec<F::Sample>]) -> Vec<F>
+where
+    F: Frame,
+{
+    let len = planar.iter().map(Vec::len).max().unwrap_or(0);
+    (0..len).map(|i| planar_frame(planar, i)).collect()
+}
+
+/// The frame at the given index of the planar buffers, padded with equilibrium.
+fn planar_frame<F>(planar: &[Vec<F::Sample>], idx: usize) -> F
+where
+    F: Frame,
+{
+    F::from_fn(|ch| {
+        planar
+            .get(ch)
+            .and_then(|channel| channel.get(idx))
+            .cloned()
+            .unwrap_or_else(F::Sample::equilibrium)
+    })
+}
+
+/// A processor that works upon planar buffers, holding one buffer of samples per channel.
+///
+/// Implemented for any `FnMut(&mut [Vec<S>], f64)`, where the `f64` is the sample rate in hertz.
+pub trait PlanarProcessor<S> {
+    /// Process the given buffers in place, one per channel, each holding the same number of
+    /// samples.
+    fn process(&mut self, channels: &mut [Vec<S>], sample_hz: f64);
+}
+
+impl<S, P> PlanarProcessor<S> for P
+where
+    P: FnMut(&mut [Vec<S>], f64),
+{
+    fn process(&mut self, channels: &mut [Vec<S>], sample_hz: f64) {
+        (*self)(channels, sample_hz)
+    }
+}
+
+/// A **Node** that hosts a **PlanarProcessor** within a chain of interleaved frames.
+///
+/// Each buffer is split into one buffer per channel, processed, then interleaved back into the
+/// frames. The planar buffers are re-used between calls, so once they have grown to the size of
+/// the largest buffer rendered, no heap allocation is performed. Samples that the processor
+/// removes from the end of a channel are replaced with equilibrium.
+#[derive(Clone, Debug)]
+pub struct PlanarAdapter<S, P> {
+    processor: P,
+    planar: Vec<Vec<S>>,
+}
+
+impl<S, P> PlanarAdapter<S, P>
+where
+    P: PlanarProcessor<S>,
+{
+    /// Wrap the given planar processor.
+    pub fn new(processor: P) -> Self {
+        PlanarAdapter {
+            processor,
+            planar: Vec::new(),
+        }
+    }
+
+    /// A reference to the wrapped processor.
+    pub fn processor(&self) -> &P {
+        &self.processor
+    }
+
+    /// A mutable reference to the wrapped processor.
+    pub fn processor_mut(&mut self) -> &mut P {
+        &mut self.processor
+    }
+
+    /// Consume the adapter, returning the wrapped processor.
+    pub fn into_processor(self) -> P {
+        self.processor
+    }
+}
+
+impl<F, P> Node<F> for PlanarAdapter<F::Sample, P>
+where
+    F: Frame,
+    P: PlanarProcessor<F::Sample>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.planar.resize(F::n_channels(), Vec::new());
+        for channel in self.planar.iter_mut() {
+            channel.clear();
+        }
+        for frame in buffer.iter() {
+            for (channel, s) in self.planar.iter_mut().zip(frame.channels()) {
+                channel.push(s);
+            }
+        }
+        self.processor.process(&mut self.planar, sample_hz);
+        for (i, frame) in buffer.iter_mut().enumerate() {
+            *frame = planar_frame(&self.planar, i);
+        }
+    }
+
+    /// Reserves `max_frames` samples for each channel.
+    fn prepare(&mut self, settings: Settings) {
+        self.planar.resize(F::n_channels(), Vec::new());
+        for channel in self.planar.iter_mut() {
+            channel.clear();
+            channel.reserve(settings.max_frames);
+        }
+    }
+}
+
+/// Reads a **DelayLine** at fractional delays using a **DelayInterpolation**, shared by every node
+/// whose read position need not fall upon a whole frame.
+///
+/// The reader holds the per-channel history required by `AllPass` interpolation, so each moving
+/// read position (e.g. each voice of a chorus) requires its own reader.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct FractionalReader {
+    interpolation: DelayInterpolation,
+    /// The previous output of the all-pass interpolator for each channel.
+    allpass: Vec<f64>,
+}
+
+impl FractionalReader {
+    /// The method used to read between stored frames.
+    pub(crate) fn interpolation(&self) -> DelayInterpolation {
+        self.interpolation
+    }
+
+    /// Set the method used to read between stored frames, clearing any all-pass history.
+    pub(crate) fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        if self.interpolation != interpolation {
+            self.interpolation = interpolation;
+            self.reset();
+        }
+    }
+
+    /// Size the all-pass history for the given number of channels. Must be called before `read`.
+    pub(crate) fn prepare(&mut self, channels: usize) {
+        self.allpass.resize(channels, 0.0);
+    }
+
+    /// Read the given channel of the line at the given delay in frames, where `1.0` is the most
+    /// recently written frame.
+    ///
+    /// The delay is clamped to at least `1.0`, and the line must store at least `delay + 1`
+    /// frames. **Cubic** interpolation reads the frames either side of these where the line holds
+    /// them, repeating the nearest frame otherwise.
+    #[inline]
+    pub(crate) fn read(&mut self, line: &DelayLine, channel: usize, delay: f64) -> f64 {
+        let delay = delay.max(1.0);
+        let mut whole = delay.floor() as usize;
+        let mut frac = delay - whole as f64;
+        match self.interpolation {
+            DelayInterpolation::NearestNeighbor => line.past(channel, delay.round() as usize),
+            DelayInterpolation::Linear => {
+                let a = line.past(channel, whole);
+                if frac == 0.0 {
+                    a
+                } else {
+                    a + (line.past(channel, whole + 1) - a) * frac
+                }
+            }
+            DelayInterpolation::Cubic => {
+                let p1 = line.past(channel, whole);
+                if frac == 0.0 {
+                    return p1;
+                }
+                let p2 = line.past(channel, whole + 1);
+                let p0 = if whole > 1 {
+                    line.past(channel, whole - 1)
+                } else {
+                    p1
+                };
+                let p3 = if whole + 2 <= line.len() {
+                    line.past(channel, whole + 2)
+                } else {
+                    p2
+                };
+                // Catmull-Rom through the four frames nearest the read position.
+                let t = frac;
+                p1 + 0.5
+                    * t
+                    * (p2 - p0
+                        + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3
+                            + t * (3.0 * (p1 - p2) + p3 - p0)))
+            }
+            DelayInterpolation::AllPass => {
+                // Keep the fraction within `0.5 .. 1.5` where possible, as the filter rings near
+                // Nyquist for fractions close to zero.
+                if frac < 0.5 && whole > 1 {
+                    whole -= 1;
+                    frac += 1.0;
+                }
+                let eta = (1.0 - frac) / (1.0 + frac);
+                let a = line.past(channel, whole);
+                let b = line.past(channel, whole + 1);
+                let y = eta * a + b - eta * self.allpass[channel];
+                self.allpass[channel] = util::flush_denormal(y);
+                y
+            }
+        }
+    }
+
+    /// Clear the all-pass history.
+    pub(crate) fn reset(&mut self) {
+        for y in self.allpass.iter_mut() {
+            *y = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::RngSource;
+
+    /// `len` frames of noise, scaled so that sums of two reach beyond full scale.
+    fn noise<F>(len: usize, seed: u64) -> Vec<F>
+    where
+        F: Frame,
+    {
+        let mut rng = RngSource::new(seed);
+        (0..len)
+            .map(|_| F::from_fn(|_| util::from_f64(rng.next_bipolar() * 0.9)))
+            .collect()
+    }
+
+    /// The element-wise loop which `mix_into` replaces.
+    fn mix_reference<F>(dst: &mut [F], src: &[F], gain: f32)
+    where
+        F: Frame,
+    {
+        let gain = <F::Sample as Sample>::Float::from_sample(gain);
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d = d.zip_map(*s, |a, b| util::add_samples(a, b.mul_amp(gain)));
+        }
+    }
+
+    fn assert_mix_matches_reference<F>(gain: f32)
+    where
+        F: Frame + ::std::fmt::Debug,
+    {
+        // An odd length leaves a partial block and a partial group of lanes.
+        let src = noise::<F>(203, 1);
+        let mut expected = noise::<F>(203, 2);
+        let mut mixed = expected.clone();
+        mix_reference(&mut expected, &src, gain);
+        mix_into(&mut mixed, &src, gain);
+        assert_eq!(mixed, expected);
+    }
+
+    #[test]
+    fn mix_into_matches_the_element_wise_loop() {
+        for &gain in &[1.0, 0.5, -0.75, 0.0] {
+            assert_mix_matches_reference::<[f32; 1]>(gain);
+            assert_mix_matches_reference::<[f32; 2]>(gain);
+            assert_mix_matches_reference::<[f32; 6]>(gain);
+            assert_mix_matches_reference::<[f64; 2]>(gain);
+            assert_mix_matches_reference::<[i16; 2]>(gain);
+            assert_mix_matches_reference::<[i32; 1]>(gain);
+        }
+    }
+
+    #[test]
+    fn mix_into_saturates_integer_sums() {
+        let mut dst = [[i16::MAX - 10], [i16::MIN + 10]];
+        mix_into(&mut dst, &[[100], [-100]], 1.0);
+        assert_eq!(dst, [[i16::MAX], [i16::MIN]]);
+    }
+
+    #[test]
+    fn mix_into_leaves_frames_beyond_the_shorter_buffer() {
+        let mut dst = [[0.5f32], [0.5], [0.5]];
+        mix_into(&mut dst, &[[0.25], [0.25]], 1.0);
+        assert_eq!(dst, [[0.75], [0.75], [0.5]]);
+    }
+
+    #[test]
+    fn lane_wise_f32_helpers_match_the_scalar_loop() {
+        let src: Vec<f32> = noise::<[f32; 1]>(1003, 3).iter().map(|f| f[0]).collect();
+        let dst: Vec<f32> = noise::<[f32; 1]>(1003, 4).iter().map(|f| f[0]).collect();
+
+        let mut expected = dst.clone();
+        for (d, &s) in expected.iter_mut().zip(&src) {
+            *d += s * 0.3;
+        }
+        let mut mixed = dst.clone();
+        mix_into_f32(&mut mixed, &src, 0.3);
+        assert_eq!(mixed, expected);
+
+        let expected: Vec<f32> = dst.iter().map(|&x| x * -1.7).collect();
+        let mut scaled = dst;
+        apply_gain_f32(&mut scaled, -1.7);
+        assert_eq!(scaled, expected);
+    }
+
+    #[test]
+    fn acquiring_and_releasing_repeatedly_re_uses_a_single_allocation() {
+        let pool = BufferPool::<[f32; 2]>::new();
+        let first = pool.acquire(256).as_ptr();
+        assert_eq!(pool.available(), 1);
+        for frames in [256, 100, 256, 1].iter().cycle().take(100) {
+            let mut buffer = pool.acquire(*frames);
+            assert_eq!(pool.available(), 0);
+            assert_eq!(buffer.len(), *frames);
+            // The same memory, zeroed again despite being written during the previous acquisition.
+            assert_eq!(buffer.as_ptr(), first);
+            assert!(buffer.iter().all(|&frame| frame == [0.0; 2]));
+            for frame in buffer.iter_mut() {
+                *frame = [1.0; 2];
+            }
+        }
+        assert_eq!(pool.available(), 1);
+    }
+
+    #[test]
+    fn each_concurrently_acquired_buffer_is_distinct() {
+        let pool = BufferPool::<[i16; 1]>::with_capacity(2, 64);
+        {
+            let mut a = pool.acquire(64);
+            let b = pool.acquire(32);
+            let c = pool.acquire(16);
+            assert_eq!(pool.available(), 0);
+            a[0] = [1];
+            assert_eq!(b[0], [0]);
+            assert_ne!(a.as_ptr(), b.as_ptr());
+            assert_ne!(b.as_ptr(), c.as_ptr());
+        }
+        assert_eq!(pool.available(), 3);
+    }
+
+    #[test]
+    fn frame_iteration_matches_interleaved_indexing() {
+        let channels = 3;
+        let mut interleaved: Vec<f32> = (0..12).map(|i| i as f32).collect();
+        let expected = interleaved.clone();
+        {
+            let frames: &mut [[f32; 3]] =
+                sample::slice::to_frame_slice_mut(&mut interleaved[..]).unwrap();
+            for (i, frame) in frames.iter_mut().enumerate() {
+                for (j, s) in frame.iter_mut().enumerate() {
+                    assert_eq!(*s, expected[i * channels + j]);
+                    *s = -*s;
+                }
+            }
+            // Each planar channel holds every `channels`th sample, starting at its index.
+            let planar = to_planar(frames);
+            for (j, channel) in planar.iter().enumerate() {
+                for (i, s) in channel.iter().enumerate() {
+                    assert_eq!(*s, -expected[i * channels + j]);
+                }
+            }
+        }
+        let negated: Vec<f32> = expected.iter().map(|s| -s).collect();
+        assert_eq!(interleaved, negated);
+    }
+
+    #[test]
+    fn planar_conversion_round_trips_stereo_and_four_channels() {
+        let stereo: Vec<[f32; 2]> = noise(64, 1);
+        let planar = to_planar(&stereo);
+        assert_eq!(planar.len(), 2);
+        assert!(planar.iter().all(|channel| channel.len() == 64));
+        assert_eq!(planar[1][5], stereo[5][1]);
+        assert_eq!(to_interleaved::<[f32; 2]>(&planar), stereo);
+
+        let quad: Vec<[i16; 4]> = noise(64, 2);
+        let planar = to_planar(&quad);
+        assert_eq!(planar.len(), 4);
+        assert_eq!(planar[3][63], quad[63][3]);
+        assert_eq!(to_interleaved::<[i16; 4]>(&planar), quad);
+    }
+
+    #[test]
+    fn interleaving_pads_short_and_missing_channels() {
+        let planar = vec![vec![1.0f32, 2.0, 3.0], vec![4.0]];
+        let frames: Vec<[f32; 3]> = to_interleaved(&planar);
+        assert_eq!(
+            frames,
+            vec![[1.0, 4.0, 0.0], [2.0, 0.0, 0.0], [3.0, 0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn a_planar_adapter_processes_each_channel_separately() {
+        // Silence the left channel and reverse the right.
+        let mut adapter = PlanarAdapter::new(|channels: &mut [Vec<f32>], _sample_hz: f64| {
+            channels[0].iter_mut().for_each(|s| *s = 0.0);
+            channels[1].reverse();
+        });
+        let input: Vec<[f32; 2]> = noise(32, 3);
+        let mut output = input.clone();
+        adapter.audio_requested(&mut output, 44_100.0);
+        for (i, frame) in output.iter().enumerate() {
+            assert_eq!(*frame, [0.0, input[31 - i][1]]);
+        }
+
+        // Samples removed from the end of a channel are replaced with equilibrium.
+        let mut truncate = PlanarAdapter::new(|channels: &mut [Vec<f32>], _sample_hz: f64| {
+            channels[1].truncate(2);
+        });
+        let mut output = input.clone();
+        truncate.audio_requested(&mut output, 44_100.0);
+        assert_eq!(output[..2], input[..2]);
+        assert!(output[2..]
+            .iter()
+            .zip(&input[2..])
+            .all(|(o, i)| *o == [i[0], 0.0]));
+    }
+
+    /// The RMS error of reading a 2 kHz tone at a fractional delay with the given interpolation,
+    /// relative to the tone evaluated exactly at the delayed time.
+    fn interpolation_error(interpolation: DelayInterpolation) -> f64 {
+        let (sample_hz, delay) = (48_000.0, 10.37);
+        let step = 2.0 * ::std::f64::consts::PI * 2_000.0 / sample_hz;
+        let mut line = DelayLine::default();
+        line.resize(16, 1);
+        let mut reader = FractionalReader::default();
+        reader.set_interpolation(interpolation);
+        reader.prepare(1);
+        let mut sum = 0.0;
+        for n in 0..4_800 {
+            let read = reader.read(&line, 0, delay);
+            line.write(0, (step * n as f64).sin());
+            line.advance();
+            if n >= 200 {
+                let exact = (step * (n as f64 - delay)).sin();
+                sum += (read - exact).powi(2);
+            }
+        }
+        (sum / 4_600.0).sqrt()
+    }
+
+    #[test]
+    fn each_interpolation_reads_a_band_limited_tone_within_its_error() {
+        let nearest = interpolation_error(DelayInterpolation::NearestNeighbor);
+        let linear = interpolation_error(DelayInterpolation::Linear);
+        let cubic = interpolation_error(DelayInterpolation::Cubic);
+        let allpass = interpolation_error(DelayInterpolation::AllPass);
+        assert!(nearest > 0.05 && nearest < 0.1, "{}", nearest);
+        assert!(linear < 0.01, "{}", linear);
+        assert!(allpass < 3e-3, "{}", allpass);
+        assert!(cubic < 5e-4, "{}", cubic);
+        assert!(cubic < allpass && allpass < linear && linear < nearest);
+    }
+
+    fn assert_gain_matches_reference<F>(gain: f32)
+    where
+        F: Frame + ::std::fmt::Debug,
+    {
+        let mut expected = noise::<F>(203, 5);
+        let mut scaled = expected.clone();
+        let amp = <F::Sample as Sample>::Float::from_sample(gain);
+        for frame in &mut expected {
+            *frame = frame.map(|s| s.mul_amp(amp));
+        }
+        apply_gain(&mut scaled, gain);
+        assert_eq!(scaled, expected);
+    }
+
+    #[test]
+    fn apply_gain_matches_the_element_wise_loop() {
+        for &gain in &[1.0, 0.5, -0.75, 0.0] {
+            assert_gain_matches_reference::<[f32; 1]>(gain);
+            assert_gain_matches_reference::<[f32; 2]>(gain);
+            assert_gain_matches_reference::<[f32; 6]>(gain);
+            assert_gain_matches_reference::<[f64; 2]>(gain);
+            assert_gain_matches_reference::<[i16; 2]>(gain);
+        }
+    }
+
+    /// Compares the lane-wise helpers against the scalar loops they replace.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture`, with and without the `simd`
+    /// feature.
+    #[test]
+    #[ignore]
+    fn bench_lane_wise_f32_helpers() {
+        use std::time::Instant;
+
+        const LEN: usize = 4096;
+        const ROUNDS: usize = 10_000;
+        let src: Vec<f32> = noise::<[f32; 1]>(LEN, 6).iter().map(|f| f[0]).collect();
+        let mut dst = vec![0.0f32; LEN];
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            for (d, &s) in dst.iter_mut().zip(&src) {
+                *d += s * 0.5;
+            }
+            for d in dst.iter_mut() {
+                *d *= 0.5;
+            }
+        }
+        let scalar = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            mix_into_f32(&mut dst, &src, 0.5);
+            apply_gain_f32(&mut dst, 0.5);
+        }
+        let lanes = start.elapsed();
+
+        println!(
+            "{} samples x {} rounds: scalar {:?}, lane-wise {:?} (simd: {})",
+            LEN,
+            ROUNDS,
+            scalar,
+            lanes,
+            cfg!(feature = "simd")
+        );
+        assert!(dst.iter().all(|s| s.is_finite()));
+    }
+
+    #[test]
+    fn sanitize_replaces_and_counts_only_non_finite_samples() {
+        let mut buffer = [
+            [f32::NAN, 0.5],
+            [f32::INFINITY, f32::NEG_INFINITY],
+            [0.25, -0.0],
+        ];
+        assert_eq!(sanitize(&mut buffer), 3);
+        assert_eq!(buffer, [[0.0, 0.5], [0.0, 0.0], [0.25, -0.0]]);
+        assert_eq!(sanitize(&mut buffer), 0);
+
+        let mut ints = [[i16::MIN, i16::MAX]; 4];
+        assert_eq!(sanitize(&mut ints), 0);
+        assert_eq!(ints, [[i16::MIN, i16::MAX]; 4]);
+    }
+}
@@ -0,0 +1,510 @@
+//! Musical time, for synchronising modulation and delays to a tempo.
+
+use crate::node::Node;
+use crate::util;
+use sample::{self, Frame};
+
+/// The basic length of a note, relative to a whole note.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoteValue {
+    /// Four beats.
+    Whole,
+    /// Two beats.
+    Half,
+    /// One beat.
+    Quarter,
+    /// Half a beat.
+    Eighth,
+    /// A quarter of a beat.
+    Sixteenth,
+    /// An eighth of a beat.
+    ThirtySecond,
+}
+
+/// Modifies the length of a **NoteValue**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoteKind {
+    /// The unmodified length.
+    #[default]
+    Straight,
+    /// One and a half times the length.
+    Dotted,
+    /// Two thirds of the length, so that three fit in the space of two.
+    Triplet,
+}
+
+/// A musical note length, e.g. a dotted eighth.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteLength {
+    /// The basic length of the note.
+    pub value: NoteValue,
+    /// The modification applied to the basic length.
+    pub kind: NoteKind,
+}
+
+impl NoteLength {
+    /// A note of the given value and kind.
+    pub fn new(value: NoteValue, kind: NoteKind) -> Self {
+        NoteLength { value, kind }
+    }
+
+    /// An unmodified note of the given value.
+    pub fn straight(value: NoteValue) -> Self {
+        NoteLength::new(value, NoteKind::Straight)
+    }
+
+    /// A dotted note of the given value.
+    pub fn dotted(value: NoteValue) -> Self {
+        NoteLength::new(value, NoteKind::Dotted)
+    }
+
+    /// A triplet note of the given value.
+    pub fn triplet(value: NoteValue) -> Self {
+        NoteLength::new(value, NoteKind::Triplet)
+    }
+
+    /// The length of the note in beats, where a beat is a quarter note.
+    pub fn beats(&self) -> f64 {
+        let beats = match self.value {
+            NoteValue::Whole => 4.0,
+            NoteValue::Half => 2.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::ThirtySecond => 0.125,
+        };
+        match self.kind {
+            NoteKind::Straight => beats,
+            NoteKind::Dotted => beats * 1.5,
+            NoteKind::Triplet => beats * 2.0 / 3.0,
+        }
+    }
+
+    /// The length of the note in seconds at the given tempo in beats per minute.
+    pub fn secs(&self, bpm: f64) -> f64 {
+        self.beats() * 60.0 / bpm
+    }
+
+    /// The rate in hertz at which the note repeats at the given tempo in beats per minute.
+    pub fn hz(&self, bpm: f64) -> f64 {
+        1.0 / self.secs(bpm)
+    }
+
+    /// The length of the note in frames at the given tempo and sample rate, rounded to the
+    /// nearest frame.
+    pub fn frames(&self, bpm: f64, sample_hz: f64) -> usize {
+        (self.secs(bpm) * sample_hz).round() as usize
+    }
+}
+
+/// A pass-through **Node** that tracks the running musical position at some tempo.
+///
+/// The position advances by the number of frames in each buffer passed to `audio_requested`.
+/// Changing the tempo affects the rate at which the position in beats advances from then on,
+/// without moving the current position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clock {
+    bpm: f64,
+    /// The number of frames elapsed.
+    frames: u64,
+    /// The number of beats elapsed.
+    beats: f64,
+}
+
+impl Clock {
+    /// Construct a **Clock** at the start of the first beat with the given tempo in beats per
+    /// minute.
+    pub fn new(bpm: f64) -> Self {
+        Clock {
+            bpm,
+            frames: 0,
+            beats: 0.0,
+        }
+    }
+
+    /// The tempo in beats per minute.
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    /// Set the tempo in beats per minute.
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm;
+    }
+
+    /// The number of frames elapsed.
+    pub fn position_frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The number of beats elapsed.
+    pub fn position_beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Return to the start of the first beat.
+    pub fn rewind(&mut self) {
+        self.frames = 0;
+        self.beats = 0.0;
+    }
+
+    /// Advance the position by the given number of frames at the given sample rate.
+    pub fn advance(&mut self, frames: usize, sample_hz: f64) {
+        self.frames += frames as u64;
+        if sample_hz > 0.0 {
+            self.beats += frames as f64 * self.bpm / 60.0 / sample_hz;
+        }
+    }
+
+    /// The length of the given note in seconds at the current tempo.
+    pub fn secs(&self, note: NoteLength) -> f64 {
+        note.secs(self.bpm)
+    }
+
+    /// The rate in hertz at which the given note repeats at the current tempo.
+    pub fn hz(&self, note: NoteLength) -> f64 {
+        note.hz(self.bpm)
+    }
+
+    /// The length of the given note in frames at the current tempo and the given sample rate.
+    pub fn frames(&self, note: NoteLength, sample_hz: f64) -> usize {
+        note.frames(self.bpm, sample_hz)
+    }
+}
+
+impl<F> Node<F> for Clock
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.advance(buffer.len(), sample_hz);
+    }
+
+    fn reset(&mut self) {
+        self.rewind();
+    }
+
+    fn type_tag(&self) -> &str {
+        "clock"
+    }
+}
+
+/// The frequency in hertz of the blip sounded by a **Metronome** on each beat.
+pub const METRONOME_CLICK_HZ: f64 = 1_000.0;
+
+/// The frequency in hertz of the blip sounded by a **Metronome** on each downbeat.
+pub const METRONOME_ACCENT_HZ: f64 = 1_500.0;
+
+/// The amplitude of the blip sounded by a **Metronome** on each beat other than the downbeat,
+/// which sounds at full scale.
+pub const METRONOME_CLICK_AMP: f64 = 0.5;
+
+/// The length of each blip sounded by a **Metronome** in milliseconds.
+pub const METRONOME_CLICK_MS: f64 = 20.0;
+
+/// The parameters of a **Metronome**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetronomeParams {
+    /// The tempo in beats per minute.
+    pub bpm: f64,
+    /// The number of beats in each bar, the first of which is accented (at least `1`).
+    pub beats_per_bar: usize,
+}
+
+/// A source **Node** that sounds a click on every beat at some tempo, for use as a timing
+/// reference.
+///
+/// Each click is a sine blip of `METRONOME_CLICK_MS` that decays exponentially. The first beat of
+/// every bar is accented, sounding at full scale and `METRONOME_ACCENT_HZ`, while the other beats
+/// sound at `METRONOME_CLICK_AMP` and `METRONOME_CLICK_HZ`. Each click starts on the first frame at
+/// or after its beat, and the position within the bar carries across buffers, so clicks are
+/// placed precisely regardless of the size of the buffers. The click is written to every channel,
+/// replacing the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metronome {
+    bpm: f64,
+    beats_per_bar: usize,
+    /// The position in beats within the current beat (0.0 ... 1.0), or `None` before the first
+    /// frame has been rendered.
+    beat_phase: Option<f64>,
+    /// The index of the current beat within the bar.
+    beat: usize,
+    /// The number of frames elapsed since the current click started, if one is sounding.
+    click_frame: Option<usize>,
+}
+
+impl Metronome {
+    /// Construct a **Metronome** with the given tempo in beats per minute and number of beats per
+    /// bar. The first click sounds on the first frame rendered.
+    pub fn new(bpm: f64, beats_per_bar: usize) -> Self {
+        Metronome {
+            bpm: bpm.max(0.0),
+            beats_per_bar: beats_per_bar.max(1),
+            beat_phase: None,
+            beat: 0,
+            click_frame: None,
+        }
+    }
+
+    /// The tempo in beats per minute.
+    pub fn bpm(&self) -> f64 {
+        self.bpm
+    }
+
+    /// Set the tempo in beats per minute, clamped to `0.0` or above.
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.bpm = bpm.max(0.0);
+    }
+
+    /// The number of beats in each bar.
+    pub fn beats_per_bar(&self) -> usize {
+        self.beats_per_bar
+    }
+
+    /// Set the number of beats in each bar (at least `1`).
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: usize) {
+        self.beats_per_bar = beats_per_bar.max(1);
+        self.beat %= self.beats_per_bar;
+    }
+
+    /// The index of the current beat within the bar, where `0` is the downbeat.
+    pub fn beat(&self) -> usize {
+        self.beat
+    }
+
+    /// The parameters of the **Metronome**.
+    pub fn params(&self) -> MetronomeParams {
+        MetronomeParams {
+            bpm: self.bpm,
+            beats_per_bar: self.beats_per_bar,
+        }
+    }
+
+    /// Apply the given parameters, leaving the position within the bar untouched.
+    pub fn set_params(&mut self, params: MetronomeParams) {
+        self.set_bpm(params.bpm);
+        self.set_beats_per_bar(params.beats_per_bar);
+    }
+
+    /// Return to the start of the bar, so that the downbeat sounds on the next frame rendered.
+    pub fn reset(&mut self) {
+        self.beat_phase = None;
+        self.beat = 0;
+        self.click_frame = None;
+    }
+}
+
+impl<F> Node<F> for Metronome
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            sample::slice::equilibrium(buffer);
+            return;
+        }
+        let step = self.bpm / 60.0 / sample_hz;
+        let click_frames = (METRONOME_CLICK_MS / 1_000.0 * sample_hz).round() as usize;
+        // Decay to -60 dB over the length of the click.
+        let decay = (0.001f64).ln() / click_frames.max(1) as f64;
+        for frame in buffer.iter_mut() {
+            // Start a new click upon reaching the next beat.
+            match self.beat_phase {
+                None => {
+                    self.beat_phase = Some(0.0);
+                    self.click_frame = Some(0);
+                }
+                Some(phase) if phase >= 1.0 => {
+                    self.beat_phase = Some(phase - 1.0);
+                    self.beat = (self.beat + 1) % self.beats_per_bar;
+                    self.click_frame = Some(0);
+                }
+                Some(_) => (),
+            }
+
+            let value = match self.click_frame {
+                Some(i) if i < click_frames => {
+                    let (hz, amp) = if self.beat == 0 {
+                        (METRONOME_ACCENT_HZ, 1.0)
+                    } else {
+                        (METRONOME_CLICK_HZ, METRONOME_CLICK_AMP)
+                    };
+                    let t = i as f64 / sample_hz;
+                    self.click_frame = Some(i + 1);
+                    amp * util::exp(decay * i as f64)
+                        * util::sin(2.0 * ::std::f64::consts::PI * hz * t)
+                }
+                _ => {
+                    self.click_frame = None;
+                    0.0
+                }
+            };
+            if let Some(phase) = self.beat_phase.as_mut() {
+                *phase += step;
+            }
+            *frame = F::from_fn(|_| util::from_f64(value));
+        }
+    }
+
+    /// Exposes:
+    ///
+    /// - `bpm`: the tempo in beats per minute (0.0 ...).
+    /// - `beats_per_bar`: the number of beats in each bar, rounded to the nearest whole number
+    ///   (1 ...).
+    fn param_names(&self) -> &[&str] {
+        &["bpm", "beats_per_bar"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "bpm" => Some(self.bpm as f32),
+            "beats_per_bar" => Some(self.beats_per_bar as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "bpm" => self.set_bpm(value as f64),
+            "beats_per_bar" => self.set_beats_per_bar(value.max(0.0).round() as usize),
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        Metronome::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "metronome"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::Delay;
+    use crate::oscillator::{Lfo, LfoShape};
+
+    #[test]
+    fn a_quarter_note_at_120_bpm_lasts_half_a_second() {
+        let quarter = NoteLength::straight(NoteValue::Quarter);
+        assert_eq!(quarter.secs(120.0), 0.5);
+        assert_eq!(quarter.hz(120.0), 2.0);
+        assert_eq!(quarter.frames(120.0, 44_100.0), 22_050);
+        assert_eq!(Clock::new(120.0).secs(quarter), 0.5);
+    }
+
+    #[test]
+    fn dotted_and_triplet_notes() {
+        let eighth = NoteValue::Eighth;
+        assert_eq!(NoteLength::dotted(eighth).secs(120.0), 0.375);
+        assert!((NoteLength::triplet(eighth).secs(120.0) - 1.0 / 6.0).abs() < 1e-12);
+        assert_eq!(NoteLength::straight(NoteValue::Whole).beats(), 4.0);
+        assert_eq!(NoteLength::dotted(NoteValue::Half).beats(), 3.0);
+    }
+
+    #[test]
+    fn the_clock_advances_by_the_frames_of_each_buffer() {
+        let mut clock = Clock::new(120.0);
+        let mut buffer = [[0.0f32; 2]; 512];
+        for _ in 0..3 {
+            clock.audio_requested(&mut buffer, 48_000.0);
+        }
+        assert_eq!(clock.position_frames(), 1_536);
+        assert!((clock.position_beats() - 1_536.0 * 2.0 / 48_000.0).abs() < 1e-12);
+        // Doubling the tempo doubles the rate at which beats advance from here.
+        let beats = clock.position_beats();
+        clock.set_bpm(240.0);
+        clock.advance(12_000, 48_000.0);
+        assert!((clock.position_beats() - beats - 1.0).abs() < 1e-12);
+        Node::<[f32; 2]>::reset(&mut clock);
+        assert_eq!(clock.position_frames(), 0);
+        assert_eq!(clock.position_beats(), 0.0);
+    }
+
+    #[test]
+    fn lfo_and_delay_rates_resolve_from_note_values() {
+        let mut lfo = Lfo::new(1.0, LfoShape::Sine);
+        lfo.set_rate_note(NoteLength::straight(NoteValue::Eighth), 120.0);
+        assert_eq!(lfo.rate_hz(), 4.0);
+        let mut delay = Delay::new(0.0);
+        delay.set_delay_note(NoteLength::dotted(NoteValue::Quarter), 100.0);
+        assert!((delay.delay_secs() - 0.9).abs() < 1e-12);
+    }
+
+    /// The frame at which each click starts, along with its peak, over `len` frames rendered in
+    /// buffers of 500 frames.
+    ///
+    /// Each click starts at a phase of zero, so its first frame is silent and the next is not.
+    fn clicks(metronome: &mut Metronome, len: usize, sample_hz: f64) -> Vec<(usize, f32)> {
+        let mut buffer = vec![[0.0f32; 1]; len];
+        for chunk in buffer.chunks_mut(500) {
+            metronome.audio_requested(chunk, sample_hz);
+        }
+        let starts: Vec<usize> = (0..len - 1)
+            .filter(|&i| buffer[i][0] == 0.0 && buffer[i + 1][0] != 0.0)
+            .filter(|&i| i == 0 || buffer[i - 1][0] == 0.0)
+            .collect();
+        starts
+            .iter()
+            .map(|&start| {
+                let peak = buffer[start..(start + 2_000).min(len)]
+                    .iter()
+                    .map(|frame| frame[0].abs())
+                    .fold(0.0, f32::max);
+                (start, peak)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clicks_are_spaced_by_the_beat_length() {
+        // 90 bpm at 44.1 kHz is exactly 29,400 frames per beat.
+        let mut metronome = Metronome::new(90.0, 3);
+        let clicks = clicks(&mut metronome, 29_400 * 7 + 100, 44_100.0);
+        let starts: Vec<usize> = clicks.iter().map(|&(start, _)| start).collect();
+        assert_eq!(starts, (0..8).map(|beat| beat * 29_400).collect::<Vec<_>>());
+        // The first of every 3 beats is accented.
+        for (beat, &(_, peak)) in clicks.iter().enumerate() {
+            let amp = if beat % 3 == 0 {
+                1.0
+            } else {
+                METRONOME_CLICK_AMP as f32
+            };
+            assert!(peak > amp * 0.9 && peak <= amp, "beat {}: {}", beat, peak);
+        }
+    }
+
+    #[test]
+    fn clicks_start_on_the_first_frame_at_or_after_each_beat() {
+        // 130 bpm at 44.1 kHz is 20,353.8 frames per beat.
+        let mut metronome = Metronome::new(130.0, 4);
+        let beat_frames = 44_100.0 * 60.0 / 130.0;
+        let starts: Vec<usize> = clicks(&mut metronome, 200_000, 44_100.0)
+            .iter()
+            .map(|&(start, _)| start)
+            .collect();
+        assert_eq!(starts.len(), 10);
+        for (beat, &start) in starts.iter().enumerate() {
+            assert_eq!(start, (beat as f64 * beat_frames - 1e-6).ceil() as usize);
+        }
+
+        metronome.set_bpm(60.0);
+        metronome.reset();
+        let starts: Vec<usize> = clicks(&mut metronome, 100_000, 44_100.0)
+            .iter()
+            .map(|&(start, _)| start)
+            .collect();
+        assert_eq!(starts, vec![0, 44_100, 88_200]);
+    }
+}
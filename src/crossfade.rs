@@ -0,0 +1,541 @@
+//! A **Node** for smoothly transitioning between two inputs, and an **AbCompare** wrapper for
+//! switching between a node's dry and wet signals.
+
+use crate::gain::{self, SmoothedGain};
+use crate::node::{Node, Settings};
+use crate::util;
+use sample::{self, Frame};
+use std::f64::consts::FRAC_PI_2;
+
+/// The curve used by a **Crossfade** to convert its `position` into a gain for each input.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FadeCurve {
+    /// The gains are `1 - position` and `position`. Their sum is always `1.0`, which suits
+    /// correlated inputs but dips by 6 dB at the center for uncorrelated inputs.
+    #[default]
+    Linear,
+    /// The gains are `cos(position * π/2)` and `sin(position * π/2)`. The sum of their squares is
+    /// always `1.0`, keeping the power of uncorrelated inputs constant.
+    ConstantPower,
+}
+
+impl FadeCurve {
+    /// The gains for the first and second inputs at the given position (0.0 ... 1.0).
+    pub fn gains(self, position: f64) -> [f64; 2] {
+        let position = position.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => [1.0 - position, position],
+            FadeCurve::ConstantPower => {
+                let angle = position * FRAC_PI_2;
+                [angle.cos(), angle.sin()]
+            }
+        }
+    }
+}
+
+/// The parameters of a **Crossfade**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrossfadeParams {
+    /// The position of the fade (0.0 = first input, 1.0 = second input).
+    pub position: f64,
+    /// The curve used to convert the position into a gain for each input.
+    pub curve: FadeCurve,
+    /// The number of frames over which changes to the position are smoothed.
+    pub smoothing_frames: usize,
+}
+
+/// A **Node** that blends between its first two inputs, rather than summing them.
+///
+/// Inputs are ordered by the order in which they were connected. Any inputs beyond the second
+/// are ignored.
+///
+/// Changes to the `position` are interpolated over `smoothing_frames` frames to avoid clicks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crossfade {
+    curve: FadeCurve,
+    smoothing_frames: usize,
+    /// The position reached at the end of the last buffer.
+    current: f64,
+    /// The position being moved towards.
+    target: f64,
+    /// The change in position per frame while smoothing.
+    step: f64,
+    /// The number of frames until the target is reached.
+    remaining: usize,
+}
+
+impl Crossfade {
+    /// Construct a **Crossfade** at the given position, using the given curve.
+    pub fn new(position: f64, curve: FadeCurve) -> Self {
+        let position = position.clamp(0.0, 1.0);
+        Crossfade {
+            curve,
+            smoothing_frames: 0,
+            current: position,
+            target: position,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// The position being faded towards (0.0 = first input, 1.0 = second input).
+    pub fn position(&self) -> f64 {
+        self.target
+    }
+
+    /// Set the position to fade towards (0.0 = first input, 1.0 = second input).
+    ///
+    /// The fade reaches the new position over `smoothing_frames` frames.
+    pub fn set_position(&mut self, position: f64) {
+        let position = position.clamp(0.0, 1.0);
+        if position == self.target {
+            return;
+        }
+        self.target = position;
+        if self.smoothing_frames == 0 {
+            self.current = position;
+            self.remaining = 0;
+        } else {
+            self.step = (position - self.current) / self.smoothing_frames as f64;
+            self.remaining = self.smoothing_frames;
+        }
+    }
+
+    /// The curve used to convert the position into a gain for each input.
+    pub fn curve(&self) -> FadeCurve {
+        self.curve
+    }
+
+    /// Set the curve used to convert the position into a gain for each input.
+    pub fn set_curve(&mut self, curve: FadeCurve) {
+        self.curve = curve;
+    }
+
+    /// The number of frames over which changes to the position are smoothed.
+    pub fn smoothing_frames(&self) -> usize {
+        self.smoothing_frames
+    }
+
+    /// Set the number of frames over which changes to the position are smoothed.
+    ///
+    /// Takes effect upon the next call to `set_position`.
+    pub fn set_smoothing_frames(&mut self, frames: usize) {
+        self.smoothing_frames = frames;
+    }
+
+    /// The parameters of the **Crossfade**.
+    pub fn params(&self) -> CrossfadeParams {
+        CrossfadeParams {
+            position: self.target,
+            curve: self.curve,
+            smoothing_frames: self.smoothing_frames,
+        }
+    }
+
+    /// Apply the given parameters, smoothing towards the new position.
+    pub fn set_params(&mut self, params: CrossfadeParams) {
+        self.curve = params.curve;
+        self.smoothing_frames = params.smoothing_frames;
+        self.set_position(params.position);
+    }
+
+    /// The position at the given frame within the current buffer.
+    fn position_at(&self, frame: usize) -> f64 {
+        if frame < self.remaining {
+            self.current + self.step * (frame + 1) as f64
+        } else {
+            self.target
+        }
+    }
+
+    /// Jump straight to the target position, abandoning any fade in progress.
+    pub fn reset(&mut self) {
+        self.current = self.target;
+        self.remaining = 0;
+    }
+}
+
+impl<F> Node<F> for Crossfade
+where
+    F: Frame,
+{
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        if input_idx > 1 {
+            return;
+        }
+        let mut frame_idx = 0;
+        sample::slice::zip_map_in_place(output, input, |out_frame, in_frame| {
+            let gain = self.curve.gains(self.position_at(frame_idx))[input_idx];
+            frame_idx += 1;
+            out_frame.zip_map(in_frame, |out_sample, in_sample| {
+                util::from_f64(util::to_f64(out_sample) + util::to_f64(in_sample) * gain)
+            })
+        });
+    }
+
+    /// Returns `false`, so that the crossfade between the inputs keeps their levels.
+    fn sums_inputs(&self) -> bool {
+        false
+    }
+
+    /// Returns `false`, as the inputs are crossfaded by `combine_input`.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        // The inputs have already been blended by `combine_input`, so we only need to advance the
+        // position by the length of the buffer.
+        if buffer.len() >= self.remaining {
+            self.current = self.target;
+            self.remaining = 0;
+        } else {
+            self.current += self.step * buffer.len() as f64;
+            self.remaining -= buffer.len();
+        }
+    }
+
+    /// Exposes:
+    ///
+    /// - `position`: the position faded towards (0.0 = first input, 1.0 = second input).
+    /// - `smoothing_frames`: the number of frames over which changes to the position are smoothed.
+    fn param_names(&self) -> &[&str] {
+        &["position", "smoothing_frames"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "position" => Some(self.target as f32),
+            "smoothing_frames" => Some(self.smoothing_frames as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "position" => self.set_position(value as f64),
+            "smoothing_frames" => self.set_smoothing_frames(value.max(0.0).round() as usize),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Crossfade::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "crossfade"
+    }
+}
+
+/// The time in milliseconds over which an **AbCompare** measures the level of each path.
+pub const AB_RMS_WINDOW_MS: f64 = 300.0;
+
+/// The time in milliseconds taken by an **AbCompare** to crossfade between its paths.
+pub const AB_FADE_MS: f64 = 20.0;
+
+/// The largest gain in decibels by which an **AbCompare** will boost or cut the wet path.
+pub const AB_MAX_MATCH_DB: f32 = 24.0;
+
+/// A **Node** wrapper for comparing a node's output (B) against its input (A) at equal loudness.
+///
+/// Both paths are rendered on every buffer. The RMS level of each is measured over roughly
+/// `AB_RMS_WINDOW_MS`, and the wet path is scaled by the ratio of the two so that an effect that
+/// merely makes the signal louder does not sound better for it. Toggling `a_or_b` crossfades
+/// between the paths over `AB_FADE_MS`.
+///
+/// This is intended for evaluating effects rather than for use within a finished patch. The dry
+/// path is not delayed to match the `latency_samples` of the wrapped node.
+#[derive(Clone, Debug)]
+pub struct AbCompare<F, N> {
+    node: N,
+    /// `false` for the dry path (A), `true` for the wet path (B).
+    a_or_b: bool,
+    /// The position of the crossfade (0.0 = A, 1.0 = B).
+    fade: SmoothedGain,
+    /// The smoothed mean square of the dry and wet paths.
+    dry_ms: f64,
+    wet_ms: f64,
+    /// A copy of the input, taken before rendering the wrapped node.
+    dry: Vec<F>,
+}
+
+impl<F, N> AbCompare<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Wrap the given node, starting on the dry path (A).
+    pub fn new(node: N) -> Self {
+        AbCompare {
+            node,
+            a_or_b: false,
+            fade: SmoothedGain::new(0.0),
+            dry_ms: 0.0,
+            wet_ms: 0.0,
+            dry: Vec::new(),
+        }
+    }
+
+    /// `false` while listening to the dry path (A), `true` while listening to the wet path (B).
+    pub fn a_or_b(&self) -> bool {
+        self.a_or_b
+    }
+
+    /// Select the dry path (A) with `false` or the wet path (B) with `true`.
+    ///
+    /// The crossfade begins upon the next call to `audio_requested`.
+    pub fn set_a_or_b(&mut self, a_or_b: bool) {
+        self.a_or_b = a_or_b;
+    }
+
+    /// Switch to whichever path is not currently selected.
+    pub fn toggle(&mut self) {
+        self.a_or_b = !self.a_or_b;
+    }
+
+    /// The gain currently applied to the wet path to match the level of the dry path.
+    pub fn match_gain(&self) -> f32 {
+        if self.wet_ms <= 0.0 || self.dry_ms <= 0.0 {
+            return 1.0;
+        }
+        let max = gain::db_to_amp(AB_MAX_MATCH_DB);
+        ((self.dry_ms / self.wet_ms).sqrt() as f32).clamp(1.0 / max, max)
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+
+    /// Forget the measured levels, jump straight to the selected path and reset the wrapped node.
+    pub fn reset(&mut self) {
+        self.fade.reset(if self.a_or_b { 1.0 } else { 0.0 });
+        self.dry_ms = 0.0;
+        self.wet_ms = 0.0;
+        self.node.reset();
+    }
+}
+
+impl<F, N> Node<F> for AbCompare<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.dry.clear();
+        self.dry.extend_from_slice(buffer);
+        self.node.audio_requested(buffer, sample_hz);
+
+        let target = if self.a_or_b { 1.0 } else { 0.0 };
+        let fade_frames = (AB_FADE_MS / 1_000.0 * sample_hz).round() as usize;
+        self.fade.set_target(target, fade_frames);
+
+        // A one-pole average of the mean square of each path, summed across channels.
+        let window_frames = (AB_RMS_WINDOW_MS / 1_000.0 * sample_hz).max(1.0);
+        let coeff = (-1.0 / window_frames).exp();
+        for (wet_frame, dry_frame) in buffer.iter_mut().zip(&self.dry) {
+            let (mut dry_sq, mut wet_sq) = (0.0, 0.0);
+            for (d, w) in dry_frame.channels().zip(wet_frame.channels()) {
+                let (d, w) = (util::to_f64(d), util::to_f64(w));
+                dry_sq += d * d;
+                wet_sq += w * w;
+            }
+            self.dry_ms = util::flush_denormal(dry_sq + (self.dry_ms - dry_sq) * coeff);
+            self.wet_ms = util::flush_denormal(wet_sq + (self.wet_ms - wet_sq) * coeff);
+
+            let match_gain = self.match_gain() as f64;
+            let [a, b] = FadeCurve::Linear.gains(self.fade.next_gain() as f64);
+            *wet_frame = dry_frame.zip_map(*wet_frame, |d, w| {
+                util::from_f64(util::to_f64(d) * a + util::to_f64(w) * match_gain * b)
+            });
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    /// Exposes:
+    ///
+    /// - `a_or_b`: `0` for the dry path (A), `1` for the wet path (B).
+    fn param_names(&self) -> &[&str] {
+        &["a_or_b"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "a_or_b" => Some(if self.a_or_b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "a_or_b" => self.set_a_or_b(value >= 0.5),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.dry.clear();
+        self.dry.reserve(settings.max_frames);
+        self.fade.reset(if self.a_or_b { 1.0 } else { 0.0 });
+        self.dry_ms = 0.0;
+        self.wet_ms = 0.0;
+        self.node.prepare(settings);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn reset(&mut self) {
+        AbCompare::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "ab_compare"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gain::Gain;
+    use crate::testing;
+
+    /// Crossfade between constant inputs of `0.5` and `-0.25` over a buffer of `len` frames.
+    fn render(crossfade: &mut Crossfade, len: usize) -> Vec<[f32; 1]> {
+        let mut output = vec![[0.0]; len];
+        crossfade.combine_input(0, &vec![[0.5]; len], &mut output);
+        crossfade.combine_input(1, &vec![[-0.25]; len], &mut output);
+        crossfade.audio_requested(&mut output, 44_100.0);
+        output
+    }
+
+    #[test]
+    fn constant_power_at_the_center_gives_equal_power_gains() {
+        let [a, b] = FadeCurve::ConstantPower.gains(0.5);
+        assert!((a - b).abs() < 1e-12);
+        assert!((a * a + b * b - 1.0).abs() < 1e-12);
+        assert!((a - 0.5f64.sqrt()).abs() < 1e-12);
+        assert_eq!(FadeCurve::Linear.gains(0.5), [0.5, 0.5]);
+        for &position in &[0.0, 0.1, 0.3, 0.7, 1.0] {
+            let [a, b] = FadeCurve::ConstantPower.gains(position);
+            assert!((a * a + b * b - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn the_ends_of_the_fade_select_a_single_input() {
+        let mut crossfade = Crossfade::new(0.0, FadeCurve::ConstantPower);
+        assert_eq!(render(&mut crossfade, 4), [[0.5]; 4]);
+        crossfade.set_position(1.0);
+        assert!(render(&mut crossfade, 4)
+            .iter()
+            .all(|frame| (frame[0] + 0.25).abs() < 1e-7));
+        // Inputs beyond the second are ignored.
+        let mut output = [[0.0]; 2];
+        crossfade.combine_input(2, &[[1.0]; 2], &mut output);
+        assert_eq!(output, [[0.0]; 2]);
+    }
+
+    #[test]
+    fn position_changes_are_smoothed_across_buffers() {
+        let mut crossfade = Crossfade::new(0.0, FadeCurve::Linear);
+        crossfade.set_smoothing_frames(8);
+        crossfade.set_position(1.0);
+        let first = render(&mut crossfade, 4);
+        let second = render(&mut crossfade, 8);
+        let fade: Vec<f32> = first.iter().chain(&second).map(|frame| frame[0]).collect();
+        // Moving an eighth of the way per frame from `0.5` to `-0.25`.
+        for (i, &s) in fade.iter().enumerate() {
+            let position = ((i + 1) as f32 / 8.0).min(1.0);
+            let expected = 0.5 * (1.0 - position) - 0.25 * position;
+            assert!(
+                (s - expected).abs() < 1e-6,
+                "frame {}: {} != {}",
+                i,
+                s,
+                expected
+            );
+        }
+    }
+
+    /// The level of a second of a steady tone rendered through the comparison, measured over its
+    /// second half.
+    fn compared_rms<N: Node<[f32; 1]>>(ab: &mut AbCompare<[f32; 1], N>) -> f64 {
+        let mut buffer: Vec<[f32; 1]> = testing::sine(48_000, 440.0, 0.25, 48_000.0);
+        for chunk in buffer.chunks_mut(256) {
+            ab.audio_requested(chunk, 48_000.0);
+        }
+        testing::rms(&buffer[24_000..])
+    }
+
+    #[test]
+    fn a_transparent_effect_is_level_matched_on_both_paths() {
+        let mut ab = AbCompare::new(Gain::new(1.0));
+        let a = compared_rms(&mut ab);
+        ab.toggle();
+        assert!(ab.a_or_b());
+        let b = compared_rms(&mut ab);
+        assert!((a / b - 1.0).abs() < 0.01, "{} != {}", a, b);
+        assert!((ab.match_gain() - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn a_louder_effect_is_matched_to_the_dry_level() {
+        let mut ab = AbCompare::new(Gain::new(2.0));
+        let a = compared_rms(&mut ab);
+        assert!((a - 0.25 / 2f64.sqrt()).abs() < 1e-3);
+        assert!(Node::<[f32; 1]>::set_param(&mut ab, "a_or_b", 1.0));
+        let b = compared_rms(&mut ab);
+        assert!((a / b - 1.0).abs() < 0.01, "{} != {}", a, b);
+        assert!((ab.match_gain() - 0.5).abs() < 0.01, "{}", ab.match_gain());
+    }
+
+    #[test]
+    fn the_position_and_smoothing_can_be_set_as_params() {
+        let mut crossfade = Crossfade::new(0.0, FadeCurve::Linear);
+        assert_eq!(
+            Node::<[f32; 1]>::param_names(&crossfade),
+            &["position", "smoothing_frames"]
+        );
+        assert!(Node::<[f32; 1]>::set_param(
+            &mut crossfade,
+            "smoothing_frames",
+            4.0
+        ));
+        assert!(Node::<[f32; 1]>::set_param(&mut crossfade, "position", 1.0));
+        assert_eq!(
+            Node::<[f32; 1]>::get_param(&crossfade, "position"),
+            Some(1.0)
+        );
+        assert_eq!(
+            Node::<[f32; 1]>::get_param(&crossfade, "smoothing_frames"),
+            Some(4.0)
+        );
+        assert!(!Node::<[f32; 1]>::set_param(&mut crossfade, "curve", 1.0));
+
+        // The change of position is smoothed just as through `set_position`.
+        let mut expected = Crossfade::new(0.0, FadeCurve::Linear);
+        expected.set_smoothing_frames(4);
+        expected.set_position(1.0);
+        assert_eq!(render(&mut crossfade, 8), render(&mut expected, 8));
+    }
+}
@@ -0,0 +1,1773 @@
+//! Delay nodes.
+
+use crate::buffer::FractionalReader;
+use crate::clock::NoteLength;
+use crate::node::{Node, Settings};
+use crate::oscillator::LfoShape;
+use crate::util;
+use sample::{self, Frame};
+
+/// A multi-channel ring buffer storing the most recent frames written to it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct DelayLine {
+    /// The stored samples, interleaved by channel.
+    samples: Vec<f64>,
+    channels: usize,
+    /// The index of the oldest frame, which is the next to be overwritten.
+    frame_idx: usize,
+}
+
+impl DelayLine {
+    /// The number of frames stored.
+    pub(crate) fn len(&self) -> usize {
+        self.samples.len().checked_div(self.channels).unwrap_or(0)
+    }
+
+    /// Re-size the line to store the given number of frames and channels.
+    ///
+    /// The most recently written frames are kept, so that audio in flight is not lost.
+    pub(crate) fn resize(&mut self, frames: usize, channels: usize) {
+        if frames == self.len() && channels == self.channels {
+            return;
+        }
+        let mut samples = vec![0.0; frames * channels];
+        if channels == self.channels {
+            let kept = frames.min(self.len());
+            for i in 0..kept {
+                // Copy frames newest first into the end of the new line.
+                let old = (self.frame_idx + self.len() - 1 - i) % self.len();
+                let new = frames - 1 - i;
+                samples[new * channels..(new + 1) * channels]
+                    .copy_from_slice(&self.samples[old * channels..(old + 1) * channels]);
+            }
+        }
+        self.samples = samples;
+        self.channels = channels;
+        self.frame_idx = 0;
+    }
+
+    /// The sample on the given channel of the oldest frame.
+    #[inline]
+    pub(crate) fn oldest(&self, channel: usize) -> f64 {
+        self.samples[self.frame_idx * self.channels + channel]
+    }
+
+    /// Overwrite the sample on the given channel of the oldest frame.
+    #[inline]
+    pub(crate) fn write(&mut self, channel: usize, sample: f64) {
+        self.samples[self.frame_idx * self.channels + channel] = sample;
+    }
+
+    /// The sample on the given channel of the frame written the given number of frames ago, where
+    /// `1` is the most recently written frame and `len` the oldest.
+    #[inline]
+    pub(crate) fn past(&self, channel: usize, frames_ago: usize) -> f64 {
+        let len = self.len();
+        let idx = (self.frame_idx + len - frames_ago) % len;
+        self.samples[idx * self.channels + channel]
+    }
+
+    /// Step forward by one frame, making the newly written frame the most recent.
+    #[inline]
+    pub(crate) fn advance(&mut self) {
+        self.frame_idx = (self.frame_idx + 1) % self.len();
+    }
+
+    /// Silence every stored frame.
+    pub(crate) fn clear(&mut self) {
+        for s in self.samples.iter_mut() {
+            *s = 0.0;
+        }
+    }
+}
+
+/// The method used by the delay nodes to read between the frames stored within their delay lines.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DelayInterpolation {
+    /// Read the nearest stored frame. The cheapest, but a moving read position steps between
+    /// frames, adding a gritty distortion.
+    NearestNeighbor,
+    /// Linearly interpolate between the two nearest frames. Cheap and free of ringing, but
+    /// slightly attenuates high frequencies at fractional delays.
+    #[default]
+    Linear,
+    /// Interpolate a Catmull-Rom spline through the four nearest frames. Attenuates high
+    /// frequencies far less than **Linear**, at a little more cost.
+    Cubic,
+    /// Use a first-order all-pass filter, which leaves the magnitude of every frequency untouched
+    /// at the cost of a little phase distortion. Best suited to delay times that change slowly.
+    AllPass,
+}
+
+/// The parameters of a **Delay**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DelayParams {
+    /// The delay time in seconds.
+    pub delay_secs: f64,
+    /// The amount of the delayed signal fed back into the delay line.
+    pub feedback: f64,
+    /// The amount of the delayed (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+    /// The method used to read between stored frames.
+    pub interpolation: DelayInterpolation,
+    /// Whether stereo echoes alternate between the left and right channels.
+    pub ping_pong: bool,
+}
+
+/// A feedback delay **Node**.
+///
+/// Each channel is delayed by `delay_secs` using a ring buffer sized from the `sample_hz` passed to
+/// `audio_requested`. The ring buffer grows whenever the delay time or sample rate requires it,
+/// keeping the most recent audio.
+///
+/// The delay need not be a whole number of frames: the ring buffer is read at a fractional
+/// position according to the **DelayInterpolation**. When the delay time changes, the read
+/// position glides to the new delay over the following buffer, so sweeping the delay time (e.g.
+/// for chorus or flanging) produces no discontinuities. Non-zero delays shorter than one frame
+/// are lengthened to one frame.
+///
+/// In ping-pong mode, stereo echoes bounce between the channels: the input and feedback of each
+/// channel are written to the delay line of the other, so an impulse on the left channel is first
+/// echoed on the right, then on the left, and so on. Ping-pong mode has no effect upon frames with
+/// any other number of channels.
+///
+/// The `mix` is applied by the `Graph` via `Node::mix`, so the **Delay** itself renders a fully wet
+/// signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delay {
+    delay_secs: f64,
+    feedback: f64,
+    mix: f32,
+    ping_pong: bool,
+    line: DelayLine,
+    reader: FractionalReader,
+    /// The delay in frames at the end of the last buffer, if any have been rendered.
+    delay_frames: Option<f64>,
+}
+
+impl Delay {
+    /// Construct a **Delay** with the given delay time in seconds, no feedback and a fully wet mix.
+    pub fn new(delay_secs: f64) -> Self {
+        Delay {
+            delay_secs: delay_secs.max(0.0),
+            feedback: 0.0,
+            mix: 1.0,
+            ping_pong: false,
+            line: DelayLine::default(),
+            reader: FractionalReader::default(),
+            delay_frames: None,
+        }
+    }
+
+    /// The delay time in seconds.
+    pub fn delay_secs(&self) -> f64 {
+        self.delay_secs
+    }
+
+    /// Set the delay time in seconds.
+    pub fn set_delay_secs(&mut self, delay_secs: f64) {
+        self.delay_secs = delay_secs.max(0.0);
+    }
+
+    /// Set the delay time to the length of the given note at the given tempo in beats per minute.
+    pub fn set_delay_note(&mut self, note: NoteLength, bpm: f64) {
+        self.set_delay_secs(note.secs(bpm));
+    }
+
+    /// The amount of the delayed signal fed back into the delay line.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Set the amount of the delayed signal fed back into the delay line.
+    ///
+    /// Clamped to the range -1.0 ... 1.0 exclusive so that the delay always decays.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        const MAX_FEEDBACK: f64 = 0.999;
+        self.feedback = feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+    }
+
+    /// The amount of the delayed (wet) signal in the output (0.0 ... 1.0).
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the delayed (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The method used to read between stored frames.
+    pub fn interpolation(&self) -> DelayInterpolation {
+        self.reader.interpolation()
+    }
+
+    /// Set the method used to read between stored frames.
+    pub fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        self.reader.set_interpolation(interpolation);
+    }
+
+    /// Whether stereo echoes alternate between the left and right channels.
+    pub fn ping_pong(&self) -> bool {
+        self.ping_pong
+    }
+
+    /// Set whether stereo echoes alternate between the left and right channels.
+    pub fn set_ping_pong(&mut self, ping_pong: bool) {
+        self.ping_pong = ping_pong;
+    }
+
+    /// The parameters of the **Delay**.
+    pub fn params(&self) -> DelayParams {
+        DelayParams {
+            delay_secs: self.delay_secs,
+            feedback: self.feedback,
+            mix: self.mix,
+            interpolation: self.reader.interpolation(),
+            ping_pong: self.ping_pong,
+        }
+    }
+
+    /// Apply the given parameters, leaving the audio within the delay line untouched.
+    pub fn set_params(&mut self, params: DelayParams) {
+        self.set_delay_secs(params.delay_secs);
+        self.set_feedback(params.feedback);
+        self.set_mix(params.mix);
+        self.set_interpolation(params.interpolation);
+        self.set_ping_pong(params.ping_pong);
+    }
+
+    /// Silence the audio within the delay line.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.delay_frames = None;
+        self.reader.reset();
+    }
+}
+
+impl<F> Node<F> for Delay
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let target = self.delay_secs * sample_hz;
+        let start = self.delay_frames.unwrap_or(target);
+        self.delay_frames = Some(target);
+        if buffer.is_empty() || (start == 0.0 && target == 0.0) {
+            return;
+        }
+
+        // Reading `n + 2` frames ago must always be possible, where `n` is the whole part of the
+        // longest delay within the buffer, so that **Cubic** interpolation reads the frame beyond
+        // the delay from the audio written rather than the silence added when the line grows.
+        let channels = F::n_channels();
+        let required = start.max(target).max(1.0).floor() as usize + 2;
+        if self.line.len() < required || self.line.channels != channels {
+            self.line.resize(required, channels);
+        }
+        self.reader.prepare(channels);
+
+        let step = (target - start) / buffer.len() as f64;
+        let mut delay = start;
+        let feedback = self.feedback;
+        let ping_pong = self.ping_pong && channels == 2;
+        let line = &mut self.line;
+        let reader = &mut self.reader;
+        sample::slice::map_in_place(buffer, |frame| {
+            delay += step;
+            let mut channel = 0;
+            // The input and feedback of each channel, written crossed in ping-pong mode.
+            let mut written = [0.0; 2];
+            let frame = frame.map(|s| {
+                let x = util::to_f64(s);
+                let delayed = reader.read(line, channel, delay);
+                let y = util::flush_denormal(x + feedback * delayed);
+                if ping_pong {
+                    written[channel] = y;
+                } else {
+                    line.write(channel, y);
+                }
+                channel += 1;
+                util::from_f64(delayed)
+            });
+            if ping_pong {
+                line.write(0, written[1]);
+                line.write(1, written[0]);
+            }
+            line.advance();
+            frame
+        });
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `delay_secs`: the delay time in seconds.
+    /// - `feedback`: the amount of the delayed signal fed back (-1.0 ... 1.0 exclusive).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    /// - `ping_pong`: whether stereo echoes alternate between the channels, where `0` is off and
+    ///   `1` on, rounded to the nearest.
+    fn param_names(&self) -> &[&str] {
+        &["delay_secs", "feedback", "mix", "ping_pong"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "delay_secs" => Some(self.delay_secs as f32),
+            "feedback" => Some(self.feedback as f32),
+            "mix" => Some(self.mix),
+            "ping_pong" => Some(if self.ping_pong { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "delay_secs" => self.set_delay_secs(value as f64),
+            "feedback" => self.set_feedback(value as f64),
+            "mix" => self.set_mix(value),
+            "ping_pong" => self.set_ping_pong(value >= 0.5),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Sizes the delay line for the current delay time at the prepared sample rate and silences
+    /// it.
+    fn prepare(&mut self, settings: Settings) {
+        let channels = F::n_channels();
+        let required = (self.delay_secs * settings.sample_hz).max(1.0).floor() as usize + 2;
+        self.line.resize(required, channels);
+        self.reader.prepare(channels);
+        Delay::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Delay::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "delay"
+    }
+}
+
+/// A **Node** that delays its input by a fixed number of frames, reporting the delay as its
+/// latency.
+///
+/// Used by `Graph::compensate_latency` to align branches of differing latency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompensationDelay {
+    frames: usize,
+    line: DelayLine,
+}
+
+impl CompensationDelay {
+    /// Construct a **CompensationDelay** delaying its input by the given number of frames.
+    pub fn new(frames: usize) -> Self {
+        CompensationDelay {
+            frames,
+            line: DelayLine::default(),
+        }
+    }
+
+    /// The number of frames by which the input is delayed.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Silence the audio within the delay line.
+    pub fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+impl<F> Node<F> for CompensationDelay
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        self.line.resize(self.frames, F::n_channels());
+        if self.frames == 0 {
+            return;
+        }
+        let line = &mut self.line;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let delayed = line.oldest(channel);
+                line.write(channel, util::to_f64(s));
+                channel += 1;
+                util::from_f64(delayed)
+            });
+            line.advance();
+            frame
+        });
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.frames
+    }
+
+    fn reset(&mut self) {
+        CompensationDelay::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "compensation_delay"
+    }
+}
+
+/// The delay in milliseconds about which each **Chorus** voice is modulated.
+pub const CHORUS_DELAY_MS: f64 = 12.0;
+
+/// The distance in milliseconds that each **Chorus** voice's delay moves either side of
+/// `CHORUS_DELAY_MS` at full depth.
+pub const CHORUS_MAX_DEPTH_MS: f64 = 6.0;
+
+/// The maximum number of **Chorus** voices.
+pub const CHORUS_MAX_VOICES: usize = 8;
+
+/// The parameters of a **Chorus**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChorusParams {
+    /// The rate at which each voice's delay is modulated, in hertz.
+    pub rate_hz: f64,
+    /// The amount by which each voice's delay is modulated (0.0 ... 1.0).
+    pub depth: f64,
+    /// The amount of the chorused (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+    /// The number of modulated voices (1 ... `CHORUS_MAX_VOICES`).
+    pub voices: usize,
+    /// The method used to read each voice between stored frames.
+    pub interpolation: DelayInterpolation,
+}
+
+/// A chorus **Node**, thickening its input by mixing it with several slightly detuned copies.
+///
+/// Each voice reads a single delay line at a delay that sweeps sinusoidally about
+/// `CHORUS_DELAY_MS`, by up to `CHORUS_MAX_DEPTH_MS` at full `depth`. The moving read position
+/// continually shifts the pitch of each copy up and down. The voices are spread evenly across the
+/// LFO's cycle, so that they are never detuned in the same direction at once. The wet signal is
+/// the average of the voices, each read according to the **DelayInterpolation**.
+///
+/// The LFO's phase and the delay line carry across buffers. As with the **Delay**, the `mix` is
+/// applied by the `Graph` via `Node::mix`, so a `mix` of `0.0` outputs the input exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chorus {
+    rate_hz: f64,
+    depth: f64,
+    mix: f32,
+    voices: usize,
+    line: DelayLine,
+    /// A reader for each voice, so that all-pass interpolation keeps separate history per voice.
+    readers: Vec<FractionalReader>,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl Chorus {
+    /// Construct a **Chorus** with the given rate, depth and number of voices, and an even mix.
+    pub fn new(rate_hz: f64, depth: f64, voices: usize) -> Self {
+        Chorus {
+            rate_hz: rate_hz.max(0.0),
+            depth: depth.clamp(0.0, 1.0),
+            mix: 0.5,
+            voices: voices.clamp(1, CHORUS_MAX_VOICES),
+            line: DelayLine::default(),
+            readers: vec![FractionalReader::default(); CHORUS_MAX_VOICES],
+            phase: 0.0,
+        }
+    }
+
+    /// The rate at which each voice's delay is modulated, in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate at which each voice's delay is modulated, in hertz.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        self.rate_hz = rate_hz.max(0.0);
+    }
+
+    /// The amount by which each voice's delay is modulated.
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    /// Set the amount by which each voice's delay is modulated (0.0 ... 1.0).
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// The amount of the chorused (wet) signal in the output.
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the chorused (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The number of modulated voices.
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+
+    /// Set the number of modulated voices (1 ... `CHORUS_MAX_VOICES`).
+    pub fn set_voices(&mut self, voices: usize) {
+        self.voices = voices.clamp(1, CHORUS_MAX_VOICES);
+    }
+
+    /// The method used to read each voice between stored frames.
+    pub fn interpolation(&self) -> DelayInterpolation {
+        self.readers[0].interpolation()
+    }
+
+    /// Set the method used to read each voice between stored frames.
+    pub fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        for reader in self.readers.iter_mut() {
+            reader.set_interpolation(interpolation);
+        }
+    }
+
+    /// The parameters of the **Chorus**.
+    pub fn params(&self) -> ChorusParams {
+        ChorusParams {
+            rate_hz: self.rate_hz,
+            depth: self.depth,
+            mix: self.mix,
+            voices: self.voices,
+            interpolation: self.interpolation(),
+        }
+    }
+
+    /// Apply the given parameters, leaving the delay line and LFO phase untouched.
+    pub fn set_params(&mut self, params: ChorusParams) {
+        self.set_rate_hz(params.rate_hz);
+        self.set_depth(params.depth);
+        self.set_mix(params.mix);
+        self.set_voices(params.voices);
+        self.set_interpolation(params.interpolation);
+    }
+
+    /// Silence the delay line and return the LFO to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        for reader in self.readers.iter_mut() {
+            reader.reset();
+        }
+        self.phase = 0.0;
+    }
+}
+
+impl<F> Node<F> for Chorus
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let frames_per_ms = sample_hz / 1_000.0;
+        let channels = F::n_channels();
+        // Leave room to read one frame beyond the longest delay.
+        let required =
+            ((CHORUS_DELAY_MS + CHORUS_MAX_DEPTH_MS) * frames_per_ms).ceil() as usize + 2;
+        if self.line.len() < required || self.line.channels != channels {
+            self.line.resize(required, channels);
+        }
+
+        let centre = CHORUS_DELAY_MS * frames_per_ms;
+        let sweep = self.depth * CHORUS_MAX_DEPTH_MS * frames_per_ms;
+        let voices = self.voices;
+        let phase_step = self.rate_hz / sample_hz;
+        for reader in self.readers.iter_mut() {
+            reader.prepare(channels);
+        }
+        let line = &mut self.line;
+        let readers = &mut self.readers;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let mut sum = 0.0;
+                for (voice, reader) in readers[..voices].iter_mut().enumerate() {
+                    let voice_phase = (*phase + voice as f64 / voices as f64).fract();
+                    let delay = centre + sweep * LfoShape::Sine.value(voice_phase);
+                    sum += reader.read(line, channel, delay);
+                }
+                line.write(channel, util::to_f64(s));
+                channel += 1;
+                util::from_f64(sum / voices as f64)
+            });
+            line.advance();
+            *phase = (*phase + phase_step).fract();
+            frame
+        });
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `rate_hz`: the rate of the modulation in hertz.
+    /// - `depth`: the amount of modulation (0.0 ... 1.0).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    /// - `voices`: the number of voices, rounded to the nearest integer (1 ...
+    ///   `CHORUS_MAX_VOICES`).
+    fn param_names(&self) -> &[&str] {
+        &["rate_hz", "depth", "mix", "voices"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth" => Some(self.depth as f32),
+            "mix" => Some(self.mix),
+            "voices" => Some(self.voices as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth" => self.set_depth(value as f64),
+            "mix" => self.set_mix(value),
+            "voices" => self.set_voices(value.round().max(0.0) as usize),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Chorus::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "chorus"
+    }
+}
+
+/// The shortest delay in milliseconds reached by a **Flanger**'s sweep.
+pub const FLANGER_MIN_DELAY_MS: f64 = 1.0;
+
+/// The longest delay in milliseconds reached by a **Flanger**'s sweep at full depth.
+pub const FLANGER_MAX_DELAY_MS: f64 = 10.0;
+
+/// The largest magnitude of a **Flanger**'s feedback. Short delays resonate strongly, so the limit
+/// is lower than that of the **Delay**.
+pub const FLANGER_MAX_FEEDBACK: f64 = 0.95;
+
+/// The parameters of a **Flanger**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlangerParams {
+    /// The rate at which the delay is swept, in hertz.
+    pub rate_hz: f64,
+    /// The extent of the sweep (0.0 ... 1.0).
+    pub depth: f64,
+    /// The amount of the delayed signal fed back into the delay line
+    /// (`-FLANGER_MAX_FEEDBACK` ... `FLANGER_MAX_FEEDBACK`).
+    pub feedback: f64,
+    /// The amount of the flanged (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+    /// The method used to read the delay line between stored frames.
+    pub interpolation: DelayInterpolation,
+}
+
+/// A flanger **Node**, sweeping a series of comb filter notches through the spectrum.
+///
+/// The input is delayed by a short time that sweeps sinusoidally upwards from
+/// `FLANGER_MIN_DELAY_MS`, reaching `FLANGER_MAX_DELAY_MS` at full `depth`. Mixing the delayed
+/// signal with the input cancels the frequencies at which the two are out of phase, forming
+/// evenly spaced notches that move as the delay does. Feeding the delayed signal back sharpens the
+/// peaks between the notches, while negative feedback shifts them by half their spacing.
+///
+/// The delay line and LFO phase carry across buffers. As with the **Delay**, the `mix` is applied
+/// by the `Graph` via `Node::mix`. A `mix` of `0.5` gives the deepest notches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flanger {
+    rate_hz: f64,
+    depth: f64,
+    feedback: f64,
+    mix: f32,
+    line: DelayLine,
+    reader: FractionalReader,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl Flanger {
+    /// Construct a **Flanger** with the given rate and depth, no feedback and an even mix.
+    pub fn new(rate_hz: f64, depth: f64) -> Self {
+        Flanger {
+            rate_hz: rate_hz.max(0.0),
+            depth: depth.clamp(0.0, 1.0),
+            feedback: 0.0,
+            mix: 0.5,
+            line: DelayLine::default(),
+            reader: FractionalReader::default(),
+            phase: 0.0,
+        }
+    }
+
+    /// The rate at which the delay is swept, in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate at which the delay is swept, in hertz.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        self.rate_hz = rate_hz.max(0.0);
+    }
+
+    /// The extent of the sweep.
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    /// Set the extent of the sweep (0.0 ... 1.0).
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// The amount of the delayed signal fed back into the delay line.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Set the amount of the delayed signal fed back into the delay line.
+    ///
+    /// Clamped to `-FLANGER_MAX_FEEDBACK ... FLANGER_MAX_FEEDBACK`, so that the comb always
+    /// decays.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.clamp(-FLANGER_MAX_FEEDBACK, FLANGER_MAX_FEEDBACK);
+    }
+
+    /// The amount of the flanged (wet) signal in the output.
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the flanged (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The method used to read between stored frames.
+    pub fn interpolation(&self) -> DelayInterpolation {
+        self.reader.interpolation()
+    }
+
+    /// Set the method used to read between stored frames.
+    pub fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        self.reader.set_interpolation(interpolation);
+    }
+
+    /// The parameters of the **Flanger**.
+    pub fn params(&self) -> FlangerParams {
+        FlangerParams {
+            rate_hz: self.rate_hz,
+            depth: self.depth,
+            feedback: self.feedback,
+            mix: self.mix,
+            interpolation: self.reader.interpolation(),
+        }
+    }
+
+    /// Apply the given parameters, leaving the delay line and LFO phase untouched.
+    pub fn set_params(&mut self, params: FlangerParams) {
+        self.set_rate_hz(params.rate_hz);
+        self.set_depth(params.depth);
+        self.set_feedback(params.feedback);
+        self.set_mix(params.mix);
+        self.set_interpolation(params.interpolation);
+    }
+
+    /// Silence the delay line and return the LFO to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.reader.reset();
+        self.phase = 0.0;
+    }
+}
+
+impl<F> Node<F> for Flanger
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let frames_per_ms = sample_hz / 1_000.0;
+        let channels = F::n_channels();
+        // Leave room to read one frame beyond the longest delay.
+        let required = (FLANGER_MAX_DELAY_MS * frames_per_ms).ceil() as usize + 2;
+        if self.line.len() < required || self.line.channels != channels {
+            self.line.resize(required, channels);
+        }
+
+        let min = FLANGER_MIN_DELAY_MS * frames_per_ms;
+        let sweep = self.depth * (FLANGER_MAX_DELAY_MS - FLANGER_MIN_DELAY_MS) * frames_per_ms;
+        let feedback = self.feedback;
+        let phase_step = self.rate_hz / sample_hz;
+        self.reader.prepare(channels);
+        let line = &mut self.line;
+        let reader = &mut self.reader;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |frame| {
+            // Sweep from the minimum delay up to the maximum and back.
+            let lfo = 0.5 + 0.5 * LfoShape::Sine.value(*phase);
+            let delay = min + sweep * lfo;
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let delayed = reader.read(line, channel, delay);
+                line.write(
+                    channel,
+                    util::flush_denormal(util::to_f64(s) + feedback * delayed),
+                );
+                channel += 1;
+                util::from_f64(delayed)
+            });
+            line.advance();
+            *phase = (*phase + phase_step).fract();
+            frame
+        });
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `rate_hz`: the rate of the sweep in hertz.
+    /// - `depth`: the extent of the sweep (0.0 ... 1.0).
+    /// - `feedback`: the amount of the delayed signal fed back (-`FLANGER_MAX_FEEDBACK` ...
+    ///   `FLANGER_MAX_FEEDBACK`).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["rate_hz", "depth", "feedback", "mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth" => Some(self.depth as f32),
+            "feedback" => Some(self.feedback as f32),
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth" => self.set_depth(value as f64),
+            "feedback" => self.set_feedback(value as f64),
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Flanger::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "flanger"
+    }
+}
+
+/// The maximum depth of a **Vibrato** in milliseconds.
+pub const VIBRATO_MAX_DEPTH_MS: f64 = 10.0;
+
+/// The parameters of a **Vibrato**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VibratoParams {
+    /// The rate of the pitch modulation in hertz.
+    pub rate_hz: f64,
+    /// The distance in milliseconds that the delay moves either side of its centre
+    /// (0.0 ... `VIBRATO_MAX_DEPTH_MS`).
+    pub depth_ms: f64,
+    /// The method used to read the delay line between stored frames.
+    pub interpolation: DelayInterpolation,
+}
+
+/// A vibrato **Node**, wavering the pitch of its input.
+///
+/// The input is read from a delay line at a delay that sweeps sinusoidally by `depth_ms` either
+/// side of its centre. As the delay shortens the input is read faster, raising its pitch, and as it
+/// lengthens the pitch falls. For a sweep of depth `d` seconds at `rate_hz`, the pitch deviates by
+/// a factor of up to `1 ± 2π · rate_hz · d`.
+///
+/// Unlike the **Chorus**, the output is purely the modulated signal with no dry signal mixed in.
+/// The centre of the sweep sits `depth_ms` plus one frame behind the input, which is reported by
+/// `latency_samples` once audio has been requested. The delay line and LFO phase carry across
+/// buffers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vibrato {
+    rate_hz: f64,
+    depth_ms: f64,
+    line: DelayLine,
+    reader: FractionalReader,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+    /// The sample rate passed to the last call to `audio_requested`.
+    sample_hz: f64,
+}
+
+impl Vibrato {
+    /// Construct a **Vibrato** with the given rate and depth.
+    pub fn new(rate_hz: f64, depth_ms: f64) -> Self {
+        Vibrato {
+            rate_hz: rate_hz.max(0.0),
+            depth_ms: depth_ms.clamp(0.0, VIBRATO_MAX_DEPTH_MS),
+            line: DelayLine::default(),
+            reader: FractionalReader::default(),
+            phase: 0.0,
+            sample_hz: 0.0,
+        }
+    }
+
+    /// The rate of the pitch modulation in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate of the pitch modulation in hertz.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        self.rate_hz = rate_hz.max(0.0);
+    }
+
+    /// The distance in milliseconds that the delay moves either side of its centre.
+    pub fn depth_ms(&self) -> f64 {
+        self.depth_ms
+    }
+
+    /// Set the distance in milliseconds that the delay moves either side of its centre
+    /// (0.0 ... `VIBRATO_MAX_DEPTH_MS`).
+    pub fn set_depth_ms(&mut self, depth_ms: f64) {
+        self.depth_ms = depth_ms.clamp(0.0, VIBRATO_MAX_DEPTH_MS);
+    }
+
+    /// The method used to read between stored frames.
+    pub fn interpolation(&self) -> DelayInterpolation {
+        self.reader.interpolation()
+    }
+
+    /// Set the method used to read between stored frames.
+    pub fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        self.reader.set_interpolation(interpolation);
+    }
+
+    /// The parameters of the **Vibrato**.
+    pub fn params(&self) -> VibratoParams {
+        VibratoParams {
+            rate_hz: self.rate_hz,
+            depth_ms: self.depth_ms,
+            interpolation: self.reader.interpolation(),
+        }
+    }
+
+    /// Apply the given parameters, leaving the delay line and LFO phase untouched.
+    pub fn set_params(&mut self, params: VibratoParams) {
+        self.set_rate_hz(params.rate_hz);
+        self.set_depth_ms(params.depth_ms);
+        self.set_interpolation(params.interpolation);
+    }
+
+    /// Silence the delay line and return the LFO to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.reader.reset();
+        self.phase = 0.0;
+    }
+
+    /// The delay in frames at the centre of the sweep.
+    fn centre_frames(&self) -> f64 {
+        self.depth_ms / 1_000.0 * self.sample_hz + 1.0
+    }
+}
+
+impl<F> Node<F> for Vibrato
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        self.sample_hz = sample_hz;
+        let channels = F::n_channels();
+        // Leave room to read one frame beyond the longest delay at the maximum depth, so that
+        // changing the depth never requires the line to grow.
+        let required = (2.0 * VIBRATO_MAX_DEPTH_MS / 1_000.0 * sample_hz).ceil() as usize + 3;
+        if self.line.len() < required || self.line.channels != channels {
+            self.line.resize(required, channels);
+        }
+
+        let centre = self.centre_frames();
+        let sweep = self.depth_ms / 1_000.0 * sample_hz;
+        let phase_step = self.rate_hz / sample_hz;
+        self.reader.prepare(channels);
+        let line = &mut self.line;
+        let reader = &mut self.reader;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |frame| {
+            let delay = centre + sweep * LfoShape::Sine.value(*phase);
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let delayed = reader.read(line, channel, delay);
+                line.write(channel, util::to_f64(s));
+                channel += 1;
+                util::from_f64(delayed)
+            });
+            line.advance();
+            *phase = (*phase + phase_step).fract();
+            frame
+        });
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.centre_frames().round() as usize
+    }
+
+    /// Exposes:
+    ///
+    /// - `rate_hz`: the rate of the modulation in hertz.
+    /// - `depth_ms`: the distance in milliseconds that the delay moves either side of its centre
+    ///   (0.0 ... `VIBRATO_MAX_DEPTH_MS`).
+    fn param_names(&self) -> &[&str] {
+        &["rate_hz", "depth_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth_ms" => Some(self.depth_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth_ms" => self.set_depth_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Vibrato::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "vibrato"
+    }
+}
+
+/// The furthest a **PitchShifter** may shift its input, in semitones either way.
+pub const PITCH_SHIFT_MAX_SEMITONES: f64 = 24.0;
+
+/// The shortest grain of a **PitchShifter** in milliseconds.
+pub const PITCH_SHIFT_MIN_GRAIN_MS: f64 = 10.0;
+
+/// The longest grain of a **PitchShifter** in milliseconds.
+pub const PITCH_SHIFT_MAX_GRAIN_MS: f64 = 200.0;
+
+/// The parameters of a **PitchShifter**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PitchShifterParams {
+    /// The shift in semitones (-`PITCH_SHIFT_MAX_SEMITONES` ... `PITCH_SHIFT_MAX_SEMITONES`).
+    pub semitones: f64,
+    /// The length of each grain in milliseconds
+    /// (`PITCH_SHIFT_MIN_GRAIN_MS` ... `PITCH_SHIFT_MAX_GRAIN_MS`).
+    pub grain_size_ms: f64,
+    /// The method used to read each tap between stored frames.
+    pub interpolation: DelayInterpolation,
+}
+
+/// A **Node** that shifts the pitch of its input without changing its duration.
+///
+/// The input is written to a delay line and read back by two taps, half a grain apart, whose delay
+/// sweeps across the length of a grain at the rate needed to play the input back at the shifted
+/// speed. Reading faster than the input is written raises the pitch and reading slower lowers it.
+/// When a tap reaches the end of its sweep it jumps back to the other end, which the taps hide by
+/// crossfading with complementary Hann windows, so that one is always silent as it jumps.
+///
+/// Longer grains smear transients less audibly on sustained material while shorter grains suit
+/// percussive material. Some warbling at the grain rate is expected, particularly for large
+/// shifts. The tap positions carry across buffers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PitchShifter {
+    semitones: f64,
+    grain_size_ms: f64,
+    line: DelayLine,
+    /// A reader for each of the two taps.
+    readers: [FractionalReader; 2],
+    /// The position of the first tap through its grain (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl PitchShifter {
+    /// Construct a **PitchShifter** with the given shift and grain size.
+    pub fn new(semitones: f64, grain_size_ms: f64) -> Self {
+        PitchShifter {
+            semitones: semitones.clamp(-PITCH_SHIFT_MAX_SEMITONES, PITCH_SHIFT_MAX_SEMITONES),
+            grain_size_ms: grain_size_ms.clamp(PITCH_SHIFT_MIN_GRAIN_MS, PITCH_SHIFT_MAX_GRAIN_MS),
+            line: DelayLine::default(),
+            readers: Default::default(),
+            phase: 0.0,
+        }
+    }
+
+    /// The shift in semitones.
+    pub fn semitones(&self) -> f64 {
+        self.semitones
+    }
+
+    /// Set the shift in semitones
+    /// (-`PITCH_SHIFT_MAX_SEMITONES` ... `PITCH_SHIFT_MAX_SEMITONES`).
+    pub fn set_semitones(&mut self, semitones: f64) {
+        self.semitones = semitones.clamp(-PITCH_SHIFT_MAX_SEMITONES, PITCH_SHIFT_MAX_SEMITONES);
+    }
+
+    /// The ratio by which the frequency of the input is multiplied.
+    pub fn ratio(&self) -> f64 {
+        (self.semitones / 12.0).exp2()
+    }
+
+    /// The length of each grain in milliseconds.
+    pub fn grain_size_ms(&self) -> f64 {
+        self.grain_size_ms
+    }
+
+    /// Set the length of each grain in milliseconds
+    /// (`PITCH_SHIFT_MIN_GRAIN_MS` ... `PITCH_SHIFT_MAX_GRAIN_MS`).
+    pub fn set_grain_size_ms(&mut self, grain_size_ms: f64) {
+        self.grain_size_ms =
+            grain_size_ms.clamp(PITCH_SHIFT_MIN_GRAIN_MS, PITCH_SHIFT_MAX_GRAIN_MS);
+    }
+
+    /// The method used to read each tap between stored frames.
+    pub fn interpolation(&self) -> DelayInterpolation {
+        self.readers[0].interpolation()
+    }
+
+    /// Set the method used to read each tap between stored frames.
+    pub fn set_interpolation(&mut self, interpolation: DelayInterpolation) {
+        for reader in self.readers.iter_mut() {
+            reader.set_interpolation(interpolation);
+        }
+    }
+
+    /// The parameters of the **PitchShifter**.
+    pub fn params(&self) -> PitchShifterParams {
+        PitchShifterParams {
+            semitones: self.semitones,
+            grain_size_ms: self.grain_size_ms,
+            interpolation: self.interpolation(),
+        }
+    }
+
+    /// Apply the given parameters, leaving the delay line and tap positions untouched.
+    pub fn set_params(&mut self, params: PitchShifterParams) {
+        self.set_semitones(params.semitones);
+        self.set_grain_size_ms(params.grain_size_ms);
+        self.set_interpolation(params.interpolation);
+    }
+
+    /// Silence the delay line and return the taps to the start of their grains.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        for reader in self.readers.iter_mut() {
+            reader.reset();
+        }
+        self.phase = 0.0;
+    }
+}
+
+impl<F> Node<F> for PitchShifter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let channels = F::n_channels();
+        // Sized for the longest grain so that changing the grain size never requires the line to
+        // grow.
+        let required = (PITCH_SHIFT_MAX_GRAIN_MS / 1_000.0 * sample_hz).ceil() as usize + 3;
+        if self.line.len() < required || self.line.channels != channels {
+            self.line.resize(required, channels);
+        }
+
+        let grain_frames = self.grain_size_ms / 1_000.0 * sample_hz;
+        // The delay of each tap changes by `1 - ratio` frames per frame.
+        let phase_step = (1.0 - self.ratio()) / grain_frames;
+        for reader in self.readers.iter_mut() {
+            reader.prepare(channels);
+        }
+        let line = &mut self.line;
+        let readers = &mut self.readers;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut taps = [(0.0, 0.0); 2];
+            for (i, tap) in taps.iter_mut().enumerate() {
+                let position = (*phase + i as f64 * 0.5).fract();
+                let delay = position * grain_frames + 1.0;
+                let window = (std::f64::consts::PI * position).sin().powi(2);
+                *tap = (delay, window);
+            }
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let shifted = taps
+                    .iter()
+                    .zip(readers.iter_mut())
+                    .map(|(&(delay, window), reader)| reader.read(line, channel, delay) * window)
+                    .sum::<f64>();
+                line.write(channel, util::to_f64(s));
+                channel += 1;
+                util::from_f64(shifted)
+            });
+            line.advance();
+            *phase = (*phase + phase_step).rem_euclid(1.0);
+            frame
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `semitones`: the shift (-`PITCH_SHIFT_MAX_SEMITONES` ... `PITCH_SHIFT_MAX_SEMITONES`).
+    /// - `grain_size_ms`: the length of each grain in milliseconds (`PITCH_SHIFT_MIN_GRAIN_MS` ...
+    ///   `PITCH_SHIFT_MAX_GRAIN_MS`).
+    fn param_names(&self) -> &[&str] {
+        &["semitones", "grain_size_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "semitones" => Some(self.semitones as f32),
+            "grain_size_ms" => Some(self.grain_size_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "semitones" => self.set_semitones(value as f64),
+            "grain_size_ms" => self.set_grain_size_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        PitchShifter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "pitch_shifter"
+    }
+}
+
+/// The shortest stretch factor of a **TimeStretch**, rendering a quarter as long.
+pub const TIME_STRETCH_MIN_FACTOR: f64 = 0.25;
+
+/// The longest stretch factor of a **TimeStretch**, rendering four times as long.
+pub const TIME_STRETCH_MAX_FACTOR: f64 = 4.0;
+
+/// The length of each window overlapped by a **TimeStretch** in milliseconds.
+pub const TIME_STRETCH_WINDOW_MS: f64 = 40.0;
+
+/// The parameters of a **TimeStretch**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeStretchParams {
+    /// The ratio of the output's duration to the input's, e.g. `2.0` for twice as long
+    /// (`TIME_STRETCH_MIN_FACTOR` ... `TIME_STRETCH_MAX_FACTOR`).
+    pub stretch_factor: f64,
+}
+
+/// Changes the duration of a signal without changing its pitch.
+///
+/// As the output is longer or shorter than the input, this is not a **Node**: it cannot pass audio
+/// through a **Graph** in real time. Instead, whole signals (e.g. a file or a recorded take) are
+/// rendered at once via `process_offline`.
+///
+/// Stretching uses WSOLA (waveform similarity overlap-add). The output is assembled from
+/// Hann-windowed segments of the input `TIME_STRETCH_WINDOW_MS` long, overlapping by half. Each
+/// segment is read from around the position of the output in the input, shifted by up to a
+/// quarter of a window to where the input best matches the continuation of the previous segment,
+/// so that overlapping segments add in phase and the pitch is preserved.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeStretch {
+    stretch_factor: f64,
+}
+
+impl TimeStretch {
+    /// Construct a **TimeStretch** with the given stretch factor.
+    pub fn new(stretch_factor: f64) -> Self {
+        TimeStretch {
+            stretch_factor: stretch_factor.clamp(TIME_STRETCH_MIN_FACTOR, TIME_STRETCH_MAX_FACTOR),
+        }
+    }
+
+    /// The ratio of the output's duration to the input's.
+    pub fn stretch_factor(&self) -> f64 {
+        self.stretch_factor
+    }
+
+    /// Set the ratio of the output's duration to the input's
+    /// (`TIME_STRETCH_MIN_FACTOR` ... `TIME_STRETCH_MAX_FACTOR`).
+    pub fn set_stretch_factor(&mut self, stretch_factor: f64) {
+        self.stretch_factor =
+            stretch_factor.clamp(TIME_STRETCH_MIN_FACTOR, TIME_STRETCH_MAX_FACTOR);
+    }
+
+    /// The parameters of the **TimeStretch**.
+    pub fn params(&self) -> TimeStretchParams {
+        TimeStretchParams {
+            stretch_factor: self.stretch_factor,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: TimeStretchParams) {
+        self.set_stretch_factor(params.stretch_factor);
+    }
+
+    /// Render the whole of the given input, sampled at `sample_hz`, stretched by the stretch
+    /// factor.
+    ///
+    /// The output has `input.len() * stretch_factor` frames, rounded to the nearest frame.
+    pub fn process_offline<F>(&self, input: &[F], sample_hz: f64) -> Vec<F>
+    where
+        F: Frame,
+    {
+        let out_len = (input.len() as f64 * self.stretch_factor).round() as usize;
+        let channels = F::n_channels();
+        let window = (((TIME_STRETCH_WINDOW_MS / 1_000.0 * sample_hz) as usize) / 2 * 2).max(4);
+        let out_hop = window / 2;
+        let in_hop = out_hop as f64 / self.stretch_factor;
+        let tolerance = window / 4;
+
+        // The input summed across channels, used to find where the windows match.
+        let mono: Vec<f64> = input
+            .iter()
+            .map(|frame| frame.channels().map(util::to_f64).sum())
+            .collect();
+        let mono_at = |idx: isize| {
+            if idx >= 0 && (idx as usize) < mono.len() {
+                mono[idx as usize]
+            } else {
+                0.0
+            }
+        };
+        let hann: Vec<f64> = (0..window)
+            .map(|i| {
+                (std::f64::consts::PI * i as f64 / window as f64)
+                    .sin()
+                    .powi(2)
+            })
+            .collect();
+
+        let mut samples = vec![0.0; (out_len + window) * channels];
+        let mut weights = vec![0.0; out_len + window];
+        // The input position of the previous window, whose continuation the next should match.
+        let mut prev: Option<isize> = None;
+        let mut segment = 0;
+        while segment * out_hop < out_len {
+            let nominal = (segment as f64 * in_hop).round() as isize;
+            let start = match prev {
+                None => nominal,
+                Some(prev) => {
+                    let target = prev + out_hop as isize;
+                    let correlation = |start: isize| {
+                        (0..out_hop)
+                            .map(|i| mono_at(start + i as isize) * mono_at(target + i as isize))
+                            .sum::<f64>()
+                    };
+                    let mut best = (nominal, f64::MIN);
+                    for offset in -(tolerance as isize)..=tolerance as isize {
+                        let score = correlation(nominal + offset);
+                        if score > best.1 {
+                            best = (nominal + offset, score);
+                        }
+                    }
+                    best.0
+                }
+            };
+
+            let out_start = segment * out_hop;
+            for (i, &w) in hann.iter().enumerate() {
+                let in_idx = start + i as isize;
+                weights[out_start + i] += w;
+                if in_idx < 0 || in_idx as usize >= input.len() {
+                    continue;
+                }
+                let out = &mut samples[(out_start + i) * channels..(out_start + i + 1) * channels];
+                for (o, s) in out.iter_mut().zip(input[in_idx as usize].channels()) {
+                    *o += util::to_f64(s) * w;
+                }
+            }
+            prev = Some(start);
+            segment += 1;
+        }
+
+        (0..out_len)
+            .map(|i| {
+                let weight = weights[i];
+                F::from_fn(|channel| {
+                    let s = samples[i * channels + channel];
+                    util::from_f64(if weight > 0.0 { s / weight } else { 0.0 })
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn an_impulse_reappears_after_the_delay() {
+        // 10 frames at 1 kHz.
+        let mut delay = Delay::new(0.01);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut delay, 32, 1_000.0);
+        for (i, frame) in output.iter().enumerate() {
+            assert_eq!(frame[0], if i == 10 { 1.0 } else { 0.0 }, "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn feedback_repeats_the_impulse_at_each_multiple_of_the_delay() {
+        let mut delay = Delay::new(0.01);
+        delay.set_feedback(0.5);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut delay, 40, 1_000.0);
+        assert_eq!(output[10], [1.0]);
+        assert_eq!(output[20], [0.5]);
+        assert_eq!(output[30], [0.25]);
+        assert_eq!(output[15], [0.0]);
+    }
+
+    #[test]
+    fn changing_the_delay_or_sample_rate_mid_stream_is_safe() {
+        let mut delay = Delay::new(0.005);
+        delay.set_feedback(0.7);
+        let mut buffer = testing::sine::<[f32; 2]>(256, 440.0, 0.5, 44_100.0);
+        delay.audio_requested(&mut buffer, 44_100.0);
+        delay.set_delay_secs(0.05);
+        delay.audio_requested(&mut buffer, 44_100.0);
+        delay.audio_requested(&mut buffer, 96_000.0);
+        delay.set_delay_secs(0.0);
+        delay.audio_requested(&mut buffer, 96_000.0);
+        assert!(buffer
+            .iter()
+            .all(|frame| frame.iter().all(|s| s.is_finite())));
+
+        // Once reset at the new rate, the impulse lands at the new length of the delay.
+        let mut delay = Delay::new(0.01);
+        let _: Vec<[f32; 1]> = testing::impulse_response(&mut delay, 32, 1_000.0);
+        delay.reset();
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut delay, 32, 2_000.0);
+        assert_eq!(output[20], [1.0]);
+        assert!(output.iter().filter(|frame| frame[0] != 0.0).count() == 1);
+    }
+
+    #[test]
+    fn a_fractional_delay_interpolates_between_frames() {
+        // 2.5 frames at 1 kHz.
+        let mut delay = Delay::new(0.0025);
+        let mut buffer: Vec<[f32; 1]> = (0..16).map(|i| [i as f32 * 0.05]).collect();
+        delay.audio_requested(&mut buffer, 1_000.0);
+        for (i, frame) in buffer.iter().enumerate().skip(3) {
+            assert!(
+                (frame[0] - (i as f32 - 2.5) * 0.05).abs() < 1e-6,
+                "frame {}",
+                i
+            );
+        }
+    }
+
+    /// The greatest second difference of the output while sweeping the delay from 5 to 20 frames.
+    fn max_curvature_of_sweep(interpolation: DelayInterpolation) -> f32 {
+        let sample_hz = 48_000.0;
+        let mut delay = Delay::new(5.0 / sample_hz);
+        delay.set_interpolation(interpolation);
+        let mut output = testing::sine::<[f32; 1]>(4_800, 100.0, 1.0, sample_hz);
+        let chunks = output.len() / 64;
+        for (i, chunk) in output.chunks_mut(64).enumerate() {
+            delay.audio_requested(chunk, sample_hz);
+            delay.set_delay_secs((5.0 + 15.0 * (i + 1) as f64 / chunks as f64) / sample_hz);
+        }
+        output[64..]
+            .windows(3)
+            .map(|w| (w[2][0] - 2.0 * w[1][0] + w[0][0]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn sweeping_the_delay_time_produces_no_discontinuities() {
+        // The second difference of the undelayed sine peaks at roughly 1.7e-4.
+        let linear = max_curvature_of_sweep(DelayInterpolation::Linear);
+        assert!(linear < 1e-3, "{}", linear);
+        let cubic = max_curvature_of_sweep(DelayInterpolation::Cubic);
+        assert!(cubic < 1e-3, "{}", cubic);
+        // Whereas stepping between frames does not.
+        let nearest = max_curvature_of_sweep(DelayInterpolation::NearestNeighbor);
+        assert!(nearest > 5e-3, "{}", nearest);
+    }
+
+    #[test]
+    fn chorus_adds_sidebands_to_a_steady_tone() {
+        // A 1 kHz tone modulated at 20 Hz, analysed over the final second so that every
+        // component completes a whole number of cycles.
+        let sample_hz = 48_000.0;
+        let input = testing::sine::<[f32; 1]>(50_400, 1_000.0, 0.5, sample_hz);
+        let mut chorus = Chorus::new(20.0, 0.01, 1);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(300) {
+            chorus.audio_requested(chunk, sample_hz);
+        }
+        let (input, output) = (&input[2_400..], &output[2_400..]);
+        for &hz in &[980.0, 1_020.0, 960.0, 1_040.0] {
+            assert!(
+                testing::power_at(input, hz, sample_hz) < 1e-12,
+                "dry {} Hz",
+                hz
+            );
+        }
+        let carrier = testing::power_at(output, 1_000.0, sample_hz);
+        for &hz in &[980.0, 1_020.0] {
+            let sideband = testing::power_at(output, hz, sample_hz);
+            assert!(
+                sideband > carrier * 0.01,
+                "{} Hz: {} vs {}",
+                hz,
+                sideband,
+                carrier
+            );
+        }
+        // Nothing appears between the sidebands.
+        assert!(testing::power_at(output, 1_010.0, sample_hz) < carrier * 1e-4);
+    }
+
+    #[test]
+    fn chorus_outputs_the_input_exactly_at_zero_mix() {
+        /// A source playing back a buffer.
+        struct Playback(Vec<[f32; 1]>);
+
+        impl Node<[f32; 1]> for Playback {
+            fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+                let len = buffer.len().min(self.0.len());
+                buffer[..len].copy_from_slice(&self.0[..len]);
+                self.0.drain(..len);
+            }
+
+            fn is_source(&self) -> bool {
+                true
+            }
+        }
+
+        let input = testing::sine::<[f32; 1]>(2_048, 440.0, 0.5, 44_100.0);
+        let mut graph: crate::Graph<[f32; 1], Box<dyn Node<[f32; 1]>>> = crate::Graph::new();
+        let mut chorus = Chorus::new(2.0, 1.0, 4);
+        chorus.set_mix(0.0);
+        let chorus = graph.add_node(Box::new(chorus) as Box<dyn Node<[f32; 1]>>);
+        graph.add_input(Box::new(Playback(input.clone())), chorus);
+        graph.set_master(Some(chorus));
+        let mut output = vec![[0.0]; 2_048];
+        for chunk in output.chunks_mut(512) {
+            graph.audio_requested(chunk, 44_100.0);
+        }
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn flanger_notches_move_with_the_sweep() {
+        // The delay sweeps from 5.5 ms up to 10 ms at a quarter of the cycle, where the notches lie
+        // at odd multiples of 50 Hz, and down to 1 ms at three quarters, with notches at odd
+        // multiples of 500 Hz.
+        let sample_hz = 48_000.0;
+        let len = 8 * 48_000;
+        let tones: Vec<Vec<[f32; 1]>> = [200.0, 250.0, 500.0, 1_000.0]
+            .iter()
+            .map(|&hz| testing::sine(len, hz, 0.2, sample_hz))
+            .collect();
+        let input: Vec<[f32; 1]> = (0..len)
+            .map(|i| [tones.iter().map(|tone| tone[i][0]).sum()])
+            .collect();
+        let mut flanger = Flanger::new(0.1, 1.0);
+        let mut wet = input.clone();
+        for chunk in wet.chunks_mut(512) {
+            flanger.audio_requested(chunk, sample_hz);
+        }
+        let output: Vec<[f32; 1]> = input
+            .iter()
+            .zip(&wet)
+            .map(|(dry, wet)| [0.5 * (dry[0] + wet[0])])
+            .collect();
+        // A tenth of a second centred on each extreme of the sweep.
+        let longest = &output[117_600..122_400];
+        let shortest = &output[357_600..362_400];
+
+        let power = |window: &[[f32; 1]], hz| testing::power_at(window, hz, sample_hz);
+        assert!(power(longest, 250.0) < power(longest, 200.0) * 1e-3);
+        assert!(power(longest, 1_000.0) > power(longest, 200.0) * 0.9);
+        assert!(power(shortest, 500.0) < power(shortest, 1_000.0) * 1e-3);
+        // The notch at 250 Hz has moved on, leaving half the power of an unfiltered tone.
+        assert!(power(shortest, 250.0) > power(longest, 250.0) * 100.0);
+        assert!((power(shortest, 250.0) / power(shortest, 1_000.0) - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn vibrato_adds_sidebands_at_the_lfo_rate_to_a_steady_tone() {
+        let sample_hz = 48_000.0;
+        let input = testing::sine::<[f32; 1]>(50_400, 1_000.0, 0.5, sample_hz);
+        let mut vibrato = Vibrato::new(20.0, 0.06);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(300) {
+            vibrato.audio_requested(chunk, sample_hz);
+        }
+        let (input, output) = (&input[2_400..], &output[2_400..]);
+        let carrier = testing::power_at(output, 1_000.0, sample_hz);
+        for &hz in &[980.0, 1_020.0] {
+            let sideband = testing::power_at(output, hz, sample_hz);
+            assert!(
+                sideband > carrier * 0.01,
+                "{} Hz: {} vs {}",
+                hz,
+                sideband,
+                carrier
+            );
+        }
+        assert!(testing::power_at(output, 1_010.0, sample_hz) < carrier * 1e-4);
+        // Only the pitch wavers, moving power from the carrier into the sidebands.
+        assert!(carrier < testing::power_at(input, 1_000.0, sample_hz) * 0.95);
+        assert!((testing::rms(output) / testing::rms(input) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn shifting_a_tone_up_an_octave_doubles_its_frequency() {
+        let sample_hz = 48_000.0;
+        let mut output = testing::sine::<[f32; 1]>(48_000, 500.0, 0.5, sample_hz);
+        let mut shifter = PitchShifter::new(12.0, 40.0);
+        for chunk in output.chunks_mut(256) {
+            shifter.audio_requested(chunk, sample_hz);
+        }
+        let window = &output[24_000..];
+        assert_eq!(peak_hz(window, sample_hz), 1_000.0);
+        assert!(
+            testing::power_at(window, 1_000.0, sample_hz)
+                > testing::power_at(window, 500.0, sample_hz) * 10.0
+        );
+    }
+
+    /// The frequency between 200 Hz and 2 kHz, to the nearest 10 Hz, with the most power.
+    fn peak_hz(buffer: &[[f32; 1]], sample_hz: f64) -> f64 {
+        (20..200)
+            .map(|i| i as f64 * 10.0)
+            .max_by(|&a, &b| {
+                let (a, b) = (
+                    testing::power_at(buffer, a, sample_hz),
+                    testing::power_at(buffer, b, sample_hz),
+                );
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn stretching_doubles_the_length_and_preserves_the_pitch() {
+        let sample_hz = 48_000.0;
+        let input = testing::sine::<[f32; 1]>(24_000, 440.0, 0.5, sample_hz);
+        let output = TimeStretch::new(2.0).process_offline(&input, sample_hz);
+        assert_eq!(output.len(), 48_000);
+        // A half second window from within the stretched signal.
+        let window = &output[12_000..36_000];
+        assert_eq!(peak_hz(window, sample_hz), 440.0);
+        assert!((testing::rms(window) / testing::rms(&input) - 1.0).abs() < 0.1);
+
+        let output = TimeStretch::new(0.5).process_offline(&input, sample_hz);
+        assert_eq!(output.len(), 12_000);
+        assert_eq!(peak_hz(&output[..9_600], sample_hz), 440.0);
+    }
+
+    #[test]
+    fn ping_pong_echoes_alternate_between_the_channels() {
+        let mut delay = Delay::new(0.01);
+        delay.set_feedback(0.5);
+        delay.set_ping_pong(true);
+        let mut buffer = vec![[0.0f32; 2]; 40];
+        buffer[0] = [1.0, 0.0];
+        delay.audio_requested(&mut buffer, 1_000.0);
+        for (i, frame) in buffer.iter().enumerate() {
+            let expected = match i {
+                10 => [0.0, 1.0],
+                20 => [0.5, 0.0],
+                30 => [0.0, 0.25],
+                _ => [0.0; 2],
+            };
+            assert_eq!(*frame, expected, "frame {}", i);
+        }
+
+        // Without ping-pong, the echoes stay on the channel of the impulse.
+        delay.set_ping_pong(false);
+        delay.reset();
+        let mut buffer = vec![[0.0f32; 2]; 21];
+        buffer[0] = [1.0, 0.0];
+        delay.audio_requested(&mut buffer, 1_000.0);
+        assert_eq!(buffer[10], [1.0, 0.0]);
+        assert_eq!(buffer[20], [0.5, 0.0]);
+    }
+
+    #[test]
+    fn ping_pong_leaves_other_channel_counts_unchanged() {
+        let mut delay = Delay::new(0.01);
+        delay.set_ping_pong(true);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut delay, 16, 1_000.0);
+        assert_eq!(output[10], [1.0]);
+    }
+
+    #[test]
+    fn prepare_sizes_the_delay_line_before_streaming() {
+        let mut delay = Delay::new(0.01);
+        Node::<[f32; 2]>::prepare(&mut delay, Settings::new(1_000.0, 64));
+        assert_eq!(delay.line.len(), 12);
+        // Preparing a higher rate and a larger block grows the line for the longer delay in frames.
+        Node::<[f32; 2]>::prepare(&mut delay, Settings::new(48_000.0, 1_024));
+        assert_eq!(delay.line.len(), 482);
+        assert_eq!(delay.line.channels, 2);
+
+        // Rendering the prepared block needs no further growth.
+        let mut buffer = vec![[0.0f32; 2]; 1_024];
+        buffer[0] = [1.0, -1.0];
+        delay.audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(delay.line.len(), 482);
+        assert_eq!(buffer[480], [1.0, -1.0]);
+        assert!(buffer
+            .iter()
+            .enumerate()
+            .all(|(i, frame)| i == 480 || *frame == [0.0; 2]));
+    }
+}
@@ -0,0 +1,467 @@
+//! Nodes that distort a signal to add harmonics.
+
+use crate::node::Node;
+use crate::util;
+use sample::{self, Frame};
+use std::f64::consts::FRAC_2_PI;
+
+/// The curve with which a **Waveshaper** maps each driven sample onto its output.
+///
+/// Every function is monotonic, odd and bounded to `-1.0 ... 1.0`. Quiet samples pass through
+/// `Tanh` and `HardClip` at unity gain, `Atan` at `2 / π` and `Polynomial` at `1.5`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransferFunction {
+    /// `tanh(x)`. Smooth, warm saturation.
+    #[default]
+    Tanh,
+    /// `2 / π * atan(x)`. Softer than `Tanh`, approaching its limits more slowly.
+    Atan,
+    /// Clamp samples to `-1.0 ... 1.0`. Harsh, with many high harmonics.
+    HardClip,
+    /// `1.5x - 0.5x³`, reaching `±1.0` with zero slope at `±1.0` beyond which samples are clamped.
+    Polynomial,
+}
+
+impl TransferFunction {
+    /// Apply the function to the given sample.
+    #[inline]
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            TransferFunction::Tanh => util::tanh(x),
+            TransferFunction::Atan => FRAC_2_PI * x.atan(),
+            TransferFunction::HardClip => x.clamp(-1.0, 1.0),
+            TransferFunction::Polynomial => {
+                let x = x.clamp(-1.0, 1.0);
+                1.5 * x - 0.5 * x * x * x
+            }
+        }
+    }
+}
+
+/// The parameters of a **Waveshaper**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WaveshaperParams {
+    /// The gain applied to samples before shaping, as a linear amplitude.
+    pub drive: f64,
+    /// The curve applied to the driven samples.
+    pub function: TransferFunction,
+    /// The offset added to driven samples before shaping (-1.0 ... 1.0).
+    pub asymmetry: f64,
+    /// Whether or not the output is scaled to offset the gain added by the drive.
+    pub compensate_gain: bool,
+}
+
+/// A **Node** that saturates its input by passing each sample through a **TransferFunction**.
+///
+/// Samples are multiplied by the `drive` before shaping, so higher drives push more of the signal
+/// into the non-linear region of the curve. The shaping is symmetric unless an `asymmetry` is set,
+/// which offsets the driven samples before shaping to add even harmonics. The resulting DC offset
+/// is subtracted, so that silence remains silent.
+///
+/// With `compensate_gain` enabled the output is scaled so that a full-scale input produces a
+/// full-scale output regardless of the drive, so that increasing the drive adds saturation rather
+/// than volume.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Waveshaper {
+    drive: f64,
+    function: TransferFunction,
+    asymmetry: f64,
+    compensate_gain: bool,
+}
+
+impl Waveshaper {
+    /// Construct a symmetric **Waveshaper** with the given drive and transfer function, and
+    /// without gain compensation.
+    pub fn new(drive: f64, function: TransferFunction) -> Self {
+        Waveshaper {
+            drive: drive.max(0.0),
+            function,
+            asymmetry: 0.0,
+            compensate_gain: false,
+        }
+    }
+
+    /// The gain applied to samples before shaping, as a linear amplitude.
+    pub fn drive(&self) -> f64 {
+        self.drive
+    }
+
+    /// Set the gain applied to samples before shaping, as a linear amplitude.
+    pub fn set_drive(&mut self, drive: f64) {
+        self.drive = drive.max(0.0);
+    }
+
+    /// The curve applied to the driven samples.
+    pub fn function(&self) -> TransferFunction {
+        self.function
+    }
+
+    /// Set the curve applied to the driven samples.
+    pub fn set_function(&mut self, function: TransferFunction) {
+        self.function = function;
+    }
+
+    /// The offset added to driven samples before shaping (-1.0 ... 1.0).
+    pub fn asymmetry(&self) -> f64 {
+        self.asymmetry
+    }
+
+    /// Set the offset added to driven samples before shaping (-1.0 ... 1.0).
+    pub fn set_asymmetry(&mut self, asymmetry: f64) {
+        self.asymmetry = asymmetry.clamp(-1.0, 1.0);
+    }
+
+    /// Whether or not the output is scaled to offset the gain added by the drive.
+    pub fn compensate_gain(&self) -> bool {
+        self.compensate_gain
+    }
+
+    /// Set whether or not the output is scaled to offset the gain added by the drive.
+    pub fn set_compensate_gain(&mut self, compensate_gain: bool) {
+        self.compensate_gain = compensate_gain;
+    }
+
+    /// The parameters of the **Waveshaper**.
+    pub fn params(&self) -> WaveshaperParams {
+        WaveshaperParams {
+            drive: self.drive,
+            function: self.function,
+            asymmetry: self.asymmetry,
+            compensate_gain: self.compensate_gain,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: WaveshaperParams) {
+        self.set_drive(params.drive);
+        self.function = params.function;
+        self.set_asymmetry(params.asymmetry);
+        self.compensate_gain = params.compensate_gain;
+    }
+
+    /// The gain applied to the shaped output, which is `1.0` unless `compensate_gain` is set.
+    pub fn output_gain(&self) -> f64 {
+        if !self.compensate_gain {
+            return 1.0;
+        }
+        // Half the peak-to-peak output for a full-scale input.
+        let f = self.function;
+        let bias = self.asymmetry;
+        let peak = (f.apply(self.drive + bias) - f.apply(-self.drive + bias)) / 2.0;
+        if peak > 0.0 {
+            1.0 / peak
+        } else {
+            1.0
+        }
+    }
+
+    /// Shape a single sample, excluding the `output_gain`.
+    #[inline]
+    pub fn shape(&self, x: f64) -> f64 {
+        let f = self.function;
+        f.apply(x * self.drive + self.asymmetry) - f.apply(self.asymmetry)
+    }
+}
+
+impl<F> Node<F> for Waveshaper
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let gain = self.output_gain();
+        sample::slice::map_in_place(buffer, |frame| {
+            frame.map(|s| util::from_f64(self.shape(util::to_f64(s)) * gain))
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `drive`: the gain applied before shaping as a linear amplitude (at least 0.0).
+    /// - `asymmetry`: the offset added before shaping (-1.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["drive", "asymmetry"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "drive" => Some(self.drive as f32),
+            "asymmetry" => Some(self.asymmetry as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "drive" => self.set_drive(value as f64),
+            "asymmetry" => self.set_asymmetry(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "waveshaper"
+    }
+}
+
+/// The parameters of a **BitCrusher**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitCrusherParams {
+    /// The number of bits to which samples are quantised (1 ... 32).
+    pub bit_depth: u32,
+    /// The number of output frames for which each input frame is held (at least 1).
+    pub downsample_factor: usize,
+}
+
+/// A lo-fi **Node** that reduces both the bit depth and the sample rate of its input.
+///
+/// Each sample is quantised to one of `2^bit_depth` evenly spaced levels across `-1.0 ... 1.0`,
+/// and each frame is held for `downsample_factor` output frames. The held frame is carried over
+/// between buffers, so the hold period is unaffected by the buffer size.
+///
+/// A bit depth of 16 and a factor of 1 leaves `i16` samples untouched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitCrusher {
+    bit_depth: u32,
+    downsample_factor: usize,
+    /// The most recently captured frame, per channel.
+    held: Vec<f64>,
+    /// The number of frames for which the held frame is still to be output.
+    hold_remaining: usize,
+}
+
+impl BitCrusher {
+    /// Construct a **BitCrusher** with the given bit depth and downsampling factor.
+    pub fn new(bit_depth: u32, downsample_factor: usize) -> Self {
+        BitCrusher {
+            bit_depth: bit_depth.clamp(1, 32),
+            downsample_factor: downsample_factor.max(1),
+            held: Vec::new(),
+            hold_remaining: 0,
+        }
+    }
+
+    /// The number of bits to which samples are quantised.
+    pub fn bit_depth(&self) -> u32 {
+        self.bit_depth
+    }
+
+    /// Set the number of bits to which samples are quantised (1 ... 32).
+    pub fn set_bit_depth(&mut self, bit_depth: u32) {
+        self.bit_depth = bit_depth.clamp(1, 32);
+    }
+
+    /// The number of output frames for which each input frame is held.
+    pub fn downsample_factor(&self) -> usize {
+        self.downsample_factor
+    }
+
+    /// Set the number of output frames for which each input frame is held (at least 1).
+    pub fn set_downsample_factor(&mut self, downsample_factor: usize) {
+        self.downsample_factor = downsample_factor.max(1);
+    }
+
+    /// The parameters of the **BitCrusher**.
+    pub fn params(&self) -> BitCrusherParams {
+        BitCrusherParams {
+            bit_depth: self.bit_depth,
+            downsample_factor: self.downsample_factor,
+        }
+    }
+
+    /// Apply the given parameters, leaving the held frame untouched.
+    pub fn set_params(&mut self, params: BitCrusherParams) {
+        self.set_bit_depth(params.bit_depth);
+        self.set_downsample_factor(params.downsample_factor);
+    }
+
+    /// Discard the held frame, so that the next input frame is captured immediately.
+    pub fn reset(&mut self) {
+        self.held.clear();
+        self.hold_remaining = 0;
+    }
+
+    /// Quantise a single sample to the bit depth.
+    #[inline]
+    pub fn quantise(&self, x: f64) -> f64 {
+        quantise(x, self.bit_depth)
+    }
+}
+
+impl<F> Node<F> for BitCrusher
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let bit_depth = self.bit_depth;
+        self.held.resize(F::n_channels(), 0.0);
+        for frame in buffer.iter_mut() {
+            if self.hold_remaining == 0 {
+                for (held, s) in self.held.iter_mut().zip(frame.channels()) {
+                    *held = quantise(util::to_f64(s), bit_depth);
+                }
+                self.hold_remaining = self.downsample_factor;
+            }
+            self.hold_remaining -= 1;
+            let held = &self.held;
+            *frame = F::from_fn(|channel| util::from_f64(held[channel]));
+        }
+    }
+
+    /// Exposes:
+    ///
+    /// - `bit_depth`: the number of bits, rounded to the nearest integer (1 ... 32).
+    /// - `downsample_factor`: the number of frames each is held for, rounded to the nearest integer
+    ///   (at least 1).
+    fn param_names(&self) -> &[&str] {
+        &["bit_depth", "downsample_factor"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "bit_depth" => Some(self.bit_depth as f32),
+            "downsample_factor" => Some(self.downsample_factor as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "bit_depth" => self.set_bit_depth(value.round().max(0.0) as u32),
+            "downsample_factor" => self.set_downsample_factor(value.round().max(0.0) as usize),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        BitCrusher::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "bit_crusher"
+    }
+}
+
+/// Round the given sample to the nearest of `2^bit_depth` levels across `-1.0 ... 1.0`.
+#[inline]
+pub(crate) fn quantise(x: f64, bit_depth: u32) -> f64 {
+    let step = 2.0 / (1u64 << bit_depth) as f64;
+    ((x / step).round() * step).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNCTIONS: [TransferFunction; 4] = [
+        TransferFunction::Tanh,
+        TransferFunction::Atan,
+        TransferFunction::HardClip,
+        TransferFunction::Polynomial,
+    ];
+
+    /// Inputs from -4.0 to 4.0 in ascending order.
+    fn sweep() -> impl Iterator<Item = f64> {
+        (-400..=400).map(|i| i as f64 / 100.0)
+    }
+
+    #[test]
+    fn every_transfer_function_is_monotonic_bounded_and_odd() {
+        for &function in FUNCTIONS.iter() {
+            let mut previous = f64::NEG_INFINITY;
+            for x in sweep() {
+                let y = function.apply(x);
+                assert!(y >= previous, "{:?} at {}", function, x);
+                assert!(y.abs() <= 1.0, "{:?} at {}", function, x);
+                assert!(
+                    (y + function.apply(-x)).abs() < 1e-12,
+                    "{:?} at {}",
+                    function,
+                    x
+                );
+                previous = y;
+            }
+        }
+        let quiet_gains = [1.0, FRAC_2_PI, 1.0, 1.5];
+        for (&function, &gain) in FUNCTIONS.iter().zip(quiet_gains.iter()) {
+            let y = function.apply(0.001);
+            assert!((y - 0.001 * gain).abs() < 1e-6, "{:?}", function);
+        }
+    }
+
+    #[test]
+    fn driven_shaping_stays_monotonic_and_bounded_with_asymmetry() {
+        for &function in FUNCTIONS.iter() {
+            let mut shaper = Waveshaper::new(8.0, function);
+            shaper.set_asymmetry(0.3);
+            assert_eq!(shaper.shape(0.0), 0.0);
+            let mut previous = f64::NEG_INFINITY;
+            for x in sweep().map(|x| x / 4.0) {
+                let y = shaper.shape(x);
+                assert!(y >= previous && y.abs() <= 2.0, "{:?} at {}", function, x);
+                previous = y;
+            }
+            // No longer symmetric.
+            assert!(
+                (shaper.shape(0.5) + shaper.shape(-0.5)).abs() > 1e-3,
+                "{:?}",
+                function
+            );
+        }
+    }
+
+    #[test]
+    fn gain_compensation_keeps_a_full_scale_input_at_full_scale() {
+        for &drive in &[0.5, 1.0, 4.0, 20.0] {
+            let mut shaper = Waveshaper::new(drive, TransferFunction::Atan);
+            shaper.set_compensate_gain(true);
+            let mut buffer = [[1.0f32], [-1.0], [0.25]];
+            shaper.audio_requested(&mut buffer, 44_100.0);
+            assert!((buffer[0][0] - 1.0).abs() < 1e-6, "{}", drive);
+            assert!((buffer[1][0] + 1.0).abs() < 1e-6, "{}", drive);
+            assert!(buffer[2][0] >= 0.25 * 0.99, "{}", drive);
+        }
+        // Without compensation a higher drive is louder.
+        let mut buffer = [[0.25f32]];
+        Waveshaper::new(4.0, TransferFunction::Tanh).audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer[0][0] > 0.7);
+    }
+
+    #[test]
+    fn a_downsample_factor_of_2_produces_pairs_of_identical_samples() {
+        let mut crusher = BitCrusher::new(16, 2);
+        let input: Vec<[f32; 2]> = (0..30)
+            .map(|i| [i as f32 / 32.0, -(i as f32) / 32.0])
+            .collect();
+        let mut output = input.clone();
+        // An odd buffer size, so that a pair spans two buffers.
+        for chunk in output.chunks_mut(7) {
+            crusher.audio_requested(chunk, 44_100.0);
+        }
+        for (i, pair) in output.chunks(2).enumerate() {
+            assert_eq!(pair[0], pair[1]);
+            assert_eq!(pair[0], input[i * 2]);
+        }
+    }
+
+    #[test]
+    fn a_16_bit_depth_and_a_factor_of_1_is_transparent() {
+        let input: Vec<[i16; 1]> = (-100..100).map(|i| [(i * 327) as i16]).collect();
+        let mut output = input.clone();
+        BitCrusher::new(16, 1).audio_requested(&mut output, 44_100.0);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn bit_depth_quantises_to_evenly_spaced_levels() {
+        let crusher = BitCrusher::new(2, 1);
+        assert_eq!(crusher.quantise(0.3), 0.5);
+        assert_eq!(crusher.quantise(-0.2), 0.0);
+        assert_eq!(crusher.quantise(-0.8), -1.0);
+        assert_eq!(crusher.quantise(2.0), 1.0);
+    }
+}
@@ -0,0 +1,2025 @@
+//! Nodes that control the dynamic range of a signal.
+
+use crate::buffer;
+use crate::delay::DelayLine;
+use crate::gain;
+use crate::meter::{Ballistics, BallisticsFilter};
+use crate::node::{Node, Settings};
+use crate::util;
+use sample::{self, Frame};
+use std::collections::VecDeque;
+
+/// The way in which a **Limiter** shapes samples that approach or exceed its ceiling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LimiterMode {
+    /// Clamp samples to exactly `±ceiling`.
+    HardClip,
+    /// `ceiling * tanh(x / ceiling)`. Unity gain for quiet samples, smoothly saturating towards
+    /// `±ceiling`.
+    TanhSoft,
+    /// A cubic curve with unity gain for quiet samples that reaches `±ceiling` with zero slope at
+    /// `±1.5 * ceiling`, beyond which samples are clamped.
+    CubicSoft,
+}
+
+/// The parameters of a **Limiter**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimiterParams {
+    /// The ceiling as a linear amplitude.
+    pub ceiling: f64,
+    /// The way in which samples are shaped.
+    pub mode: LimiterMode,
+}
+
+/// A **Node** that prevents its output from exceeding a ceiling.
+///
+/// Shaping happens per sample with no look-ahead or release. Every input sample whose magnitude
+/// exceeds the ceiling is counted, which can be read via `clipped_samples`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limiter {
+    ceiling: f64,
+    mode: LimiterMode,
+    clipped_samples: usize,
+}
+
+impl Limiter {
+    /// Construct a **Limiter** with the given ceiling (as a linear amplitude) and mode.
+    pub fn new(ceiling: f64, mode: LimiterMode) -> Self {
+        Limiter {
+            ceiling: ceiling.abs(),
+            mode,
+            clipped_samples: 0,
+        }
+    }
+
+    /// The ceiling as a linear amplitude.
+    pub fn ceiling(&self) -> f64 {
+        self.ceiling
+    }
+
+    /// Set the ceiling as a linear amplitude.
+    pub fn set_ceiling(&mut self, ceiling: f64) {
+        self.ceiling = ceiling.abs();
+    }
+
+    /// The way in which samples are shaped.
+    pub fn mode(&self) -> LimiterMode {
+        self.mode
+    }
+
+    /// Set the way in which samples are shaped.
+    pub fn set_mode(&mut self, mode: LimiterMode) {
+        self.mode = mode;
+    }
+
+    /// The number of input samples that have exceeded the ceiling since construction or the last
+    /// call to `reset_stats`.
+    pub fn clipped_samples(&self) -> usize {
+        self.clipped_samples
+    }
+
+    /// The parameters of the **Limiter**.
+    pub fn params(&self) -> LimiterParams {
+        LimiterParams {
+            ceiling: self.ceiling,
+            mode: self.mode,
+        }
+    }
+
+    /// Apply the given parameters, leaving the `clipped_samples` counter untouched.
+    pub fn set_params(&mut self, params: LimiterParams) {
+        self.set_ceiling(params.ceiling);
+        self.mode = params.mode;
+    }
+
+    /// Reset the `clipped_samples` counter to zero.
+    pub fn reset_stats(&mut self) {
+        self.clipped_samples = 0;
+    }
+
+    /// Shape a single sample according to the ceiling and mode.
+    pub fn shape(&self, x: f64) -> f64 {
+        let c = self.ceiling;
+        if c == 0.0 {
+            return 0.0;
+        }
+        match self.mode {
+            LimiterMode::HardClip => x.clamp(-c, c),
+            LimiterMode::TanhSoft => c * util::tanh(x / c),
+            LimiterMode::CubicSoft => {
+                let u = (x / (1.5 * c)).clamp(-1.0, 1.0);
+                c * (1.5 * u - 0.5 * u * u * u)
+            }
+        }
+    }
+}
+
+impl<F> Node<F> for Limiter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let mut clipped = 0;
+        sample::slice::map_in_place(buffer, |frame| {
+            frame.map(|s| {
+                let x = util::to_f64(s);
+                if x.abs() > self.ceiling {
+                    clipped += 1;
+                }
+                util::from_f64(self.shape(x))
+            })
+        });
+        self.clipped_samples += clipped;
+    }
+
+    /// Exposes:
+    ///
+    /// - `ceiling`: the ceiling as a linear amplitude.
+    fn param_names(&self) -> &[&str] {
+        &["ceiling"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "ceiling" => Some(self.ceiling as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "ceiling" => self.set_ceiling(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "limiter"
+    }
+}
+
+/// The longest look-ahead of a **LookaheadLimiter** in milliseconds.
+pub const LOOKAHEAD_LIMITER_MAX_MS: f64 = 20.0;
+
+/// The parameters of a **LookaheadLimiter**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LookaheadLimiterParams {
+    /// The ceiling as a linear amplitude.
+    pub ceiling: f64,
+    /// The time in milliseconds by which the audio is delayed while the gain is lowered
+    /// (0.0 ... `LOOKAHEAD_LIMITER_MAX_MS`).
+    pub lookahead_ms: f64,
+    /// The time constant in milliseconds with which the gain recovers.
+    pub release_ms: f64,
+}
+
+/// A **Node** that holds its output beneath a ceiling by lowering its gain ahead of each peak.
+///
+/// The audio is delayed by `lookahead_ms`, while the gain needed to bring each incoming frame
+/// beneath the ceiling is computed straight away. The gain applied to each delayed frame is the
+/// lowest needed by any frame within the look-ahead, averaged over the look-ahead, so that it
+/// ramps smoothly down to the required gain by the time the peak leaves the delay rather than
+/// jumping. Once the peak has passed, the gain recovers with the `release_ms` time constant.
+///
+/// Unlike the **Limiter**, peaks are turned down rather than shaped, so the output is not
+/// distorted. The gain is shared by every channel, preserving the stereo image, and any residue
+/// of rounding error is clamped so the output never exceeds the ceiling.
+///
+/// The look-ahead rounded to whole frames is reported by `latency_samples` once audio has been
+/// requested (or the node prepared).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LookaheadLimiter {
+    ceiling: f64,
+    lookahead_ms: f64,
+    release_ms: f64,
+    /// The look-ahead in frames at the last sample rate.
+    lookahead_frames: usize,
+    /// The delayed audio, holding the look-ahead plus the incoming frame.
+    line: DelayLine,
+    /// The index and required gain of the incoming frames that may yet be the lowest within the
+    /// look-ahead, in increasing order of both.
+    minima: VecDeque<(u64, f64)>,
+    /// The gain after release of each frame within the look-ahead, averaged to give the gain
+    /// applied.
+    window: Vec<f64>,
+    window_idx: usize,
+    window_sum: f64,
+    /// The gain after release of the last frame.
+    release_gain: f64,
+    /// The number of frames received since the last reset.
+    frame_idx: u64,
+}
+
+impl LookaheadLimiter {
+    /// Construct a **LookaheadLimiter** with the given ceiling (as a linear amplitude), look-ahead
+    /// and release time.
+    pub fn new(ceiling: f64, lookahead_ms: f64, release_ms: f64) -> Self {
+        LookaheadLimiter {
+            ceiling: ceiling.abs(),
+            lookahead_ms: lookahead_ms.clamp(0.0, LOOKAHEAD_LIMITER_MAX_MS),
+            release_ms: release_ms.max(0.0),
+            lookahead_frames: 0,
+            line: DelayLine::default(),
+            minima: VecDeque::new(),
+            window: Vec::new(),
+            window_idx: 0,
+            window_sum: 0.0,
+            release_gain: 1.0,
+            frame_idx: 0,
+        }
+    }
+
+    /// The ceiling as a linear amplitude.
+    pub fn ceiling(&self) -> f64 {
+        self.ceiling
+    }
+
+    /// Set the ceiling as a linear amplitude.
+    pub fn set_ceiling(&mut self, ceiling: f64) {
+        self.ceiling = ceiling.abs();
+    }
+
+    /// The time in milliseconds by which the audio is delayed while the gain is lowered.
+    pub fn lookahead_ms(&self) -> f64 {
+        self.lookahead_ms
+    }
+
+    /// Set the time in milliseconds by which the audio is delayed while the gain is lowered
+    /// (0.0 ... `LOOKAHEAD_LIMITER_MAX_MS`).
+    ///
+    /// Changing the look-ahead changes the latency, so the delayed audio and gain are reset when
+    /// audio is next requested.
+    pub fn set_lookahead_ms(&mut self, lookahead_ms: f64) {
+        self.lookahead_ms = lookahead_ms.clamp(0.0, LOOKAHEAD_LIMITER_MAX_MS);
+    }
+
+    /// The time constant in milliseconds with which the gain recovers.
+    pub fn release_ms(&self) -> f64 {
+        self.release_ms
+    }
+
+    /// Set the time constant in milliseconds with which the gain recovers.
+    pub fn set_release_ms(&mut self, release_ms: f64) {
+        self.release_ms = release_ms.max(0.0);
+    }
+
+    /// The parameters of the **LookaheadLimiter**.
+    pub fn params(&self) -> LookaheadLimiterParams {
+        LookaheadLimiterParams {
+            ceiling: self.ceiling,
+            lookahead_ms: self.lookahead_ms,
+            release_ms: self.release_ms,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: LookaheadLimiterParams) {
+        self.set_ceiling(params.ceiling);
+        self.set_lookahead_ms(params.lookahead_ms);
+        self.set_release_ms(params.release_ms);
+    }
+
+    /// Silence the delayed audio and return to unity gain.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.minima.clear();
+        for gain in self.window.iter_mut() {
+            *gain = 1.0;
+        }
+        self.window_idx = 0;
+        self.window_sum = self.window.len() as f64;
+        self.release_gain = 1.0;
+        self.frame_idx = 0;
+    }
+
+    /// Size the delayed audio and gain window for the look-ahead at the given sample rate and
+    /// channels, resetting them if they change.
+    fn prepare_for(&mut self, sample_hz: f64, channels: usize) {
+        let frames = (self.lookahead_ms / 1_000.0 * sample_hz).round() as usize;
+        if frames != self.lookahead_frames || self.line.len() != frames + 1 {
+            self.lookahead_frames = frames;
+            self.line = DelayLine::default();
+            self.line.resize(frames + 1, channels);
+            self.minima = VecDeque::with_capacity(frames + 1);
+            self.window = vec![1.0; frames.max(1)];
+            LookaheadLimiter::reset(self);
+        } else {
+            self.line.resize(frames + 1, channels);
+        }
+    }
+}
+
+impl<F> Node<F> for LookaheadLimiter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        self.prepare_for(sample_hz, F::n_channels());
+        let ceiling = self.ceiling;
+        let lookahead = self.lookahead_frames;
+        let release = time_coefficient(self.release_ms, sample_hz);
+        for frame in buffer.iter_mut() {
+            // The gain needed to bring the incoming frame beneath the ceiling.
+            let level = peak(*frame);
+            let required = if level > ceiling {
+                ceiling / level
+            } else {
+                1.0
+            };
+
+            // Track the lowest required gain of the incoming frame and the look-ahead before it.
+            while self.minima.back().is_some_and(|&(_, g)| g >= required) {
+                self.minima.pop_back();
+            }
+            self.minima.push_back((self.frame_idx, required));
+            while self
+                .minima
+                .front()
+                .is_some_and(|&(idx, _)| idx + (lookahead as u64) < self.frame_idx)
+            {
+                self.minima.pop_front();
+            }
+            let lowest = self.minima.front().map_or(1.0, |&(_, g)| g);
+            self.frame_idx += 1;
+
+            // Fall to the lowest gain immediately, then recover with the release.
+            self.release_gain = if lowest < self.release_gain {
+                lowest
+            } else {
+                util::flush_denormal(lowest + (self.release_gain - lowest) * release)
+            };
+
+            // Average over the look-ahead, so that the gain ramps down to meet each peak.
+            self.window_sum += self.release_gain - self.window[self.window_idx];
+            self.window[self.window_idx] = self.release_gain;
+            self.window_idx = (self.window_idx + 1) % self.window.len();
+            let gain = (self.window_sum / self.window.len() as f64).min(1.0);
+
+            for (channel, s) in frame.channels().enumerate() {
+                self.line.write(channel, util::to_f64(s));
+            }
+            self.line.advance();
+            let line = &self.line;
+            let mut channel = 0;
+            *frame = frame.map(|_| {
+                let delayed = line.past(channel, lookahead + 1);
+                channel += 1;
+                util::from_f64((delayed * gain).clamp(-ceiling, ceiling))
+            });
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.lookahead_frames
+    }
+
+    /// Exposes:
+    ///
+    /// - `ceiling`: the ceiling as a linear amplitude.
+    /// - `lookahead_ms`: the look-ahead in milliseconds (0.0 ... `LOOKAHEAD_LIMITER_MAX_MS`).
+    /// - `release_ms`: the release time constant in milliseconds.
+    fn param_names(&self) -> &[&str] {
+        &["ceiling", "lookahead_ms", "release_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "ceiling" => Some(self.ceiling as f32),
+            "lookahead_ms" => Some(self.lookahead_ms as f32),
+            "release_ms" => Some(self.release_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "ceiling" => self.set_ceiling(value as f64),
+            "lookahead_ms" => self.set_lookahead_ms(value as f64),
+            "release_ms" => self.set_release_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.prepare_for(settings.sample_hz, F::n_channels());
+        LookaheadLimiter::reset(self);
+    }
+
+    fn reset(&mut self) {
+        LookaheadLimiter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "lookahead_limiter"
+    }
+}
+
+/// The one-pole coefficient that moves `1 - 1/e` (~63%) of the way towards a new level over the
+/// given time in milliseconds.
+fn time_coefficient(ms: f64, sample_hz: f64) -> f64 {
+    let frames = ms / 1_000.0 * sample_hz;
+    if frames > 0.0 {
+        (-1.0 / frames).exp()
+    } else {
+        0.0
+    }
+}
+
+/// The parameters of an **EnvelopeFollower**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeFollowerParams {
+    /// The time constant in milliseconds with which the envelope rises.
+    pub attack_ms: f64,
+    /// The time constant in milliseconds with which the envelope falls.
+    pub release_ms: f64,
+}
+
+/// A pass-through **Node** that tracks the smoothed amplitude envelope of each channel.
+///
+/// The envelope follows the absolute value of each sample, rising with the `attack_ms` time
+/// constant and falling with the `release_ms` time constant. A step in level is ~63% complete
+/// after one time constant. This is the level detector used by the **Compressor** and **Gate**.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvelopeFollower {
+    attack_ms: f64,
+    release_ms: f64,
+    /// The sample rate for which the coefficients were last calculated.
+    sample_hz: f64,
+    attack_coeff: f64,
+    release_coeff: f64,
+    envelope: Vec<f32>,
+}
+
+impl EnvelopeFollower {
+    /// Construct an **EnvelopeFollower** with the given attack and release times in milliseconds.
+    pub fn new(attack_ms: f64, release_ms: f64) -> Self {
+        EnvelopeFollower {
+            attack_ms: attack_ms.max(0.0),
+            release_ms: release_ms.max(0.0),
+            sample_hz: 0.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            envelope: Vec::new(),
+        }
+    }
+
+    /// The time constant in milliseconds with which the envelope rises.
+    pub fn attack_ms(&self) -> f64 {
+        self.attack_ms
+    }
+
+    /// Set the time constant in milliseconds with which the envelope rises.
+    pub fn set_attack_ms(&mut self, attack_ms: f64) {
+        self.attack_ms = attack_ms.max(0.0);
+        self.sample_hz = 0.0;
+    }
+
+    /// The time constant in milliseconds with which the envelope falls.
+    pub fn release_ms(&self) -> f64 {
+        self.release_ms
+    }
+
+    /// Set the time constant in milliseconds with which the envelope falls.
+    pub fn set_release_ms(&mut self, release_ms: f64) {
+        self.release_ms = release_ms.max(0.0);
+        self.sample_hz = 0.0;
+    }
+
+    /// The current envelope of each channel, where `1.0` is full scale.
+    ///
+    /// Empty until audio has been requested.
+    pub fn envelope(&self) -> &[f32] {
+        &self.envelope
+    }
+
+    /// The parameters of the **EnvelopeFollower**.
+    pub fn params(&self) -> EnvelopeFollowerParams {
+        EnvelopeFollowerParams {
+            attack_ms: self.attack_ms,
+            release_ms: self.release_ms,
+        }
+    }
+
+    /// Apply the given parameters, leaving the current envelope untouched.
+    pub fn set_params(&mut self, params: EnvelopeFollowerParams) {
+        self.set_attack_ms(params.attack_ms);
+        self.set_release_ms(params.release_ms);
+    }
+
+    /// Return the envelope of every channel to silence.
+    pub fn reset(&mut self) {
+        for env in self.envelope.iter_mut() {
+            *env = 0.0;
+        }
+    }
+
+    /// Prepare the coefficients and envelope for the given sample rate and number of channels.
+    pub(crate) fn prepare_for(&mut self, sample_hz: f64, channels: usize) {
+        if self.sample_hz != sample_hz {
+            self.sample_hz = sample_hz;
+            self.attack_coeff = time_coefficient(self.attack_ms, sample_hz);
+            self.release_coeff = time_coefficient(self.release_ms, sample_hz);
+        }
+        self.envelope.resize(channels, 0.0);
+    }
+
+    /// Update the envelope of the given channel with the next sample, returning the new envelope.
+    ///
+    /// `prepare_for` must have been called first.
+    #[inline]
+    pub(crate) fn follow(&mut self, channel: usize, x: f64) -> f64 {
+        let level = x.abs();
+        let env = self.envelope[channel] as f64;
+        let coeff = if level > env {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        let env = level + (env - level) * coeff;
+        self.envelope[channel] = env as f32;
+        env
+    }
+}
+
+impl<F> Node<F> for EnvelopeFollower
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.prepare_for(sample_hz, F::n_channels());
+        for frame in buffer.iter() {
+            for (channel, s) in frame.channels().enumerate() {
+                self.follow(channel, util::to_f64(s));
+            }
+        }
+    }
+
+    /// Exposes:
+    ///
+    /// - `attack_ms`: the attack time constant in milliseconds.
+    /// - `release_ms`: the release time constant in milliseconds.
+    fn param_names(&self) -> &[&str] {
+        &["attack_ms", "release_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "attack_ms" => Some(self.attack_ms as f32),
+            "release_ms" => Some(self.release_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "attack_ms" => self.set_attack_ms(value as f64),
+            "release_ms" => self.set_release_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 {
+            self.prepare_for(settings.sample_hz, F::n_channels());
+        }
+        EnvelopeFollower::reset(self);
+    }
+
+    fn reset(&mut self) {
+        EnvelopeFollower::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "envelope_follower"
+    }
+}
+
+/// The index of the input that a **Compressor** or **Gate** with its sidechain enabled uses as the
+/// sidechain, i.e. the second input connected.
+pub const SIDECHAIN_INPUT: usize = 1;
+
+/// The sidechain of a **Compressor** or **Gate**, holding the level of each frame of the sidechain
+/// input combined for the next buffer.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Sidechain {
+    enabled: bool,
+    /// The absolute peak of the loudest channel of each frame of the sidechain input.
+    levels: Vec<f64>,
+}
+
+impl Sidechain {
+    /// Combine an input of the node, recording the sidechain input's levels rather than summing it
+    /// with the other inputs when the sidechain is enabled.
+    fn combine_input<F>(&mut self, input_idx: usize, input: &[F], output: &mut [F])
+    where
+        F: Frame,
+    {
+        if self.enabled && input_idx == SIDECHAIN_INPUT {
+            self.levels.clear();
+            self.levels.extend(input.iter().map(|frame| peak(*frame)));
+        } else if input_idx == 0 {
+            sample::slice::write(output, input);
+        } else {
+            buffer::mix_into(output, input, 1.0);
+        }
+    }
+
+    /// The level to detect for the frame at the given index of the buffer, given the frame.
+    ///
+    /// When the sidechain is enabled, frames beyond the levels combined are silent.
+    #[inline]
+    fn level<F>(&self, frame_idx: usize, frame: F) -> f64
+    where
+        F: Frame,
+    {
+        if self.enabled {
+            self.levels.get(frame_idx).cloned().unwrap_or(0.0)
+        } else {
+            peak(frame)
+        }
+    }
+}
+
+/// The absolute peak of the loudest channel of the frame.
+#[inline]
+fn peak<F>(frame: F) -> f64
+where
+    F: Frame,
+{
+    frame
+        .channels()
+        .map(|s| util::to_f64(s).abs())
+        .fold(0.0, f64::max)
+}
+
+/// Update the displayed gain reduction with the reduction in decibels measured over the given
+/// time.
+///
+/// The reduction is displayed as the amplitude by which it exceeds unity gain, so that no
+/// reduction is displayed as silence.
+fn display_gain_reduction(display: &mut BallisticsFilter, reduction_db: f32, elapsed_secs: f64) {
+    let level = gain::db_to_amp(reduction_db) - 1.0;
+    display.process(&[level], elapsed_secs);
+}
+
+/// The gain reduction in decibels shown by the display updated by `display_gain_reduction`.
+fn displayed_gain_reduction_db(display: &BallisticsFilter) -> f32 {
+    display
+        .levels()
+        .first()
+        .map_or(0.0, |&level| gain::amp_to_db(level + 1.0))
+}
+
+/// The parameters of a **Compressor**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressorParams {
+    /// The level in decibels above which the signal is compressed.
+    pub threshold_db: f32,
+    /// The ratio of the input level above the threshold to the output level above the threshold.
+    pub ratio: f32,
+    /// The time constant in milliseconds with which compression is applied.
+    pub attack_ms: f64,
+    /// The time constant in milliseconds with which compression is released.
+    pub release_ms: f64,
+    /// The gain in decibels applied after compression.
+    pub makeup_db: f32,
+    /// Whether make-up gain is derived from the threshold and ratio, on top of `makeup_db`.
+    pub auto_makeup: bool,
+    /// The width in decibels of the region around the threshold over which the ratio is eased in.
+    pub knee_db: f32,
+    /// Whether the level is detected from the sidechain input rather than the processed signal.
+    pub sidechain: bool,
+}
+
+/// A **Node** that reduces the dynamic range of a signal by attenuating it above a threshold.
+///
+/// The level is detected by an **EnvelopeFollower** fed the loudest channel of each frame, so that
+/// every channel receives the same gain and the stereo image is preserved. Above the threshold,
+/// every `ratio` decibels of input produce one decibel of output. With a `knee_db` greater than
+/// `0.0`, the ratio is eased in over that many decibels centered on the threshold.
+///
+/// The gain reduction is derived from the smoothed envelope, so it moves with the attack and
+/// release times rather than jumping between samples. For metering, standard **Ballistics** may
+/// also be applied to the displayed gain reduction.
+///
+/// With `auto_makeup` enabled, the gain reduction that a signal at 0 dBFS would receive is added
+/// back on top of the `makeup_db`, so that the loudest peaks return to roughly their original
+/// level and the apparent loudness of the compressed signal roughly matches that of the input.
+///
+/// With the sidechain enabled, the level is instead detected from the input at `SIDECHAIN_INPUT`,
+/// e.g. to duck music beneath a voiceover. The sidechain input is not heard, while the remaining
+/// inputs are summed and compressed as usual. While no sidechain input is connected, the detected
+/// level is silence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compressor {
+    threshold_db: f32,
+    ratio: f32,
+    makeup_db: f32,
+    auto_makeup: bool,
+    knee_db: f32,
+    detector: EnvelopeFollower,
+    sidechain: Sidechain,
+    /// The gain reduction applied to the most recent frame in decibels.
+    gain_reduction_db: f32,
+    /// The gain reduction for display.
+    display: BallisticsFilter,
+}
+
+impl Compressor {
+    /// Construct a **Compressor** with the given threshold in decibels and ratio, a 10 ms attack,
+    /// a 100 ms release, a hard knee and no makeup gain.
+    pub fn new(threshold_db: f32, ratio: f32) -> Self {
+        Compressor {
+            threshold_db,
+            ratio: ratio.max(1.0),
+            makeup_db: 0.0,
+            auto_makeup: false,
+            knee_db: 0.0,
+            detector: EnvelopeFollower::new(10.0, 100.0),
+            sidechain: Sidechain::default(),
+            gain_reduction_db: 0.0,
+            display: BallisticsFilter::default(),
+        }
+    }
+
+    /// The level in decibels above which the signal is compressed.
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// Set the level in decibels above which the signal is compressed.
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    /// The compression ratio.
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Set the compression ratio, where `1.0` applies no compression.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    /// The time constant in milliseconds with which compression is applied.
+    pub fn attack_ms(&self) -> f64 {
+        self.detector.attack_ms()
+    }
+
+    /// Set the time constant in milliseconds with which compression is applied.
+    pub fn set_attack_ms(&mut self, attack_ms: f64) {
+        self.detector.set_attack_ms(attack_ms);
+    }
+
+    /// The time constant in milliseconds with which compression is released.
+    pub fn release_ms(&self) -> f64 {
+        self.detector.release_ms()
+    }
+
+    /// Set the time constant in milliseconds with which compression is released.
+    pub fn set_release_ms(&mut self, release_ms: f64) {
+        self.detector.set_release_ms(release_ms);
+    }
+
+    /// The gain in decibels applied after compression.
+    pub fn makeup_db(&self) -> f32 {
+        self.makeup_db
+    }
+
+    /// Set the gain in decibels applied after compression.
+    pub fn set_makeup_db(&mut self, makeup_db: f32) {
+        self.makeup_db = makeup_db;
+    }
+
+    /// Whether make-up gain is derived from the threshold and ratio, on top of `makeup_db`.
+    pub fn auto_makeup(&self) -> bool {
+        self.auto_makeup
+    }
+
+    /// Set whether make-up gain is derived from the threshold and ratio, on top of `makeup_db`.
+    pub fn set_auto_makeup(&mut self, auto_makeup: bool) {
+        self.auto_makeup = auto_makeup;
+    }
+
+    /// The make-up gain in decibels derived from the threshold and ratio, i.e. the gain reduction
+    /// for a signal at 0 dBFS, or `0.0` while `auto_makeup` is disabled.
+    pub fn auto_makeup_db(&self) -> f32 {
+        if self.auto_makeup {
+            self.gain_reduction_for(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The width in decibels of the soft knee.
+    pub fn knee_db(&self) -> f32 {
+        self.knee_db
+    }
+
+    /// Set the width in decibels of the soft knee, where `0.0` is a hard knee.
+    pub fn set_knee_db(&mut self, knee_db: f32) {
+        self.knee_db = knee_db.max(0.0);
+    }
+
+    /// Whether the level is detected from the sidechain input rather than the processed signal.
+    pub fn sidechain(&self) -> bool {
+        self.sidechain.enabled
+    }
+
+    /// Set whether the level is detected from the input at `SIDECHAIN_INPUT` rather than the
+    /// processed signal.
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.sidechain.enabled = sidechain;
+    }
+
+    /// The gain reduction applied to the most recent frame in decibels, excluding the makeup
+    /// gain. This is `0.0` when no compression is being applied and positive otherwise.
+    pub fn gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db
+    }
+
+    /// The movement of the displayed gain reduction.
+    pub fn ballistics(&self) -> Ballistics {
+        self.display.ballistics()
+    }
+
+    /// Set the movement of the displayed gain reduction.
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.display.set_ballistics(ballistics);
+    }
+
+    /// The gain reduction in decibels with the `ballistics` applied, for display. As it is
+    /// updated once per buffer, this is `0.0` until audio has been requested.
+    pub fn displayed_gain_reduction_db(&self) -> f32 {
+        displayed_gain_reduction_db(&self.display)
+    }
+
+    /// The parameters of the **Compressor**.
+    pub fn params(&self) -> CompressorParams {
+        CompressorParams {
+            threshold_db: self.threshold_db,
+            ratio: self.ratio,
+            attack_ms: self.attack_ms(),
+            release_ms: self.release_ms(),
+            makeup_db: self.makeup_db,
+            auto_makeup: self.auto_makeup,
+            knee_db: self.knee_db,
+            sidechain: self.sidechain.enabled,
+        }
+    }
+
+    /// Apply the given parameters, leaving the detected level untouched.
+    pub fn set_params(&mut self, params: CompressorParams) {
+        self.threshold_db = params.threshold_db;
+        self.set_ratio(params.ratio);
+        self.set_attack_ms(params.attack_ms);
+        self.set_release_ms(params.release_ms);
+        self.makeup_db = params.makeup_db;
+        self.auto_makeup = params.auto_makeup;
+        self.set_knee_db(params.knee_db);
+        self.set_sidechain(params.sidechain);
+    }
+
+    /// Return the detected level to silence, releasing any compression.
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.gain_reduction_db = 0.0;
+        self.display.reset();
+    }
+
+    /// The gain reduction in decibels for a signal at the given level in decibels.
+    pub fn gain_reduction_for(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let slope = 1.0 - 1.0 / self.ratio;
+        let half_knee = self.knee_db / 2.0;
+        if over <= -half_knee {
+            0.0
+        } else if over < half_knee {
+            let x = over + half_knee;
+            slope * x * x / (2.0 * self.knee_db)
+        } else {
+            slope * over
+        }
+    }
+}
+
+impl<F> Node<F> for Compressor
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.detector.prepare_for(sample_hz, 1);
+        let makeup_db = self.makeup_db + self.auto_makeup_db();
+        let mut reduction_db = self.gain_reduction_db;
+        let mut frame_idx = 0;
+        sample::slice::map_in_place(buffer, |frame| {
+            let level = self.sidechain.level(frame_idx, frame);
+            frame_idx += 1;
+            let env = self.detector.follow(0, level);
+            reduction_db = self.gain_reduction_for(gain::amp_to_db(env as f32));
+            let gain = gain::db_to_amp(makeup_db - reduction_db) as f64;
+            frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+        });
+        self.gain_reduction_db = reduction_db;
+        display_gain_reduction(
+            &mut self.display,
+            reduction_db,
+            buffer.len() as f64 / sample_hz,
+        );
+        self.sidechain.levels.clear();
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.sidechain.combine_input(input_idx, input, output);
+    }
+
+    /// Returns `false` while the sidechain is enabled, so that its level is detected as rendered.
+    fn sums_inputs(&self) -> bool {
+        !self.sidechain.enabled
+    }
+
+    /// Returns `false`, as `combine_input` reads the sidechain by its index.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    /// Exposes:
+    ///
+    /// - `threshold_db`: the threshold in decibels.
+    /// - `ratio`: the compression ratio (at least 1.0).
+    /// - `attack_ms`: the attack time constant in milliseconds.
+    /// - `release_ms`: the release time constant in milliseconds.
+    /// - `makeup_db`: the gain applied after compression in decibels.
+    /// - `knee_db`: the width of the soft knee in decibels (at least 0.0).
+    fn param_names(&self) -> &[&str] {
+        &[
+            "threshold_db",
+            "ratio",
+            "attack_ms",
+            "release_ms",
+            "makeup_db",
+            "knee_db",
+        ]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "threshold_db" => Some(self.threshold_db),
+            "ratio" => Some(self.ratio),
+            "attack_ms" => Some(self.attack_ms() as f32),
+            "release_ms" => Some(self.release_ms() as f32),
+            "makeup_db" => Some(self.makeup_db),
+            "knee_db" => Some(self.knee_db),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold_db" => self.set_threshold_db(value),
+            "ratio" => self.set_ratio(value),
+            "attack_ms" => self.set_attack_ms(value as f64),
+            "release_ms" => self.set_release_ms(value as f64),
+            "makeup_db" => self.set_makeup_db(value),
+            "knee_db" => self.set_knee_db(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Compressor::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "compressor"
+    }
+}
+
+/// The parameters of a **Ducker**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuckerParams {
+    /// The sidechain level in decibels above which the signal is ducked.
+    pub threshold_db: f32,
+    /// The decibels by which the sidechain must rise above the threshold for each decibel of
+    /// reduction not applied, as with a **Compressor**'s ratio.
+    pub ratio: f32,
+    /// The time constant in milliseconds with which ducking is applied.
+    pub attack_ms: f64,
+    /// The time constant in milliseconds with which ducking is released.
+    pub release_ms: f64,
+    /// The greatest reduction in decibels.
+    pub range_db: f32,
+}
+
+/// A **Node** that lowers the level of its signal while its sidechain is loud, e.g. to duck music
+/// beneath a voiceover.
+///
+/// The level is always detected from the input at `SIDECHAIN_INPUT`, which is not heard, while
+/// the remaining inputs are summed and ducked. The loudest channel of each sidechain frame is fed
+/// to an **EnvelopeFollower**, and for every decibel that its envelope rises above the threshold,
+/// the signal is reduced by `1 - 1 / ratio` decibels, up to `range_db`. A high ratio reduces the
+/// signal by the full range as soon as the sidechain crosses the threshold, while lower ratios
+/// duck in proportion to how loud the sidechain is.
+///
+/// Unlike a **Compressor** with its sidechain enabled, the reduction is bounded by `range_db`,
+/// so the signal stays audible beneath the sidechain. While no sidechain input is connected, or
+/// the sidechain is silent, the signal passes unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ducker {
+    threshold_db: f32,
+    ratio: f32,
+    range_db: f32,
+    detector: EnvelopeFollower,
+    sidechain: Sidechain,
+    /// The gain reduction applied to the most recent frame in decibels.
+    gain_reduction_db: f32,
+    /// The gain reduction for display.
+    display: BallisticsFilter,
+}
+
+impl Ducker {
+    /// Construct a **Ducker** with the given threshold in decibels and ratio, a 10 ms attack, a
+    /// 250 ms release and a range of 12 dB.
+    pub fn new(threshold_db: f32, ratio: f32) -> Self {
+        Ducker {
+            threshold_db,
+            ratio: ratio.max(1.0),
+            range_db: 12.0,
+            detector: EnvelopeFollower::new(10.0, 250.0),
+            sidechain: Sidechain {
+                enabled: true,
+                levels: Vec::new(),
+            },
+            gain_reduction_db: 0.0,
+            display: BallisticsFilter::default(),
+        }
+    }
+
+    /// The sidechain level in decibels above which the signal is ducked.
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// Set the sidechain level in decibels above which the signal is ducked.
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    /// The ducking ratio.
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+
+    /// Set the ducking ratio, where `1.0` applies no ducking.
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.max(1.0);
+    }
+
+    /// The time constant in milliseconds with which ducking is applied.
+    pub fn attack_ms(&self) -> f64 {
+        self.detector.attack_ms()
+    }
+
+    /// Set the time constant in milliseconds with which ducking is applied.
+    pub fn set_attack_ms(&mut self, attack_ms: f64) {
+        self.detector.set_attack_ms(attack_ms);
+    }
+
+    /// The time constant in milliseconds with which ducking is released.
+    pub fn release_ms(&self) -> f64 {
+        self.detector.release_ms()
+    }
+
+    /// Set the time constant in milliseconds with which ducking is released.
+    pub fn set_release_ms(&mut self, release_ms: f64) {
+        self.detector.set_release_ms(release_ms);
+    }
+
+    /// The greatest reduction in decibels.
+    pub fn range_db(&self) -> f32 {
+        self.range_db
+    }
+
+    /// Set the greatest reduction in decibels, clamped to `0.0` or above.
+    pub fn set_range_db(&mut self, range_db: f32) {
+        self.range_db = range_db.max(0.0);
+    }
+
+    /// The gain reduction applied to the most recent frame in decibels. This is `0.0` when no
+    /// ducking is being applied and positive otherwise.
+    pub fn gain_reduction_db(&self) -> f32 {
+        self.gain_reduction_db
+    }
+
+    /// The movement of the displayed gain reduction.
+    pub fn ballistics(&self) -> Ballistics {
+        self.display.ballistics()
+    }
+
+    /// Set the movement of the displayed gain reduction.
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.display.set_ballistics(ballistics);
+    }
+
+    /// The gain reduction in decibels with the `ballistics` applied, for display. As it is
+    /// updated once per buffer, this is `0.0` until audio has been requested.
+    pub fn displayed_gain_reduction_db(&self) -> f32 {
+        displayed_gain_reduction_db(&self.display)
+    }
+
+    /// The parameters of the **Ducker**.
+    pub fn params(&self) -> DuckerParams {
+        DuckerParams {
+            threshold_db: self.threshold_db,
+            ratio: self.ratio,
+            attack_ms: self.attack_ms(),
+            release_ms: self.release_ms(),
+            range_db: self.range_db,
+        }
+    }
+
+    /// Apply the given parameters, leaving the detected level untouched.
+    pub fn set_params(&mut self, params: DuckerParams) {
+        self.threshold_db = params.threshold_db;
+        self.set_ratio(params.ratio);
+        self.set_attack_ms(params.attack_ms);
+        self.set_release_ms(params.release_ms);
+        self.set_range_db(params.range_db);
+    }
+
+    /// Return the detected level to silence, releasing any ducking.
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.gain_reduction_db = 0.0;
+        self.display.reset();
+    }
+
+    /// The gain reduction in decibels for a sidechain at the given level in decibels.
+    pub fn gain_reduction_for(&self, level_db: f32) -> f32 {
+        let over = (level_db - self.threshold_db).max(0.0);
+        (over * (1.0 - 1.0 / self.ratio)).min(self.range_db)
+    }
+}
+
+impl<F> Node<F> for Ducker
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.detector.prepare_for(sample_hz, 1);
+        let mut reduction_db = self.gain_reduction_db;
+        let mut frame_idx = 0;
+        sample::slice::map_in_place(buffer, |frame| {
+            let level = self.sidechain.level(frame_idx, frame);
+            frame_idx += 1;
+            let env = self.detector.follow(0, level);
+            reduction_db = self.gain_reduction_for(gain::amp_to_db(env as f32));
+            if reduction_db == 0.0 {
+                return frame;
+            }
+            let gain = gain::db_to_amp(-reduction_db) as f64;
+            frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+        });
+        self.gain_reduction_db = reduction_db;
+        display_gain_reduction(
+            &mut self.display,
+            reduction_db,
+            buffer.len() as f64 / sample_hz,
+        );
+        self.sidechain.levels.clear();
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.sidechain.combine_input(input_idx, input, output);
+    }
+
+    /// Returns `false` while the sidechain is enabled, so that its level is detected as rendered.
+    fn sums_inputs(&self) -> bool {
+        !self.sidechain.enabled
+    }
+
+    /// Returns `false`, as `combine_input` reads the sidechain by its index.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    /// Exposes:
+    ///
+    /// - `threshold_db`: the threshold in decibels.
+    /// - `ratio`: the ducking ratio (at least 1.0).
+    /// - `attack_ms`: the attack time constant in milliseconds.
+    /// - `release_ms`: the release time constant in milliseconds.
+    /// - `range_db`: the greatest reduction in decibels (at least 0.0).
+    fn param_names(&self) -> &[&str] {
+        &[
+            "threshold_db",
+            "ratio",
+            "attack_ms",
+            "release_ms",
+            "range_db",
+        ]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "threshold_db" => Some(self.threshold_db),
+            "ratio" => Some(self.ratio),
+            "attack_ms" => Some(self.attack_ms() as f32),
+            "release_ms" => Some(self.release_ms() as f32),
+            "range_db" => Some(self.range_db),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold_db" => self.set_threshold_db(value),
+            "ratio" => self.set_ratio(value),
+            "attack_ms" => self.set_attack_ms(value as f64),
+            "release_ms" => self.set_release_ms(value as f64),
+            "range_db" => self.set_range_db(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Ducker::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "ducker"
+    }
+}
+
+/// The time constants in milliseconds of the envelope that a **Gate** compares to its threshold.
+/// The release bridges the zero crossings of low frequencies so that the gate does not chatter.
+const GATE_DETECTOR_MS: (f64, f64) = (0.1, 20.0);
+
+/// The stage of a **Gate**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GateStage {
+    /// The signal is silenced.
+    #[default]
+    Closed,
+    /// The level has risen above the threshold and the gate is opening.
+    Attack,
+    /// The signal is passed through unchanged.
+    Open,
+    /// The level has fallen below the threshold, but the gate remains open for the hold time.
+    Hold,
+    /// The level has remained below the threshold for the hold time and the gate is closing.
+    Release,
+}
+
+/// The parameters of a **Gate**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GateParams {
+    /// The level in decibels above which the gate opens.
+    pub threshold_db: f32,
+    /// The time in milliseconds taken to open fully.
+    pub attack_ms: f64,
+    /// The time in milliseconds for which the gate remains open once the level falls below the
+    /// threshold.
+    pub hold_ms: f64,
+    /// The time in milliseconds taken to close fully.
+    pub release_ms: f64,
+    /// Whether the level is detected from the sidechain input rather than the processed signal.
+    pub sidechain: bool,
+}
+
+/// A **Node** that silences its input while the level remains below a threshold.
+///
+/// The level of the loudest channel is compared to the threshold. When it rises above the
+/// threshold the gain fades linearly to `1.0` over `attack_ms`. Once the level falls below the
+/// threshold, the gate is held open for `hold_ms` before the gain fades to `0.0` over
+/// `release_ms`, so that brief dips do not cut the signal. Rising above the threshold while
+/// holding or releasing re-opens the gate.
+///
+/// The stage and gain are carried over between buffers.
+///
+/// With the sidechain enabled, the level is instead detected from the input at `SIDECHAIN_INPUT`,
+/// e.g. to open the gate on a drum while gating another signal. The sidechain input is not heard,
+/// while the remaining inputs are summed and gated as usual. While no sidechain input is connected,
+/// the detected level is silence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gate {
+    threshold_db: f32,
+    attack_ms: f64,
+    hold_ms: f64,
+    release_ms: f64,
+    detector: EnvelopeFollower,
+    sidechain: Sidechain,
+    stage: GateStage,
+    /// The gain applied to the most recent frame.
+    gain: f64,
+    /// The number of frames remaining in the `Hold` stage.
+    hold_remaining: usize,
+}
+
+impl Gate {
+    /// Construct a closed **Gate** with the given threshold in decibels, a 1 ms attack, a 50 ms
+    /// hold and a 100 ms release.
+    pub fn new(threshold_db: f32) -> Self {
+        let (detector_attack_ms, detector_release_ms) = GATE_DETECTOR_MS;
+        Gate {
+            threshold_db,
+            attack_ms: 1.0,
+            hold_ms: 50.0,
+            release_ms: 100.0,
+            detector: EnvelopeFollower::new(detector_attack_ms, detector_release_ms),
+            sidechain: Sidechain::default(),
+            stage: GateStage::Closed,
+            gain: 0.0,
+            hold_remaining: 0,
+        }
+    }
+
+    /// The level in decibels above which the gate opens.
+    pub fn threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    /// Set the level in decibels above which the gate opens.
+    pub fn set_threshold_db(&mut self, threshold_db: f32) {
+        self.threshold_db = threshold_db;
+    }
+
+    /// The time in milliseconds taken to open fully.
+    pub fn attack_ms(&self) -> f64 {
+        self.attack_ms
+    }
+
+    /// Set the time in milliseconds taken to open fully.
+    pub fn set_attack_ms(&mut self, attack_ms: f64) {
+        self.attack_ms = attack_ms.max(0.0);
+    }
+
+    /// The time in milliseconds for which the gate is held open.
+    pub fn hold_ms(&self) -> f64 {
+        self.hold_ms
+    }
+
+    /// Set the time in milliseconds for which the gate is held open.
+    pub fn set_hold_ms(&mut self, hold_ms: f64) {
+        self.hold_ms = hold_ms.max(0.0);
+    }
+
+    /// The time in milliseconds taken to close fully.
+    pub fn release_ms(&self) -> f64 {
+        self.release_ms
+    }
+
+    /// Set the time in milliseconds taken to close fully.
+    pub fn set_release_ms(&mut self, release_ms: f64) {
+        self.release_ms = release_ms.max(0.0);
+    }
+
+    /// The current stage of the gate.
+    pub fn stage(&self) -> GateStage {
+        self.stage
+    }
+
+    /// The gain applied to the most recent frame (0.0 ... 1.0).
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Whether the level is detected from the sidechain input rather than the processed signal.
+    pub fn sidechain(&self) -> bool {
+        self.sidechain.enabled
+    }
+
+    /// Set whether the level is detected from the input at `SIDECHAIN_INPUT` rather than the
+    /// processed signal.
+    pub fn set_sidechain(&mut self, sidechain: bool) {
+        self.sidechain.enabled = sidechain;
+    }
+
+    /// The parameters of the **Gate**.
+    pub fn params(&self) -> GateParams {
+        GateParams {
+            threshold_db: self.threshold_db,
+            attack_ms: self.attack_ms,
+            hold_ms: self.hold_ms,
+            release_ms: self.release_ms,
+            sidechain: self.sidechain.enabled,
+        }
+    }
+
+    /// Apply the given parameters, leaving the stage and gain untouched.
+    pub fn set_params(&mut self, params: GateParams) {
+        self.threshold_db = params.threshold_db;
+        self.set_attack_ms(params.attack_ms);
+        self.set_hold_ms(params.hold_ms);
+        self.set_release_ms(params.release_ms);
+        self.set_sidechain(params.sidechain);
+    }
+
+    /// Close the gate immediately.
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.stage = GateStage::Closed;
+        self.gain = 0.0;
+        self.hold_remaining = 0;
+    }
+}
+
+impl<F> Node<F> for Gate
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.detector.prepare_for(sample_hz, 1);
+        let threshold = gain::db_to_amp(self.threshold_db) as f64;
+        let frames = |ms: f64| (ms / 1_000.0 * sample_hz).max(1.0);
+        let attack_step = 1.0 / frames(self.attack_ms);
+        let release_step = 1.0 / frames(self.release_ms);
+        let hold_frames = (self.hold_ms / 1_000.0 * sample_hz).round() as usize;
+        let mut frame_idx = 0;
+        sample::slice::map_in_place(buffer, |frame| {
+            let level = self.sidechain.level(frame_idx, frame);
+            frame_idx += 1;
+            let above = self.detector.follow(0, level) > threshold;
+            self.stage = match self.stage {
+                GateStage::Closed | GateStage::Release if above => GateStage::Attack,
+                GateStage::Open | GateStage::Hold if above => GateStage::Open,
+                GateStage::Open => {
+                    self.hold_remaining = hold_frames;
+                    GateStage::Hold
+                }
+                stage => stage,
+            };
+            match self.stage {
+                GateStage::Closed | GateStage::Open => (),
+                GateStage::Attack => {
+                    self.gain = (self.gain + attack_step).min(1.0);
+                    if self.gain >= 1.0 {
+                        self.stage = GateStage::Open;
+                    }
+                }
+                GateStage::Hold => {
+                    if self.hold_remaining == 0 {
+                        self.stage = GateStage::Release;
+                    } else {
+                        self.hold_remaining -= 1;
+                    }
+                }
+                GateStage::Release => {
+                    self.gain = (self.gain - release_step).max(0.0);
+                    if self.gain <= 0.0 {
+                        self.stage = GateStage::Closed;
+                    }
+                }
+            }
+            let gain = self.gain;
+            frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+        });
+        self.sidechain.levels.clear();
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.sidechain.combine_input(input_idx, input, output);
+    }
+
+    /// Returns `false` while the sidechain is enabled, so that its level is detected as rendered.
+    fn sums_inputs(&self) -> bool {
+        !self.sidechain.enabled
+    }
+
+    /// Returns `false`, as `combine_input` reads the sidechain by its index.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    /// Exposes:
+    ///
+    /// - `threshold_db`: the threshold in decibels.
+    /// - `attack_ms`: the time taken to open in milliseconds.
+    /// - `hold_ms`: the time held open in milliseconds.
+    /// - `release_ms`: the time taken to close in milliseconds.
+    fn param_names(&self) -> &[&str] {
+        &["threshold_db", "attack_ms", "hold_ms", "release_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "threshold_db" => Some(self.threshold_db),
+            "attack_ms" => Some(self.attack_ms as f32),
+            "hold_ms" => Some(self.hold_ms as f32),
+            "release_ms" => Some(self.release_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "threshold_db" => self.set_threshold_db(value),
+            "attack_ms" => self.set_attack_ms(value as f64),
+            "hold_ms" => self.set_hold_ms(value as f64),
+            "release_ms" => self.set_release_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Gate::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "gate"
+    }
+}
+
+/// The time constants in milliseconds of the envelope of a **TransientShaper** that tracks the
+/// signal most closely.
+const TRANSIENT_FAST_MS: (f64, f64) = (0.1, 50.0);
+
+/// The time constants in milliseconds of the envelope of a **TransientShaper** that rises slowly,
+/// lagging behind the fast envelope during each transient.
+const TRANSIENT_SLOW_ATTACK_MS: (f64, f64) = (20.0, 50.0);
+
+/// The time constants in milliseconds of the envelope of a **TransientShaper** that falls slowly,
+/// remaining above the fast envelope during the body of each sound.
+const TRANSIENT_SLOW_RELEASE_MS: (f64, f64) = (0.1, 500.0);
+
+/// The greatest boost or cut in decibels applied by a **TransientShaper**.
+const TRANSIENT_MAX_DB: f64 = 12.0;
+
+/// The parameters of a **TransientShaper**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransientShaperParams {
+    /// The amount by which transients are boosted (0.0 ... 1.0) or cut (-1.0 ... 0.0).
+    pub attack: f32,
+    /// The amount by which the body following each transient is boosted (0.0 ... 1.0) or cut
+    /// (-1.0 ... 0.0).
+    pub sustain: f32,
+}
+
+/// A **Node** that boosts or cuts the transients and the sustained body of a signal
+/// independently, e.g. to add snap to a drum or to tighten its ring.
+///
+/// The loudest channel of each frame is fed to three **EnvelopeFollower**s: a fast envelope that
+/// tracks the signal closely, one that rises more slowly and one that falls more slowly. At the
+/// start of each sound the fast envelope leads the slow-rising one, and by how many decibels
+/// measures the transient. As the sound decays, the slow-falling envelope remains above the fast
+/// one, measuring the body. Each difference is scaled by the `attack` or `sustain` respectively,
+/// and their sum is applied as a gain to every channel, up to 12 dB either way.
+///
+/// As only the ratios between envelopes matter, the shaping is the same whatever the level of the
+/// signal, and there is no threshold to set, unlike a **Compressor**. With both amounts at `0.0`,
+/// the signal passes unchanged.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransientShaper {
+    attack: f32,
+    sustain: f32,
+    fast: EnvelopeFollower,
+    slow_attack: EnvelopeFollower,
+    slow_release: EnvelopeFollower,
+    /// The gain applied to the most recent frame in decibels.
+    gain_db: f32,
+}
+
+impl TransientShaper {
+    /// Construct a **TransientShaper** with the given `attack` and `sustain` amounts, each within
+    /// the range -1.0 ... 1.0.
+    pub fn new(attack: f32, sustain: f32) -> Self {
+        let follower = |(attack_ms, release_ms)| EnvelopeFollower::new(attack_ms, release_ms);
+        TransientShaper {
+            attack: attack.clamp(-1.0, 1.0),
+            sustain: sustain.clamp(-1.0, 1.0),
+            fast: follower(TRANSIENT_FAST_MS),
+            slow_attack: follower(TRANSIENT_SLOW_ATTACK_MS),
+            slow_release: follower(TRANSIENT_SLOW_RELEASE_MS),
+            gain_db: 0.0,
+        }
+    }
+
+    /// The amount by which transients are boosted or cut (-1.0 ... 1.0).
+    pub fn attack(&self) -> f32 {
+        self.attack
+    }
+
+    /// Set the amount by which transients are boosted (0.0 ... 1.0) or cut (-1.0 ... 0.0).
+    pub fn set_attack(&mut self, attack: f32) {
+        self.attack = attack.clamp(-1.0, 1.0);
+    }
+
+    /// The amount by which the body of each sound is boosted or cut (-1.0 ... 1.0).
+    pub fn sustain(&self) -> f32 {
+        self.sustain
+    }
+
+    /// Set the amount by which the body of each sound is boosted (0.0 ... 1.0) or cut
+    /// (-1.0 ... 0.0).
+    pub fn set_sustain(&mut self, sustain: f32) {
+        self.sustain = sustain.clamp(-1.0, 1.0);
+    }
+
+    /// The gain applied to the most recent frame in decibels, positive when boosting and negative
+    /// when cutting.
+    pub fn gain_db(&self) -> f32 {
+        self.gain_db
+    }
+
+    /// The parameters of the **TransientShaper**.
+    pub fn params(&self) -> TransientShaperParams {
+        TransientShaperParams {
+            attack: self.attack,
+            sustain: self.sustain,
+        }
+    }
+
+    /// Apply the given parameters, leaving the detected envelopes untouched.
+    pub fn set_params(&mut self, params: TransientShaperParams) {
+        self.set_attack(params.attack);
+        self.set_sustain(params.sustain);
+    }
+
+    /// Return the detected envelopes to silence.
+    pub fn reset(&mut self) {
+        self.fast.reset();
+        self.slow_attack.reset();
+        self.slow_release.reset();
+        self.gain_db = 0.0;
+    }
+}
+
+impl<F> Node<F> for TransientShaper
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.fast.prepare_for(sample_hz, 1);
+        self.slow_attack.prepare_for(sample_hz, 1);
+        self.slow_release.prepare_for(sample_hz, 1);
+        let attack = self.attack as f64;
+        let sustain = self.sustain as f64;
+        let mut gain_db = self.gain_db as f64;
+        let TransientShaper {
+            ref mut fast,
+            ref mut slow_attack,
+            ref mut slow_release,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            let level = peak(frame);
+            let db = |env: f64| 20.0 * env.max(gain::SILENCE_AMP).log10();
+            let fast_db = db(fast.follow(0, level));
+            let transient_db = (fast_db - db(slow_attack.follow(0, level))).max(0.0);
+            let body_db = (db(slow_release.follow(0, level)) - fast_db).max(0.0);
+            gain_db = (attack * transient_db + sustain * body_db)
+                .clamp(-TRANSIENT_MAX_DB, TRANSIENT_MAX_DB);
+            let gain = 10f64.powf(gain_db / 20.0);
+            frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+        });
+        self.gain_db = gain_db as f32;
+    }
+
+    /// Exposes:
+    ///
+    /// - `attack`: the boost or cut of transients (-1.0 ... 1.0).
+    /// - `sustain`: the boost or cut of the body of each sound (-1.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["attack", "sustain"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "attack" => Some(self.attack),
+            "sustain" => Some(self.sustain),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "attack" => self.set_attack(value),
+            "sustain" => self.set_sustain(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        TransientShaper::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "transient_shaper"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Graph;
+    use crate::testing;
+
+    #[test]
+    fn a_step_in_level_rises_over_the_attack_time() {
+        // 10 frames to attack and 100 frames to release at 1 kHz.
+        let mut follower = EnvelopeFollower::new(10.0, 100.0);
+        let input = [[0.5f32, -0.25]; 10];
+        let mut buffer = input;
+        follower.audio_requested(&mut buffer, 1_000.0);
+        assert_eq!(buffer, input);
+        let one_time_constant = 1.0 - (-1.0f32).exp();
+        assert!((follower.envelope()[0] - 0.5 * one_time_constant).abs() < 1e-3);
+        assert!((follower.envelope()[1] - 0.25 * one_time_constant).abs() < 1e-3);
+        let mut buffer = [[0.5f32, -0.25]; 40];
+        follower.audio_requested(&mut buffer, 1_000.0);
+        assert!((follower.envelope()[0] - 0.5).abs() < 0.01);
+
+        // The release is much slower than the attack.
+        let mut silence = [[0.0f32; 2]; 10];
+        follower.audio_requested(&mut silence, 1_000.0);
+        let released = follower.envelope()[0];
+        assert!(released > 0.4 && released < 0.5, "{}", released);
+        Node::<[f32; 2]>::reset(&mut follower);
+        assert_eq!(follower.envelope(), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn a_signal_well_above_threshold_is_reduced_according_to_the_ratio() {
+        let mut compressor = Compressor::new(-20.0, 4.0);
+        // Half scale is ~14 dB above the threshold, so should be reduced by three quarters of that.
+        let level_db = gain::amp_to_db(0.5);
+        let expected_db = (level_db + 20.0) * 0.75;
+        let mut buffer = [[0.5f32, -0.5]; 4_410];
+        compressor.audio_requested(&mut buffer, 44_100.0);
+        assert!((compressor.gain_reduction_db() - expected_db).abs() < 0.01);
+        let out_db = gain::amp_to_db(buffer[4_409][0]);
+        assert!(
+            (out_db - (-20.0 + (level_db + 20.0) / 4.0)).abs() < 0.01,
+            "{}",
+            out_db
+        );
+        assert_eq!(buffer[4_409][1], -buffer[4_409][0]);
+        // The reduction builds with the attack rather than jumping.
+        assert!(buffer[0][0] > buffer[100][0]);
+
+        compressor.set_makeup_db(6.0);
+        compressor.audio_requested(&mut buffer[..1], 44_100.0);
+        let with_makeup = buffer[0][0];
+        assert!((gain::amp_to_db(with_makeup) - (out_db + 6.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn signals_below_threshold_pass_and_the_knee_eases_in_the_ratio() {
+        let mut compressor = Compressor::new(-6.0, 8.0);
+        let mut buffer = [[0.25f32]; 1_000];
+        compressor.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(compressor.gain_reduction_db(), 0.0);
+        assert!(buffer.iter().all(|frame| frame[0] == 0.25));
+
+        compressor.set_knee_db(6.0);
+        let slope = 1.0 - 1.0 / 8.0;
+        assert_eq!(compressor.gain_reduction_for(-9.0), 0.0);
+        assert!((compressor.gain_reduction_for(-6.0) - slope * 6.0 / 8.0).abs() < 1e-6);
+        assert!((compressor.gain_reduction_for(0.0) - slope * 6.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_quiet_segment_between_two_loud_bursts_is_attenuated() {
+        // At 48 kHz: 100 ms bursts at -6 dB around 500 ms at -40 dB.
+        let mut buffer: Vec<[f32; 1]> = (0..33_600)
+            .map(|i| {
+                [if (4_800..28_800).contains(&i) {
+                    0.01
+                } else {
+                    0.5
+                }]
+            })
+            .collect();
+        let mut gate = Gate::new(-20.0);
+        let mut stages = Vec::new();
+        // Buffers of a size unrelated to any of the times, so that the state spans buffers.
+        for chunk in buffer.chunks_mut(100) {
+            gate.audio_requested(chunk, 48_000.0);
+            stages.push(gate.stage());
+        }
+        // The bursts pass once the 1 ms attack completes.
+        assert!(buffer[48..4_800].iter().all(|frame| frame[0] == 0.5));
+        assert!(buffer[28_848..].iter().all(|frame| frame[0] == 0.5));
+        assert!(buffer[0][0] < 0.5 && buffer[28_800][0] < 0.5);
+        // Held for 50 ms, then released over 100 ms.
+        assert!(buffer[4_800..7_200].iter().all(|frame| frame[0] == 0.01));
+        assert!(buffer[16_800..28_800].iter().all(|frame| frame[0] == 0.0));
+        // Each stage is that after `(i + 1) * 100` frames.
+        assert_eq!(stages[49], GateStage::Open);
+        assert_eq!(stages[69], GateStage::Hold);
+        assert_eq!(stages[99], GateStage::Release);
+        assert_eq!(stages[199], GateStage::Closed);
+        assert_eq!(stages[335], GateStage::Open);
+        assert_eq!(gate.gain(), 1.0);
+    }
+
+    /// A source of a constant level on every channel.
+    struct Dc(f32);
+
+    impl Node<[f32; 1]> for Dc {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            sample::slice::equilibrium(buffer);
+            sample::slice::map_in_place(buffer, |_| [self.0]);
+        }
+    }
+
+    /// Render a second of the node fed `main` at its first input and `key` at its sidechain input,
+    /// returning the last frame.
+    fn keyed(node: Box<dyn Node<[f32; 1]>>, main: f32, key: f32) -> f32 {
+        let mut graph: Graph<[f32; 1], Box<dyn Node<[f32; 1]>>> = Graph::new();
+        let main = graph.add_node(Box::new(Dc(main)));
+        let (_, node) = graph.add_output(main, node);
+        let key = graph.add_node(Box::new(Dc(key)));
+        graph.add_connection(key, node).unwrap();
+        graph.set_master(Some(node));
+        let mut buffer = vec![[0.0]; 48_000];
+        for chunk in buffer.chunks_mut(480) {
+            graph.audio_requested(chunk, 48_000.0);
+        }
+        buffer[47_999][0]
+    }
+
+    #[test]
+    fn a_loud_sidechain_reduces_the_gain_of_an_unrelated_signal() {
+        let compressor = || {
+            let mut compressor = Compressor::new(-20.0, 4.0);
+            compressor.set_sidechain(true);
+            Box::new(compressor) as Box<dyn Node<[f32; 1]>>
+        };
+        // A quiet key leaves the signal untouched, and is not itself heard.
+        assert_eq!(keyed(compressor(), 0.25, 0.01), 0.25);
+        assert_eq!(keyed(compressor(), 0.0, 0.5), 0.0);
+        // A key 14 dB over the threshold reduces the gain by three quarters of that.
+        let expected = 0.25 * gain::db_to_amp(-(gain::amp_to_db(0.5) + 20.0) * 0.75);
+        let ducked = keyed(compressor(), 0.25, 0.5);
+        assert!(
+            (ducked - expected).abs() < 1e-4,
+            "{} != {}",
+            ducked,
+            expected
+        );
+
+        // Without the sidechain, both inputs are summed and compressed together.
+        let summed = keyed(Box::new(Compressor::new(-20.0, 4.0)), 0.25, 0.25);
+        assert!((summed - expected * 2.0).abs() < 1e-4, "{}", summed);
+    }
+
+    #[test]
+    fn a_gate_opens_with_its_sidechain_rather_than_its_signal() {
+        let gate = || {
+            let mut gate = Gate::new(-20.0);
+            gate.set_sidechain(true);
+            Box::new(gate) as Box<dyn Node<[f32; 1]>>
+        };
+        assert_eq!(keyed(gate(), 0.01, 0.5), 0.01);
+        assert_eq!(keyed(gate(), 0.5, 0.01), 0.0);
+        assert_eq!(keyed(Box::new(Gate::new(-20.0)), 0.5, 0.01), 0.51);
+    }
+
+    #[test]
+    fn a_sharp_transient_never_exceeds_the_lookahead_ceiling() {
+        // A quiet tone with a single full scale spike and a loud burst.
+        let mut input: Vec<[f32; 2]> = testing::sine(9_600, 220.0, 0.1, 48_000.0);
+        input[2_000] = [1.0, -1.0];
+        for frame in input[5_000..6_000].iter_mut() {
+            *frame = [frame[0] * 9.0, frame[1] * 9.0];
+        }
+        // 5 ms is 240 frames at 48 kHz.
+        let mut limiter = LookaheadLimiter::new(0.5, 5.0, 50.0);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(100) {
+            limiter.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(Node::<[f32; 2]>::latency_samples(&limiter), 240);
+        assert!(output
+            .iter()
+            .flat_map(|frame| frame.iter())
+            .all(|s| s.abs() <= 0.5));
+        // The spike is turned down to the ceiling, and the audio ahead of it is left untouched.
+        assert!((output[2_240][0] - 0.5).abs() < 1e-6, "{:?}", output[2_240]);
+        for (out, input) in output[240..2_000].iter().zip(&input) {
+            assert_eq!(out, input);
+        }
+    }
+
+    #[test]
+    fn audio_beneath_the_ceiling_is_only_delayed() {
+        let input: Vec<[f32; 1]> = testing::sine(4_800, 440.0, 0.4, 48_000.0);
+        let mut limiter = LookaheadLimiter::new(0.5, 1.0, 50.0);
+        let mut output = input.clone();
+        limiter.audio_requested(&mut output, 48_000.0);
+        assert!(output[..48].iter().all(|frame| frame[0] == 0.0));
+        assert_eq!(output[48..], input[..4_800 - 48]);
+    }
+
+    #[test]
+    fn a_loud_sidechain_ducks_the_signal_and_silence_leaves_it() {
+        let ducker = || Box::new(Ducker::new(-30.0, 4.0)) as Box<dyn Node<[f32; 1]>>;
+        assert_eq!(keyed(ducker(), 0.25, 0.0), 0.25);
+        assert_eq!(keyed(ducker(), 0.0, 0.5), 0.0);
+        // 6 dB over the threshold ducks by three quarters of that.
+        let key = gain::db_to_amp(-24.0);
+        let expected = 0.25 * gain::db_to_amp(-4.5);
+        let ducked = keyed(ducker(), 0.25, key);
+        assert!(
+            (ducked - expected).abs() < 1e-4,
+            "{} != {}",
+            ducked,
+            expected
+        );
+        // Far over the threshold, the reduction is bounded by the range.
+        let expected = 0.25 * gain::db_to_amp(-12.0);
+        let ducked = keyed(ducker(), 0.25, 0.5);
+        assert!(
+            (ducked - expected).abs() < 1e-4,
+            "{} != {}",
+            ducked,
+            expected
+        );
+    }
+
+    /// The level in decibels of the second half of a second of a loud tone through the compressor,
+    /// relative to the tone itself.
+    fn compressed_level_db(compressor: &mut Compressor) -> f32 {
+        let input: Vec<[f32; 1]> = testing::sine(48_000, 100.0, 0.9, 48_000.0);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(480) {
+            compressor.audio_requested(chunk, 48_000.0);
+        }
+        gain::amp_to_db((testing::rms(&output[24_000..]) / testing::rms(&input[24_000..])) as f32)
+    }
+
+    #[test]
+    fn auto_makeup_restores_the_level_of_a_compressed_signal() {
+        let mut compressor = Compressor::new(-20.0, 4.0);
+        let compressed = compressed_level_db(&mut compressor);
+        assert!(compressed < -12.0, "{}", compressed);
+
+        // 15 dB of reduction at 0 dBFS.
+        let mut compressor = Compressor::new(-20.0, 4.0);
+        compressor.set_auto_makeup(true);
+        assert!((compressor.auto_makeup_db() - 15.0).abs() < 1e-4);
+        let restored = compressed_level_db(&mut compressor);
+        assert!(restored.abs() < 2.0, "{}", restored);
+
+        // Manual make-up is applied on top.
+        let mut compressor = Compressor::new(-20.0, 4.0);
+        compressor.set_auto_makeup(true);
+        compressor.set_makeup_db(3.0);
+        let boosted = compressed_level_db(&mut compressor);
+        assert!((boosted - restored - 3.0).abs() < 0.01, "{}", boosted);
+    }
+
+    /// The ratio in decibels between the peak of the first 2 ms of each hit of a decaying 200 Hz
+    /// drum-like tone and the peak of its tail 100 ms later, after passing through the shaper.
+    fn transient_to_tail_db(shaper: &mut TransientShaper) -> f32 {
+        let hit: Vec<[f32; 1]> = (0..12_000)
+            .map(|i| {
+                let t = i as f32 / 48_000.0;
+                [0.8 * (-t * 20.0).exp() * (2.0 * std::f32::consts::PI * 200.0 * t).sin()]
+            })
+            .collect();
+        let mut output: Vec<[f32; 1]> = hit.iter().chain(hit.iter()).cloned().collect();
+        for chunk in output.chunks_mut(256) {
+            shaper.audio_requested(chunk, 48_000.0);
+        }
+        let peak = |frames: &[[f32; 1]]| frames.iter().fold(0.0f32, |max, f| max.max(f[0].abs()));
+        // Measure the second hit so that the envelopes start from the tail of the first.
+        let second = &output[12_000..];
+        gain::amp_to_db(peak(&second[..96]) / peak(&second[4_800..5_280]))
+    }
+
+    #[test]
+    fn increasing_the_attack_raises_transients_relative_to_their_tails() {
+        let neutral = transient_to_tail_db(&mut TransientShaper::new(0.0, 0.0));
+        let quarter = transient_to_tail_db(&mut TransientShaper::new(0.25, 0.0));
+        let half = transient_to_tail_db(&mut TransientShaper::new(0.5, 0.0));
+        let cut = transient_to_tail_db(&mut TransientShaper::new(-0.5, 0.0));
+        assert!(quarter > neutral + 3.0, "{} {}", quarter, neutral);
+        assert!(half > quarter + 3.0, "{} {}", half, quarter);
+        assert!(cut < neutral - 3.0, "{} {}", cut, neutral);
+
+        let mut shaper = TransientShaper::new(0.0, 0.0);
+        shaper.set_attack(0.5);
+        assert_eq!(transient_to_tail_db(&mut shaper), half);
+    }
+}
@@ -0,0 +1,394 @@
+//! Envelope generator nodes.
+
+use crate::midi::MidiMessage;
+use crate::node::Node;
+use crate::param::{self, Smoothing};
+use crate::util;
+use sample::{self, Frame};
+
+/// The stage of an **Adsr** envelope.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdsrStage {
+    /// The envelope is silent and waiting for a `note_on`.
+    Idle,
+    /// The envelope is rising from its current level to `1.0`.
+    Attack,
+    /// The envelope is falling from `1.0` to the sustain level.
+    Decay,
+    /// The envelope is holding at the sustain level until a `note_off`.
+    Sustain,
+    /// The envelope is falling from its level at the `note_off` to silence.
+    Release,
+}
+
+/// The parameters of an **Adsr**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdsrParams {
+    /// The attack time in seconds.
+    pub attack: f64,
+    /// The decay time in seconds.
+    pub decay: f64,
+    /// The sustain level (0.0 ... 1.0).
+    pub sustain: f64,
+    /// The release time in seconds.
+    pub release: f64,
+}
+
+/// An attack, decay, sustain, release envelope **Node**.
+///
+/// Multiplies every channel of its input by the envelope's current level, which advances once per
+/// frame. A `note_on` triggers the attack stage and a `note_off` triggers the release stage, as do
+/// MIDI note-ons and note-offs sent via `Node::handle_midi`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Adsr {
+    /// The attack time in seconds.
+    pub attack: f64,
+    /// The decay time in seconds.
+    pub decay: f64,
+    /// The sustain level (0.0 ... 1.0).
+    pub sustain: f64,
+    /// The release time in seconds.
+    pub release: f64,
+    stage: AdsrStage,
+    level: f64,
+    /// The level from which the current release stage started.
+    release_level: f64,
+}
+
+impl Adsr {
+    /// Construct an **Adsr** envelope with the given times in seconds and sustain level.
+    pub fn new(attack: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        Adsr {
+            attack,
+            decay,
+            sustain,
+            release,
+            stage: AdsrStage::Idle,
+            level: 0.0,
+            release_level: 0.0,
+        }
+    }
+
+    /// The parameters of the **Adsr**.
+    pub fn params(&self) -> AdsrParams {
+        AdsrParams {
+            attack: self.attack,
+            decay: self.decay,
+            sustain: self.sustain,
+            release: self.release,
+        }
+    }
+
+    /// Apply the given parameters, leaving the current stage and level untouched.
+    pub fn set_params(&mut self, params: AdsrParams) {
+        self.attack = params.attack;
+        self.decay = params.decay;
+        self.sustain = params.sustain;
+        self.release = params.release;
+    }
+
+    /// Trigger the attack stage, starting from the envelope's current level.
+    pub fn note_on(&mut self) {
+        self.stage = AdsrStage::Attack;
+    }
+
+    /// Trigger the release stage, starting from the envelope's current level.
+    pub fn note_off(&mut self) {
+        if self.stage != AdsrStage::Idle {
+            self.stage = AdsrStage::Release;
+            self.release_level = self.level;
+        }
+    }
+
+    /// The current stage of the envelope.
+    pub fn stage(&self) -> AdsrStage {
+        self.stage
+    }
+
+    /// The current level of the envelope (0.0 ... 1.0).
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Whether or not the envelope is producing sound, i.e. it is not `Idle`.
+    pub fn is_active(&self) -> bool {
+        self.stage != AdsrStage::Idle
+    }
+
+    /// Produce the level for the current frame and advance the envelope by one frame.
+    pub fn next_level(&mut self, sample_hz: f64) -> f64 {
+        let sustain = self.sustain.clamp(0.0, 1.0);
+        match self.stage {
+            AdsrStage::Idle => self.level = 0.0,
+            AdsrStage::Attack => {
+                self.level += step(1.0, self.attack, sample_hz);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = AdsrStage::Decay;
+                }
+            }
+            AdsrStage::Decay => {
+                self.level -= step(1.0 - sustain, self.decay, sample_hz);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.stage = AdsrStage::Sustain;
+                }
+            }
+            AdsrStage::Sustain => self.level = sustain,
+            AdsrStage::Release => {
+                self.level -= step(self.release_level, self.release, sample_hz);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = AdsrStage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+
+    /// Return to the idle stage at a level of `0.0`.
+    pub fn reset(&mut self) {
+        self.stage = AdsrStage::Idle;
+        self.level = 0.0;
+        self.release_level = 0.0;
+    }
+}
+
+/// The per-frame step required to move through `distance` over `seconds`.
+///
+/// Stages with no duration complete within a single frame.
+fn step(distance: f64, seconds: f64, sample_hz: f64) -> f64 {
+    let frames = seconds * sample_hz;
+    if frames <= 1.0 {
+        f64::INFINITY
+    } else {
+        distance / frames
+    }
+}
+
+impl<F> Node<F> for Adsr
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        sample::slice::map_in_place(buffer, |frame| {
+            let level = self.next_level(sample_hz);
+            frame.map(|s| util::from_f64(util::to_f64(s) * level))
+        });
+    }
+
+    /// Triggers `note_on` upon any note-on and `note_off` upon any note-off, whatever the note.
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        match message {
+            MidiMessage::NoteOn { velocity, .. } if velocity > 0 => self.note_on(),
+            MidiMessage::NoteOn { .. } | MidiMessage::NoteOff { .. } => self.note_off(),
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Adsr::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "adsr"
+    }
+}
+
+/// The parameters of a **Ramp**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RampParams {
+    /// The value at the start of the ramp.
+    pub start: f64,
+    /// The value at the end of the ramp, which is then held.
+    pub end: f64,
+    /// The duration of the ramp in seconds.
+    pub seconds: f64,
+    /// The curve followed from `start` to `end`.
+    pub curve: Smoothing,
+}
+
+/// A source **Node** that ramps from a start value to an end value over a duration and then holds
+/// the end value, e.g. to drive a fade or as a test signal.
+///
+/// The value is written to every channel, replacing the input. The ramp follows the same curves as
+/// a **Param**: `Smoothing::Linear` moves in equal steps, while `Smoothing::Exponential` moves a
+/// constant fraction of the remaining distance per frame and lands exactly upon the end value. The
+/// position within the ramp carries across buffers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ramp {
+    start: f64,
+    end: f64,
+    seconds: f64,
+    curve: Smoothing,
+    /// The number of frames rendered since the ramp started.
+    elapsed: u64,
+    /// The value of the last frame rendered.
+    value: f64,
+}
+
+impl Ramp {
+    /// Construct a linear **Ramp** from `start` to `end` over the given number of seconds.
+    pub fn new(start: f64, end: f64, seconds: f64) -> Self {
+        Ramp {
+            start,
+            end,
+            seconds: seconds.max(0.0),
+            curve: Smoothing::Linear,
+            elapsed: 0,
+            value: start,
+        }
+    }
+
+    /// Restart the ramp from `start` to `end` over the given number of seconds.
+    pub fn set_ramp(&mut self, start: f64, end: f64, seconds: f64) {
+        self.start = start;
+        self.end = end;
+        self.seconds = seconds.max(0.0);
+        self.reset();
+    }
+
+    /// The value at the start of the ramp.
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// The value at the end of the ramp.
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    /// The duration of the ramp in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+
+    /// The curve followed from `start` to `end`.
+    pub fn curve(&self) -> Smoothing {
+        self.curve
+    }
+
+    /// Set the curve followed from `start` to `end`, leaving the position within the ramp
+    /// untouched.
+    pub fn set_curve(&mut self, curve: Smoothing) {
+        self.curve = curve;
+    }
+
+    /// The value of the last frame rendered, or `start` if none have been rendered since the ramp
+    /// started.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The parameters of the **Ramp**.
+    pub fn params(&self) -> RampParams {
+        RampParams {
+            start: self.start,
+            end: self.end,
+            seconds: self.seconds,
+            curve: self.curve,
+        }
+    }
+
+    /// Apply the given parameters, restarting the ramp.
+    pub fn set_params(&mut self, params: RampParams) {
+        self.set_ramp(params.start, params.end, params.seconds);
+        self.set_curve(params.curve);
+    }
+
+    /// Return to the start of the ramp.
+    pub fn reset(&mut self) {
+        self.elapsed = 0;
+        self.value = self.start;
+    }
+
+    /// The value at the given number of frames into a ramp lasting `frames` frames.
+    fn value_at(&self, elapsed: u64, frames: f64) -> f64 {
+        // A ramp lasting no frames at all (where `position` is `NaN`) jumps straight to the end.
+        let position = elapsed as f64 / frames;
+        if position.is_nan() || position >= 1.0 {
+            return self.end;
+        }
+        match self.curve {
+            Smoothing::Linear => self.start + (self.end - self.start) * position,
+            Smoothing::Exponential => {
+                let residual = (param::EXPONENTIAL_RESIDUAL as f64).powf(position);
+                self.end + (self.start - self.end) * residual
+            }
+        }
+    }
+}
+
+impl<F> Node<F> for Ramp
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let frames = self.seconds * sample_hz;
+        for frame in buffer.iter_mut() {
+            self.value = self.value_at(self.elapsed, frames);
+            self.elapsed = self.elapsed.saturating_add(1);
+            let value = util::from_f64(self.value);
+            *frame = F::from_fn(|_| value);
+        }
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        Ramp::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "ramp"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first channel of the ramp's output over `len` frames, rendered in buffers of 3 frames.
+    fn render_ramp(ramp: &mut Ramp, len: usize, sample_hz: f64) -> Vec<f64> {
+        let mut buffer = vec![[0.0f64; 1]; len];
+        for chunk in buffer.chunks_mut(3) {
+            ramp.audio_requested(chunk, sample_hz);
+        }
+        buffer.iter().map(|frame| frame[0]).collect()
+    }
+
+    #[test]
+    fn a_linear_ramp_passes_through_its_midpoint_and_holds_its_end() {
+        // 100 frames at 1 kHz.
+        let mut ramp = Ramp::new(-1.0, 3.0, 0.1);
+        let values = render_ramp(&mut ramp, 150, 1_000.0);
+        assert_eq!(values[0], -1.0);
+        assert_eq!(values[50], 1.0);
+        assert!(values[..100]
+            .windows(2)
+            .all(|w| (w[1] - w[0] - 0.04).abs() < 1e-12));
+        assert!(values[100..].iter().all(|&value| value == 3.0));
+        assert_eq!(ramp.value(), 3.0);
+
+        ramp.set_ramp(1.0, 0.0, 0.01);
+        let values = render_ramp(&mut ramp, 20, 1_000.0);
+        assert_eq!(values[0], 1.0);
+        assert_eq!(values[5], 0.5);
+        assert_eq!(values[10], 0.0);
+    }
+
+    #[test]
+    fn an_exponential_ramp_lands_exactly_on_its_end() {
+        let mut ramp = Ramp::new(0.0, 1.0, 0.1);
+        ramp.set_curve(Smoothing::Exponential);
+        let values = render_ramp(&mut ramp, 150, 1_000.0);
+        assert!(values.windows(2).all(|w| w[1] >= w[0]));
+        // Moving faster at the start than a linear ramp.
+        assert!(values[50] > 0.9);
+        assert!(values[100..].iter().all(|&value| value == 1.0));
+    }
+}
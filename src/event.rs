@@ -0,0 +1,453 @@
+//! Scheduling parameter changes and notes at exact sample times.
+//!
+//! A **Scheduled** wrapper counts the frames rendered by its node and holds an **EventQueue** of
+//! events, each stamped with the sample time at which it should apply. When audio is requested,
+//! the buffer is split at the frame of each due event, so that the event takes effect at exactly
+//! that frame rather than at the start of the buffer.
+
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::{Frame, Panning, Sample, Volume};
+
+/// A change to apply to a **Node** at a scheduled sample time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// Set the named parameter via `Node::set_param`.
+    Param {
+        /// The name of the parameter, as listed by `Node::param_names`.
+        name: String,
+        /// The new value of the parameter.
+        value: f32,
+    },
+    /// Send the MIDI note message via `Node::handle_midi`.
+    Midi(MidiMessage),
+}
+
+/// **Event**s sorted by the sample time at which they apply.
+///
+/// Events with equal sample times are kept in the order in which they were pushed. The queue may
+/// also hold other kinds of event, such as the enable changes scheduled via
+/// `Graph::schedule_enable`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventQueue<E = Event> {
+    events: Vec<(u64, E)>,
+}
+
+/// A **Node** wrapper that applies **Event**s at exact sample times.
+///
+/// The wrapper counts every frame rendered by the node, starting from `0` upon construction or
+/// `reset`, and each scheduled event is applied immediately before the frame at its sample time.
+/// Events whose sample time has already passed are applied at the start of the next buffer.
+///
+/// Splitting a buffer renders the node over each segment in turn, so any further outputs (see
+/// `Node::read_output`) or per-frame panning reported by the node describe the final segment only.
+/// The node is rendered even while its input is silent, so that its count of frames keeps up
+/// with the **Graph**.
+#[derive(Clone, Debug)]
+pub struct Scheduled<N> {
+    node: N,
+    queue: EventQueue,
+    /// The sample time of the first frame of the next buffer.
+    sample_time: u64,
+}
+
+/// Apply the given event to the node.
+fn apply<F, N>(node: &mut N, event: &Event)
+where
+    F: Frame,
+    N: Node<F>,
+{
+    match *event {
+        Event::Param { ref name, value } => {
+            node.set_param(name, value);
+        }
+        Event::Midi(message) => {
+            node.handle_midi(message);
+        }
+    }
+}
+
+impl<E> EventQueue<E> {
+    /// An empty **EventQueue**.
+    pub fn new() -> Self {
+        EventQueue { events: Vec::new() }
+    }
+
+    /// Insert the event to apply at the given sample time.
+    pub fn push(&mut self, sample_time: u64, event: E) {
+        let idx = self
+            .events
+            .iter()
+            .position(|&(time, _)| time > sample_time)
+            .unwrap_or(self.events.len());
+        self.events.insert(idx, (sample_time, event));
+    }
+
+    /// The sample time of the earliest event, if any.
+    pub fn next_time(&self) -> Option<u64> {
+        self.events.first().map(|&(time, _)| time)
+    }
+
+    /// Remove and return the earliest event if it applies at or before the given sample time.
+    pub fn pop_due(&mut self, sample_time: u64) -> Option<E> {
+        match self.next_time() {
+            Some(time) if time <= sample_time => Some(self.events.remove(0).1),
+            _ => None,
+        }
+    }
+
+    /// Each queued sample time and event, earliest first.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, (u64, E)> {
+        self.events.iter()
+    }
+
+    /// The number of queued events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether or not the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Discard every queued event.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl<E> Default for EventQueue<E> {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+impl<N> Scheduled<N> {
+    /// Wrap the given node with an empty **EventQueue**, starting at sample time `0`.
+    pub fn new(node: N) -> Self {
+        Scheduled {
+            node,
+            queue: EventQueue::new(),
+            sample_time: 0,
+        }
+    }
+
+    /// Schedule the event to apply at the given sample time.
+    pub fn schedule(&mut self, sample_time: u64, event: Event) {
+        self.queue.push(sample_time, event);
+    }
+
+    /// The sample time of the first frame of the next buffer, i.e. the number of frames rendered
+    /// since construction or the last `reset`.
+    pub fn sample_time(&self) -> u64 {
+        self.sample_time
+    }
+
+    /// The queue of events yet to be applied.
+    pub fn queue(&self) -> &EventQueue {
+        &self.queue
+    }
+
+    /// A mutable reference to the queue of events yet to be applied.
+    pub fn queue_mut(&mut self) -> &mut EventQueue {
+        &mut self.queue
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<F, N> Node<F> for Scheduled<N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let len = buffer.len();
+        let end_time = self.sample_time + len as u64;
+
+        // Render each segment between events using the state of the node at its start.
+        let mut start = 0;
+        let mut num_applied = 0;
+        while start < len {
+            let start_time = self.sample_time + start as u64;
+            while num_applied < self.queue.events.len()
+                && self.queue.events[num_applied].0 <= start_time
+            {
+                apply(&mut self.node, &self.queue.events[num_applied].1);
+                num_applied += 1;
+            }
+            let end = self
+                .queue
+                .events
+                .get(num_applied)
+                .map(|&(time, _)| (time.min(end_time) - self.sample_time) as usize)
+                .unwrap_or(len);
+            self.node
+                .audio_requested(&mut buffer[start..end], sample_hz);
+            start = end;
+        }
+
+        self.queue.events.drain(..num_applied);
+        self.sample_time = end_time;
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.node.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.node.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        self.node.vol_per_channel()
+    }
+
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        self.node.vols_per_channel(vols)
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    /// Always `false`, even if the wrapped node skips silence, so that the wrapper sees every
+    /// buffer and its sample time keeps running while the input is silent. Otherwise events
+    /// would apply late, or never while the input stayed silent.
+    fn skip_silence(&self) -> bool {
+        false
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    /// Prepares the wrapped node and restarts the sample time at `0`. Unlike `reset`, any queued
+    /// events are kept, so events may be scheduled before streaming begins.
+    fn prepare(&mut self, settings: Settings) {
+        self.sample_time = 0;
+        self.node.prepare(settings);
+    }
+
+    /// Resets the wrapped node, discards any queued events and restarts the sample time at `0`.
+    fn reset(&mut self) {
+        self.queue.clear();
+        self.sample_time = 0;
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+
+    fn type_tag(&self) -> &str {
+        self.node.type_tag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gain::Gain;
+    use crate::Graph;
+
+    type Mono = [f32; 1];
+
+    /// A source writing silence for its first `silent_frames` frames and full scale after.
+    struct Onset {
+        silent_frames: usize,
+        frames: usize,
+    }
+
+    impl Node<Mono> for Onset {
+        fn audio_requested(&mut self, buffer: &mut [Mono], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [if self.frames < self.silent_frames {
+                    0.0
+                } else {
+                    1.0
+                }];
+                self.frames += 1;
+            }
+        }
+    }
+
+    fn gain_event(value: f32) -> Event {
+        Event::Param {
+            name: "gain".to_string(),
+            value,
+        }
+    }
+
+    fn unsmoothed_gain() -> Gain {
+        let mut gain = Gain::new(1.0);
+        gain.set_smoothing_ms(0.0);
+        gain
+    }
+
+    #[test]
+    fn param_event_splits_the_buffer_at_its_frame() {
+        let mut node = Scheduled::new(unsmoothed_gain());
+        node.schedule(100, gain_event(0.25));
+        let mut buffer = vec![[1.0]; 256];
+        node.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer[..100].iter().all(|f| f[0] == 1.0));
+        assert!(buffer[100..].iter().all(|f| f[0] == 0.25));
+        assert!(node.queue().is_empty());
+        assert_eq!(node.sample_time(), 256);
+    }
+
+    #[test]
+    fn events_spanning_buffers_apply_at_their_frames() {
+        let mut node = Scheduled::new(unsmoothed_gain());
+        node.schedule(300, gain_event(0.5));
+        node.schedule(10, gain_event(0.0));
+        let mut first = vec![[1.0]; 256];
+        let mut second = vec![[1.0]; 256];
+        node.audio_requested(&mut first, 44_100.0);
+        node.audio_requested(&mut second, 44_100.0);
+        assert_eq!(first[9], [1.0]);
+        assert_eq!(first[10], [0.0]);
+        assert_eq!(second[300 - 256 - 1], [0.0]);
+        assert_eq!(second[300 - 256], [0.5]);
+    }
+
+    #[test]
+    fn scheduling_keeps_time_across_silent_input() {
+        let mut graph: Graph<Mono, Box<dyn Node<Mono>>> = Graph::new();
+        let mut scheduled = Scheduled::new(unsmoothed_gain());
+        scheduled.schedule(100, gain_event(0.0));
+        let master = graph.add_node(Box::new(scheduled) as Box<dyn Node<Mono>>);
+        let onset = Onset {
+            silent_frames: 256,
+            frames: 0,
+        };
+        graph.add_input(Box::new(onset), master);
+        graph.set_master(Some(master));
+
+        let mut buffer = vec![[0.0]; 256];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|f| f[0] == 0.0));
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|f| f[0] == 0.0));
+    }
+}
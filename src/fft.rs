@@ -0,0 +1,133 @@
+//! A radix-2 fast Fourier transform shared by the crate's frequency-domain nodes.
+
+use std::f64::consts::PI;
+
+/// A complex number.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct Complex {
+    pub(crate) re: f64,
+    pub(crate) im: f64,
+}
+
+impl Complex {
+    /// The complex number `re + i·im`.
+    #[inline]
+    pub(crate) fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    /// The magnitude of the complex number.
+    #[inline]
+    pub(crate) fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// The product of two complex numbers.
+    #[inline]
+    pub(crate) fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// An in-place, iterative radix-2 FFT of a fixed power-of-two size.
+///
+/// The twiddle factors and bit-reversal permutation are calculated upon construction, so
+/// transforming performs no allocation.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Fft {
+    /// `e^(-2πik/size)` for `k` in `0 .. size / 2`.
+    twiddles: Vec<Complex>,
+    /// The index with which each index is swapped prior to the butterflies.
+    bit_reversed: Vec<usize>,
+}
+
+impl Fft {
+    /// **Panics** unless `size` is a power of two.
+    pub(crate) fn new(size: usize) -> Self {
+        assert!(
+            size.is_power_of_two(),
+            "the FFT size must be a power of two"
+        );
+        let twiddles = (0..size / 2)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f64 / size as f64;
+                Complex::new(angle.cos(), angle.sin())
+            })
+            .collect();
+        let bits = size.trailing_zeros();
+        let bit_reversed = (0..size)
+            .map(|i| {
+                if bits == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - bits)
+                }
+            })
+            .collect();
+        Fft {
+            twiddles,
+            bit_reversed,
+        }
+    }
+
+    /// The number of points transformed.
+    pub(crate) fn size(&self) -> usize {
+        self.bit_reversed.len()
+    }
+
+    /// Transform `buffer` from the time domain to the frequency domain in place.
+    ///
+    /// **Panics** if `buffer.len()` is not the size of the **Fft**.
+    pub(crate) fn forward(&self, buffer: &mut [Complex]) {
+        let size = self.size();
+        assert_eq!(buffer.len(), size, "the buffer must match the FFT size");
+
+        for (i, &j) in self.bit_reversed.iter().enumerate() {
+            if i < j {
+                buffer.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= size {
+            let half = len / 2;
+            let stride = size / len;
+            for start in (0..size).step_by(len) {
+                for k in 0..half {
+                    let twiddle = self.twiddles[k * stride];
+                    let a = buffer[start + k];
+                    let b = buffer[start + k + half].mul(twiddle);
+                    buffer[start + k] = Complex::new(a.re + b.re, a.im + b.im);
+                    buffer[start + k + half] = Complex::new(a.re - b.re, a.im - b.im);
+                }
+            }
+            len *= 2;
+        }
+    }
+
+    /// Transform `buffer` from the frequency domain back to the time domain in place, scaling by
+    /// `1 / size` so that `inverse` undoes `forward`.
+    ///
+    /// **Panics** if `buffer.len()` is not the size of the **Fft**.
+    pub(crate) fn inverse(&self, buffer: &mut [Complex]) {
+        // The inverse transform is the forward transform of the conjugate, conjugated.
+        for c in buffer.iter_mut() {
+            c.im = -c.im;
+        }
+        self.forward(buffer);
+        let scale = 1.0 / buffer.len() as f64;
+        for c in buffer.iter_mut() {
+            *c = Complex::new(c.re * scale, -c.im * scale);
+        }
+    }
+}
+
+/// A periodic Hann window of the given length.
+pub(crate) fn hann_window(len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / len as f64).cos())
+        .collect()
+}
@@ -0,0 +1,1991 @@
+//! Filter nodes.
+
+use crate::delay::DelayLine;
+use crate::node::{Node, Settings};
+use crate::oscillator::LfoShape;
+use crate::util;
+use sample::{self, Frame};
+
+/// The default pole radius used by the **DcBlocker**.
+pub const DEFAULT_DC_BLOCKER_R: f64 = 0.995;
+
+/// A **Node** that removes DC offset from its input.
+///
+/// Implements the one-pole, one-zero high-pass difference equation:
+///
+/// `y[n] = x[n] - x[n-1] + R * y[n-1]`
+///
+/// where `R` is slightly less than `1.0`. The closer `R` is to `1.0`, the lower the cutoff.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DcBlocker {
+    r: f64,
+    /// The previous input and output for each channel.
+    state: Vec<DcBlockerState>,
+}
+
+/// The parameters of a **DcBlocker**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DcBlockerParams {
+    /// The pole radius `R` (0.0 ... 1.0).
+    pub r: f64,
+}
+
+/// The previous input and output of a single channel of a **DcBlocker**.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct DcBlockerState {
+    x1: f64,
+    y1: f64,
+}
+
+impl DcBlocker {
+    /// Construct a **DcBlocker** with the default `R` of `DEFAULT_DC_BLOCKER_R`.
+    pub fn new() -> Self {
+        DcBlocker::with_r(DEFAULT_DC_BLOCKER_R)
+    }
+
+    /// Construct a **DcBlocker** with the given pole radius `R` (0.0 ... 1.0).
+    pub fn with_r(r: f64) -> Self {
+        DcBlocker {
+            r: r.clamp(0.0, 1.0),
+            state: Vec::new(),
+        }
+    }
+
+    /// The pole radius `R`.
+    pub fn r(&self) -> f64 {
+        self.r
+    }
+
+    /// Set the pole radius `R` (0.0 ... 1.0).
+    pub fn set_r(&mut self, r: f64) {
+        self.r = r.clamp(0.0, 1.0);
+    }
+
+    /// The parameters of the **DcBlocker**.
+    pub fn params(&self) -> DcBlockerParams {
+        DcBlockerParams { r: self.r }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    pub fn set_params(&mut self, params: DcBlockerParams) {
+        self.set_r(params.r);
+    }
+
+    /// Clear the filter's history, as though it had only ever received silence.
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+impl Default for DcBlocker {
+    fn default() -> Self {
+        DcBlocker::new()
+    }
+}
+
+impl<F> Node<F> for DcBlocker
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        if self.state.len() != F::n_channels() {
+            self.state
+                .resize(F::n_channels(), DcBlockerState::default());
+        }
+        let r = self.r;
+        let state = &mut self.state;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut state = state.iter_mut();
+            frame.map(|s| {
+                let state = state.next().expect("one state per channel");
+                let x = util::to_f64(s);
+                let y = x - state.x1 + r * state.y1;
+                state.x1 = x;
+                state.y1 = util::flush_denormal(y);
+                util::from_f64(y)
+            })
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `r`: the pole radius (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["r"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "r" => Some(self.r as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "r" => self.set_r(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        DcBlocker::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "dc_blocker"
+    }
+}
+
+/// The parameters of a **OnePole**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnePoleParams {
+    /// The time constant in milliseconds (0.0 ...).
+    pub time_ms: f64,
+}
+
+/// A first-order low-pass **Node**, suitable both for gently filtering audio and for smoothing
+/// control signals.
+///
+/// Implements the difference equation:
+///
+/// `y[n] = y[n-1] + a * (x[n] - y[n-1])`
+///
+/// where `a` is derived from the time constant, so that the output moves `1 - 1/e` (~63%) of the
+/// way towards a new input over `time_ms`. A `time_ms` of `0.0` passes the input through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OnePole {
+    time_ms: f64,
+    /// The previous output for each channel.
+    state: Vec<f64>,
+}
+
+impl OnePole {
+    /// Construct a **OnePole** with the given time constant in milliseconds.
+    pub fn new(time_ms: f64) -> Self {
+        OnePole {
+            time_ms: time_ms.max(0.0),
+            state: Vec::new(),
+        }
+    }
+
+    /// The time constant in milliseconds.
+    pub fn time_ms(&self) -> f64 {
+        self.time_ms
+    }
+
+    /// Set the time constant in milliseconds, clamped to `0.0` or above.
+    pub fn set_time_ms(&mut self, time_ms: f64) {
+        self.time_ms = time_ms.max(0.0);
+    }
+
+    /// The coefficient `a` at the given sample rate.
+    pub fn coefficient(&self, sample_hz: f64) -> f64 {
+        let frames = self.time_ms / 1_000.0 * sample_hz;
+        if frames > 0.0 {
+            1.0 - (-1.0 / frames).exp()
+        } else {
+            1.0
+        }
+    }
+
+    /// The parameters of the **OnePole**.
+    pub fn params(&self) -> OnePoleParams {
+        OnePoleParams {
+            time_ms: self.time_ms,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    pub fn set_params(&mut self, params: OnePoleParams) {
+        self.set_time_ms(params.time_ms);
+    }
+
+    /// Clear the filter's history, as though it had only ever received silence.
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+impl<F> Node<F> for OnePole
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if self.state.len() != F::n_channels() {
+            self.state.resize(F::n_channels(), 0.0);
+        }
+        let a = self.coefficient(sample_hz);
+        let state = &mut self.state;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut state = state.iter_mut();
+            frame.map(|s| {
+                let y1 = state.next().expect("one state per channel");
+                let y = *y1 + a * (util::to_f64(s) - *y1);
+                *y1 = util::flush_denormal(y);
+                util::from_f64(y)
+            })
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `time_ms`: the time constant in milliseconds (0.0 ...).
+    fn param_names(&self) -> &[&str] {
+        &["time_ms"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "time_ms" => Some(self.time_ms as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "time_ms" => self.set_time_ms(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        OnePole::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "one_pole"
+    }
+}
+
+/// The response of a **Biquad** filter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BiquadKind {
+    /// Attenuates frequencies above the cutoff.
+    LowPass,
+    /// Attenuates frequencies below the cutoff.
+    HighPass,
+    /// Attenuates frequencies either side of the cutoff.
+    BandPass,
+    /// Attenuates frequencies close to the cutoff.
+    Notch,
+    /// Boosts or cuts frequencies close to the cutoff by the filter's gain.
+    Peaking,
+    /// Boosts or cuts frequencies below the cutoff by the filter's gain.
+    LowShelf,
+    /// Boosts or cuts frequencies above the cutoff by the filter's gain.
+    HighShelf,
+}
+
+/// A second-order IIR filter **Node**.
+///
+/// Implements the Direct Form I difference equation with coefficients from Robert
+/// Bristow-Johnson's "Audio EQ Cookbook". The coefficients are calculated from the `sample_hz`
+/// passed to `prepare` or `audio_requested` and are re-calculated whenever it or a parameter
+/// changes, so the cutoff in hertz is kept when the sample rate changes.
+///
+/// The gain only applies to the peaking and shelf responses, for which the Q sets the width of
+/// the peak or the slope of the shelf.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biquad {
+    kind: BiquadKind,
+    cutoff_hz: f64,
+    q: f64,
+    gain_db: f64,
+    /// The sample rate for which `coefficients` were last calculated.
+    sample_hz: f64,
+    coefficients: BiquadCoefficients,
+    /// The previous two inputs and outputs for each channel.
+    state: Vec<BiquadState>,
+}
+
+/// The parameters of a **Biquad**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BiquadParams {
+    /// The response of the filter.
+    pub kind: BiquadKind,
+    /// The cutoff (or center) frequency in hertz.
+    pub cutoff_hz: f64,
+    /// The Q (resonance) of the filter.
+    pub q: f64,
+    /// The gain in decibels of the peaking and shelf responses.
+    pub gain_db: f64,
+}
+
+/// The normalised coefficients of a **Biquad** filter, where `a0` is `1.0`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct BiquadCoefficients {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+/// The previous two inputs and outputs of a single channel of a **Biquad**.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct BiquadState {
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    /// Construct a **Biquad** with the given response, cutoff frequency and Q, and a gain of 0 dB.
+    pub fn new(kind: BiquadKind, cutoff_hz: f64, q: f64) -> Self {
+        Biquad {
+            kind,
+            cutoff_hz,
+            q,
+            gain_db: 0.0,
+            sample_hz: 0.0,
+            coefficients: BiquadCoefficients::default(),
+            state: Vec::new(),
+        }
+    }
+
+    /// Construct a low-pass **Biquad**.
+    pub fn low_pass(cutoff_hz: f64, q: f64) -> Self {
+        Biquad::new(BiquadKind::LowPass, cutoff_hz, q)
+    }
+
+    /// Construct a high-pass **Biquad**.
+    pub fn high_pass(cutoff_hz: f64, q: f64) -> Self {
+        Biquad::new(BiquadKind::HighPass, cutoff_hz, q)
+    }
+
+    /// Construct a band-pass **Biquad** with a constant 0dB peak gain.
+    pub fn band_pass(cutoff_hz: f64, q: f64) -> Self {
+        Biquad::new(BiquadKind::BandPass, cutoff_hz, q)
+    }
+
+    /// Construct a notch **Biquad**.
+    pub fn notch(cutoff_hz: f64, q: f64) -> Self {
+        Biquad::new(BiquadKind::Notch, cutoff_hz, q)
+    }
+
+    /// Construct a peaking **Biquad** boosting or cutting by the given gain in decibels.
+    pub fn peaking(cutoff_hz: f64, q: f64, gain_db: f64) -> Self {
+        let mut biquad = Biquad::new(BiquadKind::Peaking, cutoff_hz, q);
+        biquad.gain_db = gain_db;
+        biquad
+    }
+
+    /// Construct a low shelf **Biquad** boosting or cutting by the given gain in decibels.
+    pub fn low_shelf(cutoff_hz: f64, q: f64, gain_db: f64) -> Self {
+        let mut biquad = Biquad::new(BiquadKind::LowShelf, cutoff_hz, q);
+        biquad.gain_db = gain_db;
+        biquad
+    }
+
+    /// Construct a high shelf **Biquad** boosting or cutting by the given gain in decibels.
+    pub fn high_shelf(cutoff_hz: f64, q: f64, gain_db: f64) -> Self {
+        let mut biquad = Biquad::new(BiquadKind::HighShelf, cutoff_hz, q);
+        biquad.gain_db = gain_db;
+        biquad
+    }
+
+    /// The response of the filter.
+    pub fn kind(&self) -> BiquadKind {
+        self.kind
+    }
+
+    /// Set the response of the filter.
+    pub fn set_kind(&mut self, kind: BiquadKind) {
+        self.kind = kind;
+        self.sample_hz = 0.0;
+    }
+
+    /// The cutoff (or center) frequency in hertz.
+    pub fn cutoff_hz(&self) -> f64 {
+        self.cutoff_hz
+    }
+
+    /// Set the cutoff (or center) frequency in hertz.
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f64) {
+        self.cutoff_hz = cutoff_hz;
+        self.sample_hz = 0.0;
+    }
+
+    /// The Q (resonance) of the filter.
+    pub fn q(&self) -> f64 {
+        self.q
+    }
+
+    /// Set the Q (resonance) of the filter.
+    pub fn set_q(&mut self, q: f64) {
+        self.q = q;
+        self.sample_hz = 0.0;
+    }
+
+    /// The gain in decibels of the peaking and shelf responses.
+    pub fn gain_db(&self) -> f64 {
+        self.gain_db
+    }
+
+    /// Set the gain in decibels of the peaking and shelf responses.
+    pub fn set_gain_db(&mut self, gain_db: f64) {
+        self.gain_db = gain_db;
+        self.sample_hz = 0.0;
+    }
+
+    /// The parameters of the **Biquad**.
+    pub fn params(&self) -> BiquadParams {
+        BiquadParams {
+            kind: self.kind,
+            cutoff_hz: self.cutoff_hz,
+            q: self.q,
+            gain_db: self.gain_db,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    ///
+    /// The coefficients are re-calculated upon the next call to `audio_requested`.
+    pub fn set_params(&mut self, params: BiquadParams) {
+        self.kind = params.kind;
+        self.cutoff_hz = params.cutoff_hz;
+        self.q = params.q;
+        self.gain_db = params.gain_db;
+        self.sample_hz = 0.0;
+    }
+
+    /// Calculate the coefficients for the given sample rate.
+    fn update_coefficients(&mut self, sample_hz: f64) {
+        // Keep the cutoff just below nyquist and the Q positive so the filter remains stable.
+        let cutoff_hz = self.cutoff_hz.clamp(1.0, sample_hz * 0.49);
+        let q = self.q.max(1e-3);
+        let w0 = 2.0 * ::std::f64::consts::PI * cutoff_hz / sample_hz;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+        // The filters without gain share the same poles.
+        let poles = (1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha);
+        let a = 10f64.powf(self.gain_db / 40.0);
+        let shelf_alpha = 2.0 * a.sqrt() * alpha;
+        let ((b0, b1, b2), (a0, a1, a2)) = match self.kind {
+            BiquadKind::LowPass => {
+                let b1 = 1.0 - cos_w0;
+                ((b1 / 2.0, b1, b1 / 2.0), poles)
+            }
+            BiquadKind::HighPass => {
+                let b1 = -(1.0 + cos_w0);
+                ((-b1 / 2.0, b1, -b1 / 2.0), poles)
+            }
+            BiquadKind::BandPass => ((alpha, 0.0, -alpha), poles),
+            BiquadKind::Notch => ((1.0, -2.0 * cos_w0, 1.0), poles),
+            BiquadKind::Peaking => (
+                (1.0 + alpha * a, -2.0 * cos_w0, 1.0 - alpha * a),
+                (1.0 + alpha / a, -2.0 * cos_w0, 1.0 - alpha / a),
+            ),
+            BiquadKind::LowShelf => (
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + shelf_alpha),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - shelf_alpha),
+                ),
+                (
+                    (a + 1.0) + (a - 1.0) * cos_w0 + shelf_alpha,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - shelf_alpha,
+                ),
+            ),
+            BiquadKind::HighShelf => (
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + shelf_alpha),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - shelf_alpha),
+                ),
+                (
+                    (a + 1.0) - (a - 1.0) * cos_w0 + shelf_alpha,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - shelf_alpha,
+                ),
+            ),
+        };
+        self.coefficients = BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        };
+        self.sample_hz = sample_hz;
+    }
+
+    /// Clear the filter's history, as though it had only ever received silence.
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+impl<F> Node<F> for Biquad
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if self.sample_hz != sample_hz {
+            self.update_coefficients(sample_hz);
+        }
+        if self.state.len() != F::n_channels() {
+            self.state.resize(F::n_channels(), BiquadState::default());
+        }
+        let c = self.coefficients;
+        let state = &mut self.state;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut state = state.iter_mut();
+            frame.map(|s| {
+                let state = state.next().expect("one state per channel");
+                let x = util::to_f64(s);
+                let y = c.b0 * x + c.b1 * state.x1 + c.b2 * state.x2
+                    - c.a1 * state.y1
+                    - c.a2 * state.y2;
+                state.x2 = state.x1;
+                state.x1 = x;
+                state.y2 = state.y1;
+                state.y1 = util::flush_denormal(y);
+                util::from_f64(y)
+            })
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `cutoff_hz`: the cutoff (or center) frequency in hertz.
+    /// - `q`: the Q (resonance) of the filter.
+    /// - `gain_db`: the gain in decibels of the peaking and shelf responses.
+    fn param_names(&self) -> &[&str] {
+        &["cutoff_hz", "q", "gain_db"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "cutoff_hz" => Some(self.cutoff_hz as f32),
+            "q" => Some(self.q as f32),
+            "gain_db" => Some(self.gain_db as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "cutoff_hz" => self.set_cutoff_hz(value as f64),
+            "q" => self.set_q(value as f64),
+            "gain_db" => self.set_gain_db(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 && self.sample_hz != settings.sample_hz {
+            self.update_coefficients(settings.sample_hz);
+        }
+        Biquad::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Biquad::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "biquad"
+    }
+}
+
+/// The Q of each of the Butterworth sections of a **Crossover**.
+const CROSSOVER_Q: f64 = ::std::f64::consts::FRAC_1_SQRT_2;
+
+/// The parameters of a **Crossover**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrossoverParams {
+    /// The frequency in hertz at which the bands are split.
+    pub cutoff_hz: f64,
+}
+
+/// A **Node** that splits its input into low and high bands.
+///
+/// The low band is the **Node**'s first output and the high band its second, selected via
+/// `Graph::add_connection_from_output` with an `output_idx` of `1`. As with every **Node**, the
+/// `Graph` applies the **Crossover**'s mix, volume and panning to the first output only.
+///
+/// Each band is a fourth-order Linkwitz-Riley filter, made of two cascaded second-order
+/// Butterworth filters. Both bands are 6 dB down at the cutoff and remain in phase with one
+/// another, so their sum has a flat magnitude response, differing from the input only in phase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crossover<F> {
+    cutoff_hz: f64,
+    low: [Biquad; 2],
+    high: [Biquad; 2],
+    /// The high band rendered by the last call to `audio_requested`.
+    high_band: Vec<F>,
+}
+
+impl<F> Crossover<F>
+where
+    F: Frame,
+{
+    /// Construct a **Crossover** splitting the bands at the given frequency.
+    pub fn new(cutoff_hz: f64) -> Self {
+        let low = Biquad::low_pass(cutoff_hz, CROSSOVER_Q);
+        let high = Biquad::high_pass(cutoff_hz, CROSSOVER_Q);
+        Crossover {
+            cutoff_hz,
+            low: [low.clone(), low],
+            high: [high.clone(), high],
+            high_band: Vec::new(),
+        }
+    }
+
+    /// The frequency in hertz at which the bands are split.
+    pub fn cutoff_hz(&self) -> f64 {
+        self.cutoff_hz
+    }
+
+    /// Set the frequency in hertz at which the bands are split.
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f64) {
+        self.cutoff_hz = cutoff_hz;
+        for filter in self.low.iter_mut().chain(self.high.iter_mut()) {
+            filter.set_cutoff_hz(cutoff_hz);
+        }
+    }
+
+    /// The parameters of the **Crossover**.
+    pub fn params(&self) -> CrossoverParams {
+        CrossoverParams {
+            cutoff_hz: self.cutoff_hz,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    pub fn set_params(&mut self, params: CrossoverParams) {
+        self.set_cutoff_hz(params.cutoff_hz);
+    }
+
+    /// Clear the history of each band's filters, as though they had only ever received silence.
+    pub fn reset(&mut self) {
+        for filter in self.low.iter_mut().chain(self.high.iter_mut()) {
+            filter.reset();
+        }
+    }
+}
+
+impl<F> Node<F> for Crossover<F>
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.high_band.clear();
+        self.high_band.extend_from_slice(buffer);
+        for filter in self.high.iter_mut() {
+            filter.audio_requested(&mut self.high_band, sample_hz);
+        }
+        for filter in self.low.iter_mut() {
+            filter.audio_requested(buffer, sample_hz);
+        }
+    }
+
+    fn num_outputs(&self) -> usize {
+        2
+    }
+
+    fn read_output(&self, _output_idx: usize, buffer: &mut [F]) {
+        sample::slice::write(buffer, &self.high_band);
+    }
+
+    /// Exposes:
+    ///
+    /// - `cutoff_hz`: the frequency in hertz at which the bands are split.
+    fn param_names(&self) -> &[&str] {
+        &["cutoff_hz"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "cutoff_hz" => Some(self.cutoff_hz as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "cutoff_hz" => self.set_cutoff_hz(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.high_band.clear();
+        self.high_band.reserve(settings.max_frames);
+        Crossover::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Crossover::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "crossover"
+    }
+}
+
+/// The parameters of a **ParametricEq**, excluding its runtime state.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParametricEqParams {
+    /// The parameters of each band, in the order in which they are applied.
+    pub bands: Vec<BiquadParams>,
+}
+
+/// An equaliser **Node** applying a chain of **Biquad** bands in series.
+///
+/// Each band is typically a `BiquadKind::Peaking`, `BiquadKind::LowShelf` or
+/// `BiquadKind::HighShelf` filter with its own frequency, gain and Q, though any **Biquad**
+/// response may be used, e.g. a high-pass to remove rumble. As with a single **Biquad**, the
+/// coefficients of each band are re-calculated whenever the sample rate or one of its parameters
+/// changes, and each band keeps the history of every channel between buffers.
+///
+/// As its bands are not exposed via `Node::param_names`, a **ParametricEq** has no
+/// `Node::type_tag` and so cannot be restored from a `preset::Preset`. Save its `params` instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParametricEq {
+    bands: Vec<Biquad>,
+}
+
+impl ParametricEq {
+    /// Construct a **ParametricEq** with no bands, leaving the signal unchanged.
+    pub fn new() -> Self {
+        ParametricEq { bands: Vec::new() }
+    }
+
+    /// Append a band with the given response, frequency in hertz, gain in decibels and Q,
+    /// returning its index.
+    pub fn add_band(&mut self, kind: BiquadKind, frequency_hz: f64, gain_db: f64, q: f64) -> usize {
+        let mut band = Biquad::new(kind, frequency_hz, q);
+        band.set_gain_db(gain_db);
+        self.bands.push(band);
+        self.bands.len() - 1
+    }
+
+    /// Remove the band at the given index, returning its parameters.
+    ///
+    /// **Panics** if there is no band at the given index.
+    pub fn remove_band(&mut self, index: usize) -> BiquadParams {
+        self.bands.remove(index).params()
+    }
+
+    /// The number of bands.
+    pub fn num_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// The parameters of the band at the given index, if there is one.
+    pub fn band(&self, index: usize) -> Option<BiquadParams> {
+        self.bands.get(index).map(Biquad::params)
+    }
+
+    /// Set the frequency in hertz, gain in decibels and Q of the band at the given index, leaving
+    /// its history untouched.
+    ///
+    /// **Panics** if there is no band at the given index.
+    pub fn set_band(&mut self, index: usize, frequency_hz: f64, gain_db: f64, q: f64) {
+        let band = &mut self.bands[index];
+        band.set_cutoff_hz(frequency_hz);
+        band.set_gain_db(gain_db);
+        band.set_q(q);
+    }
+
+    /// Set the response of the band at the given index.
+    ///
+    /// **Panics** if there is no band at the given index.
+    pub fn set_band_kind(&mut self, index: usize, kind: BiquadKind) {
+        self.bands[index].set_kind(kind);
+    }
+
+    /// The parameters of the **ParametricEq**.
+    pub fn params(&self) -> ParametricEqParams {
+        ParametricEqParams {
+            bands: self.bands.iter().map(Biquad::params).collect(),
+        }
+    }
+
+    /// Apply the given parameters, keeping the history of each band that remains.
+    pub fn set_params(&mut self, params: ParametricEqParams) {
+        self.bands.truncate(params.bands.len());
+        for (i, band) in params.bands.into_iter().enumerate() {
+            match self.bands.get_mut(i) {
+                Some(existing) => existing.set_params(band),
+                None => {
+                    let mut biquad = Biquad::new(band.kind, band.cutoff_hz, band.q);
+                    biquad.set_params(band);
+                    self.bands.push(biquad);
+                }
+            }
+        }
+    }
+
+    /// Clear the history of every band, as though they had only ever received silence.
+    pub fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.reset();
+        }
+    }
+}
+
+impl<F> Node<F> for ParametricEq
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        for band in self.bands.iter_mut() {
+            band.audio_requested(buffer, sample_hz);
+        }
+    }
+
+    fn reset(&mut self) {
+        ParametricEq::reset(self);
+    }
+}
+
+/// The output of an **Svf** filter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SvfMode {
+    /// Attenuates frequencies above the cutoff.
+    #[default]
+    LowPass,
+    /// Attenuates frequencies below the cutoff.
+    HighPass,
+    /// Attenuates frequencies either side of the cutoff, with a constant 0dB peak gain as per
+    /// `Biquad::band_pass`.
+    BandPass,
+    /// Attenuates frequencies close to the cutoff.
+    Notch,
+}
+
+/// The parameters of an **Svf**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SvfParams {
+    /// The output of the filter.
+    pub mode: SvfMode,
+    /// The cutoff (or center) frequency in hertz.
+    pub cutoff_hz: f64,
+    /// The resonance (Q) of the filter.
+    pub resonance: f64,
+}
+
+/// A second-order state-variable filter **Node**.
+///
+/// Implements Zavalishin's topology-preserving transform of the analog state-variable filter,
+/// which produces the low-pass, high-pass, band-pass and notch responses from the same pair of
+/// integrators. Unlike the **Biquad**, it remains stable at high resonance and while the cutoff is
+/// modulated, as its state holds the integrators rather than previous outputs.
+///
+/// The coefficients are calculated from the `sample_hz` passed to `prepare` or `audio_requested`
+/// and are re-calculated whenever it or a parameter changes, so the cutoff in hertz is kept when
+/// the sample rate changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Svf {
+    mode: SvfMode,
+    cutoff_hz: f64,
+    resonance: f64,
+    /// The sample rate for which `coefficients` were last calculated.
+    sample_hz: f64,
+    coefficients: SvfCoefficients,
+    /// The state of the two integrators for each channel.
+    state: Vec<SvfState>,
+}
+
+/// The coefficients of an **Svf** filter.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct SvfCoefficients {
+    /// The damping, `1 / Q`.
+    k: f64,
+    a1: f64,
+    a2: f64,
+    a3: f64,
+}
+
+/// The state of the two integrators of a single channel of an **Svf**.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct SvfState {
+    ic1: f64,
+    ic2: f64,
+}
+
+impl Svf {
+    /// Construct an **Svf** with the given output, cutoff frequency and resonance (Q).
+    pub fn new(mode: SvfMode, cutoff_hz: f64, resonance: f64) -> Self {
+        Svf {
+            mode,
+            cutoff_hz,
+            resonance,
+            sample_hz: 0.0,
+            coefficients: SvfCoefficients::default(),
+            state: Vec::new(),
+        }
+    }
+
+    /// The output of the filter.
+    pub fn mode(&self) -> SvfMode {
+        self.mode
+    }
+
+    /// Set the output of the filter.
+    ///
+    /// All outputs share the same state, so the mode may be changed without a discontinuity.
+    pub fn set_mode(&mut self, mode: SvfMode) {
+        self.mode = mode;
+    }
+
+    /// The cutoff (or center) frequency in hertz.
+    pub fn cutoff_hz(&self) -> f64 {
+        self.cutoff_hz
+    }
+
+    /// Set the cutoff (or center) frequency in hertz.
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f64) {
+        self.cutoff_hz = cutoff_hz;
+        self.sample_hz = 0.0;
+    }
+
+    /// The resonance (Q) of the filter.
+    pub fn resonance(&self) -> f64 {
+        self.resonance
+    }
+
+    /// Set the resonance (Q) of the filter.
+    pub fn set_resonance(&mut self, resonance: f64) {
+        self.resonance = resonance;
+        self.sample_hz = 0.0;
+    }
+
+    /// The parameters of the **Svf**.
+    pub fn params(&self) -> SvfParams {
+        SvfParams {
+            mode: self.mode,
+            cutoff_hz: self.cutoff_hz,
+            resonance: self.resonance,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    ///
+    /// The coefficients are re-calculated upon the next call to `audio_requested`.
+    pub fn set_params(&mut self, params: SvfParams) {
+        self.mode = params.mode;
+        self.cutoff_hz = params.cutoff_hz;
+        self.resonance = params.resonance;
+        self.sample_hz = 0.0;
+    }
+
+    /// Calculate the coefficients for the given sample rate.
+    fn update_coefficients(&mut self, sample_hz: f64) {
+        // Keep the cutoff just below nyquist and the resonance positive so the filter remains
+        // stable.
+        let cutoff_hz = self.cutoff_hz.clamp(1.0, sample_hz * 0.49);
+        let k = 1.0 / self.resonance.max(1e-3);
+        let g = (::std::f64::consts::PI * cutoff_hz / sample_hz).tan();
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        self.coefficients = SvfCoefficients { k, a1, a2, a3 };
+        self.sample_hz = sample_hz;
+    }
+
+    /// Clear the filter's history, as though it had only ever received silence.
+    pub fn reset(&mut self) {
+        self.state.clear();
+    }
+}
+
+impl<F> Node<F> for Svf
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if self.sample_hz != sample_hz {
+            self.update_coefficients(sample_hz);
+        }
+        if self.state.len() != F::n_channels() {
+            self.state.resize(F::n_channels(), SvfState::default());
+        }
+        let c = self.coefficients;
+        let mode = self.mode;
+        let state = &mut self.state;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut state = state.iter_mut();
+            frame.map(|s| {
+                let state = state.next().expect("one state per channel");
+                let v0 = util::to_f64(s);
+                let v3 = v0 - state.ic2;
+                let v1 = c.a1 * state.ic1 + c.a2 * v3;
+                let v2 = state.ic2 + c.a2 * state.ic1 + c.a3 * v3;
+                state.ic1 = util::flush_denormal(2.0 * v1 - state.ic1);
+                state.ic2 = util::flush_denormal(2.0 * v2 - state.ic2);
+                let y = match mode {
+                    SvfMode::LowPass => v2,
+                    SvfMode::HighPass => v0 - c.k * v1 - v2,
+                    SvfMode::BandPass => c.k * v1,
+                    SvfMode::Notch => v0 - c.k * v1,
+                };
+                util::from_f64(y)
+            })
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `cutoff_hz`: the cutoff (or center) frequency in hertz.
+    /// - `resonance`: the resonance (Q) of the filter.
+    fn param_names(&self) -> &[&str] {
+        &["cutoff_hz", "resonance"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "cutoff_hz" => Some(self.cutoff_hz as f32),
+            "resonance" => Some(self.resonance as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "cutoff_hz" => self.set_cutoff_hz(value as f64),
+            "resonance" => self.set_resonance(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 && self.sample_hz != settings.sample_hz {
+            self.update_coefficients(settings.sample_hz);
+        }
+        Svf::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Svf::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "svf"
+    }
+}
+
+/// The maximum number of all-pass stages within a **Phaser**.
+pub const PHASER_MAX_STAGES: usize = 16;
+
+/// The lowest frequency in hertz swept to by a **Phaser**'s all-pass stages.
+pub const PHASER_MIN_HZ: f64 = 100.0;
+
+/// The highest frequency in hertz swept to by a **Phaser**'s all-pass stages at full depth.
+pub const PHASER_MAX_HZ: f64 = 4_000.0;
+
+/// The largest magnitude of a **Phaser**'s feedback.
+pub const PHASER_MAX_FEEDBACK: f64 = 0.95;
+
+/// The parameters of a **Phaser**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaserParams {
+    /// The number of all-pass stages (1 ... `PHASER_MAX_STAGES`).
+    pub stages: usize,
+    /// The rate at which the stages are swept, in hertz.
+    pub rate_hz: f64,
+    /// The extent of the sweep (0.0 ... 1.0).
+    pub depth: f64,
+    /// The amount of the output fed back into the first stage
+    /// (`-PHASER_MAX_FEEDBACK` ... `PHASER_MAX_FEEDBACK`).
+    pub feedback: f64,
+    /// The amount of the phased (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+}
+
+/// A phaser **Node**, sweeping a set of notches through the spectrum.
+///
+/// The input passes through a cascade of first-order all-pass filters, each shifting the phase of
+/// frequencies around its break frequency by up to 180 degrees while leaving their level
+/// untouched. Mixing the result with the input cancels the frequencies shifted by an odd multiple
+/// of 180 degrees, producing one notch for every two `stages`. Unlike the evenly spaced notches of
+/// the **Flanger**, these are spread according to the break frequency.
+///
+/// The break frequency of every stage sweeps exponentially upwards from `PHASER_MIN_HZ`, reaching
+/// `PHASER_MAX_HZ` at full `depth`, following a sine LFO whose phase carries across buffers.
+/// Feedback from the last stage to the first sharpens the notches.
+///
+/// As with the **Flanger**, the `mix` is applied by the `Graph` via `Node::mix`, and a `mix` of
+/// `0.5` gives the deepest notches.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phaser {
+    stages: usize,
+    rate_hz: f64,
+    depth: f64,
+    feedback: f64,
+    mix: f32,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+    /// The state of each of the `PHASER_MAX_STAGES` stages for each channel, by channel.
+    state: Vec<f64>,
+    /// The output of the last stage for each channel.
+    outputs: Vec<f64>,
+}
+
+impl Phaser {
+    /// Construct a **Phaser** with the given number of stages, rate and depth, no feedback and an
+    /// even mix.
+    pub fn new(stages: usize, rate_hz: f64, depth: f64) -> Self {
+        Phaser {
+            stages: stages.clamp(1, PHASER_MAX_STAGES),
+            rate_hz: rate_hz.max(0.0),
+            depth: depth.clamp(0.0, 1.0),
+            feedback: 0.0,
+            mix: 0.5,
+            phase: 0.0,
+            state: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// The number of all-pass stages.
+    pub fn stages(&self) -> usize {
+        self.stages
+    }
+
+    /// Set the number of all-pass stages (1 ... `PHASER_MAX_STAGES`).
+    pub fn set_stages(&mut self, stages: usize) {
+        self.stages = stages.clamp(1, PHASER_MAX_STAGES);
+    }
+
+    /// The rate at which the stages are swept, in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate at which the stages are swept, in hertz.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        self.rate_hz = rate_hz.max(0.0);
+    }
+
+    /// The extent of the sweep.
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    /// Set the extent of the sweep (0.0 ... 1.0).
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// The amount of the output fed back into the first stage.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Set the amount of the output fed back into the first stage.
+    ///
+    /// Clamped to `-PHASER_MAX_FEEDBACK ... PHASER_MAX_FEEDBACK`, so that the phaser is always
+    /// stable.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        self.feedback = feedback.clamp(-PHASER_MAX_FEEDBACK, PHASER_MAX_FEEDBACK);
+    }
+
+    /// The amount of the phased (wet) signal in the output.
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the phased (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The parameters of the **Phaser**.
+    pub fn params(&self) -> PhaserParams {
+        PhaserParams {
+            stages: self.stages,
+            rate_hz: self.rate_hz,
+            depth: self.depth,
+            feedback: self.feedback,
+            mix: self.mix,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    pub fn set_params(&mut self, params: PhaserParams) {
+        self.set_stages(params.stages);
+        self.set_rate_hz(params.rate_hz);
+        self.set_depth(params.depth);
+        self.set_feedback(params.feedback);
+        self.set_mix(params.mix);
+    }
+
+    /// Clear the state of every stage and return the LFO to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.state.clear();
+        self.outputs.clear();
+    }
+}
+
+impl<F> Node<F> for Phaser
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let channels = F::n_channels();
+        self.state.resize(channels * PHASER_MAX_STAGES, 0.0);
+        self.outputs.resize(channels, 0.0);
+
+        let stages = self.stages;
+        let feedback = self.feedback;
+        let range = (PHASER_MAX_HZ / PHASER_MIN_HZ).powf(self.depth);
+        // Keep the break frequency safely below Nyquist at low sample rates.
+        let max_hz = sample_hz * 0.45;
+        let phase_step = self.rate_hz / sample_hz;
+        let Phaser {
+            ref mut phase,
+            ref mut state,
+            ref mut outputs,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            let lfo = 0.5 + 0.5 * LfoShape::Sine.value(*phase);
+            let hz = (PHASER_MIN_HZ * range.powf(lfo)).min(max_hz);
+            let t = (::std::f64::consts::PI * hz / sample_hz).tan();
+            let a = (t - 1.0) / (t + 1.0);
+            *phase = (*phase + phase_step).fract();
+            let mut channel = 0;
+            frame.map(|s| {
+                let offset = channel * PHASER_MAX_STAGES;
+                let mut x = util::to_f64(s) + feedback * outputs[channel];
+                for z in state[offset..offset + stages].iter_mut() {
+                    let y = a * x + *z;
+                    *z = util::flush_denormal(x - a * y);
+                    x = y;
+                }
+                outputs[channel] = util::flush_denormal(x);
+                channel += 1;
+                util::from_f64(x)
+            })
+        });
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `stages`: the number of all-pass stages, rounded to the nearest integer (1 ...
+    ///   `PHASER_MAX_STAGES`).
+    /// - `rate_hz`: the rate of the sweep in hertz.
+    /// - `depth`: the extent of the sweep (0.0 ... 1.0).
+    /// - `feedback`: the amount of the output fed back (-`PHASER_MAX_FEEDBACK` ...
+    ///   `PHASER_MAX_FEEDBACK`).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["stages", "rate_hz", "depth", "feedback", "mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "stages" => Some(self.stages as f32),
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth" => Some(self.depth as f32),
+            "feedback" => Some(self.feedback as f32),
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "stages" => self.set_stages(value.round().max(0.0) as usize),
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth" => self.set_depth(value as f64),
+            "feedback" => self.set_feedback(value as f64),
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Phaser::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "phaser"
+    }
+}
+
+/// The number of taps of the Hilbert transformer used by a **FreqShift**. Odd, so that the
+/// transformer has a whole number of frames of latency.
+pub const FREQ_SHIFT_HILBERT_TAPS: usize = 127;
+
+/// The parameters of a **FreqShift**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreqShiftParams {
+    /// The amount in hertz added to every frequency of the input. Negative shifts lower them.
+    pub shift_hz: f64,
+}
+
+/// A single-sideband frequency shifter **Node**, adding a fixed amount in hertz to every
+/// frequency of its input.
+///
+/// Unlike a **PitchShifter**, which multiplies every frequency by the same ratio, shifting by a
+/// fixed amount moves harmonics out of their ratios, giving an inharmonic, metallic timbre at
+/// large shifts and a slow phasing or beating at small shifts.
+///
+/// A windowed FIR Hilbert transformer of `FREQ_SHIFT_HILBERT_TAPS` taps produces the quadrature
+/// of each channel, which is used with a matching delay of the input to form the analytic signal.
+/// Modulating this by a complex carrier at `shift_hz` and keeping the real part shifts the
+/// spectrum without the mirrored image left by a **RingMod**. Frequencies close to `0` hertz or
+/// the Nyquist frequency lie outside the transformer's pass band, so their image is only partly
+/// suppressed.
+///
+/// The transformer delays the output by half its length, which is reported by
+/// `latency_samples`. The input history and carrier phase carry across buffers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FreqShift {
+    shift_hz: f64,
+    /// The taps of the Hilbert transformer, oldest input last.
+    kernel: Vec<f64>,
+    history: DelayLine,
+    /// The phase of the carrier (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl FreqShift {
+    /// Construct a **FreqShift** adding the given amount in hertz to every frequency.
+    pub fn new(shift_hz: f64) -> Self {
+        FreqShift {
+            shift_hz,
+            kernel: hilbert_kernel(FREQ_SHIFT_HILBERT_TAPS),
+            history: DelayLine::default(),
+            phase: 0.0,
+        }
+    }
+
+    /// The amount in hertz added to every frequency of the input.
+    pub fn shift_hz(&self) -> f64 {
+        self.shift_hz
+    }
+
+    /// Set the amount in hertz added to every frequency of the input. Negative shifts lower them.
+    pub fn set_shift_hz(&mut self, shift_hz: f64) {
+        self.shift_hz = shift_hz;
+    }
+
+    /// The parameters of the **FreqShift**.
+    pub fn params(&self) -> FreqShiftParams {
+        FreqShiftParams {
+            shift_hz: self.shift_hz,
+        }
+    }
+
+    /// Apply the given parameters, leaving the input history and carrier phase untouched.
+    pub fn set_params(&mut self, params: FreqShiftParams) {
+        self.set_shift_hz(params.shift_hz);
+    }
+
+    /// Silence the input history and return the carrier to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.phase = 0.0;
+    }
+}
+
+/// The taps of a Blackman-windowed FIR Hilbert transformer of the given odd length, where the tap
+/// at index `j` applies to the input `j` frames ago.
+///
+/// The ideal response is `2 / (π · n)` for odd `n` frames from the centre, and `0` for even `n`.
+fn hilbert_kernel(taps: usize) -> Vec<f64> {
+    let centre = (taps / 2) as isize;
+    let last = (taps - 1) as f64;
+    (0..taps)
+        .map(|j| {
+            let n = j as isize - centre;
+            if n % 2 == 0 {
+                return 0.0;
+            }
+            let x = 2.0 * ::std::f64::consts::PI * j as f64 / last;
+            let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+            2.0 / (::std::f64::consts::PI * n as f64) * window
+        })
+        .collect()
+}
+
+impl<F> Node<F> for FreqShift
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        self.history
+            .resize(FREQ_SHIFT_HILBERT_TAPS, F::n_channels());
+        let centre = FREQ_SHIFT_HILBERT_TAPS / 2;
+        let step = self.shift_hz / sample_hz;
+        let FreqShift {
+            ref kernel,
+            ref mut history,
+            ref mut phase,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            for (channel, s) in frame.channels().enumerate() {
+                history.write(channel, util::to_f64(s));
+            }
+            history.advance();
+            let (sin, cos) = (2.0 * ::std::f64::consts::PI * *phase).sin_cos();
+            *phase = (*phase + step).rem_euclid(1.0);
+            let mut channel = 0;
+            frame.map(|_| {
+                let real = history.past(channel, centre + 1);
+                // Only the taps at odd distances from the centre are non-zero.
+                let imag = (0..FREQ_SHIFT_HILBERT_TAPS)
+                    .skip(1 - centre % 2)
+                    .step_by(2)
+                    .map(|j| kernel[j] * history.past(channel, j + 1))
+                    .sum::<f64>();
+                channel += 1;
+                util::from_f64(real * cos - imag * sin)
+            })
+        });
+    }
+
+    fn latency_samples(&self) -> usize {
+        FREQ_SHIFT_HILBERT_TAPS / 2
+    }
+
+    /// Exposes:
+    ///
+    /// - `shift_hz`: the amount in hertz added to every frequency.
+    fn param_names(&self) -> &[&str] {
+        &["shift_hz"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "shift_hz" => Some(self.shift_hz as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "shift_hz" => self.set_shift_hz(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, _settings: Settings) {
+        self.history
+            .resize(FREQ_SHIFT_HILBERT_TAPS, F::n_channels());
+        FreqShift::reset(self);
+    }
+
+    fn reset(&mut self) {
+        FreqShift::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "freq_shift"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillator::Oscillator;
+    use crate::preset::{PresetError, Registry};
+    use crate::testing;
+    use crate::{Graph, Settings};
+
+    /// The mean of the first channel of each frame.
+    fn mean(buffer: &[[f32; 2]]) -> f64 {
+        buffer.iter().map(|frame| frame[0] as f64).sum::<f64>() / buffer.len() as f64
+    }
+
+    #[test]
+    fn dc_blocker_removes_a_constant_offset() {
+        let mut blocker = DcBlocker::new();
+        // A square wave at a quarter of full scale riding upon an offset of a half.
+        let input: Vec<[f32; 2]> = (0..8_192)
+            .map(|i| [if (i / 50) % 2 == 0 { 0.75 } else { 0.25 }; 2])
+            .collect();
+        let mut output = input.clone();
+        blocker.audio_requested(&mut output, 44_100.0);
+        assert!((mean(&input) - 0.5).abs() < 1e-3);
+        // Once settled, the output is centred upon zero.
+        assert!(
+            mean(&output[4_096..]).abs() < 0.01,
+            "{}",
+            mean(&output[4_096..])
+        );
+        assert!(output[4_096..].iter().any(|frame| frame[0] > 0.2));
+    }
+
+    #[test]
+    fn dc_blocker_state_persists_across_buffers() {
+        let input: Vec<[f32; 2]> = (0..256).map(|i| [0.5 + (i % 7) as f32 * 0.05; 2]).collect();
+        let mut whole = input.clone();
+        DcBlocker::new().audio_requested(&mut whole, 44_100.0);
+
+        let mut split = input;
+        let mut blocker = DcBlocker::new();
+        let (first, second) = split.split_at_mut(100);
+        blocker.audio_requested(first, 44_100.0);
+        blocker.audio_requested(second, 44_100.0);
+        assert_eq!(split, whole);
+    }
+
+    #[test]
+    fn dc_blocker_resizes_its_state_to_the_channel_count() {
+        let mut blocker = DcBlocker::new();
+        let mut mono = [[0.5f32]; 4];
+        blocker.audio_requested(&mut mono, 44_100.0);
+        let mut surround = [[0.5f32; 6]; 4];
+        blocker.audio_requested(&mut surround, 44_100.0);
+        assert_eq!(blocker.state.len(), 6);
+        // The first channel continues from its state, while the new channels start afresh.
+        let mut fresh = [[0.5f32]; 4];
+        DcBlocker::new().audio_requested(&mut fresh, 44_100.0);
+        for (frame, fresh) in surround.iter().zip(&fresh) {
+            assert!(frame[1..].iter().all(|&s| s == fresh[0]));
+            assert!(frame[0] < fresh[0]);
+        }
+    }
+
+    const SAMPLE_HZ: f64 = 44_100.0;
+
+    /// The level of a sine of the given frequency once filtered, relative to the unfiltered sine.
+    fn sine_gain<N>(filter: &mut N, hz: f64) -> f64
+    where
+        N: Node<[f32; 2]>,
+    {
+        let input = testing::sine::<[f32; 2]>(8_192, hz, 0.5, SAMPLE_HZ);
+        let mut output = input.clone();
+        filter.audio_requested(&mut output, SAMPLE_HZ);
+        // Leave the filter's transient out of the measure.
+        testing::rms(&output[4_096..]) / testing::rms(&input[4_096..])
+    }
+
+    #[test]
+    fn low_pass_at_a_low_cutoff_attenuates_a_high_sine() {
+        assert!(sine_gain(&mut Biquad::low_pass(100.0, 0.707), 10_000.0) < 0.001);
+        assert!((sine_gain(&mut Biquad::low_pass(5_000.0, 0.707), 100.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn biquad_responses_pass_and_reject_the_expected_frequencies() {
+        assert!(sine_gain(&mut Biquad::high_pass(5_000.0, 0.707), 100.0) < 0.001);
+        assert!((sine_gain(&mut Biquad::high_pass(100.0, 0.707), 10_000.0) - 1.0).abs() < 0.01);
+        assert!((sine_gain(&mut Biquad::band_pass(1_000.0, 2.0), 1_000.0) - 1.0).abs() < 0.01);
+        assert!(sine_gain(&mut Biquad::band_pass(1_000.0, 2.0), 15_000.0) < 0.05);
+        assert!(sine_gain(&mut Biquad::notch(1_000.0, 2.0), 1_000.0) < 0.01);
+        assert!((sine_gain(&mut Biquad::notch(1_000.0, 2.0), 10_000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn biquad_state_survives_across_buffers() {
+        let input = testing::sine::<[f32; 2]>(512, 3_000.0, 0.5, SAMPLE_HZ);
+        let mut whole = input.clone();
+        Biquad::low_pass(1_000.0, 2.0).audio_requested(&mut whole, SAMPLE_HZ);
+
+        let mut split = input;
+        let mut filter = Biquad::low_pass(1_000.0, 2.0);
+        for chunk in split.chunks_mut(100) {
+            filter.audio_requested(chunk, SAMPLE_HZ);
+        }
+        assert_eq!(split, whole);
+    }
+
+    #[test]
+    fn biquad_coefficients_follow_the_sample_rate() {
+        let input = testing::sine::<[f32; 2]>(256, 3_000.0, 0.5, 96_000.0);
+        let mut filter = Biquad::low_pass(1_000.0, 0.707);
+        let mut output = input.clone();
+        filter.audio_requested(&mut output, SAMPLE_HZ);
+
+        filter.reset();
+        let mut output = input.clone();
+        filter.audio_requested(&mut output, 96_000.0);
+        let mut expected = input;
+        Biquad::low_pass(1_000.0, 0.707).audio_requested(&mut expected, 96_000.0);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn a_reset_biquad_responds_as_though_just_constructed() {
+        let mut fresh = Biquad::low_pass(1_000.0, 0.707);
+        let expected: Vec<[f32; 2]> = testing::impulse_response(&mut fresh, 64, SAMPLE_HZ);
+        let mut filter = Biquad::low_pass(1_000.0, 0.707);
+        let _: Vec<[f32; 2]> = testing::step_response(&mut filter, 64, SAMPLE_HZ);
+        Node::<[f32; 2]>::reset(&mut filter);
+        let output: Vec<[f32; 2]> = testing::impulse_response(&mut filter, 64, SAMPLE_HZ);
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn svf_low_pass_matches_the_biquad_at_a_matched_cutoff() {
+        let mut svf = Svf::new(SvfMode::LowPass, 1_000.0, 0.707);
+        let mut biquad = Biquad::low_pass(1_000.0, 0.707);
+        let a: Vec<[f32; 2]> = testing::impulse_response(&mut svf, 512, SAMPLE_HZ);
+        let b: Vec<[f32; 2]> = testing::impulse_response(&mut biquad, 512, SAMPLE_HZ);
+        for (i, (a, b)) in a.iter().zip(&b).enumerate() {
+            assert!((a[0] - b[0]).abs() < 1e-5, "frame {}: {} {}", i, a[0], b[0]);
+        }
+        let mut svf = Svf::new(SvfMode::LowPass, 1_000.0, 0.707);
+        let mut biquad = Biquad::low_pass(1_000.0, 0.707);
+        for &hz in &[100.0, 1_000.0, 5_000.0] {
+            assert!((sine_gain(&mut svf, hz) - sine_gain(&mut biquad, hz)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn svf_modes_share_one_core() {
+        let gain = |mode, hz| sine_gain(&mut Svf::new(mode, 1_000.0, 0.707), hz);
+        assert!(gain(SvfMode::HighPass, 10_000.0) > 0.95);
+        assert!(gain(SvfMode::HighPass, 100.0) < 0.02);
+        assert!(gain(SvfMode::BandPass, 1_000.0) > 0.95);
+        assert!(gain(SvfMode::BandPass, 100.0) < 0.2);
+        assert!(gain(SvfMode::BandPass, 10_000.0) < 0.2);
+        assert!(gain(SvfMode::Notch, 1_000.0) < 0.05);
+        assert!(gain(SvfMode::Notch, 100.0) > 0.95);
+    }
+
+    #[test]
+    fn svf_is_stable_at_high_resonance_and_keeps_state_across_buffers() {
+        let mut svf = Svf::new(SvfMode::LowPass, 15_000.0, 50.0);
+        let mut rng = crate::noise::RngSource::new(1);
+        let input: Vec<[f32; 2]> = (0..8_192)
+            .map(|_| [rng.next_bipolar() as f32 * 0.5; 2])
+            .collect();
+        let mut whole = input.clone();
+        svf.audio_requested(&mut whole, SAMPLE_HZ);
+        assert!(whole
+            .iter()
+            .all(|frame| frame[0].is_finite() && frame[0].abs() < 100.0));
+
+        let mut split = input;
+        let mut svf = Svf::new(SvfMode::LowPass, 15_000.0, 50.0);
+        for chunk in split.chunks_mut(100) {
+            svf.audio_requested(chunk, SAMPLE_HZ);
+        }
+        assert_eq!(split, whole);
+    }
+
+    /// The number of notches in the response of a static **Phaser** with an even mix, found as
+    /// the local minima of the magnitude across a logarithmic sweep from 20 Hz to 20 kHz.
+    fn phaser_notches(stages: usize) -> usize {
+        let sample_hz = 48_000.0;
+        let mut phaser = Phaser::new(stages, 0.0, 1.0);
+        let wet: Vec<[f32; 1]> = testing::impulse_response(&mut phaser, 8_192, sample_hz);
+        let magnitudes: Vec<f64> = (0..1_000)
+            .map(|i| {
+                let hz = 20.0 * 1_000f64.powf(i as f64 / 999.0);
+                let step = 2.0 * ::std::f64::consts::PI * hz / sample_hz;
+                let (re, im) = wet.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, s)| {
+                    // Mix the impulse itself in with the wet signal.
+                    let s = 0.5 * f64::from(s[0]) + if n == 0 { 0.5 } else { 0.0 };
+                    let w = step * n as f64;
+                    (re + s * w.cos(), im - s * w.sin())
+                });
+                (re * re + im * im).sqrt()
+            })
+            .collect();
+        magnitudes
+            .windows(3)
+            .filter(|w| w[1] < w[0] && w[1] < w[2] && w[1] < 0.2)
+            .count()
+    }
+
+    #[test]
+    fn phaser_has_a_notch_for_every_two_stages() {
+        assert_eq!(phaser_notches(2), 1);
+        assert_eq!(phaser_notches(4), 2);
+        assert_eq!(phaser_notches(8), 4);
+        assert_eq!(phaser_notches(12), 6);
+    }
+
+    /// A source playing a sine at `hz`, with its phase carried across buffers.
+    struct Tone {
+        hz: f64,
+        frame: usize,
+    }
+
+    impl Node<[f32; 1]> for Tone {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], sample_hz: f64) {
+            let step = 2.0 * ::std::f64::consts::PI * self.hz / sample_hz;
+            for frame in buffer.iter_mut() {
+                *frame = [(0.5 * (step * self.frame as f64).sin()) as f32];
+                self.frame += 1;
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    /// A node which leaves the sum of its inputs as is.
+    struct Thru;
+
+    impl Node<[f32; 1]> for Thru {
+        fn audio_requested(&mut self, _buffer: &mut [[f32; 1]], _sample_hz: f64) {}
+    }
+
+    /// The levels of the low band, the high band and their sum relative to the input, for a
+    /// **Crossover** at 1 kHz fed a sine at `hz`.
+    fn crossover_levels(hz: f64) -> [f64; 3] {
+        type Mono = Box<dyn Node<[f32; 1]>>;
+        let mut graph: crate::Graph<[f32; 1], Mono> = crate::Graph::new();
+        let source = graph.add_node(Box::new(Tone { hz, frame: 0 }) as Mono);
+        let (_, crossover) = graph.add_output(source, Box::new(Crossover::new(1_000.0)));
+        let (_, low) = graph.add_output(crossover, Box::new(Thru));
+        let high = graph.add_node(Box::new(Thru));
+        graph
+            .add_connection_from_output(crossover, 1, high)
+            .unwrap();
+        let (_, master) = graph.add_output(low, Box::new(Thru));
+        graph.add_connection(high, master).unwrap();
+
+        let mut levels = [0.0; 3];
+        for (level, &idx) in levels.iter_mut().zip(&[low, high, master]) {
+            graph.set_master(Some(idx));
+            graph.reset_all();
+            let mut buffer = vec![[0.0]; 48_000];
+            for chunk in buffer.chunks_mut(512) {
+                graph.audio_requested(chunk, 48_000.0);
+            }
+            *level = testing::rms(&buffer[24_000..]) / (0.5 / 2f64.sqrt());
+        }
+        levels
+    }
+
+    #[test]
+    fn summed_crossover_bands_reconstruct_a_flat_response() {
+        for &hz in &[50.0, 200.0, 1_000.0, 4_000.0, 15_000.0] {
+            let [low, high, sum] = crossover_levels(hz);
+            assert!((sum - 1.0).abs() < 0.01, "{} Hz: {}", hz, sum);
+            if hz == 1_000.0 {
+                // Each band is 6 dB down at the cutoff.
+                assert!((low - 0.5).abs() < 0.01 && (high - 0.5).abs() < 0.01);
+            }
+        }
+        let [low, high, _] = crossover_levels(50.0);
+        assert!(low > 0.99 && high < 0.01);
+        let [low, high, _] = crossover_levels(15_000.0);
+        assert!(low < 0.01 && high > 0.99);
+    }
+
+    #[test]
+    fn a_filter_cutoff_set_by_name_is_read_back_and_applied() {
+        let mut filter: Box<dyn Node<[f32; 2]>> = Box::new(Biquad::low_pass(10_000.0, 0.707));
+        assert_eq!(filter.param_names(), &["cutoff_hz", "q", "gain_db"]);
+        assert!(sine_gain(&mut filter, 2_000.0) > 0.95);
+
+        assert!(filter.set_param("cutoff_hz", 200.0));
+        assert_eq!(filter.get_param("cutoff_hz"), Some(200.0));
+        assert!(sine_gain(&mut filter, 2_000.0) < 0.02);
+
+        assert!(!filter.set_param("resonance", 2.0));
+        assert_eq!(filter.get_param("resonance"), None);
+        for name in filter.param_names() {
+            assert!(filter.get_param(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn one_pole_step_reaches_63_percent_after_one_time_constant() {
+        // 10 ms at 1 kHz is 10 frames.
+        let mut filter = OnePole::new(10.0);
+        let output: Vec<[f64; 1]> = testing::step_response(&mut filter, 10, 1_000.0);
+        let expected = 1.0 - (-1.0f64).exp();
+        assert!((output[9][0] - expected).abs() < 1e-12, "{}", output[9][0]);
+        assert!(output.windows(2).all(|w| w[1][0] > w[0][0]));
+        // The state carries across buffers.
+        let output: Vec<[f64; 1]> = testing::step_response(&mut filter, 10, 1_000.0);
+        assert!((output[9][0] - (1.0 - (-2.0f64).exp())).abs() < 1e-12);
+
+        let mut filter = OnePole::new(0.0);
+        let output: Vec<[f64; 1]> = testing::step_response(&mut filter, 4, 1_000.0);
+        assert_eq!(output, vec![[1.0]; 4]);
+    }
+
+    #[test]
+    fn one_pole_keeps_state_per_channel() {
+        let mut filter = OnePole::new(1.0);
+        let mut buffer = vec![[1.0f32, -0.5]; 64];
+        filter.audio_requested(&mut buffer, 1_000.0);
+        let expected = 1.0 - (-1.0f32).exp();
+        assert!((buffer[0][0] - expected).abs() < 1e-6);
+        assert!((buffer[0][1] + 0.5 * expected).abs() < 1e-6);
+        assert!((buffer[63][0] - 1.0).abs() < 1e-6 && (buffer[63][1] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn linkwitz_riley_bands_sum_flat_and_in_phase() {
+        let sample_hz = 48_000.0;
+        let mut crossover = Crossover::new(2_000.0);
+        let low: Vec<[f32; 1]> = testing::impulse_response(&mut crossover, 8_192, sample_hz);
+        let mut high = vec![[0.0]; low.len()];
+        crossover.read_output(1, &mut high);
+        let sum: Vec<[f32; 1]> = low.iter().zip(&high).map(|(l, h)| [l[0] + h[0]]).collect();
+
+        let mut hz = 20.0;
+        while hz < 20_000.0 {
+            let (re, im) = testing::response_at(&sum, hz, sample_hz);
+            let magnitude = (re * re + im * im).sqrt();
+            assert!((magnitude - 1.0).abs() < 0.01, "{} Hz: {}", hz, magnitude);
+            hz *= 1.25;
+        }
+
+        // At the crossover both bands are 6 dB down and share the same phase.
+        let (low_re, low_im) = testing::response_at(&low, 2_000.0, sample_hz);
+        let (high_re, high_im) = testing::response_at(&high, 2_000.0, sample_hz);
+        assert!(((low_re * low_re + low_im * low_im).sqrt() - 0.5).abs() < 0.01);
+        assert!(((high_re * high_re + high_im * high_im).sqrt() - 0.5).abs() < 0.01);
+        assert!((low_re - high_re).abs() < 0.01 && (low_im - high_im).abs() < 0.01);
+    }
+
+    /// The gain in decibels applied by the EQ to a steady tone, rendered in chunks.
+    fn eq_gain_db(eq: &mut ParametricEq, hz: f64, sample_hz: f64) -> f64 {
+        eq.reset();
+        let len = sample_hz as usize;
+        let input: Vec<[f32; 1]> = testing::sine(len, hz, 0.25, sample_hz);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(300) {
+            eq.audio_requested(chunk, sample_hz);
+        }
+        20.0 * (testing::rms(&output[len / 2..]) / testing::rms(&input[len / 2..])).log10()
+    }
+
+    #[test]
+    fn a_peaking_band_boosts_a_tone_at_its_frequency() {
+        let mut eq = ParametricEq::new();
+        let band = eq.add_band(BiquadKind::Peaking, 1_000.0, 6.0, 1.0);
+        let boost = eq_gain_db(&mut eq, 1_000.0, 48_000.0);
+        assert!((boost - 6.0).abs() < 0.1, "{}", boost);
+        assert!(eq_gain_db(&mut eq, 50.0, 48_000.0).abs() < 0.1);
+        // The coefficients follow the sample rate.
+        let boost = eq_gain_db(&mut eq, 1_000.0, 96_000.0);
+        assert!((boost - 6.0).abs() < 0.1, "{}", boost);
+
+        eq.set_band(band, 2_000.0, -6.0, 1.0);
+        let cut = eq_gain_db(&mut eq, 2_000.0, 48_000.0);
+        assert!((cut + 6.0).abs() < 0.1, "{}", cut);
+    }
+
+    #[test]
+    fn shelf_bands_boost_and_cut_beyond_their_frequency() {
+        let mut eq = ParametricEq::new();
+        eq.add_band(BiquadKind::LowShelf, 200.0, 6.0, 0.707);
+        eq.add_band(BiquadKind::HighShelf, 5_000.0, -6.0, 0.707);
+        let low = eq_gain_db(&mut eq, 20.0, 48_000.0);
+        let mid = eq_gain_db(&mut eq, 1_000.0, 48_000.0);
+        let high = eq_gain_db(&mut eq, 20_000.0, 48_000.0);
+        assert!((low - 6.0).abs() < 0.2, "{}", low);
+        assert!(mid.abs() < 0.5, "{}", mid);
+        assert!((high + 6.0).abs() < 0.2, "{}", high);
+    }
+
+    /// Shift a second of a 1 kHz tone by the given amount, returning the steady state output.
+    fn shifted_tone(shift_hz: f64) -> Vec<[f32; 1]> {
+        let mut shifter = FreqShift::new(shift_hz);
+        let mut output: Vec<[f32; 1]> = testing::sine(48_000, 1_000.0, 0.5, 48_000.0);
+        for chunk in output.chunks_mut(500) {
+            shifter.audio_requested(chunk, 48_000.0);
+        }
+        output.split_off(8_000)
+    }
+
+    #[test]
+    fn a_frequency_shift_moves_a_tone_without_its_image() {
+        let input: Vec<[f32; 1]> = testing::sine(40_000, 1_000.0, 0.5, 48_000.0);
+        let tone = testing::power_at(&input, 1_000.0, 48_000.0);
+        let up = shifted_tone(100.0);
+        assert!((testing::power_at(&up, 1_100.0, 48_000.0) / tone - 1.0).abs() < 0.05);
+        assert!(testing::power_at(&up, 900.0, 48_000.0) < tone * 1e-4);
+        assert!(testing::power_at(&up, 1_000.0, 48_000.0) < tone * 1e-4);
+
+        let down = shifted_tone(-100.0);
+        assert!((testing::power_at(&down, 900.0, 48_000.0) / tone - 1.0).abs() < 0.05);
+        assert!(testing::power_at(&down, 1_100.0, 48_000.0) < tone * 1e-4);
+
+        let shifter = FreqShift::new(100.0);
+        assert_eq!(
+            Node::<[f32; 1]>::latency_samples(&shifter),
+            FREQ_SHIFT_HILBERT_TAPS / 2
+        );
+    }
+
+    /// The gain in decibels of the master node of the graph to the full-scale tone of its source over
+    /// the second half of a second rendered at `sample_hz`.
+    fn graph_gain_db(graph: &mut Graph<[f32; 1], Box<dyn Node<[f32; 1]>>>, sample_hz: f64) -> f64 {
+        let mut output = vec![[0.0]; sample_hz as usize];
+        for chunk in output.chunks_mut(256) {
+            graph.audio_requested(chunk, sample_hz);
+        }
+        20.0 * (testing::rms(&output[output.len() / 2..]) * 2f64.sqrt()).log10()
+    }
+
+    #[test]
+    fn a_biquad_keeps_its_cutoff_in_hertz_when_the_sample_rate_changes() {
+        let mut graph: Graph<[f32; 1], Box<dyn Node<[f32; 1]>>> = Graph::new();
+        let source = graph.add_node(Box::new(Oscillator::new(1_000.0)) as Box<dyn Node<[f32; 1]>>);
+        let low_pass = Biquad::low_pass(1_000.0, ::std::f64::consts::FRAC_1_SQRT_2);
+        let (_, filter) = graph.add_output(source, Box::new(low_pass));
+        graph.set_master(Some(filter));
+        graph.prepare_all(Settings::new(44_100.0, 256));
+
+        // A Butterworth low-pass is 3 dB down at its cutoff at either rate, where coefficients left
+        // over from 44.1 kHz would place the cutoff near 2.2 kHz instead.
+        let before = graph_gain_db(&mut graph, 44_100.0);
+        assert!((before + 3.01).abs() < 0.05, "{}", before);
+        let after = graph_gain_db(&mut graph, 96_000.0);
+        assert!((after + 3.01).abs() < 0.05, "{}", after);
+        assert_eq!(graph.settings(), Some(Settings::new(96_000.0, 256)));
+    }
+
+    #[test]
+    fn a_parametric_eq_cannot_be_restored_from_a_preset() {
+        let mut graph: Graph<[f32; 1], Box<dyn Node<[f32; 1]>>> = Graph::new();
+        let mut eq = ParametricEq::new();
+        eq.add_band(BiquadKind::Peaking, 1_000.0, 6.0, 1.0);
+        let eq = graph.add_node(Box::new(eq) as Box<dyn Node<[f32; 1]>>);
+        graph.set_master(Some(eq));
+        let preset = graph.save_preset();
+        assert_eq!(preset.nodes[0].type_tag, "");
+
+        // Loading reports the missing tag rather than losing the bands.
+        let mut registry = Registry::new();
+        registry.register("parametric_eq", || {
+            Box::new(ParametricEq::new()) as Box<dyn Node<[f32; 1]>>
+        });
+        assert_eq!(
+            graph.load_preset(&preset, &registry),
+            Err(PresetError::UnknownType(String::new()))
+        );
+    }
+}
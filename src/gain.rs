@@ -0,0 +1,1080 @@
+//! Types and functions for working with gain.
+
+use crate::buffer;
+use crate::node::{Node, Settings};
+use crate::oscillator::LfoShape;
+use crate::param::Param;
+use crate::util;
+use crate::Volume;
+use sample::{self, Frame};
+
+/// Gains at or below this level (in decibels) are treated as silence.
+pub const SILENCE_DB: f32 = -120.0;
+
+/// Convert a gain in decibels to an amplitude multiplier.
+///
+/// `0.0` dB is an amplitude of `1.0`. Anything at or below `SILENCE_DB` is silence (`0.0`).
+pub fn db_to_amp(db: f32) -> Volume {
+    if db <= SILENCE_DB {
+        0.0
+    } else {
+        10.0f32.powf(db / 20.0)
+    }
+}
+
+/// Convert an amplitude multiplier to a gain in decibels.
+///
+/// The inverse of `db_to_amp`. Silence (an amplitude of `0.0` or less) is
+/// `f32::NEG_INFINITY`.
+pub fn amp_to_db(amp: Volume) -> f32 {
+    if amp <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        20.0 * amp.log10()
+    }
+}
+
+/// `SILENCE_DB` as an amplitude multiplier.
+pub(crate) const SILENCE_AMP: f64 = 1e-6;
+
+/// Whether or not every sample within the buffer is below `SILENCE_DB`, i.e. inaudible.
+///
+/// Used by the `Graph` to skip silent inputs of nodes whose `Node::skip_silence` returns `true`.
+pub fn is_silent<F>(buffer: &[F]) -> bool
+where
+    F: Frame,
+{
+    buffer.iter().all(|frame| {
+        frame
+            .channels()
+            .all(|s| util::to_f64(s).abs() < SILENCE_AMP)
+    })
+}
+
+/// A gain that moves linearly towards its target over a given number of frames, rather than
+/// jumping to it instantly.
+///
+/// Applying a gain that jumps between buffers produces an audible click (aka "zipper noise").
+/// **SmoothedGain** avoids this by interpolating from the previous gain to the new target.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SmoothedGain {
+    current: Volume,
+    target: Volume,
+    step: Volume,
+    remaining: usize,
+}
+
+impl SmoothedGain {
+    /// Construct a **SmoothedGain** that starts at the given gain.
+    pub fn new(gain: Volume) -> Self {
+        SmoothedGain {
+            current: gain,
+            target: gain,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// The gain that will be produced by the next call to `next_gain`.
+    pub fn current(&self) -> Volume {
+        self.current
+    }
+
+    /// The gain towards which the **SmoothedGain** is moving.
+    pub fn target(&self) -> Volume {
+        self.target
+    }
+
+    /// Whether or not the gain is still moving towards its target.
+    pub fn is_smoothing(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Begin moving towards the given `target` over `smoothing_samples` frames.
+    ///
+    /// If `smoothing_samples` is `0`, the gain jumps to the target immediately. Setting the same
+    /// target again does not restart the ramp.
+    pub fn set_target(&mut self, target: Volume, smoothing_samples: usize) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        if smoothing_samples == 0 {
+            self.current = target;
+            self.step = 0.0;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.current) / smoothing_samples as Volume;
+            self.remaining = smoothing_samples;
+        }
+    }
+
+    /// Jump to the given gain immediately, cancelling any ramp in progress.
+    pub fn reset(&mut self, gain: Volume) {
+        *self = SmoothedGain::new(gain);
+    }
+
+    /// Produce the gain for the current frame and step towards the target.
+    #[inline]
+    pub fn next_gain(&mut self) -> Volume {
+        let gain = self.current;
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+        gain
+    }
+}
+
+impl Default for SmoothedGain {
+    fn default() -> Self {
+        SmoothedGain::new(1.0)
+    }
+}
+
+/// The default time in milliseconds taken by a **Gain** to glide to a new gain.
+pub const GAIN_SMOOTHING_MS: f64 = 10.0;
+
+/// The parameters of a **Gain**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GainParams {
+    /// The amplitude multiplier applied to the input (0.0 ...).
+    pub gain: Volume,
+    /// The time taken to glide to a new gain in milliseconds (0.0 ...).
+    pub smoothing_ms: f64,
+}
+
+/// A **Node** that scales its input by a gain.
+///
+/// Changes to the gain glide linearly to the new value over `smoothing_ms` rather than jumping,
+/// so that they do not click. The glide carries across buffers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gain {
+    /// The gain towards which `param` is moving.
+    gain: Volume,
+    smoothing_ms: f64,
+    param: Param,
+}
+
+impl Gain {
+    /// Construct a **Gain** with the given amplitude multiplier, gliding over
+    /// `GAIN_SMOOTHING_MS`.
+    pub fn new(gain: Volume) -> Self {
+        let gain = gain.max(0.0);
+        Gain {
+            gain,
+            smoothing_ms: GAIN_SMOOTHING_MS,
+            param: Param::new(gain),
+        }
+    }
+
+    /// Construct a **Gain** with the given gain in decibels, gliding over `GAIN_SMOOTHING_MS`.
+    pub fn from_db(db: f32) -> Self {
+        Gain::new(db_to_amp(db))
+    }
+
+    /// The amplitude multiplier towards which the **Gain** is moving.
+    pub fn gain(&self) -> Volume {
+        self.gain
+    }
+
+    /// Set the amplitude multiplier, clamped to `0.0` or above.
+    pub fn set_gain(&mut self, gain: Volume) {
+        self.gain = gain.max(0.0);
+    }
+
+    /// The gain in decibels towards which the **Gain** is moving.
+    pub fn gain_db(&self) -> f32 {
+        amp_to_db(self.gain)
+    }
+
+    /// Set the gain in decibels. Anything at or below `SILENCE_DB` is silence.
+    pub fn set_gain_db(&mut self, db: f32) {
+        self.set_gain(db_to_amp(db));
+    }
+
+    /// The time taken to glide to a new gain in milliseconds.
+    pub fn smoothing_ms(&self) -> f64 {
+        self.smoothing_ms
+    }
+
+    /// Set the time taken to glide to a new gain in milliseconds, clamped to `0.0` or above.
+    pub fn set_smoothing_ms(&mut self, smoothing_ms: f64) {
+        self.smoothing_ms = smoothing_ms.max(0.0);
+    }
+
+    /// The parameters of the **Gain**.
+    pub fn params(&self) -> GainParams {
+        GainParams {
+            gain: self.gain,
+            smoothing_ms: self.smoothing_ms,
+        }
+    }
+
+    /// Apply the given parameters, gliding from the current gain.
+    pub fn set_params(&mut self, params: GainParams) {
+        self.set_gain(params.gain);
+        self.set_smoothing_ms(params.smoothing_ms);
+    }
+
+    /// Jump straight to the target gain, cancelling any glide in progress.
+    pub fn reset(&mut self) {
+        self.param.set_value(self.gain);
+    }
+}
+
+impl Default for Gain {
+    fn default() -> Self {
+        Gain::new(1.0)
+    }
+}
+
+impl<F> Node<F> for Gain
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.param.set_smoothing_ms(self.smoothing_ms, sample_hz);
+        self.param.set_target(self.gain);
+        if !self.param.is_smoothing() {
+            let gain = self.gain as f64;
+            if gain != 1.0 {
+                sample::slice::map_in_place(buffer, |frame| {
+                    frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+                });
+            }
+            return;
+        }
+        let param = &mut self.param;
+        sample::slice::map_in_place(buffer, |frame| {
+            let gain = param.next_value() as f64;
+            frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+        });
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    /// Exposes:
+    ///
+    /// - `gain`: the amplitude multiplier (0.0 ...).
+    fn param_names(&self) -> &[&str] {
+        &["gain"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "gain" => Some(self.gain),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "gain" => self.set_gain(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Gain::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "gain"
+    }
+}
+
+/// A **Node** that flips the polarity of some or all channels of its input.
+///
+/// By default, every channel is inverted. Individual channels can be left untouched with
+/// `set_invert_channel`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseInvert {
+    /// Whether or not each channel is inverted. Channels beyond the end are inverted.
+    inverted: Vec<bool>,
+}
+
+impl PhaseInvert {
+    /// Construct a **PhaseInvert** that inverts every channel.
+    pub fn new() -> Self {
+        PhaseInvert {
+            inverted: Vec::new(),
+        }
+    }
+
+    /// Whether or not the channel at the given index is inverted.
+    pub fn invert_channel(&self, ch: usize) -> bool {
+        self.inverted.get(ch).cloned().unwrap_or(true)
+    }
+
+    /// Set whether or not the channel at the given index is inverted.
+    pub fn set_invert_channel(&mut self, ch: usize, invert: bool) {
+        if self.inverted.len() <= ch {
+            self.inverted.resize(ch + 1, true);
+        }
+        self.inverted[ch] = invert;
+    }
+
+    /// The parameters of the **PhaseInvert**.
+    pub fn params(&self) -> PhaseInvertParams {
+        PhaseInvertParams {
+            inverted: self.inverted.clone(),
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: PhaseInvertParams) {
+        self.inverted = params.inverted;
+    }
+}
+
+/// The parameters of a **PhaseInvert**.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseInvertParams {
+    /// Whether or not each channel is inverted. Channels beyond the end are inverted.
+    pub inverted: Vec<bool>,
+}
+
+impl<F> Node<F> for PhaseInvert
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut ch = 0;
+            frame.map(|s| {
+                let invert = self.invert_channel(ch);
+                ch += 1;
+                if invert {
+                    util::from_f64(-util::to_f64(s))
+                } else {
+                    s
+                }
+            })
+        });
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "phase_invert"
+    }
+}
+
+/// The time constant in milliseconds of the smoothing applied to a **Tremolo**'s modulation.
+pub const TREMOLO_SMOOTHING_MS: f64 = 2.0;
+
+/// The parameters of a **Tremolo**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TremoloParams {
+    /// The rate of the modulation in hertz.
+    pub rate_hz: f64,
+    /// The amount by which the gain dips at the bottom of each cycle (0.0 ... 1.0).
+    pub depth: f64,
+    /// The waveform of the modulation.
+    pub shape: LfoShape,
+}
+
+/// A **Node** that modulates the amplitude of its input with an LFO.
+///
+/// The gain sweeps between `1.0` at the top of the LFO's cycle and `1.0 - depth` at the bottom, so
+/// a `depth` of `0.0` leaves the input untouched and `1.0` silences it at the bottom of every
+/// cycle. The LFO's phase carries across buffers.
+///
+/// The gain is passed through a one-pole low-pass filter with a time constant of
+/// `TREMOLO_SMOOTHING_MS`, rounding off the edges of the `Square` and `Saw` shapes that would
+/// otherwise click.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tremolo {
+    rate_hz: f64,
+    depth: f64,
+    shape: LfoShape,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+    /// The smoothed gain, or `None` if no audio has been requested since the last reset.
+    gain: Option<f64>,
+}
+
+impl Tremolo {
+    /// Construct a **Tremolo** with the given rate, depth and shape.
+    pub fn new(rate_hz: f64, depth: f64, shape: LfoShape) -> Self {
+        Tremolo {
+            rate_hz: rate_hz.max(0.0),
+            depth: depth.clamp(0.0, 1.0),
+            shape,
+            phase: 0.0,
+            gain: None,
+        }
+    }
+
+    /// The rate of the modulation in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate of the modulation in hertz.
+    pub fn set_rate_hz(&mut self, rate_hz: f64) {
+        self.rate_hz = rate_hz.max(0.0);
+    }
+
+    /// The amount by which the gain dips at the bottom of each cycle.
+    pub fn depth(&self) -> f64 {
+        self.depth
+    }
+
+    /// Set the amount by which the gain dips at the bottom of each cycle (0.0 ... 1.0).
+    pub fn set_depth(&mut self, depth: f64) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// The waveform of the modulation.
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    /// Set the waveform of the modulation.
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// The parameters of the **Tremolo**.
+    pub fn params(&self) -> TremoloParams {
+        TremoloParams {
+            rate_hz: self.rate_hz,
+            depth: self.depth,
+            shape: self.shape,
+        }
+    }
+
+    /// Apply the given parameters, leaving the LFO's phase untouched.
+    pub fn set_params(&mut self, params: TremoloParams) {
+        self.set_rate_hz(params.rate_hz);
+        self.set_depth(params.depth);
+        self.set_shape(params.shape);
+    }
+
+    /// Return the LFO to the start of its cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.gain = None;
+    }
+
+    /// The unsmoothed gain at the current phase.
+    fn target_gain(&self) -> f64 {
+        1.0 - self.depth * (0.5 - 0.5 * self.shape.value(self.phase))
+    }
+}
+
+impl<F> Node<F> for Tremolo
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let phase_step = self.rate_hz / sample_hz;
+        if self.depth == 0.0 {
+            // Keep the LFO running so that raising the depth picks up from the right phase.
+            self.phase = (self.phase + phase_step * buffer.len() as f64).fract();
+            self.gain = None;
+            return;
+        }
+        let coefficient = (-1.0 / (TREMOLO_SMOOTHING_MS / 1_000.0 * sample_hz)).exp();
+        let mut gain = self.gain.unwrap_or_else(|| self.target_gain());
+        for frame in buffer.iter_mut() {
+            let target = self.target_gain();
+            gain = util::flush_denormal(target + (gain - target) * coefficient);
+            *frame = frame.map(|s| util::from_f64(util::to_f64(s) * gain));
+            self.phase = (self.phase + phase_step).fract();
+        }
+        self.gain = Some(gain);
+    }
+
+    /// Exposes:
+    ///
+    /// - `rate_hz`: the rate of the modulation in hertz.
+    /// - `depth`: the amount by which the gain dips (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["rate_hz", "depth"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth" => Some(self.depth as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth" => self.set_depth(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Tremolo::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "tremolo"
+    }
+}
+
+/// The first channel of each frame of a control input, recorded by a node's `combine_input` for
+/// the next buffer rather than being summed with its other inputs.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ControlInput {
+    levels: Vec<f64>,
+}
+
+impl ControlInput {
+    /// Combine an input of the node, recording the input at `control_idx` and summing the others.
+    fn combine_input<F>(
+        &mut self,
+        control_idx: usize,
+        input_idx: usize,
+        input: &[F],
+        output: &mut [F],
+    ) where
+        F: Frame,
+    {
+        if input_idx == control_idx {
+            self.levels.clear();
+            self.levels.extend(
+                input
+                    .iter()
+                    .map(|frame| frame.channels().next().map(util::to_f64).unwrap_or(0.0)),
+            );
+        } else if input_idx == 0 {
+            sample::slice::write(output, input);
+        } else {
+            buffer::mix_into(output, input, 1.0);
+        }
+    }
+}
+
+/// The index of the input from which a **Vca** reads its gain, i.e. the second input connected.
+pub const VCA_CONTROL_INPUT: usize = 1;
+
+/// A voltage-controlled amplifier **Node**, scaling its audio input by a control signal rendered
+/// by another node, such as an **Adsr** or **Lfo**.
+///
+/// The first input connected is the audio to be scaled, and the input at `VCA_CONTROL_INPUT` is
+/// the control signal. Rather than being summed with the audio, the first channel of each frame
+/// of the control input is recorded and multiplies every channel of the same frame of the audio.
+/// Any further inputs are summed with the audio as usual.
+///
+/// The control signal is consumed by each buffer, so the output is silent for any buffer in which
+/// no control input was combined, as with a disconnected control voltage.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Vca {
+    /// The gain of each frame of the control input combined for the next buffer.
+    control: ControlInput,
+}
+
+impl Vca {
+    /// Construct a **Vca** awaiting its control input.
+    pub fn new() -> Self {
+        Vca {
+            control: ControlInput::default(),
+        }
+    }
+
+    /// Discard the control signal recorded for the next buffer.
+    pub fn reset(&mut self) {
+        self.control.levels.clear();
+    }
+}
+
+impl<F> Node<F> for Vca
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let control = &self.control.levels;
+        for (i, frame) in buffer.iter_mut().enumerate() {
+            // Frames beyond the control combined are silent.
+            let gain = control.get(i).cloned().unwrap_or(0.0);
+            *frame = frame.map(|s| util::from_f64(util::to_f64(s) * gain));
+        }
+        self.control.levels.clear();
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.control
+            .combine_input(VCA_CONTROL_INPUT, input_idx, input, output);
+    }
+
+    /// Returns `false`, so that the control signal is read as rendered.
+    fn sums_inputs(&self) -> bool {
+        false
+    }
+
+    /// Returns `false`, as `combine_input` reads the inputs by their index.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.control.levels.reserve(settings.max_frames);
+        Vca::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Vca::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "vca"
+    }
+}
+
+/// The index of the input that a **RingMod** uses as its carrier in place of its internal
+/// oscillator, i.e. the second input connected.
+pub const RING_MOD_CARRIER_INPUT: usize = 1;
+
+/// The parameters of a **RingMod**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RingModParams {
+    /// The frequency of the internal carrier in hertz.
+    pub carrier_hz: f64,
+    /// The amount of the modulated (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+}
+
+/// A ring modulator **Node**, multiplying its input by a carrier sample by sample.
+///
+/// Multiplying a partial at `f` by a sine carrier at `c` replaces it with a pair of partials at
+/// `f + c` and `|f - c|`, which are rarely harmonically related and give the effect its metallic,
+/// bell-like timbre.
+///
+/// The carrier is an internal sine wave at `carrier_hz` whose phase carries across buffers. If an
+/// input is connected at `RING_MOD_CARRIER_INPUT`, the first channel of each of its frames is
+/// used as the carrier instead for every buffer in which it is combined, while the internal
+/// oscillator keeps running. As with the **Delay**, the `mix` is applied by the `Graph` via
+/// `Node::mix`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RingMod {
+    carrier_hz: f64,
+    mix: f32,
+    /// The phase of the internal carrier (0.0 ... 1.0).
+    phase: f64,
+    /// The carrier input combined for the next buffer, if any.
+    carrier: ControlInput,
+}
+
+impl RingMod {
+    /// Construct a **RingMod** with an internal carrier at the given frequency, fully wet.
+    pub fn new(carrier_hz: f64) -> Self {
+        RingMod {
+            carrier_hz: carrier_hz.max(0.0),
+            mix: 1.0,
+            phase: 0.0,
+            carrier: ControlInput::default(),
+        }
+    }
+
+    /// The frequency of the internal carrier in hertz.
+    pub fn carrier_hz(&self) -> f64 {
+        self.carrier_hz
+    }
+
+    /// Set the frequency of the internal carrier in hertz.
+    pub fn set_carrier_hz(&mut self, carrier_hz: f64) {
+        self.carrier_hz = carrier_hz.max(0.0);
+    }
+
+    /// The amount of the modulated (wet) signal in the output.
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the modulated (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The parameters of the **RingMod**.
+    pub fn params(&self) -> RingModParams {
+        RingModParams {
+            carrier_hz: self.carrier_hz,
+            mix: self.mix,
+        }
+    }
+
+    /// Apply the given parameters, leaving the carrier's phase untouched.
+    pub fn set_params(&mut self, params: RingModParams) {
+        self.set_carrier_hz(params.carrier_hz);
+        self.set_mix(params.mix);
+    }
+
+    /// Return the internal carrier to the start of its cycle and discard any carrier input
+    /// recorded for the next buffer.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.carrier.levels.clear();
+    }
+}
+
+impl<F> Node<F> for RingMod
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let step = self.carrier_hz / sample_hz;
+        let external = &self.carrier.levels;
+        let phase = &mut self.phase;
+        for (i, frame) in buffer.iter_mut().enumerate() {
+            let internal = util::sin(2.0 * ::std::f64::consts::PI * *phase);
+            *phase = (*phase + step).fract();
+            let carrier = if external.is_empty() {
+                internal
+            } else {
+                // Frames beyond the carrier combined are silent.
+                external.get(i).cloned().unwrap_or(0.0)
+            };
+            *frame = frame.map(|s| util::from_f64(util::to_f64(s) * carrier));
+        }
+        self.carrier.levels.clear();
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.carrier
+            .combine_input(RING_MOD_CARRIER_INPUT, input_idx, input, output);
+    }
+
+    /// Returns `false`, so that an external carrier is read as rendered.
+    fn sums_inputs(&self) -> bool {
+        false
+    }
+
+    /// Returns `false`, as `combine_input` reads the inputs by their index.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `carrier_hz`: the frequency of the internal carrier in hertz.
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["carrier_hz", "mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "carrier_hz" => Some(self.carrier_hz as f32),
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "carrier_hz" => self.set_carrier_hz(value as f64),
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.carrier.levels.reserve(settings.max_frames);
+        RingMod::reset(self);
+    }
+
+    fn reset(&mut self) {
+        RingMod::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "ring_mod"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::Adsr;
+    use crate::graph::Graph;
+    use crate::midi::MidiMessage;
+    use crate::testing;
+
+    #[test]
+    fn zero_db_is_unity_amplitude() {
+        assert_eq!(db_to_amp(0.0), 1.0);
+        assert_eq!(amp_to_db(1.0), 0.0);
+    }
+
+    #[test]
+    fn db_round_trips_through_amplitude() {
+        for &db in &[-96.0, -60.0, -24.5, -6.0, -0.1, 3.0, 12.0] {
+            let round_trip = amp_to_db(db_to_amp(db));
+            assert!((round_trip - db).abs() < 1e-4, "{} != {}", round_trip, db);
+        }
+        for &amp in &[0.001, 0.25, 0.5, 1.0, 2.0] {
+            let round_trip = db_to_amp(amp_to_db(amp));
+            assert!((round_trip - amp).abs() < 1e-6, "{} != {}", round_trip, amp);
+        }
+    }
+
+    #[test]
+    fn gains_at_or_below_the_silence_level_are_silent() {
+        assert_eq!(db_to_amp(SILENCE_DB), 0.0);
+        assert_eq!(db_to_amp(-200.0), 0.0);
+        assert!(db_to_amp(SILENCE_DB + 1.0) > 0.0);
+        assert_eq!(amp_to_db(0.0), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn the_default_vol_derives_from_vol_db() {
+        struct Quieter;
+        impl Node<[f32; 1]> for Quieter {
+            fn audio_requested(&mut self, _buffer: &mut [[f32; 1]], _sample_hz: f64) {}
+            fn vol_db(&self) -> f32 {
+                -6.0
+            }
+        }
+        assert_eq!(Node::<[f32; 1]>::vol(&Quieter), db_to_amp(-6.0));
+        assert!((Node::<[f32; 1]>::vol(&Quieter) - 0.501).abs() < 1e-3);
+    }
+
+    #[test]
+    fn inverting_twice_returns_the_original_buffer() {
+        let original = [[0.5f32, -0.25], [1.0, 0.0], [-0.75, 0.125]];
+        let mut buffer = original;
+        let mut invert = PhaseInvert::new();
+        invert.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[-0.5, 0.25], [-1.0, 0.0], [0.75, -0.125]]);
+        invert.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, original);
+
+        let original = [[12_345i16, -1], [i16::MAX, -i16::MAX]];
+        let mut buffer = original;
+        invert.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[-12_345, 1], [-i16::MAX, i16::MAX]]);
+        invert.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, original);
+    }
+
+    #[test]
+    fn channels_are_inverted_independently() {
+        let mut invert = PhaseInvert::new();
+        invert.set_invert_channel(1, false);
+        assert!(invert.invert_channel(0) && !invert.invert_channel(1) && invert.invert_channel(2));
+        let mut buffer = [[0.5f32, 0.5, 0.5]];
+        invert.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[-0.5, 0.5, -0.5]]);
+    }
+
+    /// The gain applied to a unit step by a **Tremolo** over `len` frames, rendered in buffers of
+    /// 100 frames.
+    fn tremolo_gain(tremolo: &mut Tremolo, len: usize, sample_hz: f64) -> Vec<f32> {
+        let mut gains = Vec::with_capacity(len);
+        while gains.len() < len {
+            let output: Vec<[f32; 1]> = testing::step_response(tremolo, 100, sample_hz);
+            gains.extend(output.iter().map(|frame| frame[0]));
+        }
+        gains
+    }
+
+    #[test]
+    fn tremolo_amplitude_oscillates_at_the_lfo_rate() {
+        // 5 cycles of a 5 Hz LFO at 1 kHz.
+        let mut tremolo = Tremolo::new(5.0, 0.5, LfoShape::Sine);
+        let gains = tremolo_gain(&mut tremolo, 1_000, 1_000.0);
+        let troughs: Vec<usize> = (1..gains.len() - 1)
+            .filter(|&i| gains[i] < gains[i - 1] && gains[i] <= gains[i + 1])
+            .collect();
+        assert_eq!(troughs.len(), 5);
+        // The phase carries across buffers, so the troughs are evenly spaced.
+        assert!(troughs
+            .windows(2)
+            .all(|w| (w[1] as i64 - w[0] as i64 - 200).abs() <= 1));
+        let max = gains.iter().cloned().fold(0.0, f32::max);
+        let min = gains.iter().cloned().fold(1.0, f32::min);
+        assert!(
+            (max - 1.0).abs() < 0.01 && (min - 0.5).abs() < 0.01,
+            "{} ... {}",
+            min,
+            max
+        );
+    }
+
+    #[test]
+    fn tremolo_smooths_the_edges_of_a_square() {
+        let mut tremolo = Tremolo::new(5.0, 1.0, LfoShape::Square);
+        let gains = tremolo_gain(&mut tremolo, 48_000, 48_000.0);
+        let max_step = gains
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max);
+        assert!(max_step < 0.02, "{}", max_step);
+        // Each half of the cycle still settles at its gain.
+        assert!(gains[4_700] > 0.999 && gains[9_500] < 0.001);
+    }
+
+    #[test]
+    fn tremolo_at_zero_depth_is_transparent() {
+        let input = testing::sine::<[f32; 2]>(512, 440.0, 0.5, 44_100.0);
+        let mut output = input.clone();
+        let mut tremolo = Tremolo::new(5.0, 0.0, LfoShape::Square);
+        tremolo.audio_requested(&mut output, 44_100.0);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn a_gain_of_two_doubles_the_samples() {
+        let input = testing::sine::<[f32; 2]>(256, 440.0, 0.25, 44_100.0);
+        let mut output = input.clone();
+        Gain::new(2.0).audio_requested(&mut output, 44_100.0);
+        for (out, input) in output.iter().zip(&input) {
+            assert_eq!(*out, [input[0] * 2.0, input[1] * 2.0]);
+        }
+        let mut gain = Gain::default();
+        gain.set_gain_db(amp_to_db(2.0));
+        assert!((gain.gain() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_gain_ramp_is_click_free() {
+        // 10 ms at 10 kHz glides over 100 frames.
+        let mut gain = Gain::new(1.0);
+        let mut output: Vec<[f32; 1]> = testing::step_response(&mut gain, 50, 10_000.0);
+        gain.set_gain(0.0);
+        for _ in 0..3 {
+            output.extend(testing::step_response::<[f32; 1], _>(
+                &mut gain, 50, 10_000.0,
+            ));
+        }
+        assert!(output[..50].iter().all(|&frame| frame == [1.0]));
+        // The glide carries across buffers, stepping evenly down to silence.
+        for (i, w) in output[49..150].windows(2).enumerate() {
+            assert!((w[0][0] - w[1][0] - 0.01).abs() < 1e-5, "frame {}", 50 + i);
+        }
+        assert!(output[149..].iter().all(|&frame| frame == [0.0]));
+    }
+
+    /// A source of a constant level.
+    struct Dc(f32);
+
+    impl Node<[f32; 1]> for Dc {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            sample::slice::map_in_place(buffer, |_| [self.0]);
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    type Mono = Box<dyn Node<[f32; 1]>>;
+
+    /// Render `buffers` buffers of 25 frames from the graph at 1 kHz.
+    fn render_mono(graph: &mut Graph<[f32; 1], Mono>, buffers: usize) -> Vec<f32> {
+        let mut output = Vec::new();
+        for _ in 0..buffers {
+            let mut buffer = [[0.0]; 25];
+            graph.audio_requested(&mut buffer, 1_000.0);
+            output.extend(buffer.iter().map(|frame| frame[0]));
+        }
+        output
+    }
+
+    #[test]
+    fn an_adsr_at_the_control_input_envelopes_the_audio() {
+        // 10 ms attack, 20 ms decay to half, 30 ms release.
+        let mut adsr = Adsr::new(0.01, 0.02, 0.5, 0.03);
+        adsr.note_on();
+        let mut reference = adsr.clone();
+
+        let mut graph: Graph<[f32; 1], Mono> = Graph::new();
+        let audio = graph.add_node(Box::new(Dc(0.5)) as Mono);
+        let (_, vca) = graph.add_output(audio, Box::new(Vca::new()));
+        let gate = graph.add_node(Box::new(Dc(1.0)));
+        let (_, envelope) = graph.add_output(gate, Box::new(adsr));
+        graph.add_connection(envelope, vca).unwrap();
+        graph.set_master(Some(vca));
+
+        let mut output = render_mono(&mut graph, 4);
+        graph[envelope].handle_midi(MidiMessage::NoteOff { note: 60 });
+        output.extend(render_mono(&mut graph, 2));
+
+        let mut expected = vec![[1.0f32]; 150];
+        reference.audio_requested(&mut expected[..100], 1_000.0);
+        reference.note_off();
+        reference.audio_requested(&mut expected[100..], 1_000.0);
+        for (i, (&s, env)) in output.iter().zip(&expected).enumerate() {
+            assert_eq!(s, 0.5 * env[0], "frame {}", i);
+        }
+        // The audio rises to full, holds at the sustain level, then releases to silence.
+        assert_eq!(output.iter().cloned().fold(0.0, f32::max), 0.5);
+        assert!(output[50..100].iter().all(|&s| s == 0.25));
+        assert_eq!(output[149], 0.0);
+    }
+
+    #[test]
+    fn a_vca_without_a_control_input_is_silent() {
+        let mut graph: Graph<[f32; 1], Mono> = Graph::new();
+        let audio = graph.add_node(Box::new(Dc(0.5)) as Mono);
+        let (_, vca) = graph.add_output(audio, Box::new(Vca::new()));
+        graph.set_master(Some(vca));
+        assert!(render_mono(&mut graph, 1).iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn ring_modulation_moves_a_tone_to_the_sum_and_difference_frequencies() {
+        let sample_hz = 48_000.0;
+        let input: Vec<[f32; 1]> = testing::sine(48_000, 1_000.0, 0.5, sample_hz);
+        let mut ring = RingMod::new(300.0);
+        let mut output = input.clone();
+        // Buffers of a size unrelated to the carrier's period, so that its phase spans buffers.
+        for chunk in output.chunks_mut(333) {
+            ring.audio_requested(chunk, sample_hz);
+        }
+        // Each sideband holds half the amplitude of the tone, i.e. a quarter of its power.
+        let tone = testing::power_at(&input, 1_000.0, sample_hz);
+        for &hz in &[700.0, 1_300.0] {
+            let sideband = testing::power_at(&output, hz, sample_hz);
+            assert!(
+                (sideband / tone - 0.25).abs() < 0.01,
+                "{} Hz: {}",
+                hz,
+                sideband / tone
+            );
+        }
+        assert!(testing::power_at(&output, 1_000.0, sample_hz) < tone * 1e-6);
+        assert!(testing::power_at(&output, 300.0, sample_hz) < tone * 1e-6);
+
+        // The carrier's phase carries across buffers exactly as within one.
+        ring.reset();
+        let mut whole = input.clone();
+        ring.audio_requested(&mut whole, sample_hz);
+        assert_eq!(whole, output);
+    }
+}
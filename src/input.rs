@@ -0,0 +1,268 @@
+//! Feeding live audio, e.g. from a capture device, into a **Graph**.
+//!
+//! [`input_source`](./fn.input_source.html) returns a pair of handles. The **InputProducer** is
+//! moved to the capture thread, which pushes received audio to it via `InputNode::audio_received`.
+//! The **InputSource** is added to the **Graph**, and emits the received audio when audio is
+//! requested.
+//!
+//! The handles share a single-producer, single-consumer ring buffer of atomics, so neither thread
+//! ever blocks or allocates while passing audio to the other. The ring decouples the two sides, so
+//! the capture and playback buffer sizes need not match.
+
+use crate::node::Node;
+use crate::util;
+use sample::{self, Frame, Sample};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Types that receive audio from some external source, e.g. an audio capture callback.
+pub trait InputNode<F> {
+    /// Receive the given buffer of audio, captured at the given sample rate.
+    fn audio_received(&mut self, buffer: &[F], sample_hz: f64);
+}
+
+/// Construct a connected **InputSource** and **InputProducer** pair, sharing a ring buffer with
+/// space for `capacity` frames of `channels` channels each.
+///
+/// The capacity should comfortably exceed the largest capture buffer plus the largest playback
+/// buffer, so that the capture thread can run ahead of the audio thread without dropping frames.
+pub fn input_source(channels: usize, capacity: usize) -> (InputSource, InputProducer) {
+    let ring = Arc::new(Ring {
+        samples: (0..channels * capacity)
+            .map(|_| AtomicU32::new(0))
+            .collect(),
+        channels,
+        capacity,
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    let source = InputSource {
+        ring: ring.clone(),
+        underruns: 0,
+    };
+    let producer = InputProducer { ring, overruns: 0 };
+    (source, producer)
+}
+
+/// A ring buffer of `f32` samples stored as bits within atomics.
+///
+/// `read` and `write` count the frames ever read and written, wrapping on overflow. Only the
+/// **InputSource** stores to `read` and only the **InputProducer** stores to `write`.
+#[derive(Debug)]
+struct Ring {
+    samples: Vec<AtomicU32>,
+    channels: usize,
+    /// The number of frames stored.
+    capacity: usize,
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl Ring {
+    /// The number of frames written but not yet read.
+    fn available(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+
+    /// The index of the given channel of the frame at the given count.
+    #[inline]
+    fn sample_idx(&self, frame: usize, channel: usize) -> usize {
+        (frame % self.capacity) * self.channels + channel
+    }
+}
+
+/// The **Node** end of an [`input_source`](./fn.input_source.html) pair.
+///
+/// Each call to `audio_requested` replaces the buffer with the oldest received frames. If too few
+/// frames have been received, the remainder of the buffer is silenced and counted as an underrun.
+/// Channels missing from the received audio are silent and extra channels are ignored.
+#[derive(Debug)]
+pub struct InputSource {
+    ring: Arc<Ring>,
+    underruns: usize,
+}
+
+/// The capture end of an [`input_source`](./fn.input_source.html) pair.
+///
+/// Received frames that do not fit within the ring buffer are dropped and counted as overruns.
+#[derive(Debug)]
+pub struct InputProducer {
+    ring: Arc<Ring>,
+    overruns: usize,
+}
+
+impl InputSource {
+    /// The number of frames received but not yet emitted.
+    pub fn available(&self) -> usize {
+        self.ring.available()
+    }
+
+    /// The number of frames that have been silenced due to too few frames being received.
+    pub fn underruns(&self) -> usize {
+        self.underruns
+    }
+
+    /// Discard all frames received but not yet emitted.
+    pub fn reset(&mut self) {
+        let write = self.ring.write.load(Ordering::Acquire);
+        self.ring.read.store(write, Ordering::Release);
+    }
+}
+
+impl InputProducer {
+    /// The number of frames of free space within the ring buffer.
+    pub fn free(&self) -> usize {
+        self.ring.capacity - self.ring.available()
+    }
+
+    /// The number of received frames that have been dropped due to the ring buffer being full.
+    pub fn overruns(&self) -> usize {
+        self.overruns
+    }
+
+    /// Push as many of the given frames as fit within the ring buffer, returning the number
+    /// pushed.
+    pub fn push<F>(&mut self, frames: &[F]) -> usize
+    where
+        F: Frame,
+    {
+        let ring = &*self.ring;
+        let count = frames.len().min(self.free());
+        let write = ring.write.load(Ordering::Relaxed);
+        for (i, frame) in frames[..count].iter().enumerate() {
+            let frame_idx = write.wrapping_add(i);
+            for (channel, s) in frame.channels().take(ring.channels).enumerate() {
+                let value = util::to_f64(s) as f32;
+                ring.samples[ring.sample_idx(frame_idx, channel)]
+                    .store(value.to_bits(), Ordering::Relaxed);
+            }
+        }
+        ring.write
+            .store(write.wrapping_add(count), Ordering::Release);
+        self.overruns += frames.len() - count;
+        count
+    }
+}
+
+impl<F> InputNode<F> for InputProducer
+where
+    F: Frame,
+{
+    fn audio_received(&mut self, buffer: &[F], _sample_hz: f64) {
+        self.push(buffer);
+    }
+}
+
+impl<F> Node<F> for InputSource
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let ring = &*self.ring;
+        let count = buffer.len().min(ring.available());
+        let read = ring.read.load(Ordering::Relaxed);
+        for (i, frame) in buffer[..count].iter_mut().enumerate() {
+            let frame_idx = read.wrapping_add(i);
+            *frame = F::from_fn(|channel| {
+                if channel < ring.channels {
+                    let bits =
+                        ring.samples[ring.sample_idx(frame_idx, channel)].load(Ordering::Relaxed);
+                    util::from_f64(f32::from_bits(bits) as f64)
+                } else {
+                    F::Sample::equilibrium()
+                }
+            });
+        }
+        ring.read.store(read.wrapping_add(count), Ordering::Release);
+        sample::slice::equilibrium(&mut buffer[count..]);
+        self.underruns += buffer.len() - count;
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        InputSource::reset(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn received_audio_appears_in_the_next_requested_buffer() {
+        let (mut source, mut producer) = input_source(2, 64);
+        let input = [[0.5f32, -0.5], [0.25, -0.25], [1.0, -1.0]];
+        producer.audio_received(&input, 44_100.0);
+        assert_eq!(source.available(), 3);
+        let mut buffer = [[9.0f32; 2]; 4];
+        source.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(&buffer[..3], &input);
+        // Too few frames were received, so the rest is silenced.
+        assert_eq!(buffer[3], [0.0; 2]);
+        assert_eq!(source.underruns(), 1);
+        assert_eq!(source.available(), 0);
+    }
+
+    #[test]
+    fn capture_and_playback_buffer_sizes_may_differ() {
+        let (mut source, mut producer) = input_source(1, 512);
+        let input: Vec<[f32; 1]> = (0..300).map(|i| [i as f32 / 300.0]).collect();
+        let mut output = Vec::new();
+        for chunk in input.chunks(100) {
+            assert_eq!(producer.push(chunk), 100);
+            let mut buffer = [[0.0f32]; 64];
+            while source.available() >= buffer.len() {
+                source.audio_requested(&mut buffer, 44_100.0);
+                output.extend_from_slice(&buffer);
+            }
+        }
+        assert_eq!(output.len(), 256);
+        assert_eq!(&output[..], &input[..256]);
+        assert_eq!(source.underruns(), 0);
+    }
+
+    #[test]
+    fn frames_beyond_the_capacity_are_dropped_and_missing_channels_silent() {
+        let (mut source, mut producer) = input_source(1, 4);
+        assert_eq!(producer.push(&[[0.5f32, 0.75]; 6]), 4);
+        assert_eq!(producer.overruns(), 2);
+        assert_eq!(producer.free(), 0);
+        let mut buffer = [[1.0f32; 2]; 4];
+        source.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[0.5, 0.0]; 4]);
+        producer.push(&[[0.5f32]; 2]);
+        Node::<[f32; 1]>::reset(&mut source);
+        assert_eq!(source.available(), 0);
+    }
+
+    #[test]
+    fn audio_passes_between_threads_in_order() {
+        let (mut source, mut producer) = input_source(1, 256);
+        let capture = thread::spawn(move || {
+            let mut next = 0;
+            while next < 10_000 {
+                let chunk: Vec<[f32; 1]> = (next..(next + 37).min(10_000))
+                    .map(|i| [i as f32])
+                    .collect();
+                next += producer.push(&chunk);
+                thread::yield_now();
+            }
+        });
+        let mut received = Vec::new();
+        let mut buffer = [[0.0f32]; 50];
+        while received.len() < 10_000 {
+            let count = source.available().min(buffer.len());
+            source.audio_requested(&mut buffer[..count], 44_100.0);
+            received.extend(buffer[..count].iter().map(|frame| frame[0]));
+            thread::yield_now();
+        }
+        capture.join().unwrap();
+        assert!(received.iter().enumerate().all(|(i, &s)| s == i as f32));
+    }
+}
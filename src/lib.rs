@@ -11,19 +11,55 @@
 
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 
 pub use daggy::{self, Walker};
 pub use graph::{
-    Connection, Dag, EdgeIndex, Graph, Inputs, NodeIndex, NodesMut, Outputs, PetGraph, RawEdges,
-    RawNodes, VisitOrder, VisitOrderReverse, WouldCycle,
+    Connection, Dag, EdgeIndex, Graph, GraphError, Inputs, NodeIndex, NodesMut, Outputs, PetGraph,
+    RawEdges, RawNodes, VisitOrder, VisitOrderReverse, WouldCycle,
 };
-pub use node::Node;
+pub use node::{Node, Settings};
+pub use pan::{pan_compensation_db, PanLaw};
 pub use sample::{
     self, conv, rate, signal, slice, Duplex as DuplexSample, Frame, FromSample, Sample, Signal, ToSample,
 };
 
+pub mod automation;
+pub mod buffer;
+pub mod clock;
+pub mod crossfade;
+pub mod delay;
+pub mod distortion;
+pub mod dynamics;
+pub mod envelope;
+pub mod event;
+#[cfg(feature = "fast_math")]
+pub mod fast_math;
+mod fft;
+pub mod filter;
+pub mod gain;
 mod graph;
+pub mod input;
+pub mod meter;
+pub mod midi;
+pub mod mixer;
+pub mod multiband;
+pub mod named;
 mod node;
+pub mod noise;
+pub mod oscillator;
+mod pan;
+pub mod param;
+pub mod preset;
+pub mod resample;
+pub mod reverb;
+pub mod sampler;
+pub mod shared;
+pub mod split;
+pub mod stereo;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod util;
 
 /// The amplitude multiplier.
 pub type Volume = f32;
@@ -0,0 +1,1626 @@
+//! Nodes that measure or record a signal without altering it.
+
+use crate::delay::DelayLine;
+use crate::fft::{self, Complex, Fft};
+use crate::gain;
+use crate::node::{Node, Settings};
+use crate::resample::{self, OVERSAMPLE_PHASE_TAPS};
+use crate::util;
+use sample::Frame;
+use std::collections::VecDeque;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+/// The time in milliseconds over which a VU meter rises to 99% of a sudden change in level, as
+/// specified by IEC 60268-17. A VU meter falls back at the same rate.
+pub const VU_RISE_MS: f64 = 300.0;
+
+/// The time in milliseconds over which a peak programme meter rises to within 2 dB of a sudden
+/// change in level, as specified for the Type I meters of IEC 60268-10.
+pub const PPM_RISE_MS: f64 = 5.0;
+
+/// The rate in decibels per second at which a peak programme meter falls back, i.e. 20 dB in
+/// 1.7 s as specified for the Type I meters of IEC 60268-10.
+pub const PPM_FALL_DB_PER_SEC: f64 = 20.0 / 1.7;
+
+/// The movement of a meter's display in response to changes in the measured level.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ballistics {
+    /// The display follows the measured level exactly.
+    #[default]
+    Instant,
+    /// A volume unit meter, rising and falling alike over `VU_RISE_MS`, showing roughly the
+    /// average level.
+    Vu,
+    /// A peak programme meter, rising within `PPM_RISE_MS` so that peaks are caught and falling
+    /// back slowly at `PPM_FALL_DB_PER_SEC` so that they can be read.
+    Ppm,
+}
+
+/// Smooths the levels reported by a meter for display, according to its **Ballistics**.
+///
+/// Each level is a magnitude such as an amplitude, where rising and falling are relative to
+/// `0.0`. The levels are updated once per buffer by `process`, given the length of time the
+/// buffer covers, so the movement of the display does not depend on the size of the buffers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BallisticsFilter {
+    ballistics: Ballistics,
+    levels: Vec<f32>,
+}
+
+impl BallisticsFilter {
+    /// Construct a **BallisticsFilter** with the given ballistics, displaying silence.
+    pub fn new(ballistics: Ballistics) -> Self {
+        BallisticsFilter {
+            ballistics,
+            levels: Vec::new(),
+        }
+    }
+
+    /// The movement of the display.
+    pub fn ballistics(&self) -> Ballistics {
+        self.ballistics
+    }
+
+    /// Set the movement of the display, continuing from the levels currently displayed.
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.ballistics = ballistics;
+    }
+
+    /// Move the displayed levels towards the given measured levels over `elapsed_secs`.
+    pub fn process(&mut self, measured: &[f32], elapsed_secs: f64) {
+        if self.levels.len() != measured.len() {
+            self.levels.resize(measured.len(), 0.0);
+        }
+        let elapsed_ms = elapsed_secs.max(0.0) * 1_000.0;
+        for (level, &target) in self.levels.iter_mut().zip(measured) {
+            *level = match self.ballistics {
+                Ballistics::Instant => target,
+                Ballistics::Vu => approach(*level, target, elapsed_ms, VU_RISE_MS / 100f64.ln()),
+                Ballistics::Ppm if target >= *level => {
+                    // The fraction of the change remaining once the level is within 2 dB.
+                    let remaining = 1.0 - 10f64.powf(-2.0 / 20.0);
+                    let time_constant_ms = PPM_RISE_MS / -remaining.ln();
+                    approach(*level, target, elapsed_ms, time_constant_ms)
+                }
+                Ballistics::Ppm => {
+                    let fall_db = PPM_FALL_DB_PER_SEC * elapsed_ms / 1_000.0;
+                    let fallen = *level as f64 * 10f64.powf(-fall_db / 20.0);
+                    fallen.max(target as f64) as f32
+                }
+            };
+        }
+    }
+
+    /// The displayed levels, one per level given to `process`.
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    /// Return the displayed levels to silence.
+    pub fn reset(&mut self) {
+        for level in self.levels.iter_mut() {
+            *level = 0.0;
+        }
+    }
+}
+
+/// Move `level` towards `target` as a one-pole filter with the given time constant would over
+/// `elapsed_ms`.
+fn approach(level: f32, target: f32, elapsed_ms: f64, time_constant_ms: f64) -> f32 {
+    let remaining = (-elapsed_ms / time_constant_ms).exp();
+    (target as f64 + (level as f64 - target as f64) * remaining) as f32
+}
+
+/// A pass-through **Node** that tracks the absolute peak of each channel.
+///
+/// The peak is measured over each buffer passed to `audio_requested`. With a `decay` greater than
+/// `0.0`, the reported peak falls gradually between buffers rather than snapping straight to the
+/// latest buffer's level, which is useful for driving VU displays. Alternatively, standard
+/// metering **Ballistics** may be applied to the displayed peak, leaving the measured peak as is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeakMeter {
+    peaks: Vec<f32>,
+    decay: f32,
+    display: BallisticsFilter,
+}
+
+impl PeakMeter {
+    /// Construct a **PeakMeter** that reports the peak of the most recent buffer only.
+    pub fn new() -> Self {
+        PeakMeter::with_decay(0.0)
+    }
+
+    /// Construct a **PeakMeter** whose reported peak is multiplied by `decay` (0.0 ... 1.0) once
+    /// per buffer, unless a higher peak is measured.
+    pub fn with_decay(decay: f32) -> Self {
+        PeakMeter {
+            peaks: Vec::new(),
+            decay: decay.clamp(0.0, 1.0),
+            display: BallisticsFilter::default(),
+        }
+    }
+
+    /// The decay coefficient applied to the reported peak once per buffer.
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    /// Set the decay coefficient applied to the reported peak once per buffer (0.0 ... 1.0).
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.clamp(0.0, 1.0);
+    }
+
+    /// The absolute peak of each channel, where `1.0` is full scale.
+    ///
+    /// Empty until audio has been requested.
+    pub fn peak(&self) -> &[f32] {
+        &self.peaks
+    }
+
+    /// The movement of the displayed peak.
+    pub fn ballistics(&self) -> Ballistics {
+        self.display.ballistics()
+    }
+
+    /// Set the movement of the displayed peak.
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.display.set_ballistics(ballistics);
+    }
+
+    /// The peak of each channel with the `ballistics` applied, for display.
+    ///
+    /// Empty until audio has been requested.
+    pub fn displayed_peak(&self) -> &[f32] {
+        self.display.levels()
+    }
+
+    /// Return the measured and displayed peaks to silence.
+    pub fn reset(&mut self) {
+        for peak in self.peaks.iter_mut() {
+            *peak = 0.0;
+        }
+        self.display.reset();
+    }
+}
+
+impl Default for PeakMeter {
+    fn default() -> Self {
+        PeakMeter::new()
+    }
+}
+
+impl<F> Node<F> for PeakMeter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if self.peaks.len() != F::n_channels() {
+            self.peaks.resize(F::n_channels(), 0.0);
+        }
+        for peak in self.peaks.iter_mut() {
+            *peak *= self.decay;
+        }
+        for frame in buffer.iter() {
+            for (peak, s) in self.peaks.iter_mut().zip(frame.channels()) {
+                let amp = util::to_f64(s).abs() as f32;
+                if amp > *peak {
+                    *peak = amp;
+                }
+            }
+        }
+        self.display
+            .process(&self.peaks, buffer.len() as f64 / sample_hz);
+    }
+
+    fn reset(&mut self) {
+        PeakMeter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "peak_meter"
+    }
+}
+
+/// A pass-through **Node** that detects clipping, e.g. to drive a mixer's clip indicator.
+///
+/// A sample is clipped when it is at or beyond full scale for its format: `±1.0` for floating
+/// point formats, or the greatest and least values of integer formats. A channel is flagged as
+/// overloaded once `run_samples` consecutive samples have clipped, and remains flagged until
+/// `reset_clip` is called, so that brief overloads are not missed between display updates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipMeter {
+    run_samples: usize,
+    /// The number of consecutive clipped samples most recently received on each channel.
+    runs: Vec<usize>,
+    /// The total number of clipped samples on each channel since the last reset.
+    clipped: Vec<u64>,
+    overloaded: Vec<bool>,
+}
+
+impl ClipMeter {
+    /// Construct a **ClipMeter** that flags a channel as overloaded after the given number of
+    /// consecutive clipped samples (at least `1`).
+    pub fn new(run_samples: usize) -> Self {
+        ClipMeter {
+            run_samples: run_samples.max(1),
+            runs: Vec::new(),
+            clipped: Vec::new(),
+            overloaded: Vec::new(),
+        }
+    }
+
+    /// The number of consecutive clipped samples after which a channel is flagged as overloaded.
+    pub fn run_samples(&self) -> usize {
+        self.run_samples
+    }
+
+    /// Set the number of consecutive clipped samples after which a channel is flagged as
+    /// overloaded (at least `1`).
+    pub fn set_run_samples(&mut self, run_samples: usize) {
+        self.run_samples = run_samples.max(1);
+    }
+
+    /// Whether or not any channel has been overloaded since the last call to `reset_clip`.
+    pub fn overloaded(&self) -> bool {
+        self.overloaded.iter().any(|&overloaded| overloaded)
+    }
+
+    /// Whether or not each channel has been overloaded since the last call to `reset_clip`.
+    ///
+    /// Empty until audio has been requested.
+    pub fn overloaded_channels(&self) -> &[bool] {
+        &self.overloaded
+    }
+
+    /// The total number of clipped samples received on each channel since the last call to
+    /// `reset_clip`.
+    ///
+    /// Empty until audio has been requested.
+    pub fn clipped_samples(&self) -> &[u64] {
+        &self.clipped
+    }
+
+    /// Clear the overload flags and clipped sample counts.
+    pub fn reset_clip(&mut self) {
+        for run in self.runs.iter_mut() {
+            *run = 0;
+        }
+        for clipped in self.clipped.iter_mut() {
+            *clipped = 0;
+        }
+        for overloaded in self.overloaded.iter_mut() {
+            *overloaded = false;
+        }
+    }
+}
+
+impl Default for ClipMeter {
+    fn default() -> Self {
+        ClipMeter::new(1)
+    }
+}
+
+impl<F> Node<F> for ClipMeter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let channels = F::n_channels();
+        if self.runs.len() != channels {
+            self.runs.resize(channels, 0);
+            self.clipped.resize(channels, 0);
+            self.overloaded.resize(channels, false);
+        }
+        // Full scale converted to the sample format, saturating at the limits of integer formats.
+        let max: F::Sample = util::from_f64(1.0);
+        let min: F::Sample = util::from_f64(-1.0);
+        for frame in buffer.iter() {
+            for (ch, s) in frame.channels().enumerate() {
+                if s >= max || s <= min {
+                    self.clipped[ch] += 1;
+                    self.runs[ch] += 1;
+                    if self.runs[ch] >= self.run_samples {
+                        self.overloaded[ch] = true;
+                    }
+                } else {
+                    self.runs[ch] = 0;
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.reset_clip();
+    }
+
+    fn type_tag(&self) -> &str {
+        "clip_meter"
+    }
+}
+
+/// A pass-through **Node** that measures the root-mean-square level of each channel over a
+/// sliding window.
+///
+/// The window is continuous across buffers, so the reported level does not depend on the size of
+/// the buffers passed to `audio_requested`. Standard metering **Ballistics** may be applied to the
+/// displayed level, leaving the measured level as is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RmsMeter {
+    window_ms: f64,
+    /// The sample rate for which the window was last sized.
+    sample_hz: f64,
+    /// The squared samples within the window, interleaved by channel.
+    squares: Vec<f64>,
+    /// The index of the oldest frame within the window.
+    frame_idx: usize,
+    /// The running sum of squares within the window for each channel.
+    sums: Vec<f64>,
+    rms: Vec<f32>,
+    display: BallisticsFilter,
+}
+
+impl RmsMeter {
+    /// Construct an **RmsMeter** that measures over a window of the given length in milliseconds.
+    pub fn new(window_ms: f64) -> Self {
+        RmsMeter {
+            window_ms: window_ms.max(0.0),
+            sample_hz: 0.0,
+            squares: Vec::new(),
+            frame_idx: 0,
+            sums: Vec::new(),
+            rms: Vec::new(),
+            display: BallisticsFilter::default(),
+        }
+    }
+
+    /// The length of the window in milliseconds.
+    pub fn window_ms(&self) -> f64 {
+        self.window_ms
+    }
+
+    /// Set the length of the window in milliseconds.
+    ///
+    /// The window is cleared and re-sized upon the next call to `audio_requested`.
+    pub fn set_window_ms(&mut self, window_ms: f64) {
+        self.window_ms = window_ms.max(0.0);
+        self.sample_hz = 0.0;
+    }
+
+    /// The RMS level of each channel, where `1.0` is full scale.
+    ///
+    /// Empty until audio has been requested.
+    pub fn rms(&self) -> &[f32] {
+        &self.rms
+    }
+
+    /// The RMS level of each channel in decibels relative to full scale.
+    pub fn rms_db(&self) -> Vec<f32> {
+        self.rms.iter().map(|&rms| gain::amp_to_db(rms)).collect()
+    }
+
+    /// The movement of the displayed level.
+    pub fn ballistics(&self) -> Ballistics {
+        self.display.ballistics()
+    }
+
+    /// Set the movement of the displayed level.
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.display.set_ballistics(ballistics);
+    }
+
+    /// The RMS level of each channel with the `ballistics` applied, for display.
+    ///
+    /// Empty until audio has been requested.
+    pub fn displayed_rms(&self) -> &[f32] {
+        self.display.levels()
+    }
+
+    /// Clear the window and re-size it for the given sample rate and number of channels.
+    fn prepare_for(&mut self, sample_hz: f64, channels: usize) {
+        let window_frames = ((self.window_ms / 1_000.0 * sample_hz).round() as usize).max(1);
+        self.sample_hz = sample_hz;
+        self.squares.clear();
+        self.squares.resize(window_frames * channels, 0.0);
+        self.frame_idx = 0;
+        self.sums.clear();
+        self.sums.resize(channels, 0.0);
+        self.rms.clear();
+        self.rms.resize(channels, 0.0);
+    }
+
+    /// Clear the window, as though the meter had only ever received silence.
+    pub fn reset(&mut self) {
+        for x in self.squares.iter_mut().chain(self.sums.iter_mut()) {
+            *x = 0.0;
+        }
+        for rms in self.rms.iter_mut() {
+            *rms = 0.0;
+        }
+        self.frame_idx = 0;
+        self.display.reset();
+    }
+}
+
+impl<F> Node<F> for RmsMeter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let channels = F::n_channels();
+        if self.sample_hz != sample_hz || self.sums.len() != channels {
+            self.prepare_for(sample_hz, channels);
+        }
+        let window_frames = self.squares.len() / channels;
+        for frame in buffer.iter() {
+            let start = self.frame_idx * channels;
+            let squares = &mut self.squares[start..start + channels];
+            for ((square, sum), s) in squares.iter_mut().zip(&mut self.sums).zip(frame.channels()) {
+                let x = util::to_f64(s);
+                *sum += x * x - *square;
+                *square = x * x;
+            }
+            self.frame_idx = (self.frame_idx + 1) % window_frames;
+        }
+        for (rms, &sum) in self.rms.iter_mut().zip(&self.sums) {
+            *rms = (sum.max(0.0) / window_frames as f64).sqrt() as f32;
+        }
+        self.display
+            .process(&self.rms, buffer.len() as f64 / sample_hz);
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.prepare_for(settings.sample_hz, F::n_channels());
+    }
+
+    fn reset(&mut self) {
+        RmsMeter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "rms_meter"
+    }
+}
+
+/// A pass-through **Node** that measures the correlation between the left and right channels of
+/// a stereo signal over a sliding window, for monitoring phase and mono compatibility.
+///
+/// The correlation is `+1.0` for identical channels (mono), `0.0` for unrelated channels and
+/// `-1.0` for channels that are out of phase, which cancel when summed to mono. Like the
+/// **RmsMeter**, the window is continuous across buffers. Signals that are not stereo are not
+/// measured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Correlation {
+    window_ms: f64,
+    /// The sample rate for which the window was last sized.
+    sample_hz: f64,
+    /// The products `left * right`, `left²` and `right²` of each frame within the window.
+    products: Vec<[f64; 3]>,
+    /// The index of the oldest frame within the window.
+    frame_idx: usize,
+    /// The running sum of each product within the window.
+    sums: [f64; 3],
+}
+
+impl Correlation {
+    /// Construct a **Correlation** meter that measures over a window of the given length in
+    /// milliseconds.
+    pub fn new(window_ms: f64) -> Self {
+        Correlation {
+            window_ms: window_ms.max(0.0),
+            sample_hz: 0.0,
+            products: Vec::new(),
+            frame_idx: 0,
+            sums: [0.0; 3],
+        }
+    }
+
+    /// The length of the window in milliseconds.
+    pub fn window_ms(&self) -> f64 {
+        self.window_ms
+    }
+
+    /// Set the length of the window in milliseconds.
+    ///
+    /// The window is cleared and re-sized upon the next call to `audio_requested`.
+    pub fn set_window_ms(&mut self, window_ms: f64) {
+        self.window_ms = window_ms.max(0.0);
+        self.sample_hz = 0.0;
+    }
+
+    /// The correlation between the left and right channels (-1.0 ... 1.0).
+    ///
+    /// `0.0` while either channel is silent throughout the window.
+    pub fn correlation(&self) -> f32 {
+        let [lr, ll, rr] = self.sums;
+        let power = (ll.max(0.0) * rr.max(0.0)).sqrt();
+        if power <= 0.0 {
+            0.0
+        } else {
+            (lr / power).clamp(-1.0, 1.0) as f32
+        }
+    }
+
+    /// Clear the window and re-size it for the given sample rate.
+    fn prepare_for(&mut self, sample_hz: f64) {
+        let window_frames = ((self.window_ms / 1_000.0 * sample_hz).round() as usize).max(1);
+        self.sample_hz = sample_hz;
+        self.products.clear();
+        self.products.resize(window_frames, [0.0; 3]);
+        self.frame_idx = 0;
+        self.sums = [0.0; 3];
+    }
+
+    /// Clear the window, as though the meter had only ever received silence.
+    pub fn reset(&mut self) {
+        for products in self.products.iter_mut() {
+            *products = [0.0; 3];
+        }
+        self.frame_idx = 0;
+        self.sums = [0.0; 3];
+    }
+}
+
+impl<F> Node<F> for Correlation
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if F::n_channels() != 2 {
+            return;
+        }
+        if self.sample_hz != sample_hz || self.products.is_empty() {
+            self.prepare_for(sample_hz);
+        }
+        for frame in buffer.iter() {
+            let mut channels = frame.channels().map(util::to_f64);
+            let left = channels.next().unwrap_or(0.0);
+            let right = channels.next().unwrap_or(0.0);
+            let products = [left * right, left * left, right * right];
+            let oldest = &mut self.products[self.frame_idx];
+            for ((sum, old), new) in self.sums.iter_mut().zip(oldest.iter()).zip(&products) {
+                *sum += new - old;
+            }
+            *oldest = products;
+            self.frame_idx = (self.frame_idx + 1) % self.products.len();
+        }
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.prepare_for(settings.sample_hz);
+    }
+
+    fn reset(&mut self) {
+        Correlation::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "correlation"
+    }
+}
+
+/// A pass-through **Node** that collects the points plotted by a goniometer (aka vectorscope) to
+/// visualise the stereo field.
+///
+/// Each frame of the most recent buffer becomes a point, rotated by 45° so that its `y` is the mid
+/// `(left + right) / √2` and its `x` the side `(right - left) / √2`. A mono signal therefore lies
+/// along the vertical axis, a signal panned hard left along the upper-left diagonal and one that
+/// is out of phase along the horizontal axis.
+///
+/// Buffers longer than `max_points` are decimated, keeping every `n`th frame, so that the points
+/// never require more than `max_points` of memory. Signals that are not stereo produce no points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Goniometer {
+    max_points: usize,
+    points: Vec<(f32, f32)>,
+}
+
+impl Goniometer {
+    /// Construct a **Goniometer** that keeps at most `max_points` points per buffer (at least 1).
+    pub fn new(max_points: usize) -> Self {
+        let max_points = max_points.max(1);
+        Goniometer {
+            max_points,
+            points: Vec::with_capacity(max_points),
+        }
+    }
+
+    /// The largest number of points kept per buffer.
+    pub fn max_points(&self) -> usize {
+        self.max_points
+    }
+
+    /// Set the largest number of points kept per buffer (at least 1), taking effect from the next
+    /// buffer.
+    pub fn set_max_points(&mut self, max_points: usize) {
+        self.max_points = max_points.max(1);
+        self.points.truncate(self.max_points);
+        self.points.shrink_to(self.max_points);
+    }
+
+    /// The `(x, y)` point of each frame kept from the most recent buffer, oldest first.
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    /// Discard the collected points.
+    pub fn reset(&mut self) {
+        self.points.clear();
+    }
+}
+
+impl<F> Node<F> for Goniometer
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        self.points.clear();
+        if F::n_channels() != 2 {
+            return;
+        }
+        let step = buffer.len().div_ceil(self.max_points);
+        for frame in buffer.iter().step_by(step.max(1)) {
+            let mut channels = frame.channels().map(util::to_f64);
+            let left = channels.next().unwrap_or(0.0);
+            let right = channels.next().unwrap_or(0.0);
+            let x = (right - left) * FRAC_1_SQRT_2;
+            let y = (left + right) * FRAC_1_SQRT_2;
+            self.points.push((x as f32, y as f32));
+        }
+    }
+
+    fn reset(&mut self) {
+        Goniometer::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "goniometer"
+    }
+}
+
+/// A pass-through **Node** that measures the magnitude spectrum of its input.
+///
+/// The input is mixed down to mono and accumulated into a sliding window of `fft_size` frames.
+/// Every `hop_size` frames, the window is weighted by a Hann window and transformed, so the FFT
+/// size is independent of the size of the buffers passed to `audio_requested`. A `hop_size` less
+/// than the `fft_size` produces overlapping windows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpectrumAnalyzer {
+    fft: Fft,
+    hop_size: usize,
+    window: Vec<f64>,
+    /// The sum of the window's weights, used to normalise the magnitudes.
+    window_sum: f64,
+    /// The most recent `fft_size` mono samples.
+    input: Vec<f64>,
+    /// The index of the oldest sample within `input`.
+    input_idx: usize,
+    /// The number of frames received since the last transform.
+    frames_since_hop: usize,
+    /// Re-used for each transform.
+    scratch: Vec<Complex>,
+    magnitudes: Vec<f32>,
+}
+
+impl SpectrumAnalyzer {
+    /// Construct a **SpectrumAnalyzer** with the given FFT size and hop size in frames.
+    ///
+    /// The `hop_size` is clamped to `1 ... fft_size`.
+    ///
+    /// **Panics** unless `fft_size` is a power of two.
+    pub fn new(fft_size: usize, hop_size: usize) -> Self {
+        let fft = Fft::new(fft_size);
+        let window = fft::hann_window(fft_size);
+        let window_sum = window.iter().sum();
+        SpectrumAnalyzer {
+            fft,
+            hop_size: hop_size.clamp(1, fft_size),
+            window,
+            window_sum,
+            input: vec![0.0; fft_size],
+            input_idx: 0,
+            frames_since_hop: 0,
+            scratch: vec![Complex::default(); fft_size],
+            magnitudes: vec![0.0; fft_size / 2 + 1],
+        }
+    }
+
+    /// The number of frames transformed at once.
+    pub fn fft_size(&self) -> usize {
+        self.fft.size()
+    }
+
+    /// The number of frames between the start of each transformed window.
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// Set the number of frames between the start of each transformed window, clamped to
+    /// `1 ... fft_size`.
+    pub fn set_hop_size(&mut self, hop_size: usize) {
+        self.hop_size = hop_size.clamp(1, self.fft_size());
+    }
+
+    /// The magnitude of each frequency bin from `0` Hz up to the Nyquist frequency, from the most
+    /// recently transformed window.
+    ///
+    /// Magnitudes are normalised so that a full scale sine wave measures `1.0` at its bin. Zeroed
+    /// until the first window has been transformed.
+    pub fn magnitudes(&self) -> &[f32] {
+        &self.magnitudes
+    }
+
+    /// The center frequency of the given bin in hertz, at the given sample rate.
+    pub fn bin_hz(&self, bin: usize, sample_hz: f64) -> f64 {
+        bin as f64 * sample_hz / self.fft_size() as f64
+    }
+
+    /// Transform the current window, updating the magnitudes.
+    fn transform(&mut self) {
+        let size = self.fft_size();
+        for (i, (x, w)) in self.scratch.iter_mut().zip(&self.window).enumerate() {
+            let sample = self.input[(self.input_idx + i) % size];
+            *x = Complex::new(sample * w, 0.0);
+        }
+        self.fft.forward(&mut self.scratch);
+        let scale = 2.0 / self.window_sum;
+        for (magnitude, x) in self.magnitudes.iter_mut().zip(&self.scratch) {
+            *magnitude = (x.norm() * scale) as f32;
+        }
+    }
+
+    /// Clear the window and magnitudes, as though the analyzer had only ever received silence.
+    pub fn reset(&mut self) {
+        for x in self.input.iter_mut() {
+            *x = 0.0;
+        }
+        for magnitude in self.magnitudes.iter_mut() {
+            *magnitude = 0.0;
+        }
+        self.input_idx = 0;
+        self.frames_since_hop = 0;
+    }
+}
+
+impl<F> Node<F> for SpectrumAnalyzer
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let size = self.fft_size();
+        let channels = F::n_channels() as f64;
+        for frame in buffer.iter() {
+            let sum: f64 = frame.channels().map(util::to_f64).sum();
+            self.input[self.input_idx] = sum / channels;
+            self.input_idx = (self.input_idx + 1) % size;
+            self.frames_since_hop += 1;
+            if self.frames_since_hop >= self.hop_size {
+                self.frames_since_hop = 0;
+                self.transform();
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        SpectrumAnalyzer::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "spectrum_analyzer"
+    }
+}
+
+/// The factor by which a **TruePeakMeter** oversamples its input.
+pub const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// A pass-through **Node** that measures the true peak of each channel, as specified by ITU-R
+/// BS.1770.
+///
+/// Between samples, a reconstructed signal may swing beyond the sample values themselves, so
+/// clipping upon conversion to analog or to a lossy format even though no sample exceeds full
+/// scale. Each channel is oversampled by `TRUE_PEAK_OVERSAMPLING` with a polyphase low-pass
+/// filter, and the highest absolute value of the oversampled signal since construction or `reset`
+/// is held. The filter delays the measurement by `OVERSAMPLE_PHASE_TAPS - 1` frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruePeakMeter {
+    kernel: Vec<f64>,
+    /// The most recent input frames, feeding the upsampling filter.
+    history: DelayLine,
+    peaks: Vec<f32>,
+}
+
+impl TruePeakMeter {
+    /// Construct a **TruePeakMeter** that has measured nothing.
+    pub fn new() -> Self {
+        TruePeakMeter {
+            kernel: resample::oversample_kernel(TRUE_PEAK_OVERSAMPLING),
+            history: DelayLine::default(),
+            peaks: Vec::new(),
+        }
+    }
+
+    /// The highest absolute value of each oversampled channel, where `1.0` is full scale.
+    ///
+    /// Empty until audio has been requested.
+    pub fn true_peaks(&self) -> &[f32] {
+        &self.peaks
+    }
+
+    /// The highest true peak across every channel in decibels relative to full scale (dBTP).
+    ///
+    /// `f32::NEG_INFINITY` until a non-silent sample has been measured.
+    pub fn true_peak_db(&self) -> f32 {
+        gain::amp_to_db(self.peaks.iter().cloned().fold(0.0, f32::max))
+    }
+
+    /// Return the measured peaks and the filter to silence.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        for peak in self.peaks.iter_mut() {
+            *peak = 0.0;
+        }
+    }
+}
+
+impl Default for TruePeakMeter {
+    fn default() -> Self {
+        TruePeakMeter::new()
+    }
+}
+
+impl<F> Node<F> for TruePeakMeter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let channels = F::n_channels();
+        if self.peaks.len() != channels {
+            self.history.resize(OVERSAMPLE_PHASE_TAPS, channels);
+            self.peaks.clear();
+            self.peaks.resize(channels, 0.0);
+        }
+        let factor = TRUE_PEAK_OVERSAMPLING;
+        let TruePeakMeter {
+            ref kernel,
+            ref mut history,
+            ref mut peaks,
+        } = *self;
+        // Compute each phase of the upsampling filter from the input history, scaled by the
+        // factor to make up for the energy lost to the stuffed zeros.
+        for frame in buffer.iter() {
+            for (channel, s) in frame.channels().enumerate() {
+                history.write(channel, util::to_f64(s));
+            }
+            history.advance();
+            for (channel, peak) in peaks.iter_mut().enumerate() {
+                for phase in 0..factor {
+                    let sum: f64 = (0..OVERSAMPLE_PHASE_TAPS)
+                        .map(|j| kernel[phase + j * factor] * history.past(channel, j + 1))
+                        .sum();
+                    *peak = peak.max((sum * factor as f64).abs() as f32);
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        TruePeakMeter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "true_peak_meter"
+    }
+}
+
+/// The loudness in LUFS below which a block is ignored by the integrated loudness of a
+/// **LufsMeter**.
+pub const LUFS_ABSOLUTE_GATE: f32 = -70.0;
+
+/// The loudness in LU, relative to the absolute-gated loudness, below which a block is ignored by
+/// the integrated loudness of a **LufsMeter**.
+pub const LUFS_RELATIVE_GATE: f32 = -10.0;
+
+/// The length of the sub-blocks from which a **LufsMeter** builds each of its windows.
+const LUFS_SUB_BLOCK_SECS: f64 = 0.1;
+
+/// The number of sub-blocks within the 400 ms momentary window, which is also the gating block.
+const LUFS_MOMENTARY_SUB_BLOCKS: usize = 4;
+
+/// The number of sub-blocks within the 3 s short-term window.
+const LUFS_SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+/// The resolution in LU of the histogram of gating blocks.
+const LUFS_HISTOGRAM_STEP: f32 = 0.1;
+
+/// The number of histogram bins, spanning `LUFS_ABSOLUTE_GATE` up to +10 LUFS. Louder blocks are
+/// counted within the highest bin.
+const LUFS_HISTOGRAM_BINS: usize = 800;
+
+/// The normalised coefficients of one of the K-weighting biquads, where `a0` is `1.0`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct KWeighting {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl KWeighting {
+    /// The high shelf modelling the acoustic effect of the head, as specified by BS.1770.
+    fn shelf(sample_hz: f64) -> Self {
+        let k = (PI * 1_681.974_450_955_533 / sample_hz).tan();
+        let q = 0.707_175_236_955_419_6;
+        let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        KWeighting {
+            b: [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    }
+
+    /// The high-pass filter (the "RLB" weighting), as specified by BS.1770.
+    fn high_pass(sample_hz: f64) -> Self {
+        let k = (PI * 38.135_470_876_024_44 / sample_hz).tan();
+        let q = 0.500_327_037_323_877_3;
+        let a0 = 1.0 + k / q + k * k;
+        KWeighting {
+            b: [1.0, -2.0, 1.0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        }
+    }
+
+    /// Filter the next sample, given the previous two inputs and outputs `[x1, x2, y1, y2]`.
+    #[inline]
+    fn process(&self, history: &mut [f64; 4], x: f64) -> f64 {
+        let [x1, x2, y1, y2] = *history;
+        let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+        let y = util::flush_denormal(y);
+        *history = [x, x1, y, y1];
+        y
+    }
+}
+
+/// The loudness in LUFS of the given mean square of the K-weighted signal.
+fn lufs(mean_square: f64) -> f32 {
+    if mean_square <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        (-0.691 + 10.0 * mean_square.log10()) as f32
+    }
+}
+
+/// A pass-through **Node** that measures loudness as specified by ITU-R BS.1770 and EBU R 128.
+///
+/// Each channel is K-weighted by a high shelf and a high-pass filter before its power is measured.
+/// The momentary loudness covers the most recent 400 ms and the short-term loudness the most
+/// recent 3 s, both updated every 100 ms. The integrated loudness covers everything since
+/// construction or `reset`, over 400 ms blocks overlapping by 75%, ignoring blocks quieter than
+/// `LUFS_ABSOLUTE_GATE` and then those more than `LUFS_RELATIVE_GATE` below the remainder.
+///
+/// Every channel is weighted equally, except for 6-channel (5.1) frames, where the fourth (LFE)
+/// channel is ignored and the surround channels are weighted by +1.5 dB. Blocks are counted within
+/// a fixed histogram rather than stored, so measuring indefinitely performs no allocation, and the
+/// relative gate is applied with a resolution of 0.1 LU.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LufsMeter {
+    /// The sample rate for which the filters were last designed.
+    sample_hz: f64,
+    shelf: KWeighting,
+    high_pass: KWeighting,
+    /// The history of each filter for each channel.
+    history: Vec<[[f64; 4]; 2]>,
+    sub_block_frames: usize,
+    /// The number of frames and the weighted sum of squares within the current sub-block.
+    sub_block_len: usize,
+    sub_block_sum: f64,
+    /// The mean square of the most recent sub-blocks, where `sub_block_idx` is the oldest.
+    sub_blocks: [f64; LUFS_SHORT_TERM_SUB_BLOCKS],
+    sub_block_idx: usize,
+    /// The number of sub-blocks completed, saturating at the length of the short-term window.
+    num_sub_blocks: usize,
+    /// The number and total mean square of the gating blocks counted within each bin.
+    block_counts: Vec<u64>,
+    block_sums: Vec<f64>,
+    momentary: f64,
+    short_term: f64,
+}
+
+impl LufsMeter {
+    /// Construct a **LufsMeter** that has measured nothing.
+    pub fn new() -> Self {
+        LufsMeter {
+            sample_hz: 0.0,
+            shelf: KWeighting::default(),
+            high_pass: KWeighting::default(),
+            history: Vec::new(),
+            sub_block_frames: 0,
+            sub_block_len: 0,
+            sub_block_sum: 0.0,
+            sub_blocks: [0.0; LUFS_SHORT_TERM_SUB_BLOCKS],
+            sub_block_idx: 0,
+            num_sub_blocks: 0,
+            block_counts: vec![0; LUFS_HISTOGRAM_BINS],
+            block_sums: vec![0.0; LUFS_HISTOGRAM_BINS],
+            momentary: 0.0,
+            short_term: 0.0,
+        }
+    }
+
+    /// The loudness in LUFS over the most recent 400 ms.
+    ///
+    /// `f32::NEG_INFINITY` until the first 100 ms have been measured, or while silent.
+    pub fn momentary_lufs(&self) -> f32 {
+        lufs(self.momentary)
+    }
+
+    /// The loudness in LUFS over the most recent 3 s.
+    ///
+    /// `f32::NEG_INFINITY` until the first 100 ms have been measured, or while silent.
+    pub fn short_term_lufs(&self) -> f32 {
+        lufs(self.short_term)
+    }
+
+    /// The gated loudness in LUFS of everything measured since construction or `reset`.
+    ///
+    /// `f32::NEG_INFINITY` until a 400 ms block louder than `LUFS_ABSOLUTE_GATE` has been
+    /// measured.
+    pub fn integrated_lufs(&self) -> f32 {
+        let total = |from_bin: usize| {
+            let count: u64 = self.block_counts[from_bin..].iter().sum();
+            let sum: f64 = self.block_sums[from_bin..].iter().sum();
+            if count == 0 {
+                0.0
+            } else {
+                sum / count as f64
+            }
+        };
+        let gate = lufs(total(0)) + LUFS_RELATIVE_GATE;
+        if gate == f32::NEG_INFINITY {
+            return gate;
+        }
+        lufs(total(histogram_bin(gate)))
+    }
+
+    /// Design the filters for the given sample rate and channel count, and clear every
+    /// measurement.
+    fn prepare_for(&mut self, sample_hz: f64, channels: usize) {
+        self.sample_hz = sample_hz;
+        self.shelf = KWeighting::shelf(sample_hz);
+        self.high_pass = KWeighting::high_pass(sample_hz);
+        self.sub_block_frames = ((LUFS_SUB_BLOCK_SECS * sample_hz).round() as usize).max(1);
+        self.history.resize(channels, [[0.0; 4]; 2]);
+        self.reset();
+    }
+
+    /// Complete the current sub-block, updating the momentary and short-term loudness and counting
+    /// the gating block that ends with it.
+    fn complete_sub_block(&mut self) {
+        self.sub_blocks[self.sub_block_idx] = self.sub_block_sum / self.sub_block_frames as f64;
+        self.sub_block_idx = (self.sub_block_idx + 1) % LUFS_SHORT_TERM_SUB_BLOCKS;
+        self.num_sub_blocks = (self.num_sub_blocks + 1).min(LUFS_SHORT_TERM_SUB_BLOCKS);
+        self.sub_block_len = 0;
+        self.sub_block_sum = 0.0;
+
+        // Windows reaching back before the first sub-block are measured as though preceded by
+        // silence.
+        let (sub_blocks, oldest) = (&self.sub_blocks, self.sub_block_idx);
+        let newest_first = (1..=LUFS_SHORT_TERM_SUB_BLOCKS).map(|i| {
+            sub_blocks[(oldest + LUFS_SHORT_TERM_SUB_BLOCKS - i) % LUFS_SHORT_TERM_SUB_BLOCKS]
+        });
+        let momentary: f64 = newest_first.clone().take(LUFS_MOMENTARY_SUB_BLOCKS).sum();
+        let short_term: f64 = newest_first.sum();
+        self.momentary = momentary / LUFS_MOMENTARY_SUB_BLOCKS as f64;
+        self.short_term = short_term / LUFS_SHORT_TERM_SUB_BLOCKS as f64;
+
+        if self.num_sub_blocks >= LUFS_MOMENTARY_SUB_BLOCKS {
+            let loudness = lufs(self.momentary);
+            if loudness > LUFS_ABSOLUTE_GATE {
+                let bin = histogram_bin(loudness);
+                self.block_counts[bin] += 1;
+                self.block_sums[bin] += self.momentary;
+            }
+        }
+    }
+
+    /// Clear every measurement and the history of the filters, as though the meter had just been
+    /// constructed.
+    pub fn reset(&mut self) {
+        for history in self.history.iter_mut() {
+            *history = [[0.0; 4]; 2];
+        }
+        self.sub_block_len = 0;
+        self.sub_block_sum = 0.0;
+        self.sub_blocks = [0.0; LUFS_SHORT_TERM_SUB_BLOCKS];
+        self.sub_block_idx = 0;
+        self.num_sub_blocks = 0;
+        for count in self.block_counts.iter_mut() {
+            *count = 0;
+        }
+        for sum in self.block_sums.iter_mut() {
+            *sum = 0.0;
+        }
+        self.momentary = 0.0;
+        self.short_term = 0.0;
+    }
+}
+
+/// The histogram bin counting gating blocks of the given loudness.
+fn histogram_bin(loudness: f32) -> usize {
+    let bin = ((loudness - LUFS_ABSOLUTE_GATE) / LUFS_HISTOGRAM_STEP).floor();
+    (bin.max(0.0) as usize).min(LUFS_HISTOGRAM_BINS - 1)
+}
+
+impl Default for LufsMeter {
+    fn default() -> Self {
+        LufsMeter::new()
+    }
+}
+
+impl<F> Node<F> for LufsMeter
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let channels = F::n_channels();
+        if self.sample_hz != sample_hz || self.history.len() != channels {
+            self.prepare_for(sample_hz, channels);
+        }
+        for frame in buffer.iter() {
+            let mut sum = 0.0;
+            for (channel, (s, history)) in frame.channels().zip(&mut self.history).enumerate() {
+                let weight = match (channels, channel) {
+                    (6, 3) => 0.0,
+                    (6, 4) | (6, 5) => 1.41,
+                    _ => 1.0,
+                };
+                let z = self.shelf.process(&mut history[0], util::to_f64(s));
+                let z = self.high_pass.process(&mut history[1], z);
+                sum += weight * z * z;
+            }
+            self.sub_block_sum += sum;
+            self.sub_block_len += 1;
+            if self.sub_block_len >= self.sub_block_frames {
+                self.complete_sub_block();
+            }
+        }
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 {
+            self.prepare_for(settings.sample_hz, F::n_channels());
+        }
+    }
+
+    fn reset(&mut self) {
+        LufsMeter::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "lufs_meter"
+    }
+}
+
+/// A pass-through **Node** that records a copy of the signal passing through it.
+///
+/// At most `max_frames` frames are held at once. Once full, the oldest frames are discarded to
+/// make room, so the recording always holds the most recent audio, even over long sessions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tap<F> {
+    recording: VecDeque<F>,
+    max_frames: usize,
+}
+
+impl<F> Tap<F>
+where
+    F: Frame,
+{
+    /// Construct a **Tap** that holds up to `max_frames` of the most recent frames.
+    pub fn new(max_frames: usize) -> Self {
+        Tap {
+            recording: VecDeque::new(),
+            max_frames,
+        }
+    }
+
+    /// The maximum number of frames held.
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Set the maximum number of frames held, discarding the oldest frames if there are now too
+    /// many.
+    pub fn set_max_frames(&mut self, max_frames: usize) {
+        self.max_frames = max_frames;
+        self.discard_excess();
+    }
+
+    /// The number of frames currently held.
+    pub fn len(&self) -> usize {
+        self.recording.len()
+    }
+
+    /// Whether or not no frames are currently held.
+    pub fn is_empty(&self) -> bool {
+        self.recording.is_empty()
+    }
+
+    /// Take the recorded frames, oldest first, leaving the **Tap** empty.
+    pub fn take_recording(&mut self) -> Vec<F> {
+        ::std::mem::take(&mut self.recording).into()
+    }
+
+    /// Discard the recorded frames.
+    pub fn reset(&mut self) {
+        self.recording.clear();
+    }
+
+    /// Discard the oldest frames until no more than `max_frames` are held.
+    fn discard_excess(&mut self) {
+        let excess = self.recording.len().saturating_sub(self.max_frames);
+        self.recording.drain(..excess);
+    }
+}
+
+impl<F> Node<F> for Tap<F>
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        // Only the most recent `max_frames` of the buffer can be kept.
+        let start = buffer.len().saturating_sub(self.max_frames);
+        self.recording.extend(buffer[start..].iter().cloned());
+        self.discard_excess();
+    }
+
+    fn reset(&mut self) {
+        Tap::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "tap"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::RngSource;
+    use crate::testing;
+
+    /// The index of the bin with the greatest magnitude.
+    fn peak_bin(magnitudes: &[f32]) -> usize {
+        (0..magnitudes.len())
+            .max_by(|&a, &b| magnitudes[a].partial_cmp(&magnitudes[b]).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn a_pure_tone_peaks_in_its_bin_and_passes_through_untouched() {
+        let mut analyzer = SpectrumAnalyzer::new(1_024, 256);
+        assert!(analyzer.magnitudes().iter().all(|&m| m == 0.0));
+        let hz = analyzer.bin_hz(64, 48_000.0);
+        assert_eq!(hz, 3_000.0);
+        let input: Vec<[f32; 2]> = testing::sine(4_096, hz, 1.0, 48_000.0);
+        let mut buffer = input.clone();
+        // Buffers of a size unrelated to either the FFT or hop size.
+        for chunk in buffer.chunks_mut(100) {
+            analyzer.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(buffer, input);
+        let magnitudes = analyzer.magnitudes();
+        assert_eq!(magnitudes.len(), 513);
+        assert_eq!(peak_bin(magnitudes), 64);
+        assert!((magnitudes[64] - 1.0).abs() < 1e-3, "{}", magnitudes[64]);
+        assert!(magnitudes[70] < 1e-3);
+    }
+
+    #[test]
+    fn the_spectrum_is_transformed_every_hop() {
+        let mut analyzer = SpectrumAnalyzer::new(256, 64);
+        let mut buffer: Vec<[f32; 1]> = testing::sine(63, 48_000.0 / 8.0, 0.5, 48_000.0);
+        analyzer.audio_requested(&mut buffer, 48_000.0);
+        assert!(analyzer.magnitudes().iter().all(|&m| m == 0.0));
+        analyzer.audio_requested(&mut [[0.0f32]], 48_000.0);
+        // A partial window, but the peak is already in place.
+        assert_eq!(peak_bin(analyzer.magnitudes()), 32);
+        analyzer.set_hop_size(1_000);
+        assert_eq!(analyzer.hop_size(), 256);
+        analyzer.reset();
+        assert!(analyzer.magnitudes().iter().all(|&m| m == 0.0));
+    }
+
+    #[test]
+    fn tapping_a_known_source_yields_its_samples() {
+        let input: Vec<[f32; 2]> = testing::sine(300, 1_000.0, 0.5, 48_000.0);
+        let mut tap = Tap::new(1_000);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(64) {
+            tap.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(output, input);
+        assert_eq!(tap.len(), 300);
+        assert_eq!(tap.take_recording(), input);
+        assert!(tap.is_empty());
+    }
+
+    #[test]
+    fn a_tap_holds_only_the_most_recent_max_frames() {
+        let input: Vec<[i16; 1]> = (0..100).map(|i| [i]).collect();
+        let mut tap = Tap::new(30);
+        let mut buffer = input.clone();
+        tap.audio_requested(&mut buffer[..20], 44_100.0);
+        tap.audio_requested(&mut buffer[20..], 44_100.0);
+        assert_eq!(tap.len(), 30);
+        tap.set_max_frames(10);
+        assert_eq!(tap.take_recording(), &input[90..]);
+        // A single buffer longer than the cap.
+        tap.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(tap.take_recording(), &input[90..]);
+    }
+
+    #[test]
+    fn a_clipped_buffer_latches_the_overload_flag() {
+        let mut meter = ClipMeter::new(3);
+        // Runs of two clipped samples are not an overload.
+        let mut buffer = [
+            [0.5f32, 0.0],
+            [1.0, 0.0],
+            [-1.2, 0.0],
+            [0.9, 0.0],
+            [1.0, 0.0],
+        ];
+        meter.audio_requested(&mut buffer, 44_100.0);
+        assert!(!meter.overloaded());
+        assert_eq!(meter.clipped_samples(), &[3, 0]);
+        // The run carries into the next buffer, overloading the left channel.
+        let mut next = [[1.5f32, 0.0], [-1.0, 0.0], [0.0, 0.0]];
+        meter.audio_requested(&mut next, 44_100.0);
+        assert!(meter.overloaded());
+        assert_eq!(meter.overloaded_channels(), &[true, false]);
+        // The meter passes audio through untouched.
+        assert_eq!(next, [[1.5, 0.0], [-1.0, 0.0], [0.0, 0.0]]);
+
+        // The flag stays latched through clean audio until cleared.
+        let mut clean = [[0.5f32, 0.5]; 64];
+        meter.audio_requested(&mut clean, 44_100.0);
+        assert!(meter.overloaded());
+        meter.reset_clip();
+        assert!(!meter.overloaded());
+        assert_eq!(meter.clipped_samples(), &[0, 0]);
+    }
+
+    #[test]
+    fn integer_formats_clip_at_their_limits() {
+        let mut meter = ClipMeter::new(1);
+        let mut buffer = [[i16::MAX - 1], [i16::MIN + 1]];
+        meter.audio_requested(&mut buffer, 44_100.0);
+        assert!(!meter.overloaded());
+        let mut buffer = [[0i16], [i16::MIN]];
+        meter.audio_requested(&mut buffer, 44_100.0);
+        assert!(meter.overloaded());
+        meter.reset_clip();
+        let mut buffer = [[i16::MAX]];
+        meter.audio_requested(&mut buffer, 44_100.0);
+        assert!(meter.overloaded());
+    }
+
+    /// Meter `secs` of a stereo 1 kHz tone at the given peak level in dBFS, returning the tone.
+    fn meter_tone(meter: &mut LufsMeter, level_db: f32, secs: f64) -> Vec<[f32; 2]> {
+        let amp = gain::db_to_amp(level_db) as f64;
+        let input: Vec<[f32; 2]> =
+            testing::sine((secs * 48_000.0) as usize, 1_000.0, amp, 48_000.0);
+        let mut buffer = input.clone();
+        for chunk in buffer.chunks_mut(512) {
+            meter.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(buffer, input);
+        input
+    }
+
+    #[test]
+    fn a_stereo_tone_at_minus_23_dbfs_measures_minus_23_lufs() {
+        // EBU Tech 3341 test case 1.
+        let mut meter = LufsMeter::new();
+        assert_eq!(meter.integrated_lufs(), f32::NEG_INFINITY);
+        meter_tone(&mut meter, -23.0, 20.0);
+        for &lufs in &[
+            meter.momentary_lufs(),
+            meter.short_term_lufs(),
+            meter.integrated_lufs(),
+        ] {
+            assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+        }
+        Node::<[f32; 2]>::reset(&mut meter);
+        assert_eq!(meter.momentary_lufs(), f32::NEG_INFINITY);
+        assert_eq!(meter.integrated_lufs(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn quiet_passages_are_gated_from_the_integrated_loudness() {
+        // Similar to EBU Tech 3341 test case 3, with silence beneath the absolute gate.
+        let mut meter = LufsMeter::new();
+        meter_tone(&mut meter, -36.0, 5.0);
+        meter_tone(&mut meter, -23.0, 20.0);
+        meter_tone(&mut meter, -36.0, 5.0);
+        meter_tone(&mut meter, -200.0, 5.0);
+        let integrated = meter.integrated_lufs();
+        assert!((integrated + 23.0).abs() < 0.1, "{}", integrated);
+        // The momentary loudness falls with the silence.
+        assert_eq!(meter.momentary_lufs(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn an_inter_sample_overshoot_reads_above_the_sample_peak() {
+        // A tone at a quarter of the sample rate, sampled 45 degrees from its crests, never samples
+        // beyond 1/sqrt(2) of its true amplitude.
+        let input: Vec<[f32; 2]> = (0..4_800)
+            .map(|i| {
+                let s = (::std::f64::consts::FRAC_PI_2 * i as f64 + ::std::f64::consts::FRAC_PI_4)
+                    .sin();
+                [s as f32 * 0.9, 0.0]
+            })
+            .collect();
+        let sample_peak = input.iter().map(|frame| frame[0].abs()).fold(0.0, f32::max);
+        assert!((sample_peak - 0.9 * FRAC_1_SQRT_2 as f32).abs() < 1e-6);
+
+        let mut meter = TruePeakMeter::new();
+        let mut buffer = input.clone();
+        for chunk in buffer.chunks_mut(256) {
+            meter.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(buffer, input);
+        assert!(
+            (meter.true_peaks()[0] - 0.9).abs() < 0.02,
+            "{:?}",
+            meter.true_peaks()
+        );
+        assert_eq!(meter.true_peaks()[1], 0.0);
+        assert!(meter.true_peak_db() > gain::amp_to_db(sample_peak) + 2.8);
+
+        meter.reset();
+        assert_eq!(meter.true_peak_db(), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn a_slow_tone_has_a_true_peak_at_its_sample_peak() {
+        let input: Vec<[f32; 1]> = testing::sine(4_800, 100.0, 0.5, 48_000.0);
+        let mut meter = TruePeakMeter::new();
+        let mut buffer = input.clone();
+        meter.audio_requested(&mut buffer, 48_000.0);
+        assert!(
+            (meter.true_peaks()[0] - 0.5).abs() < 0.005,
+            "{:?}",
+            meter.true_peaks()
+        );
+    }
+
+    /// The correlation measured over a 50 ms window after metering the given stereo buffer.
+    fn correlation_of(input: &[[f32; 2]]) -> f32 {
+        let mut meter = Correlation::new(50.0);
+        let mut buffer = input.to_vec();
+        for chunk in buffer.chunks_mut(100) {
+            meter.audio_requested(chunk, 48_000.0);
+        }
+        assert_eq!(buffer, input);
+        meter.correlation()
+    }
+
+    #[test]
+    fn correlation_distinguishes_mono_inverted_and_unrelated_channels() {
+        let tone: Vec<[f32; 1]> = testing::sine(9_600, 440.0, 0.5, 48_000.0);
+        let mono: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], s[0]]).collect();
+        let inverted: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], -s[0]]).collect();
+        let mut rng = RngSource::new(9);
+        let unrelated: Vec<[f32; 2]> = (0..9_600)
+            .map(|_| [rng.next_bipolar() as f32, rng.next_bipolar() as f32])
+            .collect();
+        assert!((correlation_of(&mono) - 1.0).abs() < 1e-4);
+        assert!((correlation_of(&inverted) + 1.0).abs() < 1e-4);
+        assert!(correlation_of(&unrelated).abs() < 0.05);
+        // A silent channel has no correlation.
+        let one_sided: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], 0.0]).collect();
+        assert_eq!(correlation_of(&one_sided), 0.0);
+    }
+
+    /// The goniometer points of the given stereo buffer, checking that it passes through untouched.
+    fn goniometer_points(goniometer: &mut Goniometer, input: &[[f32; 2]]) -> Vec<(f32, f32)> {
+        let mut buffer = input.to_vec();
+        goniometer.audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(buffer, input);
+        goniometer.points().to_vec()
+    }
+
+    #[test]
+    fn mono_lies_on_the_vertical_axis_and_hard_left_on_a_diagonal() {
+        let tone: Vec<[f32; 1]> = testing::sine(480, 440.0, 0.5, 48_000.0);
+        let mut goniometer = Goniometer::new(1_024);
+
+        let mono: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], s[0]]).collect();
+        let points = goniometer_points(&mut goniometer, &mono);
+        assert_eq!(points.len(), 480);
+        for (&(x, y), s) in points.iter().zip(&tone) {
+            assert_eq!(x, 0.0);
+            assert!((y - s[0] * 2f32.sqrt()).abs() < 1e-6);
+        }
+
+        let left: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], 0.0]).collect();
+        let points = goniometer_points(&mut goniometer, &left);
+        assert!(points.iter().any(|&(_, y)| y > 0.3));
+        assert!(points.iter().all(|&(x, y)| x == -y));
+
+        let inverted: Vec<[f32; 2]> = tone.iter().map(|s| [s[0], -s[0]]).collect();
+        let points = goniometer_points(&mut goniometer, &inverted);
+        assert!(points.iter().all(|&(_, y)| y == 0.0));
+    }
+
+    #[test]
+    fn goniometer_points_are_decimated_to_the_maximum() {
+        let mut goniometer = Goniometer::new(100);
+        let input: Vec<[f32; 2]> = (0..1_000).map(|i| [i as f32 / 1_000.0; 2]).collect();
+        let points = goniometer_points(&mut goniometer, &input);
+        assert_eq!(points.len(), 100);
+        // Every tenth frame is kept.
+        assert!((points[1].1 - input[10][0] * 2f32.sqrt()).abs() < 1e-6);
+
+        let mono: Vec<[f32; 1]> = vec![[0.5]; 8];
+        Node::<[f32; 1]>::audio_requested(&mut goniometer, &mut mono.clone(), 48_000.0);
+        assert!(goniometer.points().is_empty());
+    }
+
+    /// The displayed peak of a PPM-ballistics peak meter after each 1 ms buffer of the given level.
+    fn ppm_display(meter: &mut PeakMeter, level: f32, buffers: usize) -> Vec<f32> {
+        let mut displayed = Vec::with_capacity(buffers);
+        for _ in 0..buffers {
+            let mut buffer = [[level]; 48];
+            meter.audio_requested(&mut buffer, 48_000.0);
+            assert_eq!(buffer, [[level]; 48]);
+            assert_eq!(meter.peak(), &[level]);
+            displayed.push(meter.displayed_peak()[0]);
+        }
+        displayed
+    }
+
+    #[test]
+    fn a_ppm_rises_within_2_db_over_its_rise_time_and_falls_back_slowly() {
+        let mut meter = PeakMeter::new();
+        meter.set_ballistics(Ballistics::Ppm);
+        let rise = ppm_display(&mut meter, 1.0, 10);
+        let within_2_db = gain::db_to_amp(-2.0);
+        assert!(rise[3] < within_2_db, "{}", rise[3]);
+        assert!((rise[4] - within_2_db).abs() < 1e-4, "{}", rise[4]);
+        assert!(rise.windows(2).all(|w| w[1] > w[0]));
+
+        // Silence is shown falling by 20 dB over 1.7 s, while the measured peak drops at once.
+        let start = rise[9];
+        let fall = ppm_display(&mut meter, 0.0, 1_700);
+        let fallen = gain::amp_to_db(fall[1_699] / start);
+        assert!((fallen + 20.0).abs() < 0.01, "{}", fallen);
+    }
+
+    #[test]
+    fn a_vu_rises_and_falls_to_99_percent_over_its_rise_time() {
+        let mut meter = RmsMeter::new(1.0);
+        meter.set_ballistics(Ballistics::Vu);
+        let mut render = |level: f32| {
+            for _ in 0..300 {
+                let mut buffer = [[level]; 48];
+                meter.audio_requested(&mut buffer, 48_000.0);
+            }
+            meter.displayed_rms()[0]
+        };
+        assert!((render(1.0) - 0.99).abs() < 1e-4);
+        assert!((render(0.0) - 0.0099).abs() < 1e-4);
+
+        // Instant ballistics show the measured level.
+        meter.set_ballistics(Ballistics::Instant);
+        let mut buffer = [[0.5f32]; 48];
+        meter.audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(meter.displayed_rms(), meter.rms());
+    }
+}
@@ -0,0 +1,779 @@
+//! Driving **Node**s from MIDI note events.
+
+use crate::node::{Node, Settings};
+use crate::noise;
+use crate::oscillator::{BlOsc, Oscillator, Wavetable};
+use crate::pan::PanLaw;
+use crate::util;
+use crate::{Panning, Volume};
+use sample::{self, Frame, Sample};
+
+/// The frequency in hertz of the given MIDI note number, where note `69` is A4 at 440 Hz.
+pub fn note_to_hz(note: u8) -> f64 {
+    440.0 * 2.0f64.powf((note as f64 - 69.0) / 12.0)
+}
+
+/// A MIDI note message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MidiMessage {
+    /// A key was pressed with the given velocity (0 ... 127).
+    ///
+    /// As per the MIDI specification, a velocity of `0` is treated as a `NoteOff`.
+    NoteOn {
+        /// The MIDI note number.
+        note: u8,
+        /// The velocity with which the key was pressed.
+        velocity: u8,
+    },
+    /// A key was released.
+    NoteOff {
+        /// The MIDI note number.
+        note: u8,
+    },
+}
+
+/// A **MidiMessage** along with the frame at which it should be applied.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MidiEvent {
+    /// The offset in frames from the start of the next buffer requested from the **MidiNode**.
+    ///
+    /// Events beyond the end of the next buffer are carried over to the following buffers.
+    pub frame: usize,
+    /// The message to apply.
+    pub message: MidiMessage,
+}
+
+/// Types whose pitch may be driven by a **MidiNode**.
+pub trait MidiVoice {
+    /// Set the frequency at which the voice sounds in hertz.
+    fn set_frequency(&mut self, hz: f64);
+}
+
+impl MidiVoice for Oscillator {
+    fn set_frequency(&mut self, hz: f64) {
+        Oscillator::set_frequency(self, hz);
+    }
+}
+
+impl MidiVoice for BlOsc {
+    fn set_frequency(&mut self, hz: f64) {
+        BlOsc::set_frequency(self, hz);
+    }
+}
+
+impl MidiVoice for Wavetable {
+    fn set_frequency(&mut self, hz: f64) {
+        Wavetable::set_frequency(self, hz);
+    }
+}
+
+/// A monophonic adapter that drives a **MidiVoice** from queued MIDI note events.
+///
+/// Each event is applied at its frame offset within the buffer, so pitch changes and note
+/// on/off transitions are sample-accurate. The most recent note-on takes priority, and only the
+/// release of the sounding note silences the voice.
+///
+/// While a note is held, the voice's output is scaled by the note's velocity (`velocity / 127`)
+/// on top of its own `vol`. Otherwise, the output is silent.
+#[derive(Clone, Debug)]
+pub struct MidiNode<N> {
+    voice: N,
+    /// Events waiting to be applied, sorted by frame.
+    events: Vec<MidiEvent>,
+    /// The note currently sounding, if any.
+    note: Option<u8>,
+    /// The amplitude derived from the velocity of the sounding note.
+    velocity_vol: Volume,
+}
+
+impl<N> MidiNode<N>
+where
+    N: MidiVoice,
+{
+    /// Construct a **MidiNode** that drives the given voice. The voice is silent until a note-on
+    /// is received.
+    pub fn new(voice: N) -> Self {
+        MidiNode {
+            voice,
+            events: Vec::new(),
+            note: None,
+            velocity_vol: 0.0,
+        }
+    }
+
+    /// Queue an event to be applied during the next call to `audio_requested`.
+    ///
+    /// Events with equal frame offsets are applied in the order in which they were queued.
+    pub fn queue(&mut self, event: MidiEvent) {
+        let idx = self
+            .events
+            .iter()
+            .position(|e| e.frame > event.frame)
+            .unwrap_or(self.events.len());
+        self.events.insert(idx, event);
+    }
+
+    /// The note currently sounding, if any.
+    pub fn note(&self) -> Option<u8> {
+        self.note
+    }
+
+    /// The amplitude derived from the velocity of the sounding note, or `0.0` if no note is
+    /// sounding.
+    pub fn velocity_vol(&self) -> Volume {
+        self.velocity_vol
+    }
+
+    /// A reference to the driven voice.
+    pub fn voice(&self) -> &N {
+        &self.voice
+    }
+
+    /// A mutable reference to the driven voice.
+    pub fn voice_mut(&mut self) -> &mut N {
+        &mut self.voice
+    }
+
+    /// Apply the given message immediately.
+    fn apply(&mut self, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { note, velocity } if velocity > 0 => {
+                self.voice.set_frequency(note_to_hz(note));
+                self.note = Some(note);
+                self.velocity_vol = velocity.min(127) as Volume / 127.0;
+            }
+            MidiMessage::NoteOn { note, .. } | MidiMessage::NoteOff { note } => {
+                if self.note == Some(note) {
+                    self.note = None;
+                    self.velocity_vol = 0.0;
+                }
+            }
+        }
+    }
+}
+
+impl<F, N> Node<F> for MidiNode<N>
+where
+    F: Frame,
+    N: Node<F> + MidiVoice,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let len = buffer.len();
+
+        // Render each segment between events using the state of the voice at its start.
+        let mut start = 0;
+        let mut num_applied = 0;
+        while start < len {
+            while num_applied < self.events.len() && self.events[num_applied].frame <= start {
+                let message = self.events[num_applied].message;
+                self.apply(message);
+                num_applied += 1;
+            }
+            let end = self
+                .events
+                .get(num_applied)
+                .map(|e| e.frame.min(len))
+                .unwrap_or(len);
+            let segment = &mut buffer[start..end];
+            self.voice.audio_requested(segment, sample_hz);
+            let gain = self.velocity_vol as f64;
+            if gain != 1.0 {
+                sample::slice::map_in_place(segment, |frame| {
+                    frame.map(|s| util::from_f64(util::to_f64(s) * gain))
+                });
+            }
+            start = end;
+        }
+
+        // Carry the remaining events over to the next buffer.
+        self.events.drain(..num_applied);
+        for event in self.events.iter_mut() {
+            event.frame -= len;
+        }
+    }
+
+    fn vol(&self) -> Volume {
+        self.voice.vol()
+    }
+
+    fn pan(&self) -> Panning {
+        self.voice.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.voice.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.voice.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.voice.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.voice.channel_gains()
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.voice.smoothing_samples()
+    }
+
+    fn is_source(&self) -> bool {
+        self.voice.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.voice.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.voice.input_channels()
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.voice.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.voice.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.voice.set_param(name, value)
+    }
+
+    /// Applies the message immediately, ahead of any queued events.
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.apply(message);
+        true
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.voice.set_seed(seed);
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.voice.on_buffer_processed(output, sample_hz);
+    }
+
+    /// Prepares the voice, silences the sounding note and discards any queued events.
+    fn prepare(&mut self, settings: Settings) {
+        self.events.clear();
+        self.note = None;
+        self.velocity_vol = 0.0;
+        self.voice.prepare(settings);
+    }
+
+    /// Resets the voice, silences the sounding note and discards any queued events.
+    fn reset(&mut self) {
+        self.events.clear();
+        self.note = None;
+        self.velocity_vol = 0.0;
+        self.voice.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.voice.name()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.voice.latency_samples()
+    }
+}
+
+/// A polyphonic **Node** that allocates MIDI notes between a number of copies of a voice and sums
+/// their outputs, e.g. to play chords.
+///
+/// The voice may be any **Node** that responds to `Node::handle_midi`, such as a **MidiNode**, an
+/// `envelope::Adsr` or a `Graph` containing them. Each note-on is sent to a voice of its own: the
+/// voice released longest ago if any are free, or otherwise the voice whose note began longest
+/// ago, which first receives a note-off for the note it is stolen from. Each note-off is sent to
+/// the voice holding that note, triggering its release.
+///
+/// Every voice renders every buffer, so that released voices may complete their release, and
+/// each receives a copy of the input. As with a `Graph`'s nodes, the voices' outputs are summed
+/// via `Node::combine`. Messages apply as soon as they are handled, so wrap the **PolyVoices** in
+/// an `event::Scheduled` to apply notes at exact sample times.
+#[derive(Clone, Debug)]
+pub struct PolyVoices<F, N> {
+    /// The voice from which any voices added by `set_voice_count` are cloned.
+    prototype: N,
+    voices: Vec<N>,
+    /// The note held by each voice, if any.
+    notes: Vec<Option<u8>>,
+    /// The order in which each voice last received a note-on or note-off, where `0` is never.
+    stamps: Vec<u64>,
+    /// The stamp of the most recent note-on or note-off.
+    stamp: u64,
+    /// The input to the current buffer.
+    input: Vec<F>,
+    /// The rendered output of each voice in turn.
+    scratch: Vec<F>,
+    /// The volume of each channel of the voice being summed.
+    vols: Vec<Volume>,
+    /// The seed given to `set_seed`, if any, from which any voices added later derive theirs.
+    seed: Option<u64>,
+}
+
+impl<F, N> PolyVoices<F, N>
+where
+    F: Frame,
+    N: Node<F> + Clone,
+{
+    /// Construct a **PolyVoices** from the given number of copies of `voice`.
+    pub fn new(voice: N, voice_count: usize) -> Self {
+        let mut poly = PolyVoices {
+            prototype: voice,
+            voices: Vec::new(),
+            notes: Vec::new(),
+            stamps: Vec::new(),
+            stamp: 0,
+            input: Vec::new(),
+            scratch: Vec::new(),
+            vols: Vec::new(),
+            seed: None,
+        };
+        poly.set_voice_count(voice_count);
+        poly
+    }
+
+    /// The number of voices.
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Set the number of voices.
+    ///
+    /// Voices are added as copies of the voice with which the **PolyVoices** was constructed, or
+    /// removed from the end along with any notes they hold. Following a call to `set_seed`, each
+    /// added voice is seeded as if it had been present for the call.
+    pub fn set_voice_count(&mut self, voice_count: usize) {
+        let prototype = &self.prototype;
+        let added = self.voices.len()..voice_count;
+        self.voices.resize_with(voice_count, || prototype.clone());
+        if let Some(seed) = self.seed {
+            for i in added {
+                self.voices[i].set_seed(noise::derive_seed(seed, i as u64));
+            }
+        }
+        self.notes.resize(voice_count, None);
+        self.stamps.resize(voice_count, 0);
+    }
+
+    /// The voices, in the order in which they are allocated when none has been used.
+    pub fn voices(&self) -> &[N] {
+        &self.voices
+    }
+
+    /// Mutable access to the voices.
+    pub fn voices_mut(&mut self) -> &mut [N] {
+        &mut self.voices
+    }
+
+    /// The note held by the voice at the given index, if any.
+    ///
+    /// **Panics** if there is no voice at the given index.
+    pub fn voice_note(&self, idx: usize) -> Option<u8> {
+        self.notes[idx]
+    }
+
+    /// The number of voices holding a note.
+    pub fn active_voices(&self) -> usize {
+        self.notes.iter().filter(|note| note.is_some()).count()
+    }
+
+    /// Release every held note.
+    pub fn release_all(&mut self) {
+        for idx in 0..self.voices.len() {
+            if let Some(note) = self.notes[idx] {
+                self.release(idx, note);
+            }
+        }
+    }
+
+    /// Send a note-off for the given note to the voice at the given index and free the voice.
+    fn release(&mut self, idx: usize, note: u8) {
+        self.stamp += 1;
+        self.voices[idx].handle_midi(MidiMessage::NoteOff { note });
+        self.notes[idx] = None;
+        self.stamps[idx] = self.stamp;
+    }
+
+    /// Free every voice without releasing it, forgetting the order in which they were used.
+    fn clear_notes(&mut self) {
+        for (note, stamp) in self.notes.iter_mut().zip(self.stamps.iter_mut()) {
+            *note = None;
+            *stamp = 0;
+        }
+        self.stamp = 0;
+    }
+
+    /// The index of the voice to which the next note-on should be sent, if there are any voices.
+    fn allocate(&self) -> Option<usize> {
+        let oldest = |free: bool| {
+            (0..self.voices.len())
+                .filter(|&idx| self.notes[idx].is_none() == free)
+                .min_by_key(|&idx| self.stamps[idx])
+        };
+        oldest(true).or_else(|| oldest(false))
+    }
+}
+
+impl<F, N> Node<F> for PolyVoices<F, N>
+where
+    F: Frame,
+    N: Node<F> + Clone,
+{
+    /// Renders every voice and sums their outputs, each scaled by the volume of each channel given
+    /// by the voice's `vols_per_channel`, as the `Graph` would scale it.
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.input.clear();
+        self.input.extend_from_slice(buffer);
+        self.vols.resize(F::n_channels(), 1.0);
+        sample::slice::equilibrium(buffer);
+        for voice in self.voices.iter_mut() {
+            self.scratch.clear();
+            self.scratch.extend_from_slice(&self.input);
+            voice.audio_requested(&mut self.scratch, sample_hz);
+            voice.vols_per_channel(&mut self.vols);
+            if self.vols.iter().any(|&vol| vol != 1.0) {
+                let vols = &self.vols;
+                sample::slice::map_in_place(&mut self.scratch, |frame| {
+                    let mut vols = vols.iter();
+                    frame.map(|s| {
+                        let vol = vols.next().cloned().unwrap_or(1.0);
+                        s.mul_amp(<F::Sample as Sample>::Float::from_sample(vol))
+                    })
+                });
+            }
+            sample::slice::zip_map_in_place(buffer, &self.scratch, |out_frame, voice_frame| {
+                out_frame.zip_map(voice_frame, |acc, s| voice.combine(acc, s))
+            });
+        }
+    }
+
+    fn is_source(&self) -> bool {
+        self.prototype.is_source()
+    }
+
+    /// The greatest latency of any voice, as the voices are summed into a single output.
+    fn latency_samples(&self) -> usize {
+        self.voices
+            .iter()
+            .map(|voice| voice.latency_samples())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.prototype.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.prototype.get_param(name)
+    }
+
+    /// Sets the parameter of every voice, including any added later by `set_voice_count`.
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        for voice in self.voices.iter_mut() {
+            voice.set_param(name, value);
+        }
+        self.prototype.set_param(name, value)
+    }
+
+    /// Allocates a voice to each note-on and releases the voice holding the note of each
+    /// note-off.
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        match message {
+            MidiMessage::NoteOn { note, velocity } if velocity > 0 => {
+                let held = self.notes.iter().position(|&held| held == Some(note));
+                let idx = match held.or_else(|| self.allocate()) {
+                    Some(idx) => idx,
+                    None => return true,
+                };
+                if let Some(stolen) = self.notes[idx] {
+                    self.release(idx, stolen);
+                }
+                self.stamp += 1;
+                self.voices[idx].handle_midi(message);
+                self.notes[idx] = Some(note);
+                self.stamps[idx] = self.stamp;
+            }
+            MidiMessage::NoteOn { note, .. } | MidiMessage::NoteOff { note } => {
+                if let Some(idx) = self.notes.iter().position(|&held| held == Some(note)) {
+                    self.release(idx, note);
+                }
+            }
+        }
+        true
+    }
+
+    /// Prepares every voice, frees every voice without releasing it and reserves the working
+    /// buffers.
+    fn prepare(&mut self, settings: Settings) {
+        for voice in self.voices.iter_mut() {
+            voice.prepare(settings);
+        }
+        self.prototype.prepare(settings);
+        self.input.reserve(settings.max_frames);
+        self.scratch.reserve(settings.max_frames);
+        self.vols.reserve(F::n_channels());
+        self.clear_notes();
+    }
+
+    /// Seeds each voice with a seed derived from the given seed and the index of the voice,
+    /// including any voices added later by `set_voice_count`.
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        for (i, voice) in self.voices.iter_mut().enumerate() {
+            voice.set_seed(noise::derive_seed(seed, i as u64));
+        }
+    }
+
+    /// Resets every voice and frees every voice without releasing it.
+    fn reset(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.reset();
+        }
+        self.clear_notes();
+    }
+
+    fn type_tag(&self) -> &str {
+        "poly_voices"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillator::Oscillator;
+
+    /// A voice rendering a constant while it holds a note.
+    #[derive(Clone)]
+    struct Voice {
+        note: Option<u8>,
+        vol: Volume,
+        pan: Panning,
+        seed: u64,
+        latency: usize,
+    }
+
+    impl Voice {
+        fn new() -> Self {
+            Voice {
+                note: None,
+                vol: 1.0,
+                pan: 0.0,
+                seed: 0,
+                latency: 0,
+            }
+        }
+    }
+
+    impl<F> Node<F> for Voice
+    where
+        F: Frame,
+    {
+        fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+            let value = if self.note.is_some() { 0.25 } else { 0.0 };
+            sample::slice::map_in_place(buffer, |_| F::from_fn(|_| util::from_f64(value)));
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+
+        fn vol(&self) -> Volume {
+            self.vol
+        }
+
+        fn pan(&self) -> Panning {
+            self.pan
+        }
+
+        fn handle_midi(&mut self, message: MidiMessage) -> bool {
+            self.note = match message {
+                MidiMessage::NoteOn { note, velocity } if velocity > 0 => Some(note),
+                _ => None,
+            };
+            true
+        }
+
+        fn set_seed(&mut self, seed: u64) {
+            self.seed = seed;
+        }
+
+        fn latency_samples(&self) -> usize {
+            self.latency
+        }
+    }
+
+    impl MidiVoice for Voice {
+        fn set_frequency(&mut self, _hz: f64) {}
+    }
+
+    fn note_on(note: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            note,
+            velocity: 100,
+        }
+    }
+
+    fn render(poly: &mut PolyVoices<[f32; 2], Voice>) -> [f32; 2] {
+        let mut buffer = [[0.0; 2]; 8];
+        poly.audio_requested(&mut buffer, 44_100.0);
+        buffer[7]
+    }
+
+    #[test]
+    fn a_chord_activates_a_voice_per_note() {
+        let mut poly = PolyVoices::new(Voice::new(), 8);
+        for &note in &[60, 64, 67] {
+            Node::<[f32; 2]>::handle_midi(&mut poly, note_on(note));
+        }
+        assert_eq!(poly.active_voices(), 3);
+        assert_eq!(render(&mut poly), [0.75; 2]);
+
+        Node::<[f32; 2]>::handle_midi(&mut poly, MidiMessage::NoteOff { note: 64 });
+        assert_eq!(poly.active_voices(), 2);
+        assert_eq!(render(&mut poly), [0.5; 2]);
+    }
+
+    #[test]
+    fn each_voice_is_scaled_by_its_volume_per_channel() {
+        let voice = Voice {
+            vol: 0.5,
+            ..Voice::new()
+        };
+        let mut poly = PolyVoices::new(voice, 4);
+        Node::<[f32; 2]>::handle_midi(&mut poly, note_on(60));
+        Node::<[f32; 2]>::handle_midi(&mut poly, note_on(62));
+        assert_eq!(render(&mut poly), [0.25; 2]);
+
+        // A voice panned hard left is silent on the right.
+        poly.voices_mut()[1].pan = -1.0;
+        let [left, right] = Node::<[f32; 2]>::vol_per_channel(&poly.voices()[1]);
+        assert_eq!(right, 0.0);
+        assert_eq!(render(&mut poly), [0.125 + 0.25 * left, 0.125]);
+    }
+
+    #[test]
+    fn voices_added_after_seeding_derive_their_own_seeds() {
+        let mut poly = PolyVoices::new(Voice::new(), 2);
+        Node::<[f32; 2]>::set_seed(&mut poly, 7);
+        poly.set_voice_count(4);
+        for (i, voice) in poly.voices().iter().enumerate() {
+            assert_eq!(voice.seed, noise::derive_seed(7, i as u64));
+        }
+        // The same seeds as seeding all four voices at once.
+        let mut other = PolyVoices::new(Voice::new(), 4);
+        Node::<[f32; 2]>::set_seed(&mut other, 7);
+        let seeds = |poly: &PolyVoices<[f32; 2], Voice>| -> Vec<u64> {
+            poly.voices().iter().map(|voice| voice.seed).collect()
+        };
+        assert_eq!(seeds(&poly), seeds(&other));
+    }
+
+    fn event(frame: usize, message: MidiMessage) -> MidiEvent {
+        MidiEvent { frame, message }
+    }
+
+    #[test]
+    fn note_69_is_440_hz() {
+        assert_eq!(note_to_hz(69), 440.0);
+        assert!((note_to_hz(81) - 880.0).abs() < 1e-9);
+        assert!((note_to_hz(60) - 261.6256).abs() < 1e-4);
+
+        let mut node = MidiNode::new(Oscillator::new(100.0));
+        node.queue(event(0, note_on(69)));
+        let mut buffer = [[0.0f32; 1]; 8];
+        node.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(node.voice().frequency(), 440.0);
+        assert_eq!(node.note(), Some(69));
+    }
+
+    #[test]
+    fn events_apply_at_their_frame_offsets() {
+        let mut node = MidiNode::new(Oscillator::new(100.0));
+        node.queue(event(20, MidiMessage::NoteOff { note: 69 }));
+        node.queue(event(10, note_on(69)));
+        let mut buffer = [[0.0f32; 1]; 32];
+        node.audio_requested(&mut buffer, 44_100.0);
+        // The voice keeps running while silent, so sounds from the note-on's frame.
+        assert!(buffer[..10].iter().all(|frame| frame[0] == 0.0));
+        assert!(buffer[10..20].iter().all(|frame| frame[0] > 0.0));
+        assert!(buffer[20..].iter().all(|frame| frame[0] == 0.0));
+        assert_eq!(node.note(), None);
+
+        // Events beyond the buffer are carried over to the next.
+        node.queue(event(40, note_on(60)));
+        node.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(node.note(), None);
+        node.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(node.note(), Some(60));
+        assert!(buffer[..8].iter().all(|frame| frame[0] == 0.0));
+        assert!(buffer[8..].iter().all(|frame| frame[0] != 0.0));
+    }
+
+    #[test]
+    fn velocity_scales_the_voice() {
+        let peak = |velocity: u8| {
+            let mut node = MidiNode::new(Oscillator::new(100.0));
+            node.queue(event(0, MidiMessage::NoteOn { note: 69, velocity }));
+            let mut buffer = [[0.0f32; 1]; 200];
+            node.audio_requested(&mut buffer, 44_100.0);
+            buffer
+                .iter()
+                .map(|frame| frame[0].abs())
+                .fold(0.0, f32::max)
+        };
+        assert!((peak(127) - 1.0).abs() < 1e-3);
+        assert!((peak(64) - 64.0 / 127.0).abs() < 1e-3);
+        // A note-on with no velocity is a note-off.
+        assert_eq!(peak(0), 0.0);
+    }
+
+    #[test]
+    fn a_note_beyond_the_voice_count_steals_the_oldest_voice() {
+        let mut poly = PolyVoices::new(Voice::new(), 2);
+        for &note in &[60, 64, 67] {
+            Node::<[f32; 2]>::handle_midi(&mut poly, note_on(note));
+        }
+        assert_eq!(poly.active_voices(), 2);
+        assert_eq!(
+            (poly.voice_note(0), poly.voice_note(1)),
+            (Some(67), Some(64))
+        );
+        assert_eq!(poly.voices()[0].note, Some(67));
+
+        // The stolen note is no longer held, so its note-off is ignored.
+        Node::<[f32; 2]>::handle_midi(&mut poly, MidiMessage::NoteOff { note: 60 });
+        assert_eq!(poly.active_voices(), 2);
+
+        // An added voice is free, so it takes the next note rather than a held one being stolen.
+        poly.set_voice_count(3);
+        Node::<[f32; 2]>::handle_midi(&mut poly, note_on(72));
+        assert_eq!(poly.voice_note(2), Some(72));
+        assert_eq!(poly.active_voices(), 3);
+        assert_eq!(render(&mut poly), [0.75; 2]);
+    }
+
+    #[test]
+    fn the_latency_of_the_voices_is_reported() {
+        let voice = Voice {
+            latency: 16,
+            ..Voice::new()
+        };
+        let node = MidiNode::new(voice.clone());
+        assert_eq!(Node::<[f32; 2]>::latency_samples(&node), 16);
+
+        // The voices of a PolyVoices are summed, so it reports the greatest latency among them.
+        let mut poly = PolyVoices::new(voice, 4);
+        poly.voices_mut()[2].latency = 64;
+        assert_eq!(Node::<[f32; 2]>::latency_samples(&poly), 64);
+        poly.set_voice_count(0);
+        assert_eq!(Node::<[f32; 2]>::latency_samples(&poly), 0);
+    }
+}
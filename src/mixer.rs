@@ -0,0 +1,1017 @@
+//! Nodes for mixing inputs and channels together.
+
+use crate::buffer;
+use crate::delay::CompensationDelay;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::util;
+use crate::{Panning, Volume};
+use sample::{self, Frame};
+
+/// The parameters of a **Mixer**.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MixerParams {
+    /// The gain applied to each input. Inputs beyond the end are left at unity gain.
+    pub gains: Vec<Volume>,
+    /// Whether or not each input is soloed. Inputs beyond the end are not soloed.
+    pub soloed: Vec<bool>,
+    /// Whether or not the polarity of each input is inverted. Inputs beyond the end are not
+    /// inverted.
+    pub inverted: Vec<bool>,
+    /// Whether or not each input is muted. Inputs beyond the end are not muted.
+    pub muted: Vec<bool>,
+}
+
+/// A **Node** that scales each of its inputs by its own gain before summing them, like the channel
+/// strips of a mixing console.
+///
+/// Inputs are indexed in the order in which they were connected. Inputs without a gain are
+/// summed at unity gain. The polarity of each input may also be inverted, e.g. to correct a
+/// microphone wired out of phase, in which case the input is subtracted rather than added.
+///
+/// While any input is soloed, only the soloed inputs are summed and all others are discarded.
+/// Soloing is additive, so soloing two inputs sums both. Inputs are still rendered by the `Graph`
+/// while discarded, so un-soloing them is seamless. Muting takes precedence over soloing: a muted
+/// input node outputs silence even while soloed, though it still silences the inputs that aren't
+/// soloed.
+///
+/// Likewise, an input muted by the **Mixer** is discarded even while soloed. Discarded inputs are
+/// not summed, but their nodes are still rendered so that any tail (e.g. of a reverb) carries on
+/// and un-muting is seamless. To stop rendering an input entirely, mute its node instead (see
+/// `Node::is_muted`).
+///
+/// As the settings of each input are not exposed via `Node::param_names`, a **Mixer** has no
+/// `Node::type_tag` and so cannot be restored from a `preset::Preset`. Save its `params` instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mixer {
+    gains: Vec<Volume>,
+    soloed: Vec<bool>,
+    inverted: Vec<bool>,
+    muted: Vec<bool>,
+}
+
+/// Set the flag of the input at the given index, where inputs beyond the end of `flags` are
+/// `false`.
+fn set_input_flag(flags: &mut Vec<bool>, index: usize, value: bool) {
+    if flags.len() <= index {
+        if !value {
+            return;
+        }
+        flags.resize(index + 1, false);
+    }
+    flags[index] = value;
+}
+
+impl Mixer {
+    /// Construct a **Mixer** that sums all inputs at unity gain.
+    pub fn new() -> Self {
+        Mixer::with_gains(Vec::new())
+    }
+
+    /// Construct a **Mixer** with the given gain for each input.
+    pub fn with_gains(gains: Vec<Volume>) -> Self {
+        Mixer {
+            gains,
+            soloed: Vec::new(),
+            inverted: Vec::new(),
+            muted: Vec::new(),
+        }
+    }
+
+    /// The gain applied to the input at the given index.
+    pub fn input_gain(&self, index: usize) -> Volume {
+        self.gains.get(index).cloned().unwrap_or(1.0)
+    }
+
+    /// Set the gain applied to the input at the given index.
+    pub fn set_input_gain(&mut self, index: usize, gain: Volume) {
+        if self.gains.len() <= index {
+            self.gains.resize(index + 1, 1.0);
+        }
+        self.gains[index] = gain;
+    }
+
+    /// Solo the input at the given index, in addition to any inputs that are already soloed.
+    pub fn solo(&mut self, index: usize) {
+        if self.soloed.len() <= index {
+            self.soloed.resize(index + 1, false);
+        }
+        self.soloed[index] = true;
+    }
+
+    /// Stop soloing the input at the given index.
+    pub fn unsolo(&mut self, index: usize) {
+        if let Some(soloed) = self.soloed.get_mut(index) {
+            *soloed = false;
+        }
+    }
+
+    /// Stop soloing all inputs, so that every input is summed again.
+    pub fn clear_solo(&mut self) {
+        self.soloed.clear();
+    }
+
+    /// Whether or not the input at the given index is soloed.
+    pub fn is_soloed(&self, index: usize) -> bool {
+        self.soloed.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Whether or not any input is soloed.
+    pub fn is_solo_active(&self) -> bool {
+        self.soloed.iter().any(|&soloed| soloed)
+    }
+
+    /// Whether or not the polarity of the input at the given index is inverted.
+    pub fn input_polarity(&self, index: usize) -> bool {
+        self.inverted.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Set whether or not the polarity of the input at the given index is inverted.
+    pub fn set_input_polarity(&mut self, index: usize, inverted: bool) {
+        set_input_flag(&mut self.inverted, index, inverted);
+    }
+
+    /// Whether or not the input at the given index is muted.
+    pub fn is_input_muted(&self, index: usize) -> bool {
+        self.muted.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Set whether or not the input at the given index is muted.
+    pub fn set_input_mute(&mut self, index: usize, muted: bool) {
+        set_input_flag(&mut self.muted, index, muted);
+    }
+
+    /// The parameters of the **Mixer**.
+    pub fn params(&self) -> MixerParams {
+        MixerParams {
+            gains: self.gains.clone(),
+            soloed: self.soloed.clone(),
+            inverted: self.inverted.clone(),
+            muted: self.muted.clone(),
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: MixerParams) {
+        self.gains = params.gains;
+        self.soloed = params.soloed;
+        self.inverted = params.inverted;
+        self.muted = params.muted;
+    }
+}
+
+impl<F> Node<F> for Mixer
+where
+    F: Frame,
+{
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        if self.is_input_muted(input_idx) || (self.is_solo_active() && !self.is_soloed(input_idx)) {
+            return;
+        }
+        let mut gain = self.input_gain(input_idx);
+        if self.input_polarity(input_idx) {
+            gain = -gain;
+        }
+        buffer::mix_into(output, input, gain);
+    }
+
+    fn audio_requested(&mut self, _buffer: &mut [F], _sample_hz: f64) {
+        // The inputs have already been scaled and summed by `combine_input`.
+    }
+
+    /// Returns `false`, as each input is scaled by its gain within `combine_input`.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+}
+
+/// How a **Sum** treats a sum that exceeds full scale.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SumStrategy {
+    /// Clip the sum to full scale (-1.0 ... 1.0), for floating point formats as well as integer.
+    #[default]
+    Clamp,
+    /// Wrap a sum beyond full scale around from one end of the full scale range to the other, as
+    /// two's complement integer addition does. A sum of exactly full scale is left as is.
+    Wrap,
+    /// Divide the sum by the number of inputs, i.e. take the average, so that it can never exceed
+    /// full scale.
+    Scale,
+}
+
+/// A **Node** that sums its inputs, treating the sum according to a **SumStrategy**.
+///
+/// The inputs are summed at `f64` precision and the **SumStrategy** is applied to the total once
+/// every input has been summed, so the result doesn't depend upon the order of the inputs. This
+/// differs from a **Node**'s default `combine_input`, which saturates integer formats at full scale
+/// but never clips floating point formats.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sum {
+    strategy: SumStrategy,
+    /// The sum of the inputs so far, interleaved by channel.
+    sums: Vec<f64>,
+    /// Whether each input has been summed since the last call to `audio_requested`.
+    combined: Vec<bool>,
+    /// The number of inputs summed since the last call to `audio_requested`.
+    inputs: usize,
+}
+
+impl Sum {
+    /// Construct a **Sum** using the given strategy.
+    pub fn new(strategy: SumStrategy) -> Self {
+        Sum {
+            strategy,
+            sums: Vec::new(),
+            combined: Vec::new(),
+            inputs: 0,
+        }
+    }
+
+    /// The treatment of sums exceeding full scale.
+    pub fn strategy(&self) -> SumStrategy {
+        self.strategy
+    }
+
+    /// Set the treatment of sums exceeding full scale.
+    pub fn set_strategy(&mut self, strategy: SumStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Begin summing a new buffer with the given number of samples.
+    fn begin(&mut self, samples: usize) {
+        self.sums.clear();
+        self.sums.resize(samples, 0.0);
+        for combined in self.combined.iter_mut() {
+            *combined = false;
+        }
+        self.inputs = 0;
+    }
+}
+
+impl<F> Node<F> for Sum
+where
+    F: Frame,
+{
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        // An input combined twice means that the last buffer was never rendered.
+        let samples = output.len() * F::n_channels();
+        if self.sums.len() != samples || self.combined.get(input_idx) == Some(&true) {
+            self.begin(samples);
+        }
+        if self.combined.len() <= input_idx {
+            self.combined.resize(input_idx + 1, false);
+        }
+        self.combined[input_idx] = true;
+        self.inputs += 1;
+
+        let samples = input.iter().flat_map(|frame| frame.channels());
+        for (sum, s) in self.sums.iter_mut().zip(samples) {
+            *sum += util::to_f64(s);
+        }
+        // Keep the default sum within `output` as the dry signal.
+        buffer::mix_into(output, input, 1.0);
+    }
+
+    /// Returns `false`, as `combine_input` keeps the sums of the inputs.
+    fn combines_by_sum(&self) -> bool {
+        false
+    }
+
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let samples = buffer.len() * F::n_channels();
+        if self.inputs == 0 || self.sums.len() != samples {
+            return;
+        }
+        let scale = match self.strategy {
+            SumStrategy::Scale => 1.0 / self.inputs as f64,
+            SumStrategy::Clamp | SumStrategy::Wrap => 1.0,
+        };
+        let strategy = self.strategy;
+        let mut sums = self.sums.iter();
+        sample::slice::map_in_place(buffer, |frame| {
+            frame.map(|_| {
+                let sum = sums.next().expect("a sum for each sample") * scale;
+                util::from_f64_saturating(match strategy {
+                    SumStrategy::Clamp => sum.clamp(-1.0, 1.0),
+                    SumStrategy::Wrap if sum.abs() > 1.0 => (sum + 1.0).rem_euclid(2.0) - 1.0,
+                    SumStrategy::Wrap | SumStrategy::Scale => sum,
+                })
+            })
+        });
+        self.begin(samples);
+    }
+
+    fn reset(&mut self) {
+        self.begin(0);
+    }
+
+    fn type_tag(&self) -> &str {
+        "sum"
+    }
+}
+
+/// The gain compensation applied by a **MonoSum** when summing its channels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MonoSumLaw {
+    /// Scale the sum by `1 / sqrt(channels)` (-3 dB for stereo), preserving the power of
+    /// uncorrelated channels.
+    Minus3dB,
+    /// Scale the sum by `1 / channels` (-6 dB for stereo), i.e. take the average. Correlated
+    /// channels are left at their original level, so the sum can never clip.
+    #[default]
+    Minus6dB,
+}
+
+impl MonoSumLaw {
+    /// The gain applied to the sum of the given number of channels.
+    pub fn gain(self, channels: usize) -> f64 {
+        let channels = channels.max(1) as f64;
+        match self {
+            MonoSumLaw::Minus3dB => 1.0 / channels.sqrt(),
+            MonoSumLaw::Minus6dB => 1.0 / channels,
+        }
+    }
+}
+
+/// A **Node** that collapses all channels to mono.
+///
+/// All channels are summed and scaled according to the **MonoSumLaw**, then the result is written
+/// to every channel, so the buffer keeps its layout.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MonoSum {
+    law: MonoSumLaw,
+}
+
+impl MonoSum {
+    /// Construct a **MonoSum** using the given law.
+    pub fn new(law: MonoSumLaw) -> Self {
+        MonoSum { law }
+    }
+
+    /// The gain compensation applied to the sum.
+    pub fn law(&self) -> MonoSumLaw {
+        self.law
+    }
+
+    /// Set the gain compensation applied to the sum.
+    pub fn set_law(&mut self, law: MonoSumLaw) {
+        self.law = law;
+    }
+}
+
+impl<F> Node<F> for MonoSum
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let channels = F::n_channels();
+        if channels < 2 {
+            return;
+        }
+        let gain = self.law.gain(channels);
+        sample::slice::map_in_place(buffer, |frame| {
+            let sum: f64 = frame.channels().map(util::to_f64).sum();
+            let mono = util::from_f64(sum * gain);
+            F::from_fn(|_| mono)
+        });
+    }
+
+    fn output_channels(&self, _input_channels: usize) -> usize {
+        1
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "mono_sum"
+    }
+}
+
+/// The parameters of an **Upmix**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpmixParams {
+    /// The volume of the upmixed signal.
+    pub volume: Volume,
+    /// The panning of the upmixed signal (-1.0 ... 1.0).
+    pub pan: Panning,
+    /// The law used to distribute the signal across the left and right channels.
+    pub pan_law: PanLaw,
+}
+
+/// A **Node** that distributes a mono signal held in the first channel across every channel.
+///
+/// The first channel of each frame is copied to every other channel, after which the `Graph`
+/// applies the **Upmix**'s `vol` and `pan` to each channel using its `pan_law`. Unlike a plain
+/// copy, this places the mono signal within the stereo field at the correct gain. Frames with more
+/// than two channels receive the signal at `vol` on every channel, as per
+/// `Node::vols_per_channel`.
+///
+/// Defaults to `PanLaw::ConstantPower`, so that the power of a mono signal is preserved wherever
+/// it is panned.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Upmix {
+    volume: Volume,
+    pan: Panning,
+    pan_law: PanLaw,
+}
+
+impl Upmix {
+    /// Construct a centered, full-volume **Upmix** using the given pan law.
+    pub fn new(pan_law: PanLaw) -> Self {
+        Upmix {
+            volume: 1.0,
+            pan: 0.0,
+            pan_law,
+        }
+    }
+
+    /// Set the volume of the upmixed signal.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// Set the panning of the upmixed signal, clamped to `-1.0 ... 1.0`.
+    pub fn set_pan(&mut self, pan: Panning) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    /// Set the law used to distribute the signal across the left and right channels.
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.pan_law = pan_law;
+    }
+
+    /// The parameters of the **Upmix**.
+    pub fn params(&self) -> UpmixParams {
+        UpmixParams {
+            volume: self.volume,
+            pan: self.pan,
+            pan_law: self.pan_law,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: UpmixParams) {
+        self.volume = params.volume;
+        self.set_pan(params.pan);
+        self.pan_law = params.pan_law;
+    }
+}
+
+impl Default for Upmix {
+    fn default() -> Self {
+        Upmix::new(PanLaw::ConstantPower)
+    }
+}
+
+impl<F> Node<F> for Upmix
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        if F::n_channels() < 2 {
+            return;
+        }
+        sample::slice::map_in_place(buffer, |frame| {
+            let mono = frame.channels().next().expect("the first channel");
+            F::from_fn(|_| mono)
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    fn pan(&self) -> Panning {
+        self.pan
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.pan_law
+    }
+
+    fn output_channels(&self, _input_channels: usize) -> usize {
+        F::n_channels()
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "upmix"
+    }
+}
+
+/// The parameters of a **ParallelRoute**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParallelRouteParams {
+    /// The gain applied to the unprocessed path.
+    pub dry_vol: Volume,
+    /// The gain applied to the processed path.
+    pub wet_vol: Volume,
+}
+
+/// A **Node** that renders its input through a wet node alongside an unprocessed dry path and
+/// sums the two, e.g. for parallel compression.
+///
+/// Unlike the dry/wet `mix` applied by the `Graph`, the dry path is delayed by the wet node's
+/// `latency_samples` so that the paths remain aligned when summed, rather than comb filtering.
+/// The **ParallelRoute** reports the wet node's latency as its own.
+#[derive(Clone, Debug)]
+pub struct ParallelRoute<F, N> {
+    node: N,
+    dry_vol: Volume,
+    wet_vol: Volume,
+    /// Aligns the dry path with the latency of the wet node.
+    delay: CompensationDelay,
+    /// The dry path of the current buffer.
+    dry: Vec<F>,
+}
+
+impl<F, N> ParallelRoute<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Construct a **ParallelRoute** summing both the dry path and the output of `node` at unity
+    /// gain.
+    pub fn new(node: N) -> Self {
+        ParallelRoute {
+            node,
+            dry_vol: 1.0,
+            wet_vol: 1.0,
+            delay: CompensationDelay::new(0),
+            dry: Vec::new(),
+        }
+    }
+
+    /// The gain applied to the unprocessed path.
+    pub fn dry_vol(&self) -> Volume {
+        self.dry_vol
+    }
+
+    /// Set the gain applied to the unprocessed path, clamped to `0.0` or more.
+    pub fn set_dry_vol(&mut self, vol: Volume) {
+        self.dry_vol = vol.max(0.0);
+    }
+
+    /// The gain applied to the processed path.
+    pub fn wet_vol(&self) -> Volume {
+        self.wet_vol
+    }
+
+    /// Set the gain applied to the processed path, clamped to `0.0` or more.
+    pub fn set_wet_vol(&mut self, vol: Volume) {
+        self.wet_vol = vol.max(0.0);
+    }
+
+    /// The parameters of the **ParallelRoute**.
+    pub fn params(&self) -> ParallelRouteParams {
+        ParallelRouteParams {
+            dry_vol: self.dry_vol,
+            wet_vol: self.wet_vol,
+        }
+    }
+
+    /// Apply the given parameters, leaving the runtime state untouched.
+    pub fn set_params(&mut self, params: ParallelRouteParams) {
+        self.set_dry_vol(params.dry_vol);
+        self.set_wet_vol(params.wet_vol);
+    }
+
+    /// A reference to the wet **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wet **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the **ParallelRoute**, returning the wet **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+
+    /// Silence the dry path's delay and reset the wet node.
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.node.reset();
+    }
+}
+
+impl<F, N> Node<F> for ParallelRoute<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.dry.clear();
+        self.dry.extend_from_slice(buffer);
+        self.node.audio_requested(buffer, sample_hz);
+
+        let latency = self.node.latency_samples();
+        if self.delay.frames() != latency {
+            self.delay = CompensationDelay::new(latency);
+        }
+        self.delay.audio_requested(&mut self.dry, sample_hz);
+
+        let dry_vol = self.dry_vol as f64;
+        let wet_vol = self.wet_vol as f64;
+        sample::slice::zip_map_in_place(buffer, &self.dry, |wet, dry| {
+            wet.zip_map(dry, |w, d| {
+                util::from_f64(util::to_f64(w) * wet_vol + util::to_f64(d) * dry_vol)
+            })
+        });
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    /// Exposes:
+    ///
+    /// - `dry_vol`: the gain applied to the unprocessed path (0.0 ...).
+    /// - `wet_vol`: the gain applied to the processed path (0.0 ...).
+    fn param_names(&self) -> &[&str] {
+        &["dry_vol", "wet_vol"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "dry_vol" => Some(self.dry_vol),
+            "wet_vol" => Some(self.wet_vol),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "dry_vol" => self.set_dry_vol(value),
+            "wet_vol" => self.set_wet_vol(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.dry.clear();
+        self.dry.reserve(settings.max_frames);
+        self.node.prepare(settings);
+        self.delay = CompensationDelay::new(self.node.latency_samples());
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn reset(&mut self) {
+        ParallelRoute::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "parallel_route"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gain::Gain;
+    use crate::preset::{PresetError, Registry};
+    use crate::Graph;
+
+    fn sum<F>(strategy: SumStrategy, inputs: &[F]) -> F
+    where
+        F: Frame,
+    {
+        let mut node = Sum::new(strategy);
+        let mut output = [F::equilibrium()];
+        for (i, input) in inputs.iter().enumerate() {
+            node.combine_input(i, &[*input], &mut output);
+        }
+        node.audio_requested(&mut output, 44_100.0);
+        output[0]
+    }
+
+    #[test]
+    fn each_strategy_treats_two_full_scale_integer_inputs() {
+        let inputs = [[i16::MAX], [i16::MAX]];
+        assert_eq!(sum(SumStrategy::Clamp, &inputs), [i16::MAX]);
+        assert_eq!(sum(SumStrategy::Scale, &inputs), [i16::MAX]);
+        // As two's complement addition wraps.
+        assert_eq!(
+            sum(SumStrategy::Wrap, &inputs),
+            [i16::MAX.wrapping_add(i16::MAX)]
+        );
+        let inputs = [[i16::MIN], [i16::MIN]];
+        assert_eq!(sum(SumStrategy::Clamp, &inputs), [i16::MIN]);
+        assert_eq!(sum(SumStrategy::Scale, &inputs), [i16::MIN]);
+        assert_eq!(sum(SumStrategy::Wrap, &inputs), [0]);
+    }
+
+    #[test]
+    fn wrap_leaves_sums_within_full_scale() {
+        assert_eq!(sum(SumStrategy::Wrap, &[[0.5f32], [0.5]]), [1.0]);
+        assert_eq!(sum(SumStrategy::Wrap, &[[-0.5f32], [-0.5]]), [-1.0]);
+        assert_eq!(sum(SumStrategy::Wrap, &[[0.25f32], [-0.5]]), [-0.25]);
+        assert_eq!(sum(SumStrategy::Wrap, &[[-0.75f32], [-0.75]]), [0.5]);
+        assert_eq!(sum(SumStrategy::Clamp, &[[0.75f32], [0.75]]), [1.0]);
+    }
+
+    /// Mix the given inputs, each a buffer of a single frame, through the **Mixer**.
+    fn mix(mixer: &mut Mixer, inputs: &[f32]) -> f32 {
+        let mut output = [[0.0f32]];
+        for (i, &input) in inputs.iter().enumerate() {
+            mixer.combine_input(i, &[[input]], &mut output);
+        }
+        mixer.audio_requested(&mut output, 44_100.0);
+        output[0][0]
+    }
+
+    #[test]
+    fn mixer_applies_each_input_gain_before_summing() {
+        let mut mixer = Mixer::with_gains(vec![0.5, 0.25]);
+        assert_eq!(mix(&mut mixer, &[0.5, 0.8]), 0.5 * 0.5 + 0.8 * 0.25);
+        mixer.set_input_gain(0, 2.0);
+        assert_eq!(mix(&mut mixer, &[0.25, 0.8]), 0.25 * 2.0 + 0.8 * 0.25);
+    }
+
+    #[test]
+    fn inputs_beyond_the_gains_are_mixed_at_unity() {
+        let mut mixer = Mixer::with_gains(vec![0.5]);
+        assert_eq!(mixer.input_gain(2), 1.0);
+        assert_eq!(mix(&mut mixer, &[0.5, 0.25, 0.125]), 0.25 + 0.25 + 0.125);
+        // Setting a gain beyond the end fills the gap at unity.
+        mixer.set_input_gain(3, 0.0);
+        assert_eq!(mixer.input_gain(1), 1.0);
+        assert_eq!(
+            mix(&mut mixer, &[0.5, 0.25, 0.125, 1.0]),
+            0.25 + 0.25 + 0.125
+        );
+    }
+
+    #[test]
+    fn mixer_solo_mute_and_polarity() {
+        let mut mixer = Mixer::new();
+        mixer.set_input_polarity(1, true);
+        assert_eq!(mix(&mut mixer, &[0.5, 0.25]), 0.25);
+        mixer.set_input_mute(0, true);
+        assert_eq!(mix(&mut mixer, &[0.5, 0.25]), -0.25);
+        mixer.set_input_mute(0, false);
+        mixer.solo(0);
+        assert!(mixer.is_solo_active());
+        assert_eq!(mix(&mut mixer, &[0.5, 0.25]), 0.5);
+        mixer.clear_solo();
+        assert_eq!(mix(&mut mixer, &[0.5, 0.25]), 0.25);
+    }
+
+    #[test]
+    fn two_equal_channels_sum_to_the_compensated_level() {
+        let mut buffer = [[0.5f32, 0.5]; 4];
+        MonoSum::new(MonoSumLaw::Minus6dB).audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|&frame| frame == [0.5, 0.5]));
+
+        let mut buffer = [[0.5f32, 0.5]; 4];
+        MonoSum::new(MonoSumLaw::Minus3dB).audio_requested(&mut buffer, 44_100.0);
+        let expected = (0.5 * 2.0 / 2.0f64.sqrt()) as f32;
+        assert!(buffer
+            .iter()
+            .all(|frame| frame.iter().all(|&s| (s - expected).abs() < 1e-6)));
+    }
+
+    #[test]
+    fn mono_sum_writes_the_mix_to_every_channel() {
+        let mut buffer = [[1.0f32, 0.0, 0.5, -0.5]];
+        MonoSum::new(MonoSumLaw::Minus6dB).audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[0.25; 4]]);
+        assert!((MonoSumLaw::Minus3dB.gain(4) - 0.5).abs() < 1e-12);
+        // A mono buffer is left as is.
+        let mut buffer = [[0.75f32]];
+        MonoSum::new(MonoSumLaw::Minus3dB).audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[0.75]]);
+    }
+
+    /// A source writing its value to every sample, which may be muted.
+    struct Dc {
+        value: f32,
+        muted: bool,
+    }
+
+    impl Node<[f32; 2]> for Dc {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 2]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [self.value; 2];
+            }
+        }
+
+        fn is_muted(&self) -> bool {
+            self.muted
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    type MixerGraph = Graph<[f32; 2], Box<dyn Node<[f32; 2]>>>;
+
+    /// A **Mixer** summing sources of `0.5` and `0.25`, connected in that order.
+    fn mixer_graph(first_muted: bool) -> (MixerGraph, crate::NodeIndex) {
+        let mut graph: MixerGraph = Graph::new();
+        let mixer = graph.add_node(Box::new(Mixer::new()) as Box<dyn Node<[f32; 2]>>);
+        for &(value, muted) in &[(0.5, first_muted), (0.25, false)] {
+            graph.add_input(Box::new(Dc { value, muted }), mixer);
+        }
+        graph.set_master(Some(mixer));
+        (graph, mixer)
+    }
+
+    fn render_mix(
+        graph: &mut MixerGraph,
+        mixer: crate::NodeIndex,
+        update: impl FnOnce(&mut Mixer),
+    ) -> f32 {
+        let mut node = Mixer::new();
+        update(&mut node);
+        graph.replace_node(mixer, Box::new(node));
+        let mut buffer = [[0.0f32; 2]; 4];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        buffer[3][0]
+    }
+
+    #[test]
+    fn soloing_input_0_silences_the_contribution_of_input_1() {
+        let (mut graph, mixer) = mixer_graph(false);
+        assert_eq!(render_mix(&mut graph, mixer, |_| ()), 0.75);
+        assert_eq!(render_mix(&mut graph, mixer, |m| m.solo(0)), 0.5);
+        assert_eq!(render_mix(&mut graph, mixer, |m| m.solo(1)), 0.25);
+        // Solos are additive.
+        assert_eq!(
+            render_mix(&mut graph, mixer, |m| {
+                m.solo(0);
+                m.solo(1);
+            }),
+            0.75
+        );
+        assert_eq!(
+            render_mix(&mut graph, mixer, |m| {
+                m.solo(0);
+                m.clear_solo();
+            }),
+            0.75
+        );
+    }
+
+    #[test]
+    fn muting_takes_precedence_over_soloing() {
+        // Muted by the **Mixer**.
+        let (mut graph, mixer) = mixer_graph(false);
+        let mix = render_mix(&mut graph, mixer, |m| {
+            m.solo(0);
+            m.set_input_mute(0, true);
+        });
+        assert_eq!(mix, 0.0);
+        // A muted input node outputs silence, but its solo still silences the other input.
+        let (mut graph, mixer) = mixer_graph(true);
+        assert_eq!(render_mix(&mut graph, mixer, |_| ()), 0.25);
+        assert_eq!(render_mix(&mut graph, mixer, |m| m.solo(0)), 0.0);
+    }
+
+    /// A source writing its value to the first channel only.
+    struct Left(f32);
+
+    impl Node<[f32; 2]> for Left {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 2]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [self.0, 0.0];
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    fn upmixed(upmix: Upmix) -> [f32; 2] {
+        let mut graph: MixerGraph = Graph::new();
+        let idx = graph.add_node(Box::new(upmix) as Box<dyn Node<[f32; 2]>>);
+        graph.add_input(Box::new(Left(0.5)), idx);
+        graph.set_master(Some(idx));
+        let mut buffer = [[0.0f32; 2]; 4];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        buffer[3]
+    }
+
+    #[test]
+    fn a_centered_mono_signal_is_upmixed_equally_at_constant_power() {
+        let [left, right] = upmixed(Upmix::default());
+        assert_eq!(left, right);
+        assert!(
+            (left - 0.5 * ::std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6,
+            "{}",
+            left
+        );
+        // The power of the mono signal is preserved.
+        assert!((left * left + right * right - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn upmix_applies_its_volume_and_panning() {
+        let mut upmix = Upmix::new(PanLaw::ConstantPower);
+        upmix.set_pan(-1.0);
+        upmix.set_vol(0.5);
+        let [left, right] = upmixed(upmix);
+        assert!((left - 0.25).abs() < 1e-6 && right.abs() < 1e-6);
+        // The signal is copied to every channel before the volume is applied.
+        let mut buffer = [[0.5f32, 0.0, -1.0, 0.25]];
+        Upmix::default().audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, [[0.5; 4]]);
+    }
+
+    #[test]
+    fn a_latent_wet_path_stays_aligned_with_the_dry_path() {
+        let mut route = ParallelRoute::new(CompensationDelay::new(32));
+        route.set_wet_vol(0.5);
+        assert_eq!(Node::<[f32; 1]>::latency_samples(&route), 32);
+        let mut buffer = vec![[0.0f32; 1]; 100];
+        buffer[10] = [1.0];
+        // Buffers shorter than the latency, so that the alignment spans buffers.
+        for chunk in buffer.chunks_mut(7) {
+            route.audio_requested(chunk, 48_000.0);
+        }
+        for (i, frame) in buffer.iter().enumerate() {
+            assert_eq!(frame[0], if i == 42 { 1.5 } else { 0.0 }, "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn a_parallel_route_sums_its_paths_at_their_volumes() {
+        let mut route = ParallelRoute::new(Gain::new(0.5));
+        assert!(Node::<[f32; 1]>::set_param(&mut route, "dry_vol", 0.75));
+        let mut buffer = vec![[0.5f32]; 4];
+        route.audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(buffer, vec![[0.625]; 4]);
+    }
+
+    #[test]
+    fn inverting_one_of_two_identical_inputs_sums_to_silence() {
+        let mut graph: MixerGraph = Graph::new();
+        let mut mixer = Mixer::new();
+        mixer.set_input_polarity(1, true);
+        let mixer = graph.add_node(Box::new(mixer) as Box<dyn Node<[f32; 2]>>);
+        for _ in 0..2 {
+            let dc = Dc {
+                value: 0.5,
+                muted: false,
+            };
+            graph.add_input(Box::new(dc), mixer);
+        }
+        graph.set_master(Some(mixer));
+        let mut buffer = [[1.0f32; 2]; 16];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|&frame| frame == [0.0; 2]));
+
+        // Muting the inverted input by the mixer leaves the other at its own level.
+        let mut node = Mixer::new();
+        node.set_input_polarity(1, true);
+        node.set_input_mute(1, true);
+        graph.replace_node(mixer, Box::new(node));
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|&frame| frame == [0.5; 2]));
+    }
+
+    #[test]
+    fn a_mixer_cannot_be_restored_from_a_preset() {
+        let mut graph: MixerGraph = Graph::new();
+        let mut mixer = Mixer::new();
+        mixer.set_input_gain(0, 0.5);
+        let mixer = graph.add_node(Box::new(mixer) as Box<dyn Node<[f32; 2]>>);
+        graph.set_master(Some(mixer));
+        let preset = graph.save_preset();
+        assert_eq!(preset.nodes[0].type_tag, "");
+
+        // Loading reports the missing tag rather than losing the mixer's gains.
+        let mut registry = Registry::new();
+        registry.register("mixer", || {
+            Box::new(Mixer::new()) as Box<dyn Node<[f32; 2]>>
+        });
+        assert_eq!(
+            graph.load_preset(&preset, &registry),
+            Err(PresetError::UnknownType(String::new()))
+        );
+        assert_eq!(graph[mixer].type_tag(), "");
+    }
+}
@@ -0,0 +1,346 @@
+//! Processing a signal as several frequency bands.
+//!
+//! A **MultiBand** splits its input into bands via cascaded Linkwitz-Riley crossovers (see
+//! `filter::Crossover`), renders a separate node over each band and sums the processed bands back
+//! together. This is the basis of multiband compressors, EQs and distortions.
+
+use crate::buffer;
+use crate::delay::CompensationDelay;
+use crate::filter::Crossover;
+use crate::node::{Node, Settings};
+use crate::noise;
+use sample::{self, Frame};
+
+/// A **Node** that splits its input into bands, renders each band through its own node and sums
+/// the results.
+///
+/// With `n` crossover frequencies there are `n + 1` bands, ordered from lowest to highest. The
+/// input is split at the lowest crossover first, and the remaining high band is split again at
+/// each crossover above. Each lower band is passed through the all-pass response of every
+/// crossover above it, so that all bands remain in phase with one another. With a band node that
+/// leaves its band untouched, the bands sum to the input with a flat magnitude response,
+/// differing only in phase.
+///
+/// The band nodes may report differing latency (see `Node::latency_samples`), in which case the
+/// bands with less latency are delayed to match the band with the most, which the **MultiBand**
+/// reports as its own latency.
+#[derive(Clone, Debug)]
+pub struct MultiBand<F, N> {
+    crossover_hz: Vec<f64>,
+    /// The crossover splitting each band from the bands above it.
+    splits: Vec<Crossover<F>>,
+    /// For each band below the highest two, a copy of each crossover above the one producing it,
+    /// through which the band is passed as an all-pass filter.
+    allpasses: Vec<Vec<Crossover<F>>>,
+    bands: Vec<N>,
+    /// Aligns the latency of each band with that of the band with the most.
+    delays: Vec<CompensationDelay>,
+    /// The signal of each band, re-used between calls to `audio_requested`.
+    band_buffers: Vec<Vec<F>>,
+    /// Receives the high band of each crossover.
+    high_buffer: Vec<F>,
+}
+
+/// Pass the buffer through the all-pass response of the crossover, i.e. the sum of its bands.
+fn apply_allpass<F>(crossover: &mut Crossover<F>, buffer: &mut [F], high: &mut [F], sample_hz: f64)
+where
+    F: Frame,
+{
+    crossover.audio_requested(buffer, sample_hz);
+    crossover.read_output(1, high);
+    buffer::mix_into(buffer, high, 1.0);
+}
+
+impl<F, N> MultiBand<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Construct a **MultiBand** splitting its input at the given crossover frequencies in hertz,
+    /// rendering each band through the node at the same index within `bands`.
+    ///
+    /// The frequencies are sorted in ascending order.
+    ///
+    /// **Panics** if `bands` does not hold exactly one more node than there are crossover
+    /// frequencies.
+    pub fn new(crossover_hz: Vec<f64>, bands: Vec<N>) -> Self {
+        assert_eq!(
+            bands.len(),
+            crossover_hz.len() + 1,
+            "A MultiBand requires one more band than crossover frequencies"
+        );
+        let mut crossover_hz = crossover_hz;
+        crossover_hz.sort_by(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Equal));
+        let splits = crossover_hz.iter().map(|&hz| Crossover::new(hz)).collect();
+        let allpasses = (0..crossover_hz.len())
+            .map(|band| {
+                crossover_hz[band + 1..]
+                    .iter()
+                    .map(|&hz| Crossover::new(hz))
+                    .collect()
+            })
+            .collect();
+        let num_bands = bands.len();
+        MultiBand {
+            crossover_hz,
+            splits,
+            allpasses,
+            bands,
+            delays: vec![CompensationDelay::new(0); num_bands],
+            band_buffers: vec![Vec::new(); num_bands],
+            high_buffer: Vec::new(),
+        }
+    }
+
+    /// The number of bands.
+    pub fn num_bands(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// The crossover frequencies in hertz, in ascending order.
+    pub fn crossover_hz(&self) -> &[f64] {
+        &self.crossover_hz
+    }
+
+    /// Set the crossover frequency at the given index in hertz, clamped between the crossover
+    /// frequencies either side of it so that the bands remain in order.
+    ///
+    /// **Panics** if there is no crossover at the given index.
+    pub fn set_crossover_hz(&mut self, idx: usize, hz: f64) {
+        let min = if idx > 0 {
+            self.crossover_hz[idx - 1]
+        } else {
+            0.0
+        };
+        let max = self
+            .crossover_hz
+            .get(idx + 1)
+            .cloned()
+            .unwrap_or(f64::INFINITY);
+        let hz = hz.max(min).min(max);
+        self.crossover_hz[idx] = hz;
+        self.splits[idx].set_cutoff_hz(hz);
+        for (band, allpasses) in self.allpasses.iter_mut().enumerate().take(idx) {
+            allpasses[idx - band - 1].set_cutoff_hz(hz);
+        }
+    }
+
+    /// A reference to the node processing the band at the given index, where `0` is the lowest.
+    pub fn band_node(&self, idx: usize) -> Option<&N> {
+        self.bands.get(idx)
+    }
+
+    /// A mutable reference to the node processing the band at the given index, where `0` is the
+    /// lowest.
+    pub fn band_node_mut(&mut self, idx: usize) -> Option<&mut N> {
+        self.bands.get_mut(idx)
+    }
+
+    /// Consume the **MultiBand**, returning the node of each band, lowest first.
+    pub fn into_band_nodes(self) -> Vec<N> {
+        self.bands
+    }
+
+    /// Clear the history of every crossover and delay and reset every band node.
+    pub fn reset(&mut self) {
+        for crossover in self
+            .splits
+            .iter_mut()
+            .chain(self.allpasses.iter_mut().flatten())
+        {
+            crossover.reset();
+        }
+        for band in self.bands.iter_mut() {
+            band.reset();
+        }
+        for delay in self.delays.iter_mut() {
+            delay.reset();
+        }
+    }
+}
+
+impl<F, N> Node<F> for MultiBand<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let len = buffer.len();
+        self.high_buffer.resize(len, F::equilibrium());
+
+        // Split the input into bands, the highest of which is what remains after the last split.
+        let (last, lower) = self
+            .band_buffers
+            .split_last_mut()
+            .expect("a MultiBand always has at least one band");
+        last.clear();
+        last.extend_from_slice(buffer);
+        for (band, band_buffer) in lower.iter_mut().enumerate() {
+            band_buffer.clear();
+            band_buffer.extend_from_slice(last);
+            self.splits[band].audio_requested(band_buffer, sample_hz);
+            self.splits[band].read_output(1, last);
+            for allpass in self.allpasses[band].iter_mut() {
+                apply_allpass(allpass, band_buffer, &mut self.high_buffer, sample_hz);
+            }
+        }
+
+        // Process each band, aligning their latencies.
+        let max_latency = self
+            .bands
+            .iter()
+            .map(|band| band.latency_samples())
+            .max()
+            .unwrap_or(0);
+        sample::slice::equilibrium(buffer);
+        let iter = self
+            .bands
+            .iter_mut()
+            .zip(self.delays.iter_mut())
+            .zip(self.band_buffers.iter_mut());
+        for ((band, delay), band_buffer) in iter {
+            band.audio_requested(band_buffer, sample_hz);
+            let frames = max_latency - band.latency_samples();
+            if delay.frames() != frames {
+                *delay = CompensationDelay::new(frames);
+            }
+            delay.audio_requested(band_buffer, sample_hz);
+            buffer::mix_into(buffer, band_buffer, 1.0);
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.bands
+            .iter()
+            .map(|band| band.latency_samples())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        for crossover in self
+            .splits
+            .iter_mut()
+            .chain(self.allpasses.iter_mut().flatten())
+        {
+            crossover.prepare(settings);
+        }
+        for band in self.bands.iter_mut() {
+            band.prepare(settings);
+        }
+        for delay in self.delays.iter_mut() {
+            delay.reset();
+        }
+        for buffer in self
+            .band_buffers
+            .iter_mut()
+            .chain(Some(&mut self.high_buffer))
+        {
+            buffer.clear();
+            buffer.reserve(settings.max_frames);
+        }
+    }
+
+    /// Seeds the node of each band with a seed derived from the given seed and the index of the
+    /// band.
+    fn set_seed(&mut self, seed: u64) {
+        for (i, band) in self.bands.iter_mut().enumerate() {
+            band.set_seed(noise::derive_seed(seed, i as u64));
+        }
+    }
+
+    fn reset(&mut self) {
+        MultiBand::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "multiband"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const SAMPLE_HZ: f64 = 48_000.0;
+
+    /// The magnitude at `hz` of the first channel of an impulse response.
+    fn magnitude_at(response: &[[f32; 1]], hz: f64) -> f64 {
+        let step = -2.0 * ::std::f64::consts::PI * hz / SAMPLE_HZ;
+        let (re, im) = response
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, frame)| {
+                let (sin, cos) = (step * i as f64).sin_cos();
+                (re + frame[0] as f64 * cos, im + frame[0] as f64 * sin)
+            });
+        (re * re + im * im).sqrt()
+    }
+
+    /// A **MultiBand** whose bands are each delayed by the given number of frames.
+    fn delayed_bands(
+        crossover_hz: Vec<f64>,
+        frames: &[usize],
+    ) -> MultiBand<[f32; 1], CompensationDelay> {
+        let bands = frames.iter().map(|&f| CompensationDelay::new(f)).collect();
+        MultiBand::new(crossover_hz, bands)
+    }
+
+    #[test]
+    fn a_single_band_passes_the_input_untouched() {
+        let mut multiband = delayed_bands(vec![], &[0]);
+        let input: Vec<[f32; 1]> = testing::sine(256, 440.0, 0.5, SAMPLE_HZ);
+        let mut output = input.clone();
+        multiband.audio_requested(&mut output, SAMPLE_HZ);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn identity_bands_sum_back_to_the_input() {
+        let mut multiband = delayed_bands(vec![2_000.0, 200.0], &[0, 0, 0]);
+        assert_eq!(multiband.crossover_hz(), &[200.0, 2_000.0]);
+        let response: Vec<[f32; 1]> = testing::impulse_response(&mut multiband, 8_192, SAMPLE_HZ);
+        let mut hz = 20.0;
+        while hz < 20_000.0 {
+            let magnitude = magnitude_at(&response, hz);
+            assert!((magnitude - 1.0).abs() < 0.01, "{} Hz: {}", hz, magnitude);
+            hz *= 1.25;
+        }
+
+        // A steady tone has the same level on the way out as on the way in.
+        multiband.reset();
+        let input: Vec<[f32; 1]> = testing::sine(48_000, 1_000.0, 0.5, SAMPLE_HZ);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(512) {
+            multiband.audio_requested(chunk, SAMPLE_HZ);
+        }
+        let ratio = testing::rms(&output[24_000..]) / testing::rms(&input[24_000..]);
+        assert!((ratio - 1.0).abs() < 0.01, "{}", ratio);
+    }
+
+    #[test]
+    fn band_latencies_are_aligned_to_the_slowest_band() {
+        let mut aligned = delayed_bands(vec![200.0, 2_000.0], &[0, 32, 0]);
+        assert_eq!(aligned.latency_samples(), 32);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut aligned, 1_024, SAMPLE_HZ);
+
+        let mut identity = delayed_bands(vec![200.0, 2_000.0], &[0, 0, 0]);
+        let expected: Vec<[f32; 1]> =
+            testing::impulse_response(&mut identity, 1_024 - 32, SAMPLE_HZ);
+        assert!(output[..32].iter().all(|frame| frame[0] == 0.0));
+        for (a, b) in output[32..].iter().zip(&expected) {
+            assert!((a[0] - b[0]).abs() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn band_nodes_are_reachable_by_index() {
+        let mut multiband = delayed_bands(vec![200.0, 2_000.0], &[0, 0, 0]);
+        assert_eq!(multiband.num_bands(), 3);
+        *multiband.band_node_mut(2).unwrap() = CompensationDelay::new(8);
+        assert_eq!(multiband.band_node(2).map(|band| band.frames()), Some(8));
+        assert!(multiband.band_node_mut(3).is_none());
+        assert_eq!(Node::<[f32; 1]>::latency_samples(&multiband), 8);
+    }
+}
@@ -0,0 +1,211 @@
+//! Naming nodes for diagnostics.
+
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::{Frame, Panning, Sample, Volume};
+
+/// A **Node** wrapper that reports the given name via `Node::name`.
+///
+/// Naming is purely introspective, e.g. for locating a node with `Graph::find_by_name` or
+/// labelling it while walking the **Graph** with `Graph::visit`. All other **Node** methods are
+/// delegated to the wrapped node, so the audio is unaffected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Named<N> {
+    node: N,
+    name: String,
+}
+
+impl<N> Named<N> {
+    /// Wrap the given node with the given name.
+    pub fn new<S>(node: N, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Named {
+            node,
+            name: name.into(),
+        }
+    }
+
+    /// Set the name reported by the wrapper.
+    pub fn set_name<S>(&mut self, name: S)
+    where
+        S: Into<String>,
+    {
+        self.name = name.into();
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<F, N> Node<F> for Named<N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.node.audio_requested(buffer, sample_hz);
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.node.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.node.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        self.node.vol_per_channel()
+    }
+
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        self.node.vols_per_channel(vols)
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn skip_silence(&self) -> bool {
+        self.node.skip_silence()
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.node.prepare(settings);
+    }
+
+    fn reset(&mut self) {
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn type_tag(&self) -> &str {
+        self.node.type_tag()
+    }
+}
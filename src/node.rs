@@ -1,4 +1,28 @@
-use crate::{Frame, Sample};
+use crate::gain;
+use crate::midi::MidiMessage;
+use crate::pan::{self, PanLaw};
+use crate::util;
+use crate::{Frame, Panning, Sample, Volume};
+
+/// The stream settings passed to `Node::prepare` before audio is first requested.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Settings {
+    /// The sample rate in hertz at which audio will be requested.
+    pub sample_hz: f64,
+    /// The largest number of frames that will be requested by a single call to `audio_requested`.
+    pub max_frames: usize,
+}
+
+impl Settings {
+    /// **Settings** for the given sample rate and largest number of frames per buffer.
+    pub fn new(sample_hz: f64, max_frames: usize) -> Self {
+        Settings {
+            sample_hz,
+            max_frames,
+        }
+    }
+}
 
 /// Types to be used as a **Node** within the DSP **Graph**.
 pub trait Node<F>
@@ -7,27 +31,91 @@ where
 {
     /// Request audio from the **Node** given some `sample_hz` (aka sample rate in hertz).
     /// If the **Node** has no inputs, the `buffer` will be zeroed.
-    /// If the **Node** has some inputs, the `buffer` will consist of the inputs summed together.
+    /// If the **Node** has some inputs, the `buffer` will consist of the inputs combined by
+    /// `combine_input` (summed together by default).
     ///
     /// Any source/generator type nodes should simply render straight to the buffer.
     /// Any effects/processor type nodes should mutate the buffer directly.
+    ///
+    /// Once `prepare` has been called, audio is assumed to be requested at the prepared sample
+    /// rate with buffers no longer than `max_frames`, so need not allocate. Nodes still re-size
+    /// their state upon receiving anything else, at the cost of allocating on the audio thread.
     fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64);
 
+    /// Combine the audio rendered by one of the **Node**'s inputs onto `output`, prior to the
+    /// call to `audio_requested`.
+    ///
+    /// The `Graph` sets `output` to equilibrium, then calls this once for each input in the order
+    /// in which they were connected, where `input_idx` is the index of the input in that order.
+    ///
+    /// By default, the first input (`input_idx` `0`) is written to `output` and each further input
+    /// is folded onto it sample by sample via `combine`. Nodes that treat their inputs differently
+    /// (for example, crossfading between them) may override this.
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        if input_idx == 0 {
+            sample::slice::write(output, input);
+            return;
+        }
+        sample::slice::zip_map_in_place(output, input, |out_frame, in_frame| {
+            out_frame.zip_map(in_frame, |acc, incoming| self.combine(acc, incoming))
+        });
+    }
+
+    /// Combine a sample of one of the **Node**'s inputs with the corresponding sample of the
+    /// inputs combined so far, as used by the default `combine_input`.
+    ///
+    /// By default, this sums the samples. The sum is exact for every sample format and integer
+    /// formats saturate at full scale rather than overflowing. Overriding this changes how the
+    /// inputs are combined without overriding `combine_input`, e.g. multiplying the samples turns
+    /// the **Node** into a ring modulator, while taking the greatest combines envelopes.
+    ///
+    /// An input skipped for being silent (see `skip_silence`) leaves the samples combined so far
+    /// as is, while if the first input is skipped, the next is combined with equilibrium.
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        util::add_samples(acc, incoming)
+    }
+
+    /// Whether the **Node** sums its inputs together, in which case the `Graph` attenuates each
+    /// input by its `headroom_db` when there are several.
+    ///
+    /// Nodes that read some input by its index rather than summing it, such as the control input
+    /// of a `gain::Vca` or the sidechain of a `dynamics::Compressor`, return `false`, so that the
+    /// headroom does not change the depth of modulation or the level detected.
+    ///
+    /// By default, this returns `true`.
+    fn sums_inputs(&self) -> bool {
+        true
+    }
+
+    /// Whether the **Node** combines its inputs with the default `combine_input` and `combine`,
+    /// summing them.
+    ///
+    /// For integer sample formats, the `Graph` then sums all of the inputs in `f64` and converts
+    /// the sum once at the end, so that only the final sum saturates at full scale, rather than
+    /// the sum of each input so far. Nodes overriding `combine_input` or `combine` must return
+    /// `false`, so that the `Graph` calls them.
+    ///
+    /// By default, this returns `true`.
+    fn combines_by_sum(&self) -> bool {
+        true
+    }
+
     /// Following the call to the `Node`'s `audio_requested` method, the `Graph` will sum together
     /// some of the original (dry) signal with some of the processed (wet) signal.
     ///
     /// This method specifies the amount of the dry signal to be used (0.0 ... 1.0).
     ///
-    /// By default, we don't want any of the original signal. This default is useful for generator
-    /// types, where the original signal is often 0.0 anyway.
+    /// By default, this is `1.0 - mix`. As `mix` defaults to `1.0`, we don't want any of the
+    /// original signal. This default is useful for generator types, where the original signal is
+    /// often 0.0 anyway.
     ///
-    /// For processors and effects, this should be overridden to return the amount of the
+    /// For processors and effects, this (or `mix`) should be overridden to return the amount of the
     /// non-processed signal that should be summed with the processed.
     ///
     /// Note: overriding this method will be more efficient than implementing your own dry/wet
     /// summing in audio_requested, as `Graph` reserves a single buffer especially for this.
     fn dry(&self) -> <F::Sample as Sample>::Float {
-        Sample::equilibrium()
+        <F::Sample as Sample>::Float::from_sample(1.0 - self.mix())
     }
 
     /// Following the call to the `Node`'s `audio_requested` method, the `Graph` will sum together
@@ -35,16 +123,380 @@ where
     ///
     /// This method specifies the amount of the wet signal to be used (0.0 ... 1.0).
     ///
-    /// By default, we want only the fully wet signal (1.0). This default is useful for generator
-    /// types where we are generating a brand new signal.
+    /// By default, this is `mix`. As `mix` defaults to `1.0`, we want only the fully wet signal.
+    /// This default is useful for generator types where we are generating a brand new signal.
     ///
-    /// For processors and effects, this should be overridden to return the amount of the processed
-    /// signal that should be summed with the non-processed.
+    /// For processors and effects, this (or `mix`) should be overridden to return the amount of the
+    /// processed signal that should be summed with the non-processed.
     ///
     /// Note: overriding this method will be more efficient than implementing your own dry/wet
     /// summing in audio_requested, as `Graph` reserves a single buffer especially for this.
     fn wet(&self) -> <F::Sample as Sample>::Float {
-        <F::Sample as Sample>::identity()
+        <F::Sample as Sample>::Float::from_sample(self.mix())
+    }
+
+    /// The balance between the dry and wet signals (0.0 ... 1.0), where `0.0` is fully dry and
+    /// `1.0` is fully wet.
+    ///
+    /// Only used by the default implementations of `dry` and `wet`, so overriding either of those
+    /// takes precedence. A `mix` of `0.0` produces output identical to the bypassed **Node**,
+    /// however `audio_requested` is still called so that any internal state is kept up to date.
+    ///
+    /// By default, this returns `1.0`.
+    fn mix(&self) -> f32 {
+        1.0
+    }
+
+    /// The amplitude multiplier applied to the **Node**'s output by the `Graph`, after the dry and
+    /// wet signals have been summed.
+    ///
+    /// By default, this is derived from `vol_db`.
+    fn vol(&self) -> Volume {
+        gain::db_to_amp(self.vol_db())
+    }
+
+    /// The gain applied to the **Node**'s output in decibels.
+    ///
+    /// Only used by the default implementation of `vol`, so overriding `vol` takes precedence.
+    /// Gains at or below `gain::SILENCE_DB` are silent.
+    ///
+    /// By default, the output is left at full volume (0.0 dB).
+    fn vol_db(&self) -> f32 {
+        0.0
+    }
+
+    /// The spacial positioning of the **Node**'s output (-1.0 = left, 0.0 = center, 1.0 = right).
+    ///
+    /// Only applies to stereo frames. By default, the output is centered.
+    fn pan(&self) -> Panning {
+        0.0
+    }
+
+    /// The pan position of each frame of the buffer most recently rendered by `audio_requested`,
+    /// allowing the pan to glide smoothly within a buffer rather than stepping between buffers.
+    ///
+    /// When this returns `Some` for a stereo frame and `channel_gains` returns `None`, the `Graph`
+    /// calculates the gain of each frame from `vol`, the frame's pan and `pan_law`, in place of
+    /// `vols_per_channel`. A slice shorter than the buffer holds its last pan for the remaining
+    /// frames. When `None`, the `Graph` uses its cheaper per-buffer path, so nodes should return
+    /// `None` whenever the pan is constant.
+    ///
+    /// By default, this returns `None`.
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        None
+    }
+
+    /// The law used to convert `vol` and `pan` into a gain per channel.
+    ///
+    /// Defaults to `PanLaw::Linear` so that a centered node is left at full volume.
+    fn pan_law(&self) -> PanLaw {
+        PanLaw::Linear
+    }
+
+    /// The balance between the left and right channels of the **Node**'s stereo output
+    /// (-1.0 ... 1.0).
+    ///
+    /// Where `pan` positions a source between the channels, balance only attenuates one of the
+    /// existing channels, without mixing either into the other: positive values attenuate the left
+    /// channel (silencing it at `1.0`) and negative values attenuate the right. When both are set,
+    /// the gain of each channel is the product of its gain from `pan_law` and its gain from the
+    /// balance, so that the balance applies after panning.
+    ///
+    /// Only applies to stereo frames. By default, the channels are balanced (`0.0`).
+    fn balance(&self) -> f32 {
+        0.0
+    }
+
+    /// An explicit gain for each channel of the **Node**'s output, e.g. to place a source among the
+    /// channels of a surround frame.
+    ///
+    /// When this returns `Some`, the gain of each channel is `vol` multiplied by the gain at the
+    /// channel's index, and `pan`, `pan_law`, `balance` and `pan_per_frame` are ignored. Channels
+    /// beyond the end of the slice are silenced. This applies to frames with any number of
+    /// channels.
+    ///
+    /// By default, this returns `None`, in which case the gains are derived from `vol` and `pan`.
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        None
+    }
+
+    /// The volume for the left and right channels, calculated from `vol` and `pan` using the
+    /// **Node**'s `pan_law`, then attenuated by its `balance`.
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        let [left, right] = self.pan_law().vol_per_channel(self.vol(), self.pan());
+        let [left_balance, right_balance] = pan::balance_gains(self.balance());
+        [left * left_balance, right * right_balance]
+    }
+
+    /// Write the volume for each channel into `vols`, where `vols.len()` is the number of channels.
+    ///
+    /// This is what the `Graph` uses to apply `vol` and `pan` to the **Node**'s output. By default:
+    ///
+    /// - Any frame uses `vol` multiplied by `channel_gains`, if the **Node** has some.
+    /// - Mono frames use `vol`.
+    /// - Stereo frames use `vol_per_channel`.
+    /// - Frames with more than two channels have no pan position yet, so every channel uses `vol`.
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        if let Some(gains) = self.channel_gains() {
+            let vol = self.vol();
+            for (i, v) in vols.iter_mut().enumerate() {
+                *v = vol * gains.get(i).cloned().unwrap_or(0.0);
+            }
+            return;
+        }
+        match vols.len() {
+            2 => {
+                let [left, right] = self.vol_per_channel();
+                vols[0] = left;
+                vols[1] = right;
+            }
+            _ => {
+                let vol = self.vol();
+                for v in vols.iter_mut() {
+                    *v = vol;
+                }
+            }
+        }
+    }
+
+    /// The number of frames over which the `Graph` interpolates from the previous
+    /// `vol_per_channel` to the current one whenever it changes.
+    ///
+    /// Smoothing avoids the audible clicks (aka "zipper noise") caused by changing the volume or
+    /// panning between buffers.
+    ///
+    /// By default this is `0`, meaning changes are applied instantly.
+    fn smoothing_samples(&self) -> usize {
+        0
+    }
+
+    /// Whether or not the **Node** is bypassed.
+    ///
+    /// When `true`, the `Graph` does not call `audio_requested` and the **Node**'s summed inputs
+    /// are passed through unchanged. The dry/wet mix, `vol` and `pan` are not applied.
+    ///
+    /// If the **Node** is also muted, `is_muted` takes precedence.
+    ///
+    /// By default, this returns `false`.
+    fn is_bypassed(&self) -> bool {
+        false
+    }
+
+    /// Whether or not the **Node** is muted.
+    ///
+    /// When `true`, the `Graph` does not call `audio_requested` and the **Node** outputs silence
+    /// regardless of its inputs. Muting takes precedence over `is_bypassed`.
+    ///
+    /// By default, this returns `false`.
+    fn is_muted(&self) -> bool {
+        false
+    }
+
+    /// The number of frames by which the **Node** delays its input, e.g. due to look-ahead or
+    /// block-based processing.
+    ///
+    /// Used by `Graph::total_latency` and `Graph::compensate_latency` to align parallel branches.
+    ///
+    /// By default, this returns `0`.
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    /// Whether or not the **Node**'s inputs may be rendered in parallel.
+    ///
+    /// Only respected by `Graph::audio_requested_from_parallel`, which requires that all nodes are
+    /// `Send`. When `true`, each of the **Node**'s inputs that are independent of one another are
+    /// rendered upon their own thread before being summed.
+    ///
+    /// By default, this returns `false`.
+    fn process_inputs_parallel(&self) -> bool {
+        false
+    }
+
+    /// Whether or not the **Node** generates its output without reading its inputs, e.g. an
+    /// oscillator or noise generator.
+    ///
+    /// A source overwrites the buffer passed to `audio_requested`, so any inputs connected to it
+    /// cannot affect its output. The `Graph`'s tools treat sources as leaves: `Graph::visit` does
+    /// not descend into their inputs, and `Graph::total_latency` does not add the latency of their
+    /// inputs to their own. Their inputs are rendered as usual.
+    ///
+    /// By default, this returns `false`.
+    fn is_source(&self) -> bool {
+        false
+    }
+
+    /// The number of distinct channels within the **Node**'s output, given the number of distinct
+    /// channels within its input, e.g. so that a host can decide how to route it.
+    ///
+    /// Every node within a `Graph` renders the same **Frame** type, so this describes the content
+    /// of the frames rather than their layout. For example, a node that sums every channel to mono
+    /// returns `1`, as it writes the same signal to every channel. `Graph::validate` uses this to
+    /// check that each node receives the channels given by its `input_channels`.
+    ///
+    /// By default, this returns `input_channels`.
+    fn output_channels(&self, input_channels: usize) -> usize {
+        input_channels
+    }
+
+    /// The number of distinct channels that the **Node** requires within its input in order to
+    /// process it as intended, e.g. `2` for a mid/side encoder, or `None` if any will do.
+    ///
+    /// By default, this returns `None`.
+    fn input_channels(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether or not the `Graph` may skip the **Node** while its inputs are silent.
+    ///
+    /// When `true`, the `Graph` checks each input's buffer with `gain::is_silent` and skips
+    /// combining the silent ones. If every input is silent, `audio_requested` is not called at all
+    /// and the **Node** outputs silence, saving the cost of rendering idle branches.
+    ///
+    /// Only return `true` for stateless processors whose output is always silent when their input
+    /// is. Generators must not, as they have no inputs and would never be rendered, and neither
+    /// must stateful effects such as delays, reverbs and filters, which must keep running to
+    /// render their tails.
+    ///
+    /// By default, this returns `false`.
+    fn skip_silence(&self) -> bool {
+        false
+    }
+
+    /// Observe the **Node**'s final output for a buffer, e.g. to log, meter or visualise it, or to
+    /// check assertions about it while testing.
+    ///
+    /// Called by the `Graph` immediately after rendering the **Node**, with its output after the
+    /// `Graph` has applied its mix, volume, panning and any enable fade, i.e. exactly the audio
+    /// written to its outgoing connections. The output may be read but not altered. Nodes that are
+    /// not rendered for a buffer, because they are muted, bypassed, disabled or skipped while
+    /// silent, are not called.
+    ///
+    /// By default, this does nothing.
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        let _ = (output, sample_hz);
+    }
+
+    /// The number of outputs rendered by the **Node**, e.g. one per band of a crossover.
+    ///
+    /// The first output is the buffer passed to `audio_requested`, to which the `Graph` applies
+    /// the **Node**'s mix, volume and panning. Any further outputs are read with `read_output` and
+    /// are passed on to the connections that select them exactly as rendered.
+    ///
+    /// By default, this returns `1`.
+    fn num_outputs(&self) -> usize {
+        1
+    }
+
+    /// Write the output at the given index, as rendered by the last call to `audio_requested`, to
+    /// `buffer`.
+    ///
+    /// Only called for indices in `1 .. num_outputs()`, with a `buffer` of the same length as that
+    /// passed to the last call to `audio_requested`.
+    ///
+    /// By default, this writes silence.
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        let _ = output_idx;
+        sample::slice::equilibrium(buffer);
+    }
+
+    /// The names of the parameters that may be read and set generically via `get_param` and
+    /// `set_param`, e.g. by a plugin host that does not know the **Node**'s concrete type.
+    ///
+    /// Names match the fields of the **Node**'s parameters struct where it has one, e.g.
+    /// `"cutoff_hz"` for a `filter::Biquad`. Values are clamped to the same ranges as the
+    /// **Node**'s own setters, which each implementation documents. Parameters that are not
+    /// numeric, such as a filter's response, are not exposed.
+    ///
+    /// By default, this returns an empty slice.
+    fn param_names(&self) -> &[&str] {
+        &[]
+    }
+
+    /// The current value of the parameter with the given name, or `None` if the **Node** has no
+    /// such parameter.
+    ///
+    /// By default, this returns `None`.
+    fn get_param(&self, name: &str) -> Option<f32> {
+        let _ = name;
+        None
+    }
+
+    /// Set the parameter with the given name, returning `false` if the **Node** has no such
+    /// parameter.
+    ///
+    /// By default, this returns `false`.
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        let _ = (name, value);
+        false
+    }
+
+    /// Apply the given MIDI note message immediately, returning `false` if the **Node** does not
+    /// respond to notes.
+    ///
+    /// This allows notes to be sent to a **Node** without knowing its concrete type, e.g. from an
+    /// `event::Scheduled` wrapper.
+    ///
+    /// By default, this returns `false`.
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        let _ = message;
+        false
+    }
+
+    /// Re-seed any random number generator used by the **Node** (see `noise::RngSource`), so
+    /// that its randomness is reproducible from the given seed.
+    ///
+    /// Each generator restarts its sequence from the new seed, which is also restored by
+    /// `reset`. Nodes holding several generators or nodes derive a distinct seed for each via
+    /// `noise::derive_seed`, so that they are not correlated. Called upon every node by
+    /// `Graph::set_seed`.
+    ///
+    /// By default, this does nothing.
+    fn set_seed(&mut self, seed: u64) {
+        let _ = seed;
+    }
+
+    /// Allocate and clear any state the **Node** requires for rendering with the given settings,
+    /// e.g. scratch buffers for `max_frames` frames or delay lines sized for `sample_hz`.
+    ///
+    /// Called once before streaming begins, and again whenever the host changes the sample rate
+    /// or block size, so that `audio_requested` need not allocate.
+    ///
+    /// By default, this calls `reset`.
+    fn prepare(&mut self, settings: Settings) {
+        let _ = settings;
+        self.reset();
+    }
+
+    /// Clear any state the **Node** has accumulated while rendering, e.g. delay lines, filter
+    /// history or oscillator phase, so that it renders as though it had just been constructed.
+    ///
+    /// Parameters such as volume and frequency are left untouched.
+    ///
+    /// By default, this does nothing.
+    fn reset(&mut self) {}
+
+    /// A name identifying the **Node** for diagnostics, e.g. via `Graph::find_by_name`.
+    ///
+    /// This is purely introspective and is never used while rendering audio. Any **Node** may be
+    /// named by wrapping it in a [**Named**](../named/struct.Named.html).
+    ///
+    /// By default, this returns `"unnamed"`.
+    fn name(&self) -> &str {
+        "unnamed"
+    }
+
+    /// A tag identifying the type of the **Node**, with which a
+    /// [**Registry**](../preset/struct.Registry.html) reconstructs it when a preset is loaded.
+    ///
+    /// Unlike the `name`, the tag is shared by every **Node** of the same type. It is stored
+    /// within saved presets, so should not change between versions. Wrappers that add no state of
+    /// their own that need saving return the tag of the **Node** they wrap.
+    ///
+    /// By default, this returns `""`, which no **Registry** accepts, so the **Node** cannot be
+    /// restored from a preset.
+    fn type_tag(&self) -> &str {
+        ""
     }
 }
 
@@ -57,6 +509,22 @@ where
         (**self).audio_requested(buffer, sample_hz);
     }
     #[inline]
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        (**self).combine_input(input_idx, input, output);
+    }
+    #[inline]
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        (**self).combine(acc, incoming)
+    }
+    #[inline]
+    fn sums_inputs(&self) -> bool {
+        (**self).sums_inputs()
+    }
+    #[inline]
+    fn combines_by_sum(&self) -> bool {
+        (**self).combines_by_sum()
+    }
+    #[inline]
     fn dry(&self) -> <F::Sample as Sample>::Float {
         (**self).dry()
     }
@@ -64,4 +532,128 @@ where
     fn wet(&self) -> <F::Sample as Sample>::Float {
         (**self).wet()
     }
+    #[inline]
+    fn mix(&self) -> f32 {
+        (**self).mix()
+    }
+    #[inline]
+    fn vol(&self) -> Volume {
+        (**self).vol()
+    }
+    #[inline]
+    fn vol_db(&self) -> f32 {
+        (**self).vol_db()
+    }
+    #[inline]
+    fn pan(&self) -> Panning {
+        (**self).pan()
+    }
+    #[inline]
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        (**self).pan_per_frame()
+    }
+    #[inline]
+    fn pan_law(&self) -> PanLaw {
+        (**self).pan_law()
+    }
+    #[inline]
+    fn balance(&self) -> f32 {
+        (**self).balance()
+    }
+    #[inline]
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        (**self).channel_gains()
+    }
+    #[inline]
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        (**self).vol_per_channel()
+    }
+    #[inline]
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        (**self).vols_per_channel(vols)
+    }
+    #[inline]
+    fn smoothing_samples(&self) -> usize {
+        (**self).smoothing_samples()
+    }
+    #[inline]
+    fn is_bypassed(&self) -> bool {
+        (**self).is_bypassed()
+    }
+    #[inline]
+    fn is_muted(&self) -> bool {
+        (**self).is_muted()
+    }
+    #[inline]
+    fn latency_samples(&self) -> usize {
+        (**self).latency_samples()
+    }
+    #[inline]
+    fn process_inputs_parallel(&self) -> bool {
+        (**self).process_inputs_parallel()
+    }
+    #[inline]
+    fn is_source(&self) -> bool {
+        (**self).is_source()
+    }
+    #[inline]
+    fn output_channels(&self, input_channels: usize) -> usize {
+        (**self).output_channels(input_channels)
+    }
+    #[inline]
+    fn input_channels(&self) -> Option<usize> {
+        (**self).input_channels()
+    }
+    #[inline]
+    fn skip_silence(&self) -> bool {
+        (**self).skip_silence()
+    }
+    #[inline]
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        (**self).on_buffer_processed(output, sample_hz)
+    }
+    #[inline]
+    fn num_outputs(&self) -> usize {
+        (**self).num_outputs()
+    }
+    #[inline]
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        (**self).read_output(output_idx, buffer)
+    }
+    #[inline]
+    fn param_names(&self) -> &[&str] {
+        (**self).param_names()
+    }
+    #[inline]
+    fn get_param(&self, name: &str) -> Option<f32> {
+        (**self).get_param(name)
+    }
+    #[inline]
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        (**self).set_param(name, value)
+    }
+    #[inline]
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        (**self).handle_midi(message)
+    }
+    #[inline]
+    fn set_seed(&mut self, seed: u64) {
+        (**self).set_seed(seed)
+    }
+    #[inline]
+    fn prepare(&mut self, settings: Settings) {
+        (**self).prepare(settings)
+    }
+    #[inline]
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+    #[inline]
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+    #[inline]
+    fn type_tag(&self) -> &str {
+        (**self).type_tag()
+    }
 }
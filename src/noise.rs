@@ -0,0 +1,626 @@
+//! Noise source nodes, and dither.
+
+use crate::distortion;
+use crate::node::Node;
+use crate::util;
+use crate::Volume;
+use sample::{self, Frame};
+
+/// The seed used by `WhiteNoise::new`, `PinkNoise::new` and `Dither::new`.
+pub const DEFAULT_SEED: u64 = 0x853c_49e6_748f_ea9b;
+
+/// The number of rows summed by **PinkNoise**, each updated at half the rate of the last.
+const PINK_ROWS: usize = 16;
+
+/// A small, fast xorshift64* pseudo-random number generator, the source of randomness for every
+/// stochastic node.
+///
+/// Its sequence is entirely determined by its seed, so that output using it is reproducible
+/// between runs. `Graph::set_seed` re-seeds every generator within a **Graph** at once, via
+/// `Node::set_seed`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RngSource {
+    state: u64,
+    /// The state upon seeding, restored by `reset`.
+    seed: u64,
+}
+
+impl RngSource {
+    /// Construct a generator with the given seed. A zero state would only ever produce zeroes,
+    /// so it is replaced by the `DEFAULT_SEED`.
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { DEFAULT_SEED } else { seed };
+        RngSource { state, seed: state }
+    }
+
+    /// The seed from which the sequence starts.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Re-seed the generator, restarting its sequence from the given seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        *self = RngSource::new(seed);
+    }
+
+    /// Return to the start of the sequence.
+    pub fn reset(&mut self) {
+        self.state = self.seed;
+    }
+
+    /// The next value, uniformly distributed over every `u64`.
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// The next value, uniformly distributed over `-1.0 .. 1.0`.
+    #[inline]
+    pub fn next_bipolar(&mut self) -> f64 {
+        // Use the top 53 bits for a uniformly distributed `f64` in `0.0 .. 1.0`.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+impl Default for RngSource {
+    fn default() -> Self {
+        RngSource::new(DEFAULT_SEED)
+    }
+}
+
+/// Derive a distinct seed for the stream with the given index from a single seed, e.g. for each
+/// node of a **Graph** or each voice of a `midi::PolyVoices`, so that their generators are not
+/// correlated.
+///
+/// The seed and index are mixed with the SplitMix64 finaliser, so that neighbouring indices give
+/// unrelated seeds.
+pub fn derive_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed ^ index.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// The parameters of a **WhiteNoise** or **PinkNoise**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseParams {
+    /// The volume of the noise.
+    pub volume: Volume,
+}
+
+/// A white noise source **Node**, with equal power at all frequencies.
+///
+/// Samples are uniformly distributed over `-1.0 .. 1.0`. Like the **Oscillator**, the same value
+/// is written to every channel, and the volume is reported via `Node::vol` and applied by the
+/// `Graph`.
+///
+/// The output is entirely determined by the seed, so it is reproducible between runs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhiteNoise {
+    rng: RngSource,
+    volume: Volume,
+}
+
+impl WhiteNoise {
+    /// Construct a full-volume **WhiteNoise** using the `DEFAULT_SEED`.
+    pub fn new() -> Self {
+        WhiteNoise::with_seed(DEFAULT_SEED)
+    }
+
+    /// Construct a full-volume **WhiteNoise** using the given seed.
+    pub fn with_seed(seed: u64) -> Self {
+        WhiteNoise {
+            rng: RngSource::new(seed),
+            volume: 1.0,
+        }
+    }
+
+    /// Set the volume of the noise.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The parameters of the noise.
+    pub fn params(&self) -> NoiseParams {
+        NoiseParams {
+            volume: self.volume,
+        }
+    }
+
+    /// Apply the given parameters, leaving the generator's state untouched.
+    pub fn set_params(&mut self, params: NoiseParams) {
+        self.volume = params.volume;
+    }
+
+    /// The seed of the generator.
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Re-seed the generator with the given seed, restarting its sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.set_seed(seed);
+    }
+
+    /// Re-seed the generator, so that the output repeats from the start.
+    pub fn reset(&mut self) {
+        self.rng.reset();
+    }
+}
+
+impl Default for WhiteNoise {
+    fn default() -> Self {
+        WhiteNoise::new()
+    }
+}
+
+impl<F> Node<F> for WhiteNoise
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let rng = &mut self.rng;
+        sample::slice::map_in_place(buffer, |_| {
+            let value = rng.next_bipolar();
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        WhiteNoise::reset(self);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        WhiteNoise::set_seed(self, seed);
+    }
+
+    fn type_tag(&self) -> &str {
+        "white_noise"
+    }
+}
+
+/// A pink noise source **Node**, whose power falls by 3 dB per octave.
+///
+/// Generated using the Voss-McCartney algorithm, which sums a number of white noise rows where
+/// each row is updated at half the rate of the previous one. The result is normalised so that
+/// samples always lie within `-1.0 .. 1.0`.
+///
+/// As with **WhiteNoise**, the same value is written to every channel, the volume is applied by
+/// the `Graph` and the output is reproducible from the seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PinkNoise {
+    rng: RngSource,
+    volume: Volume,
+    rows: [f64; PINK_ROWS],
+    /// The sum of all `rows`.
+    running_sum: f64,
+    /// Counts the samples generated. Its trailing zeros select the row to update.
+    counter: u32,
+}
+
+impl PinkNoise {
+    /// Construct a full-volume **PinkNoise** using the `DEFAULT_SEED`.
+    pub fn new() -> Self {
+        PinkNoise::with_seed(DEFAULT_SEED)
+    }
+
+    /// Construct a full-volume **PinkNoise** using the given seed.
+    pub fn with_seed(seed: u64) -> Self {
+        PinkNoise {
+            rng: RngSource::new(seed),
+            volume: 1.0,
+            rows: [0.0; PINK_ROWS],
+            running_sum: 0.0,
+            counter: 0,
+        }
+    }
+
+    /// Set the volume of the noise.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The parameters of the noise.
+    pub fn params(&self) -> NoiseParams {
+        NoiseParams {
+            volume: self.volume,
+        }
+    }
+
+    /// Apply the given parameters, leaving the generator's state untouched.
+    pub fn set_params(&mut self, params: NoiseParams) {
+        self.volume = params.volume;
+    }
+
+    /// The seed of the generator.
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Re-seed the generator with the given seed, restarting its sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.set_seed(seed);
+    }
+
+    /// Re-seed the generator and clear the rows, so that the output repeats from the start.
+    pub fn reset(&mut self) {
+        self.rng.reset();
+        self.rows = [0.0; PINK_ROWS];
+        self.running_sum = 0.0;
+        self.counter = 0;
+    }
+
+    /// Generate the next sample.
+    #[inline]
+    fn next_sample(&mut self) -> f64 {
+        self.counter = self.counter.wrapping_add(1);
+        let row = self.counter.trailing_zeros() as usize;
+        if row < PINK_ROWS {
+            let value = self.rng.next_bipolar();
+            self.running_sum += value - self.rows[row];
+            self.rows[row] = value;
+        }
+        let white = self.rng.next_bipolar();
+        (self.running_sum + white) / (PINK_ROWS + 1) as f64
+    }
+}
+
+impl Default for PinkNoise {
+    fn default() -> Self {
+        PinkNoise::new()
+    }
+}
+
+impl<F> Node<F> for PinkNoise
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        sample::slice::map_in_place(buffer, |_| {
+            let value = self.next_sample();
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        PinkNoise::reset(self);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        PinkNoise::set_seed(self, seed);
+    }
+
+    fn type_tag(&self) -> &str {
+        "pink_noise"
+    }
+}
+
+/// The parameters of a **Dither**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DitherParams {
+    /// The bit depth of the integer format to which the output will be converted (1 ... 32), or
+    /// `None` for a floating point format.
+    pub bit_depth: Option<u32>,
+    /// Whether or not the quantisation error is shaped towards high frequencies.
+    pub noise_shaping: bool,
+}
+
+/// A **Node** that dithers and quantises its input in preparation for conversion to an integer
+/// sample format, and is intended to be the last node before the output.
+///
+/// Triangular (TPDF) dither spanning one least significant bit either side of zero is added to
+/// each sample before rounding it to the `bit_depth`, decorrelating the quantisation error from
+/// the signal so that it is heard as a constant, benign noise floor rather than as distortion.
+/// Full scale is `-1.0 ... 1.0`, so a bit depth of 16 matches conversion to `i16`.
+///
+/// With `noise_shaping` enabled, the quantisation error of each sample is subtracted from the
+/// next, pushing the noise towards high frequencies where the ear is less sensitive, at the cost
+/// of raising its total power.
+///
+/// A `bit_depth` of `None` indicates a floating point output format, which needs no dither, and
+/// the **Dither** passes audio through untouched. As with the noise sources, the dither is
+/// reproducible from the seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dither {
+    rng: RngSource,
+    bit_depth: Option<u32>,
+    noise_shaping: bool,
+    /// The quantisation error of the previous sample, per channel.
+    errors: Vec<f64>,
+}
+
+impl Dither {
+    /// Construct a **Dither** for the given bit depth using the `DEFAULT_SEED`, without noise
+    /// shaping.
+    pub fn new(bit_depth: Option<u32>) -> Self {
+        Dither::with_seed(bit_depth, DEFAULT_SEED)
+    }
+
+    /// Construct a **Dither** for the given bit depth using the given seed, without noise
+    /// shaping.
+    pub fn with_seed(bit_depth: Option<u32>, seed: u64) -> Self {
+        Dither {
+            rng: RngSource::new(seed),
+            bit_depth: bit_depth.map(|bits| bits.clamp(1, 32)),
+            noise_shaping: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// The bit depth of the output format, or `None` for a floating point format.
+    pub fn bit_depth(&self) -> Option<u32> {
+        self.bit_depth
+    }
+
+    /// Set the bit depth of the output format (1 ... 32), or `None` for a floating point format.
+    pub fn set_bit_depth(&mut self, bit_depth: Option<u32>) {
+        self.bit_depth = bit_depth.map(|bits| bits.clamp(1, 32));
+    }
+
+    /// Whether or not the quantisation error is shaped towards high frequencies.
+    pub fn noise_shaping(&self) -> bool {
+        self.noise_shaping
+    }
+
+    /// Set whether or not the quantisation error is shaped towards high frequencies.
+    pub fn set_noise_shaping(&mut self, noise_shaping: bool) {
+        self.noise_shaping = noise_shaping;
+    }
+
+    /// The parameters of the **Dither**.
+    pub fn params(&self) -> DitherParams {
+        DitherParams {
+            bit_depth: self.bit_depth,
+            noise_shaping: self.noise_shaping,
+        }
+    }
+
+    /// Apply the given parameters, leaving the generator's state untouched.
+    pub fn set_params(&mut self, params: DitherParams) {
+        self.set_bit_depth(params.bit_depth);
+        self.set_noise_shaping(params.noise_shaping);
+    }
+
+    /// The seed of the generator.
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Re-seed the generator with the given seed, restarting its sequence.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.set_seed(seed);
+    }
+
+    /// Re-seed the generator and discard the quantisation error carried between samples.
+    pub fn reset(&mut self) {
+        self.rng.reset();
+        self.errors.clear();
+    }
+}
+
+impl<F> Node<F> for Dither
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let bit_depth = match self.bit_depth {
+            Some(bit_depth) => bit_depth,
+            None => return,
+        };
+        let step = 2.0 / (1u64 << bit_depth) as f64;
+        let noise_shaping = self.noise_shaping;
+        self.errors.resize(F::n_channels(), 0.0);
+        let Dither {
+            ref mut rng,
+            ref mut errors,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut errors = errors.iter_mut();
+            frame.map(|s| {
+                let error = errors.next().expect("one error per channel");
+                let v = util::to_f64(s) - if noise_shaping { *error } else { 0.0 };
+                let tpdf = (rng.next_bipolar() + rng.next_bipolar()) * 0.5;
+                let q = distortion::quantise(v + tpdf * step, bit_depth);
+                // Limit the error fed back when the output clips, so as not to run away.
+                *error = (q - v).clamp(-2.0 * step, 2.0 * step);
+                util::from_f64(q)
+            })
+        });
+    }
+
+    fn reset(&mut self) {
+        Dither::reset(self);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        Dither::set_seed(self, seed);
+    }
+
+    fn type_tag(&self) -> &str {
+        "dither"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::Graph;
+
+    /// Render `len` frames of the given source.
+    fn render<N: Node<[f32; 2]>>(node: &mut N, len: usize) -> Vec<[f32; 2]> {
+        let mut buffer = vec![[0.0f32; 2]; len];
+        node.audio_requested(&mut buffer, 48_000.0);
+        buffer
+    }
+
+    /// The summed power of the first channel over the DFT bins `bins`.
+    fn band_power(buffer: &[[f32; 2]], bins: ::std::ops::Range<usize>) -> f64 {
+        let len = buffer.len() as f64;
+        bins.map(|k| {
+            let step = 2.0 * ::std::f64::consts::PI * k as f64 / len;
+            let (re, im) = buffer
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (i, frame)| {
+                    let s = frame[0] as f64;
+                    (
+                        re + s * (step * i as f64).cos(),
+                        im - s * (step * i as f64).sin(),
+                    )
+                });
+            re * re + im * im
+        })
+        .sum()
+    }
+
+    #[test]
+    fn white_noise_has_a_near_zero_mean_over_a_large_buffer() {
+        let buffer = render(&mut WhiteNoise::new(), 100_000);
+        let mean = buffer.iter().map(|frame| frame[0] as f64).sum::<f64>() / buffer.len() as f64;
+        assert!(mean.abs() < 0.01, "{}", mean);
+        // Uniform over -1 .. 1.
+        assert!(buffer.iter().all(|frame| frame[0].abs() < 1.0));
+        assert!((testing::rms(&buffer) - (1.0f64 / 3.0).sqrt()).abs() < 0.01);
+    }
+
+    #[test]
+    fn noise_writes_the_same_value_to_every_channel() {
+        let white = render(&mut WhiteNoise::new(), 1_000);
+        let pink = render(&mut PinkNoise::new(), 1_000);
+        for frame in white.iter().chain(pink.iter()) {
+            assert_eq!(frame[0], frame[1]);
+        }
+        assert!(white.iter().any(|frame| frame[0] != 0.0));
+        assert!(pink.iter().any(|frame| frame[0] != 0.0));
+    }
+
+    #[test]
+    fn noise_is_reproducible_from_its_seed() {
+        let a = render(&mut WhiteNoise::with_seed(7), 1_000);
+        assert_eq!(a, render(&mut WhiteNoise::with_seed(7), 1_000));
+        assert_ne!(a, render(&mut WhiteNoise::with_seed(8), 1_000));
+
+        let mut pink = PinkNoise::with_seed(7);
+        let first = render(&mut pink, 1_000);
+        pink.reset();
+        assert_eq!(first, render(&mut pink, 1_000));
+        assert_eq!(first, render(&mut PinkNoise::with_seed(7), 1_000));
+    }
+
+    #[test]
+    fn noise_is_scaled_by_its_volume() {
+        let full = render(&mut WhiteNoise::with_seed(3), 480);
+        let mut noise = WhiteNoise::with_seed(3);
+        noise.set_vol(0.25);
+        let mut graph = Graph::new();
+        let idx = graph.add_node(noise);
+        graph.set_master(Some(idx));
+        let mut buffer = [[0.0f32; 2]; 480];
+        graph.audio_requested(&mut buffer, 48_000.0);
+        for (scaled, full) in buffer.iter().zip(full.iter()) {
+            assert!((scaled[0] - full[0] * 0.25).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn pink_noise_has_equal_power_per_octave_where_white_noise_doubles() {
+        // Three octaves apart: white noise has eight times the power in the upper band.
+        let len = 16_384;
+        let white = render(&mut WhiteNoise::new(), len);
+        let ratio = band_power(&white, 256..512) / band_power(&white, 32..64);
+        assert!(ratio > 5.0 && ratio < 12.0, "{}", ratio);
+        let pink = render(&mut PinkNoise::new(), len);
+        let ratio = band_power(&pink, 256..512) / band_power(&pink, 32..64);
+        assert!(ratio > 0.6 && ratio < 1.6, "{}", ratio);
+    }
+
+    /// Dither `len` frames of the constant `x` at the given bit depth, returning the added noise.
+    fn dither_noise(dither: &mut Dither, x: f64, len: usize) -> Vec<f64> {
+        let mut buffer = vec![[x]; len];
+        dither.audio_requested(&mut buffer, 48_000.0);
+        buffer.iter().map(|frame| frame[0] - x).collect()
+    }
+
+    #[test]
+    fn dither_noise_has_tpdf_statistics() {
+        let step = 2.0 / 256.0;
+        let noise = dither_noise(&mut Dither::new(Some(8)), 0.0, 100_000);
+        // Triangular dither across one step either side of zero rounds to a step away a quarter of
+        // the time, split evenly between each side.
+        let count = |level: f64| noise.iter().filter(|&&n| (n - level).abs() < 1e-9).count() as f64;
+        assert!((count(step) / 100_000.0 - 0.125).abs() < 0.01);
+        assert!((count(-step) / 100_000.0 - 0.125).abs() < 0.01);
+        assert!((count(0.0) / 100_000.0 - 0.75).abs() < 0.01);
+        assert_eq!(count(step) + count(-step) + count(0.0), 100_000.0);
+
+        // The quantised mean tracks an input between steps, and the noise power is a quarter step².
+        let x = 0.25 * step;
+        let noise = dither_noise(&mut Dither::new(Some(8)), x, 100_000);
+        let mean = noise.iter().sum::<f64>() / noise.len() as f64;
+        assert!(mean.abs() < step * 0.01, "{}", mean / step);
+        let power = noise.iter().map(|n| n * n).sum::<f64>() / noise.len() as f64;
+        assert!(
+            (power / (step * step) - 0.25).abs() < 0.02,
+            "{}",
+            power / (step * step)
+        );
+    }
+
+    #[test]
+    fn noise_shaping_pushes_the_noise_towards_high_frequencies() {
+        let lag_1_correlation = |noise: &[f64]| {
+            let power: f64 = noise.iter().map(|n| n * n).sum();
+            noise.windows(2).map(|w| w[0] * w[1]).sum::<f64>() / power
+        };
+        let flat = dither_noise(&mut Dither::new(Some(8)), 0.1, 50_000);
+        assert!(lag_1_correlation(&flat).abs() < 0.02);
+        let mut dither = Dither::new(Some(8));
+        dither.set_noise_shaping(true);
+        let shaped = dither_noise(&mut dither, 0.1, 50_000);
+        assert!(
+            lag_1_correlation(&shaped) < -0.4,
+            "{}",
+            lag_1_correlation(&shaped)
+        );
+    }
+
+    #[test]
+    fn dither_is_reproducible_and_a_no_op_for_float_formats() {
+        let a = dither_noise(&mut Dither::with_seed(Some(16), 5), 0.3, 1_000);
+        assert_eq!(
+            a,
+            dither_noise(&mut Dither::with_seed(Some(16), 5), 0.3, 1_000)
+        );
+        assert_ne!(
+            a,
+            dither_noise(&mut Dither::with_seed(Some(16), 6), 0.3, 1_000)
+        );
+        let input = testing::sine::<[f32; 2]>(256, 1_000.0, 0.5, 48_000.0);
+        let mut buffer = input.clone();
+        Dither::new(None).audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(buffer, input);
+    }
+}
@@ -0,0 +1,1199 @@
+//! Oscillator source nodes.
+
+use crate::clock::NoteLength;
+use crate::fft::{Complex, Fft};
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::util;
+use crate::Volume;
+use sample::{self, Frame};
+
+/// An oscillator whose phase advances at its frequency and may be read and set, e.g. to
+/// synchronise one oscillator to another via **HardSync**.
+pub trait Periodic {
+    /// The phase of the oscillator (0.0 ... 1.0).
+    fn phase(&self) -> f64;
+    /// Set the phase of the oscillator, wrapped into the range `0.0 ... 1.0`.
+    fn set_phase(&mut self, phase: f64);
+    /// The frequency of the oscillator in hertz.
+    fn frequency(&self) -> f64;
+}
+
+/// The parameters of an **Oscillator**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OscillatorParams {
+    /// The frequency of the oscillator in hertz.
+    pub frequency_hz: f64,
+    /// The volume of the oscillator.
+    pub volume: Volume,
+}
+
+/// A sine wave oscillator **Node**.
+///
+/// The **Oscillator** is a source: it ignores its input and writes the same value to every channel.
+/// Its phase advances once per frame and carries across buffers so that there are no
+/// discontinuities between calls to `audio_requested`.
+///
+/// The oscillator's volume is reported via `Node::vol` and applied by the `Graph`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Oscillator {
+    frequency_hz: f64,
+    volume: Volume,
+    /// The phase of the oscillator (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl Oscillator {
+    /// Construct a full-volume **Oscillator** with the given frequency in hertz.
+    pub fn new(frequency_hz: f64) -> Self {
+        Oscillator {
+            frequency_hz,
+            volume: 1.0,
+            phase: 0.0,
+        }
+    }
+
+    /// The frequency of the oscillator in hertz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency_hz
+    }
+
+    /// Set the frequency of the oscillator in hertz.
+    pub fn set_frequency(&mut self, hz: f64) {
+        self.frequency_hz = hz;
+    }
+
+    /// Set the volume of the oscillator.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The phase of the oscillator (0.0 ... 1.0).
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Set the phase of the oscillator, wrapped into the range `0.0 ... 1.0`.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// The parameters of the **Oscillator**.
+    pub fn params(&self) -> OscillatorParams {
+        OscillatorParams {
+            frequency_hz: self.frequency_hz,
+            volume: self.volume,
+        }
+    }
+
+    /// Apply the given parameters, leaving the phase untouched.
+    pub fn set_params(&mut self, params: OscillatorParams) {
+        self.frequency_hz = params.frequency_hz;
+        self.volume = params.volume;
+    }
+
+    /// Return the phase to the start of the cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+impl Periodic for Oscillator {
+    fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    fn set_phase(&mut self, phase: f64) {
+        Oscillator::set_phase(self, phase);
+    }
+
+    fn frequency(&self) -> f64 {
+        Oscillator::frequency(self)
+    }
+}
+
+impl<F> Node<F> for Oscillator
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let step = self.frequency_hz / sample_hz;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |_| {
+            let value = util::sin(*phase * 2.0 * ::std::f64::consts::PI);
+            *phase = (*phase + step).rem_euclid(1.0);
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    /// Exposes:
+    ///
+    /// - `frequency_hz`: the frequency in hertz.
+    /// - `volume`: the volume as a linear amplitude.
+    fn param_names(&self) -> &[&str] {
+        &["frequency_hz", "volume"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "frequency_hz" => Some(self.frequency_hz as f32),
+            "volume" => Some(self.volume),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "frequency_hz" => self.set_frequency(value as f64),
+            "volume" => self.set_vol(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        Oscillator::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "oscillator"
+    }
+}
+
+/// The number of frames to which every cycle loaded into a **Wavetable** is resampled.
+pub const WAVETABLE_FRAMES: usize = 2048;
+
+/// The number of band-limited versions of the cycle held by a **Wavetable**. The first holds
+/// `WAVETABLE_FRAMES / 4` harmonics and each of the others half as many as the last, down to a
+/// single harmonic.
+pub const WAVETABLE_LEVELS: usize = 10;
+
+/// The parameters of a **Wavetable**, excluding its table and runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WavetableParams {
+    /// The frequency of the oscillator in hertz.
+    pub frequency_hz: f64,
+    /// The volume of the oscillator.
+    pub volume: Volume,
+}
+
+/// An oscillator **Node** that plays back a user-supplied single cycle of a waveform.
+///
+/// Like the **Oscillator**, the **Wavetable** is a source that writes the same value to every
+/// channel, its phase carries across buffers and its volume is applied by the `Graph`. The cycle
+/// is read with linear interpolation.
+///
+/// To avoid aliasing at high pitches, `load_table` prepares `WAVETABLE_LEVELS` band-limited
+/// versions of the cycle (a "mipmap"), each with half the harmonics of the last. When audio is
+/// requested, the version with the most harmonics that all lie below the Nyquist frequency is
+/// played. Above the Nyquist frequency, only the fundamental remains.
+///
+/// The **Wavetable** is silent until a table is loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wavetable {
+    frequency_hz: f64,
+    volume: Volume,
+    /// The band-limited versions of the cycle, from the most harmonics to the fewest.
+    levels: Vec<Vec<f64>>,
+    /// The phase of the oscillator (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl Wavetable {
+    /// Construct a full-volume **Wavetable** with the given frequency in hertz and no table.
+    pub fn new(frequency_hz: f64) -> Self {
+        Wavetable {
+            frequency_hz,
+            volume: 1.0,
+            levels: Vec::new(),
+            phase: 0.0,
+        }
+    }
+
+    /// Load a single cycle of a waveform, replacing any previously loaded.
+    ///
+    /// The cycle may have any number of samples. It is resampled to `WAVETABLE_FRAMES` frames and
+    /// band-limited into each level of the mipmap, so loading allocates and is best done away from
+    /// the audio thread. An empty cycle silences the **Wavetable**.
+    pub fn load_table(&mut self, samples: &[f32]) {
+        self.levels.clear();
+        if samples.is_empty() {
+            return;
+        }
+
+        // Resample the cycle to the size of the table, wrapping around at the end.
+        let n = WAVETABLE_FRAMES;
+        let step = samples.len() as f64 / n as f64;
+        let mut spectrum: Vec<Complex> = (0..n)
+            .map(|i| {
+                let position = i as f64 * step;
+                let index = position.floor() as usize;
+                let a = samples[index % samples.len()] as f64;
+                let b = samples[(index + 1) % samples.len()] as f64;
+                Complex::new(a + (b - a) * (position - index as f64), 0.0)
+            })
+            .collect();
+        let fft = Fft::new(n);
+        fft.forward(&mut spectrum);
+
+        // Remove the harmonics above each level's limit from both halves of the spectrum.
+        let mut buffer = vec![Complex::default(); n];
+        for level in 0..WAVETABLE_LEVELS {
+            let harmonics = (n / 4) >> level;
+            for (bin, (x, &y)) in buffer.iter_mut().zip(&spectrum).enumerate() {
+                let harmonic = bin.min(n - bin);
+                *x = if harmonic <= harmonics {
+                    y
+                } else {
+                    Complex::default()
+                };
+            }
+            fft.inverse(&mut buffer);
+            self.levels.push(buffer.iter().map(|x| x.re).collect());
+        }
+    }
+
+    /// Whether or not a table has been loaded.
+    pub fn has_table(&self) -> bool {
+        !self.levels.is_empty()
+    }
+
+    /// The frequency of the oscillator in hertz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency_hz
+    }
+
+    /// Set the frequency of the oscillator in hertz.
+    pub fn set_frequency(&mut self, hz: f64) {
+        self.frequency_hz = hz;
+    }
+
+    /// Set the volume of the oscillator.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The phase of the oscillator (0.0 ... 1.0).
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Set the phase of the oscillator, wrapped into the range `0.0 ... 1.0`.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// The parameters of the **Wavetable**.
+    pub fn params(&self) -> WavetableParams {
+        WavetableParams {
+            frequency_hz: self.frequency_hz,
+            volume: self.volume,
+        }
+    }
+
+    /// Apply the given parameters, leaving the table and phase untouched.
+    pub fn set_params(&mut self, params: WavetableParams) {
+        self.frequency_hz = params.frequency_hz;
+        self.volume = params.volume;
+    }
+
+    /// Return the phase to the start of the cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// The index of the level of the mipmap with the most harmonics that all lie below the
+    /// Nyquist frequency.
+    fn level(&self, sample_hz: f64) -> usize {
+        let max_harmonic = (0.5 * sample_hz / self.frequency_hz.abs()).floor();
+        (0..WAVETABLE_LEVELS)
+            .find(|&level| ((WAVETABLE_FRAMES / 4) >> level) as f64 <= max_harmonic)
+            .unwrap_or(WAVETABLE_LEVELS - 1)
+    }
+}
+
+impl Periodic for Wavetable {
+    fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    fn set_phase(&mut self, phase: f64) {
+        Wavetable::set_phase(self, phase);
+    }
+
+    fn frequency(&self) -> f64 {
+        Wavetable::frequency(self)
+    }
+}
+
+impl<F> Node<F> for Wavetable
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if self.levels.is_empty() {
+            sample::slice::equilibrium(buffer);
+            return;
+        }
+        let table = &self.levels[self.level(sample_hz)];
+        let step = self.frequency_hz / sample_hz;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |_| {
+            let position = *phase * WAVETABLE_FRAMES as f64;
+            let index = (position.floor() as usize).min(WAVETABLE_FRAMES - 1);
+            let a = table[index];
+            let b = table[(index + 1) % WAVETABLE_FRAMES];
+            let value = a + (b - a) * (position - index as f64);
+            *phase = (*phase + step).rem_euclid(1.0);
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    /// Exposes:
+    ///
+    /// - `frequency_hz`: the frequency in hertz.
+    /// - `volume`: the volume as a linear amplitude.
+    fn param_names(&self) -> &[&str] {
+        &["frequency_hz", "volume"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "frequency_hz" => Some(self.frequency_hz as f32),
+            "volume" => Some(self.volume),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "frequency_hz" => self.set_frequency(value as f64),
+            "volume" => self.set_vol(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        Wavetable::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "wavetable"
+    }
+}
+
+/// The waveform of a **BlOsc**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlShape {
+    /// A sawtooth wave, rising from -1.0 to 1.0 over each cycle.
+    #[default]
+    Saw,
+    /// A square wave, at 1.0 for the first half of each cycle and -1.0 for the second.
+    Square,
+    /// A triangle wave, rising from -1.0 to 1.0 over the first half of each cycle and falling back
+    /// over the second.
+    Triangle,
+}
+
+/// The parameters of a **BlOsc**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlOscParams {
+    /// The frequency of the oscillator in hertz.
+    pub frequency_hz: f64,
+    /// The volume of the oscillator.
+    pub volume: Volume,
+    /// The waveform of the oscillator.
+    pub shape: BlShape,
+}
+
+/// A band-limited oscillator **Node** generating classic saw, square and triangle waves.
+///
+/// Generated naively, the discontinuities of these waveforms contain harmonics far above the
+/// Nyquist frequency, which alias back down as inharmonic tones. The **BlOsc** corrects the
+/// samples either side of each jump with a polynomial band-limited step (PolyBLEP), and either side
+/// of each corner of the triangle with its integral (PolyBLAMP), greatly reducing the aliasing at
+/// little cost.
+///
+/// Like the **Oscillator**, the **BlOsc** is a source that writes the same value to every channel,
+/// its phase carries across buffers and its volume is applied by the `Graph`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlOsc {
+    frequency_hz: f64,
+    volume: Volume,
+    shape: BlShape,
+    /// The phase of the oscillator (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl BlOsc {
+    /// Construct a full-volume **BlOsc** with the given shape and frequency in hertz.
+    pub fn new(shape: BlShape, frequency_hz: f64) -> Self {
+        BlOsc {
+            frequency_hz,
+            volume: 1.0,
+            shape,
+            phase: 0.0,
+        }
+    }
+
+    /// The frequency of the oscillator in hertz.
+    pub fn frequency(&self) -> f64 {
+        self.frequency_hz
+    }
+
+    /// Set the frequency of the oscillator in hertz.
+    pub fn set_frequency(&mut self, hz: f64) {
+        self.frequency_hz = hz;
+    }
+
+    /// Set the volume of the oscillator.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The waveform of the oscillator.
+    pub fn shape(&self) -> BlShape {
+        self.shape
+    }
+
+    /// Set the waveform of the oscillator.
+    pub fn set_shape(&mut self, shape: BlShape) {
+        self.shape = shape;
+    }
+
+    /// The phase of the oscillator (0.0 ... 1.0).
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Set the phase of the oscillator, wrapped into the range `0.0 ... 1.0`.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// The parameters of the **BlOsc**.
+    pub fn params(&self) -> BlOscParams {
+        BlOscParams {
+            frequency_hz: self.frequency_hz,
+            volume: self.volume,
+            shape: self.shape,
+        }
+    }
+
+    /// Apply the given parameters, leaving the phase untouched.
+    pub fn set_params(&mut self, params: BlOscParams) {
+        self.frequency_hz = params.frequency_hz;
+        self.volume = params.volume;
+        self.shape = params.shape;
+    }
+
+    /// Return the phase to the start of the cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+/// The PolyBLEP residual to subtract for a downward step of `2.0` at phase `0.0`, where `dt` is the
+/// phase step per frame.
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// The PolyBLAMP residual to add for an increase in slope of `2.0` per frame at phase `0.0`, where
+/// `dt` is the phase step per frame. This is the integral of the PolyBLEP residual.
+fn poly_blamp(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let t = t / dt - 1.0;
+        -t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+impl Periodic for BlOsc {
+    fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    fn set_phase(&mut self, phase: f64) {
+        BlOsc::set_phase(self, phase);
+    }
+
+    fn frequency(&self) -> f64 {
+        BlOsc::frequency(self)
+    }
+}
+
+impl<F> Node<F> for BlOsc
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let step = self.frequency_hz / sample_hz;
+        // The residuals assume that the phase rises, and cannot correct a step of a cycle or more.
+        let dt = step.abs().min(0.5);
+        let shape = self.shape;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |_| {
+            let t = *phase;
+            let half = (t + 0.5).fract();
+            let value = match shape {
+                BlShape::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+                BlShape::Square => {
+                    let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                    naive + poly_blep(t, dt) - poly_blep(half, dt)
+                }
+                BlShape::Triangle => {
+                    let naive = 1.0 - 4.0 * (t - 0.5).abs();
+                    // The slope changes by `8 * dt` per frame at each corner, rising at `0.0` and
+                    // falling at `0.5`.
+                    naive + 4.0 * dt * (poly_blamp(t, dt) - poly_blamp(half, dt))
+                }
+            };
+            *phase = (*phase + step).rem_euclid(1.0);
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    /// Exposes:
+    ///
+    /// - `frequency_hz`: the frequency in hertz.
+    /// - `volume`: the volume as a linear amplitude.
+    fn param_names(&self) -> &[&str] {
+        &["frequency_hz", "volume"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "frequency_hz" => Some(self.frequency_hz as f32),
+            "volume" => Some(self.volume),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "frequency_hz" => self.set_frequency(value as f64),
+            "volume" => self.set_vol(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        BlOsc::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "bl_osc"
+    }
+}
+
+/// The waveform of an **Lfo**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LfoShape {
+    /// A sine wave.
+    #[default]
+    Sine,
+    /// A triangle wave, rising from `-1.0` at the start of the cycle to `1.0` half way through.
+    Triangle,
+    /// A square wave, `1.0` for the first half of the cycle and `-1.0` for the second.
+    Square,
+    /// A sawtooth wave, rising from `-1.0` to `1.0` over the cycle.
+    Saw,
+}
+
+impl LfoShape {
+    /// The value of the waveform (-1.0 ... 1.0) at the given phase (0.0 ... 1.0).
+    pub fn value(self, phase: f64) -> f64 {
+        match self {
+            LfoShape::Sine => util::sin(phase * 2.0 * ::std::f64::consts::PI),
+            LfoShape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::Saw => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// The parameters of an **Lfo**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LfoParams {
+    /// The rate of the LFO in hertz.
+    pub rate_hz: f64,
+    /// The waveform of the LFO.
+    pub shape: LfoShape,
+    /// The amplitude of the LFO (0.0 ... 1.0).
+    pub depth: f32,
+}
+
+/// A low-frequency oscillator **Node**, producing a control signal for modulating other nodes.
+///
+/// Like the **Oscillator**, the **Lfo** is a source that writes the same value to every channel,
+/// with its phase carrying across buffers. Rather than being listened to directly, its output is
+/// intended to be read via `value` once per buffer, or connected to nodes that treat their input
+/// as a control signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lfo {
+    rate_hz: f64,
+    shape: LfoShape,
+    depth: f32,
+    /// The phase of the LFO (0.0 ... 1.0).
+    phase: f64,
+}
+
+impl Lfo {
+    /// Construct a full-depth **Lfo** with the given rate in hertz and shape.
+    pub fn new(rate_hz: f64, shape: LfoShape) -> Self {
+        Lfo {
+            rate_hz,
+            shape,
+            depth: 1.0,
+            phase: 0.0,
+        }
+    }
+
+    /// The rate of the LFO in hertz.
+    pub fn rate_hz(&self) -> f64 {
+        self.rate_hz
+    }
+
+    /// Set the rate of the LFO in hertz.
+    pub fn set_rate_hz(&mut self, hz: f64) {
+        self.rate_hz = hz;
+    }
+
+    /// Set the rate of the LFO so that one cycle lasts the given note at the given tempo in beats
+    /// per minute.
+    pub fn set_rate_note(&mut self, note: NoteLength, bpm: f64) {
+        self.rate_hz = note.hz(bpm);
+    }
+
+    /// The waveform of the LFO.
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    /// Set the waveform of the LFO. The phase is left untouched.
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    /// The amplitude of the LFO.
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Set the amplitude of the LFO (0.0 ... 1.0).
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    /// The phase of the LFO (0.0 ... 1.0).
+    pub fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    /// Set the phase of the LFO, wrapped into the range `0.0 ... 1.0`.
+    pub fn set_phase(&mut self, phase: f64) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// The value of the LFO (`-depth ... depth`) at the start of the next buffer.
+    pub fn value(&self) -> f32 {
+        (self.shape.value(self.phase) * self.depth as f64) as f32
+    }
+
+    /// The parameters of the **Lfo**.
+    pub fn params(&self) -> LfoParams {
+        LfoParams {
+            rate_hz: self.rate_hz,
+            shape: self.shape,
+            depth: self.depth,
+        }
+    }
+
+    /// Apply the given parameters, leaving the phase untouched.
+    pub fn set_params(&mut self, params: LfoParams) {
+        self.rate_hz = params.rate_hz;
+        self.shape = params.shape;
+        self.set_depth(params.depth);
+    }
+
+    /// Return the phase to the start of the cycle.
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+impl Periodic for Lfo {
+    fn phase(&self) -> f64 {
+        self.phase
+    }
+
+    fn set_phase(&mut self, phase: f64) {
+        Lfo::set_phase(self, phase);
+    }
+
+    fn frequency(&self) -> f64 {
+        self.rate_hz
+    }
+}
+
+impl<F> Node<F> for Lfo
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let step = self.rate_hz / sample_hz;
+        let depth = self.depth as f64;
+        let shape = self.shape;
+        let phase = &mut self.phase;
+        sample::slice::map_in_place(buffer, |_| {
+            let value = shape.value(*phase) * depth;
+            *phase = (*phase + step).rem_euclid(1.0);
+            F::from_fn(|_| util::from_f64(value))
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `rate_hz`: the rate of the LFO in hertz.
+    /// - `depth`: the amplitude of the LFO (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["rate_hz", "depth"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "rate_hz" => Some(self.rate_hz as f32),
+            "depth" => Some(self.depth),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "rate_hz" => self.set_rate_hz(value as f64),
+            "depth" => self.set_depth(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        Lfo::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "lfo"
+    }
+}
+
+/// A **Node** hard-syncing one oscillator to another.
+///
+/// Each time the phase of the `master` wraps around to the start of its cycle, the phase of the
+/// `slave` is restarted with it, so that the slave repeats at the frequency of the master. Tuning
+/// the slave above the master then cuts its cycle short, producing the bright, vocal tones of
+/// classic hard-sync leads, which are swept by modulating the frequency of the slave.
+///
+/// Only the slave is rendered: the phase of the master is advanced by its frequency but the master
+/// is never listened to. The restart falls between frames, so the slave restarts from the phase
+/// it would have reached since the exact moment of the wrap. A master with a negative frequency
+/// never wraps forwards, so never syncs the slave.
+///
+/// The audible output is that of the slave, whose volume and parameters are reported by the
+/// **HardSync**. The master is reached via `master` and `master_mut`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HardSync<M, S> {
+    master: M,
+    slave: S,
+}
+
+impl<M, S> HardSync<M, S> {
+    /// Sync the given `slave` to the given `master`.
+    pub fn new(master: M, slave: S) -> Self {
+        HardSync { master, slave }
+    }
+
+    /// A reference to the master oscillator.
+    pub fn master(&self) -> &M {
+        &self.master
+    }
+
+    /// A mutable reference to the master oscillator.
+    pub fn master_mut(&mut self) -> &mut M {
+        &mut self.master
+    }
+
+    /// A reference to the slave oscillator.
+    pub fn slave(&self) -> &S {
+        &self.slave
+    }
+
+    /// A mutable reference to the slave oscillator.
+    pub fn slave_mut(&mut self) -> &mut S {
+        &mut self.slave
+    }
+
+    /// Consume the **HardSync**, returning the master and slave oscillators.
+    pub fn into_inner(self) -> (M, S) {
+        (self.master, self.slave)
+    }
+}
+
+impl<F, M, S> Node<F> for HardSync<M, S>
+where
+    F: Frame,
+    M: Periodic,
+    S: Node<F> + Periodic,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let master_step = self.master.frequency() / sample_hz;
+        let slave_step = self.slave.frequency() / sample_hz;
+
+        // Render the slave up to each frame at which the master has wrapped, then restart it.
+        let mut phase = self.master.phase();
+        let mut start = 0;
+        for i in 0..buffer.len() {
+            let next = phase + master_step;
+            phase = next.rem_euclid(1.0);
+            if master_step > 0.0 && next >= 1.0 {
+                self.slave
+                    .audio_requested(&mut buffer[start..i + 1], sample_hz);
+                let frames_since_wrap = phase / master_step;
+                self.slave.set_phase(frames_since_wrap * slave_step);
+                start = i + 1;
+            }
+        }
+        if start < buffer.len() {
+            self.slave.audio_requested(&mut buffer[start..], sample_hz);
+        }
+        self.master.set_phase(phase);
+    }
+
+    fn vol(&self) -> Volume {
+        self.slave.vol()
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.slave.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.slave.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.slave.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.slave.handle_midi(message)
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.slave.prepare(settings);
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.slave.set_seed(seed);
+    }
+
+    /// Resets the slave and restarts both oscillators at the start of their cycles.
+    fn reset(&mut self) {
+        self.slave.reset();
+        self.master.set_phase(0.0);
+        self.slave.set_phase(0.0);
+    }
+
+    fn type_tag(&self) -> &str {
+        "hard_sync"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::Graph;
+
+    /// The number of times the first channel changes sign within the buffer.
+    fn zero_crossings(buffer: &[[f32; 2]]) -> usize {
+        buffer
+            .windows(2)
+            .filter(|pair| (pair[0][0] < 0.0) != (pair[1][0] < 0.0))
+            .count()
+    }
+
+    #[test]
+    fn a_buffer_of_a_known_frequency_crosses_zero_twice_per_cycle() {
+        // 100 cycles, the first starting at zero and the last ending just short of it.
+        let mut buffer = [[0.0f32; 2]; 4_800];
+        Oscillator::new(1_000.0).audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(zero_crossings(&buffer), 199);
+        assert!(buffer.iter().all(|frame| frame[0] == frame[1]));
+
+        let mut oscillator = Oscillator::new(440.0);
+        oscillator.set_frequency(1_500.0);
+        oscillator.audio_requested(&mut buffer, 48_000.0);
+        assert_eq!(zero_crossings(&buffer), 299);
+    }
+
+    #[test]
+    fn oscillator_phase_carries_across_buffers() {
+        let mut whole = [[0.0f32; 2]; 1_000];
+        Oscillator::new(441.0).audio_requested(&mut whole, 44_100.0);
+        let mut split = [[0.0f32; 2]; 1_000];
+        let mut oscillator = Oscillator::new(441.0);
+        for chunk in split.chunks_mut(64) {
+            oscillator.audio_requested(chunk, 44_100.0);
+        }
+        for (a, b) in split.iter().zip(whole.iter()) {
+            assert!((a[0] - b[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn oscillator_output_is_scaled_by_its_volume() {
+        let mut oscillator = Oscillator::new(1_000.0);
+        oscillator.set_vol(0.5);
+        let mut graph = Graph::new();
+        let idx = graph.add_node(oscillator);
+        graph.set_master(Some(idx));
+        let mut buffer = [[0.0f32; 2]; 480];
+        graph.audio_requested(&mut buffer, 48_000.0);
+        let peak = buffer
+            .iter()
+            .map(|frame| frame[0].abs())
+            .fold(0.0, f32::max);
+        assert!((peak - 0.5).abs() < 1e-3, "{}", peak);
+    }
+
+    #[test]
+    fn a_1_hz_sine_lfo_completes_a_cycle_over_a_second_of_samples() {
+        let mut lfo = Lfo::new(1.0, LfoShape::Sine);
+        let mut buffer = [[0.0f32; 1]; 1_000];
+        for chunk in buffer.chunks_mut(100) {
+            lfo.audio_requested(chunk, 1_000.0);
+        }
+        assert!((buffer[250][0] - 1.0).abs() < 1e-6);
+        assert!((buffer[750][0] + 1.0).abs() < 1e-6);
+        // Back at the start of the cycle, ready for the next buffer.
+        assert!(lfo.phase().min(1.0 - lfo.phase()) < 1e-9);
+        assert!(lfo.value().abs() < 1e-6);
+    }
+
+    #[test]
+    fn lfo_shapes_and_depth() {
+        assert_eq!(LfoShape::Triangle.value(0.0), -1.0);
+        assert_eq!(LfoShape::Triangle.value(0.5), 1.0);
+        assert_eq!(LfoShape::Square.value(0.25), 1.0);
+        assert_eq!(LfoShape::Square.value(0.75), -1.0);
+        assert_eq!(LfoShape::Saw.value(0.0), -1.0);
+        assert_eq!(LfoShape::Saw.value(0.5), 0.0);
+
+        let mut lfo = Lfo::new(2.0, LfoShape::Square);
+        lfo.set_depth(0.25);
+        lfo.set_shape(LfoShape::Saw);
+        lfo.set_rate_hz(4.0);
+        let mut buffer = [[0.0f32; 1]; 125];
+        lfo.audio_requested(&mut buffer, 1_000.0);
+        // Half way through a cycle of 250 frames.
+        assert!((lfo.phase() - 0.5).abs() < 1e-9);
+        assert_eq!(buffer[0], [-0.25]);
+        assert!(buffer.iter().all(|frame| frame[0].abs() <= 0.25));
+    }
+
+    #[test]
+    fn a_loaded_sine_table_plays_a_clean_sine() {
+        let table: Vec<f32> = (0..100)
+            .map(|i| (2.0 * ::std::f64::consts::PI * i as f64 / 100.0).sin() as f32)
+            .collect();
+        let mut wavetable = Wavetable::new(440.0);
+        let mut buffer = [[0.0f32; 1]; 4_800];
+        wavetable.audio_requested(&mut buffer, 48_000.0);
+        assert!(buffer.iter().all(|frame| frame[0] == 0.0));
+
+        wavetable.load_table(&table);
+        for chunk in buffer.chunks_mut(100) {
+            wavetable.audio_requested(chunk, 48_000.0);
+        }
+        let step = 2.0 * ::std::f64::consts::PI * 440.0 / 48_000.0;
+        for (i, frame) in buffer.iter().enumerate() {
+            let expected = (step * i as f64).sin() as f32;
+            assert!((frame[0] - expected).abs() < 1e-3, "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn high_wavetable_notes_play_only_harmonics_below_nyquist() {
+        // A saw at 5 kHz has harmonics at 5, 10, 15 and 20 kHz below the Nyquist frequency of
+        // 24 kHz. The 5th at 25 kHz would alias to 23 kHz.
+        let table: Vec<f32> = (0..2_048).map(|i| i as f32 / 1_024.0 - 1.0).collect();
+        let mut wavetable = Wavetable::new(5_000.0);
+        wavetable.load_table(&table);
+        let mut buffer = [[0.0f32; 1]; 4_800];
+        wavetable.audio_requested(&mut buffer, 48_000.0);
+        let fundamental = testing::power_at(&buffer, 5_000.0, 48_000.0);
+        assert!(testing::power_at(&buffer, 20_000.0, 48_000.0) > fundamental / 100.0);
+        assert!(testing::power_at(&buffer, 23_000.0, 48_000.0) < fundamental * 1e-6);
+    }
+
+    /// The power of everything but the harmonics of `hz` below the Nyquist frequency, i.e. of the
+    /// aliases, where `hz` completes a whole number of cycles within the buffer.
+    fn alias_power(buffer: &[[f32; 1]], hz: f64, sample_hz: f64) -> f64 {
+        let total =
+            buffer.iter().map(|s| f64::from(s[0]).powi(2)).sum::<f64>() / buffer.len() as f64;
+        let harmonics = (1..)
+            .map(|k| k as f64 * hz)
+            .take_while(|&harmonic| harmonic < sample_hz / 2.0)
+            .map(|harmonic| 2.0 * testing::power_at(buffer, harmonic, sample_hz))
+            .sum::<f64>();
+        total - harmonics
+    }
+
+    /// A naive waveform as a function of phase (0.0 ... 1.0).
+    type NaiveShape = fn(f64) -> f64;
+
+    #[test]
+    fn band_limited_shapes_alias_far_less_than_naive_ones() {
+        // 310 cycles of 3.1 kHz at 48 kHz, whose 8th and higher harmonics alias to inharmonic tones.
+        let (hz, sample_hz) = (3_100.0, 48_000.0);
+        let naive_shapes: [(BlShape, NaiveShape); 3] = [
+            (BlShape::Saw, |p| 2.0 * p - 1.0),
+            (BlShape::Square, |p| if p < 0.5 { 1.0 } else { -1.0 }),
+            (BlShape::Triangle, |p| {
+                if p < 0.5 {
+                    4.0 * p - 1.0
+                } else {
+                    3.0 - 4.0 * p
+                }
+            }),
+        ];
+        for &(shape, naive) in &naive_shapes {
+            let naive: Vec<[f32; 1]> = (0..4_800)
+                .map(|i| [naive((i as f64 * hz / sample_hz).fract()) as f32])
+                .collect();
+            let mut band_limited = vec![[0.0f32; 1]; 4_800];
+            let mut osc = BlOsc::new(shape, hz);
+            for chunk in band_limited.chunks_mut(256) {
+                osc.audio_requested(chunk, sample_hz);
+            }
+            let (naive, band_limited) = (
+                alias_power(&naive, hz, sample_hz),
+                alias_power(&band_limited, hz, sample_hz),
+            );
+            assert!(
+                band_limited < naive / 10.0,
+                "{:?}: {} vs {}",
+                shape,
+                band_limited,
+                naive
+            );
+        }
+    }
+
+    #[test]
+    fn hard_sync_restarts_the_slave_each_time_the_master_wraps() {
+        // A master period of exactly 512 frames, with the slave running at 340 Hz.
+        let mut slave = Oscillator::new(340.0);
+        let mut cycle = [[0.0f32]; 512];
+        slave.audio_requested(&mut cycle, 48_000.0);
+        let mut sync = HardSync::new(Oscillator::new(93.75), Oscillator::new(340.0));
+        let mut buffer = vec![[0.0f32]; 512 * 6];
+        for chunk in buffer.chunks_mut(100) {
+            sync.audio_requested(chunk, 48_000.0);
+        }
+        for (i, frame) in buffer.iter().enumerate() {
+            assert!((frame[0] - cycle[i % 512][0]).abs() < 1e-5, "{}", i);
+        }
+        assert_eq!(buffer[512], [0.0]);
+        assert_eq!(sync.master().phase(), 0.0);
+    }
+
+    #[test]
+    fn hard_sync_restarts_the_slave_between_frames() {
+        // A master period of 341 1/3 frames, so that each wrap falls between two frames.
+        let master_step = 3.0 / 1_024.0;
+        let slave_hz = 440.0;
+        let mut sync = HardSync::new(
+            Oscillator::new(master_step * 48_000.0),
+            Oscillator::new(slave_hz),
+        );
+        let mut buffer = [[0.0f32]; 64];
+        for block in 1..=40 {
+            sync.audio_requested(&mut buffer, 48_000.0);
+            // The slave's phase is that reached since the master's last wrap.
+            let frames = (block * buffer.len()) as f64;
+            let since_wrap = (frames * master_step).fract() / master_step;
+            let expected = (since_wrap * slave_hz / 48_000.0).fract();
+            assert!((sync.slave().phase() - expected).abs() < 1e-9, "{}", block);
+            assert!((sync.master().phase() - (frames * master_step).fract()).abs() < 1e-12);
+        }
+
+        // A master running backwards never syncs the slave.
+        let mut sync = HardSync::new(Oscillator::new(-100.0), Oscillator::new(slave_hz));
+        let mut free = Oscillator::new(slave_hz);
+        let mut synced = [[0.0f32]; 1_024];
+        let mut unsynced = synced;
+        sync.audio_requested(&mut synced, 48_000.0);
+        free.audio_requested(&mut unsynced, 48_000.0);
+        assert_eq!(synced, unsynced);
+    }
+}
@@ -0,0 +1,110 @@
+//! Pan laws for distributing a **Node**'s volume across the channels of a stereo signal.
+
+use crate::{Panning, Volume};
+
+/// The law used to convert a **Node**'s `pan` and `vol` into a gain per channel.
+///
+/// All laws are evaluated with `pan` clamped to the range -1.0 (left) ... 1.0 (right).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PanLaw {
+    /// Attenuates the opposite channel linearly while leaving the panned-towards channel at full
+    /// volume. Both channels are at full volume when centered.
+    ///
+    /// This is the default law.
+    #[default]
+    Linear,
+    /// Follows `cos`/`sin` of the pan angle so that `left² + right²` stays constant. Each channel
+    /// sits at -3dB when centered.
+    ConstantPower,
+    /// Follows the square root of the linear position so that each channel's *power* changes
+    /// linearly with `pan`. Like **ConstantPower**, `left² + right²` stays constant and each
+    /// channel sits at -3dB when centered, but the gain moves faster near the edges.
+    Minus3dB,
+}
+
+impl PanLaw {
+    /// The gain for the left and right channels respectively for the given `vol` and `pan`.
+    ///
+    /// This is allocation-free and cheap enough to call once per buffer within
+    /// `audio_requested`.
+    pub fn vol_per_channel(self, vol: Volume, pan: Panning) -> [Volume; 2] {
+        let pan = pan.clamp(-1.0, 1.0);
+        match self {
+            PanLaw::Linear => {
+                if pan >= 0.0 {
+                    [vol * (pan - 1.0).abs(), vol]
+                } else {
+                    [vol, vol * (pan + 1.0)]
+                }
+            }
+            PanLaw::ConstantPower => {
+                let angle = (pan + 1.0) * ::std::f32::consts::FRAC_PI_4;
+                [vol * angle.cos(), vol * angle.sin()]
+            }
+            PanLaw::Minus3dB => {
+                let right = (pan + 1.0) * 0.5;
+                [vol * (1.0 - right).sqrt(), vol * right.sqrt()]
+            }
+        }
+    }
+}
+
+/// The gain in decibels that, applied at the given `pan`, restores the loudness of a signal
+/// hard-panned under the same law.
+///
+/// Loudness is taken as the total power `left² + right²`, so laws that keep this constant, such as
+/// **ConstantPower** and **Minus3dB**, need no compensation at any position. **Linear** keeps both
+/// channels at full volume when centered, so its center is 3 dB louder than its edges and the
+/// compensation there is roughly -3 dB. UIs may display this as the level change expected when
+/// switching a centered signal between laws.
+pub fn pan_compensation_db(pan: Panning, law: PanLaw) -> f32 {
+    let power = |pan: Panning| {
+        let [left, right] = law.vol_per_channel(1.0, pan);
+        left * left + right * right
+    };
+    let edge = if pan < 0.0 { -1.0 } else { 1.0 };
+    10.0 * (power(edge) / power(pan)).log10()
+}
+
+/// The gain for the left and right channels respectively for the given balance, clamped to the
+/// range -1.0 (right attenuated) ... 1.0 (left attenuated).
+///
+/// A balance of `0.0` leaves both channels at full volume.
+pub(crate) fn balance_gains(balance: f32) -> [Volume; 2] {
+    let balance = balance.clamp(-1.0, 1.0);
+    if balance >= 0.0 {
+        [1.0 - balance, 1.0]
+    } else {
+        [1.0, 1.0 + balance]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_center_is_louder_than_constant_power() {
+        let linear = pan_compensation_db(0.0, PanLaw::Linear);
+        let constant_power = pan_compensation_db(0.0, PanLaw::ConstantPower);
+        // Twice the power of a hard-panned signal, i.e. 3 dB louder.
+        assert!((linear + 3.0103).abs() < 1e-3, "{}", linear);
+        assert!(constant_power.abs() < 1e-5, "{}", constant_power);
+        assert!((linear - constant_power + 3.0103).abs() < 1e-3);
+    }
+
+    #[test]
+    fn power_preserving_laws_need_no_compensation() {
+        for &pan in &[-1.0, -0.7, -0.2, 0.0, 0.4, 1.0] {
+            assert!(pan_compensation_db(pan, PanLaw::ConstantPower).abs() < 1e-5);
+            assert!(pan_compensation_db(pan, PanLaw::Minus3dB).abs() < 1e-5);
+        }
+        // The linear compensation eases from the center to nothing at either edge.
+        assert_eq!(pan_compensation_db(-1.0, PanLaw::Linear), 0.0);
+        assert_eq!(pan_compensation_db(1.0, PanLaw::Linear), 0.0);
+        let half = pan_compensation_db(0.5, PanLaw::Linear);
+        assert!(half < 0.0 && half > pan_compensation_db(0.0, PanLaw::Linear));
+        assert_eq!(half, pan_compensation_db(-0.5, PanLaw::Linear));
+    }
+}
@@ -0,0 +1,211 @@
+//! Parameters that glide towards new values rather than jumping to them.
+
+/// The curve followed by a **Param** as it moves towards its target.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Smoothing {
+    /// Move towards the target in equal steps.
+    #[default]
+    Linear,
+    /// Move a constant fraction of the remaining distance with each step, so that changes begin
+    /// quickly and settle gently. The value lands exactly upon the target at the end of the
+    /// smoothing time, by which point it is within 60 dB of the distance travelled.
+    Exponential,
+}
+
+/// The fraction of the distance remaining at the end of an exponential glide (-60 dB).
+pub(crate) const EXPONENTIAL_RESIDUAL: f32 = 0.001;
+
+/// A parameter value that is smoothed towards its target one sample at a time.
+///
+/// Call `next_value` once per sample within `audio_requested` to retrieve the smoothed value.
+/// When the smoothing time is zero, the value jumps to each new target immediately.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Param {
+    current: f32,
+    target: f32,
+    smoothing: Smoothing,
+    smoothing_samples: usize,
+    /// The change per sample for `Linear`, or the fraction of the remaining distance kept per
+    /// sample for `Exponential`.
+    step: f32,
+    /// The number of samples until the target is reached.
+    remaining: usize,
+}
+
+impl Param {
+    /// Construct a **Param** at the given value that jumps to new targets immediately.
+    pub fn new(value: f32) -> Self {
+        Param {
+            current: value,
+            target: value,
+            smoothing: Smoothing::default(),
+            smoothing_samples: 0,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// The value that was produced by the last call to `next_value`.
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    /// The value towards which the **Param** is moving.
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Whether or not the value is still moving towards its target.
+    pub fn is_smoothing(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// The curve followed while moving towards the target.
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    /// Set the curve followed while moving towards the target.
+    ///
+    /// Takes effect upon the next call to `set_target`.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+    }
+
+    /// The number of samples taken to reach a new target.
+    pub fn smoothing_samples(&self) -> usize {
+        self.smoothing_samples
+    }
+
+    /// Set the number of samples taken to reach a new target.
+    ///
+    /// Takes effect upon the next call to `set_target`.
+    pub fn set_smoothing_samples(&mut self, samples: usize) {
+        self.smoothing_samples = samples;
+    }
+
+    /// Set the time taken to reach a new target in milliseconds at the given sample rate.
+    ///
+    /// Takes effect upon the next call to `set_target`.
+    pub fn set_smoothing_ms(&mut self, ms: f64, sample_hz: f64) {
+        let samples = (ms.max(0.0) / 1_000.0 * sample_hz).round() as usize;
+        self.set_smoothing_samples(samples);
+    }
+
+    /// Begin moving towards the given target over `smoothing_samples` samples.
+    ///
+    /// Setting the same target again does not restart the glide.
+    pub fn set_target(&mut self, target: f32) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        let samples = self.smoothing_samples;
+        if samples == 0 {
+            self.current = target;
+            self.remaining = 0;
+            return;
+        }
+        self.step = match self.smoothing {
+            Smoothing::Linear => (target - self.current) / samples as f32,
+            Smoothing::Exponential => EXPONENTIAL_RESIDUAL.powf(1.0 / samples as f32),
+        };
+        self.remaining = samples;
+    }
+
+    /// Jump to the given value immediately, cancelling any glide in progress.
+    pub fn set_value(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+    }
+
+    /// Step towards the target, returning the new value.
+    ///
+    /// The value produced by the `smoothing_samples`th call after `set_target` is exactly the
+    /// target.
+    #[inline]
+    pub fn next_value(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                match self.smoothing {
+                    Smoothing::Linear => self.current + self.step,
+                    Smoothing::Exponential => {
+                        self.target + (self.current - self.target) * self.step
+                    }
+                }
+            };
+        }
+        self.current
+    }
+}
+
+impl Default for Param {
+    fn default() -> Self {
+        Param::new(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_target_change_is_reached_within_the_smoothing_time() {
+        for &smoothing in &[Smoothing::Linear, Smoothing::Exponential] {
+            let mut param = Param::new(0.0);
+            param.set_smoothing(smoothing);
+            // 10 ms at 1 kHz.
+            param.set_smoothing_ms(10.0, 1_000.0);
+            assert_eq!(param.smoothing_samples(), 10);
+            param.set_target(1.0);
+            let values: Vec<f32> = (0..12).map(|_| param.next_value()).collect();
+            assert!(
+                values[..9].iter().all(|&v| v > 0.0 && v < 1.0),
+                "{:?}",
+                values
+            );
+            assert!(values.windows(2).all(|w| w[1] >= w[0]), "{:?}", values);
+            assert_eq!(values[9], 1.0);
+            assert_eq!(values[11], 1.0);
+            assert!(!param.is_smoothing());
+        }
+    }
+
+    #[test]
+    fn linear_steps_are_equal_and_exponential_steps_shrink() {
+        let mut param = Param::new(1.0);
+        param.set_smoothing_samples(4);
+        param.set_target(0.0);
+        let values: Vec<f32> = (0..4).map(|_| param.next_value()).collect();
+        assert_eq!(values, [0.75, 0.5, 0.25, 0.0]);
+
+        let mut param = Param::new(0.0);
+        param.set_smoothing(Smoothing::Exponential);
+        param.set_smoothing_samples(100);
+        param.set_target(1.0);
+        let values: Vec<f32> = (0..100).map(|_| param.next_value()).collect();
+        assert!(values[1] - values[0] > values[50] - values[49]);
+        // Within 60 dB of the distance just before landing upon the target.
+        assert!(1.0 - values[98] < 0.002, "{}", values[98]);
+    }
+
+    #[test]
+    fn zero_smoothing_and_set_value_jump_immediately() {
+        let mut param = Param::new(0.0);
+        param.set_target(0.5);
+        assert_eq!(param.value(), 0.5);
+        assert!(!param.is_smoothing());
+        param.set_smoothing_samples(100);
+        param.set_target(1.0);
+        param.next_value();
+        assert!(param.is_smoothing());
+        param.set_value(-1.0);
+        assert_eq!(param.next_value(), -1.0);
+        assert_eq!(param.target(), -1.0);
+    }
+}
@@ -0,0 +1,133 @@
+//! Saving and restoring whole patches.
+//!
+//! A **Preset** records the type and named parameters (see `Node::param_names`) of every node
+//! within a **Graph**, along with the connections between them. It holds no audio or runtime
+//! state, so may be stored (e.g. via serde when the `serde` feature is enabled) and loaded into a
+//! **Graph** via `Graph::load_preset` to recreate the patch. Switching between presets of the same
+//! patch while playing is best done via `Graph::load_preset_ramped`, which glides each changed
+//! parameter rather than jumping to it.
+//!
+//! As nodes are generic, the **Graph** cannot construct them itself. Instead, a **Registry** maps
+//! each `Node::type_tag` to a function constructing a **Node** of that type, to which the saved
+//! parameters are then applied. Anything not exposed as a named parameter, such as a filter's
+//! response, is as constructed by that function.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// The nodes, parameters and connections of a **Graph**.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Preset {
+    /// Every node, in the order of their indices within the **Graph**.
+    pub nodes: Vec<NodePreset>,
+    /// Every connection. The connections to each node are listed in the order in which they were
+    /// connected, so nodes that treat their inputs by index are restored correctly.
+    pub connections: Vec<ConnectionPreset>,
+    /// The index within `nodes` of the master node, if one was set.
+    pub master: Option<usize>,
+}
+
+/// A single node within a **Preset**.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodePreset {
+    /// The `Node::type_tag` of the node.
+    pub type_tag: String,
+    /// The value of each of the node's named parameters.
+    pub params: Vec<(String, f32)>,
+}
+
+/// A single connection within a **Preset**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionPreset {
+    /// The index within `Preset::nodes` of the node whose output is carried.
+    pub src: usize,
+    /// The index of the output of `src` that is carried.
+    pub output_idx: usize,
+    /// The index within `Preset::nodes` of the node receiving the output.
+    pub dest: usize,
+}
+
+/// A function constructing a **Node**, to which the parameters saved within a **Preset** are
+/// applied.
+pub type Constructor<N> = Box<dyn Fn() -> N + Send>;
+
+/// Maps the `Node::type_tag` of each type of node that may be loaded from a **Preset** to a
+/// function constructing a node of that type.
+pub struct Registry<N> {
+    constructors: HashMap<String, Constructor<N>>,
+}
+
+/// The errors that may be returned when loading a **Preset**.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresetError {
+    /// No constructor is registered for the given type tag.
+    UnknownType(String),
+    /// A connection or the master refers to a node index beyond the end of `Preset::nodes`.
+    MissingNode(usize),
+    /// The connections would create a cycle.
+    Cycle,
+}
+
+impl<N> Registry<N> {
+    /// An empty **Registry**.
+    pub fn new() -> Self {
+        Registry {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Register the function constructing nodes with the given type tag, replacing any function
+    /// previously registered for the tag.
+    ///
+    /// The tag must match the `Node::type_tag` of the nodes constructed.
+    pub fn register<C>(&mut self, type_tag: &str, constructor: C)
+    where
+        C: Fn() -> N + Send + 'static,
+    {
+        self.constructors
+            .insert(type_tag.to_string(), Box::new(constructor));
+    }
+
+    /// Whether or not a constructor is registered for the given type tag.
+    pub fn contains(&self, type_tag: &str) -> bool {
+        self.constructors.contains_key(type_tag)
+    }
+
+    /// Construct a node with the given type tag, or `None` if no constructor is registered for it.
+    pub fn construct(&self, type_tag: &str) -> Option<N> {
+        self.constructors
+            .get(type_tag)
+            .map(|constructor| constructor())
+    }
+}
+
+impl<N> Default for Registry<N> {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+impl<N> fmt::Debug for Registry<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("type_tags", &self.constructors.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PresetError::UnknownType(ref tag) => {
+                write!(f, "No constructor is registered for the type tag {:?}", tag)
+            }
+            PresetError::MissingNode(idx) => write!(f, "The preset has no node at index {}", idx),
+            PresetError::Cycle => write!(f, "The preset's connections contain a cycle"),
+        }
+    }
+}
+
+impl ::std::error::Error for PresetError {}
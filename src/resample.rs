@@ -0,0 +1,728 @@
+//! Sample rate conversion.
+
+use crate::delay::DelayLine;
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::util;
+use sample::Frame;
+use std::f64::consts::PI;
+
+/// The number of input frames on either side of the read position used by
+/// `Interpolation::Sinc`.
+pub const SINC_HALF_WIDTH: usize = 8;
+
+/// The number of taps within each phase of the polyphase filters used by an **Oversample**.
+///
+/// The anti-imaging and anti-aliasing filters each have `OVERSAMPLE_PHASE_TAPS * factor` taps,
+/// and together delay the signal by `OVERSAMPLE_PHASE_TAPS - 1` frames.
+pub const OVERSAMPLE_PHASE_TAPS: usize = 32;
+
+/// The cutoff of the oversampling filters as a fraction of the original sample rate, leaving a
+/// little room below the original Nyquist frequency for the transition band.
+const OVERSAMPLE_CUTOFF: f64 = 0.45;
+
+/// The method used by a **Resampler** to calculate values between input frames.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    /// Linear interpolation between the two nearest input frames. Cheap, but aliases.
+    #[default]
+    Linear,
+    /// A Hann-windowed sinc over the nearest `2 * SINC_HALF_WIDTH` input frames. When
+    /// downsampling, the cutoff is lowered to the output rate's Nyquist frequency.
+    Sinc,
+}
+
+/// A **Node** that renders a source **Node** at some input sample rate and converts it to the
+/// sample rate at which audio is requested.
+///
+/// Source frames that have been rendered but not yet consumed (along with the fractional read
+/// position) are kept between calls to `audio_requested`, so there are no glitches at buffer
+/// boundaries.
+///
+/// The resampled source replaces the contents of the buffer, so the **Resampler** acts as a
+/// generator.
+#[derive(Clone, Debug)]
+pub struct Resampler<F, N> {
+    source: N,
+    input_hz: f64,
+    interpolation: Interpolation,
+    /// Source frames, where the first frame is at position `0.0`.
+    frames: Vec<F>,
+    /// The read position within `frames` of the next output frame.
+    position: f64,
+    /// The output of a single frame, per channel.
+    channels: Vec<f64>,
+}
+
+impl<F, N> Resampler<F, N>
+where
+    F: Frame,
+{
+    /// Construct a **Resampler** that renders `source` at `input_hz` using linear interpolation.
+    pub fn new(source: N, input_hz: f64) -> Self {
+        Resampler::with_interpolation(source, input_hz, Interpolation::Linear)
+    }
+
+    /// Construct a **Resampler** that renders `source` at `input_hz` using the given
+    /// interpolation.
+    pub fn with_interpolation(source: N, input_hz: f64, interpolation: Interpolation) -> Self {
+        let mut resampler = Resampler {
+            source,
+            input_hz,
+            interpolation,
+            frames: Vec::new(),
+            position: 0.0,
+            channels: vec![0.0; F::n_channels()],
+        };
+        resampler.reset_frames();
+        resampler
+    }
+
+    /// The sample rate at which the source is rendered.
+    pub fn input_rate(&self) -> f64 {
+        self.input_hz
+    }
+
+    /// Set the sample rate at which the source is rendered.
+    pub fn set_input_rate(&mut self, hz: f64) {
+        self.input_hz = hz;
+    }
+
+    /// The method used to calculate values between input frames.
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Set the method used to calculate values between input frames.
+    ///
+    /// This discards any source frames that have been rendered but not yet consumed.
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        if self.interpolation != interpolation {
+            self.interpolation = interpolation;
+            self.reset_frames();
+        }
+    }
+
+    /// A reference to the source **Node**.
+    pub fn source(&self) -> &N {
+        &self.source
+    }
+
+    /// A mutable reference to the source **Node**.
+    pub fn source_mut(&mut self) -> &mut N {
+        &mut self.source
+    }
+
+    /// Consume the **Resampler**, returning the source **Node**.
+    pub fn into_source(self) -> N {
+        self.source
+    }
+
+    /// The number of frames required before and after the read position.
+    fn taps(&self) -> (usize, usize) {
+        match self.interpolation {
+            Interpolation::Linear => (0, 1),
+            Interpolation::Sinc => (SINC_HALF_WIDTH - 1, SINC_HALF_WIDTH),
+        }
+    }
+
+    /// Discard all source frames, leaving silence in place of the frames before the read
+    /// position.
+    fn reset_frames(&mut self) {
+        let (before, _) = self.taps();
+        self.frames.clear();
+        self.frames.resize(before, F::equilibrium());
+        self.position = before as f64;
+    }
+}
+
+impl<F, N> Node<F> for Resampler<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if buffer.is_empty() || self.input_hz <= 0.0 || sample_hz <= 0.0 {
+            return;
+        }
+        if self.channels.len() != F::n_channels() {
+            self.channels.resize(F::n_channels(), 0.0);
+        }
+        let step = self.input_hz / sample_hz;
+        let (before, after) = self.taps();
+
+        // Render enough source frames to reach the last output frame, and to keep the source
+        // continuous when the read position skips beyond the end of the rendered frames.
+        let last = self.position + (buffer.len() - 1) as f64 * step;
+        let next = self.position + buffer.len() as f64 * step;
+        let required =
+            (last.floor() as usize + after + 1).max((next.floor() as usize).saturating_sub(before));
+        if self.frames.len() < required {
+            let start = self.frames.len();
+            self.frames.resize(required, F::equilibrium());
+            self.source
+                .audio_requested(&mut self.frames[start..], self.input_hz);
+        }
+
+        // When downsampling, lower the sinc's cutoff to avoid aliasing.
+        let cutoff = if step > 1.0 { 1.0 / step } else { 1.0 };
+
+        for (i, out) in buffer.iter_mut().enumerate() {
+            let position = self.position + i as f64 * step;
+            let index = position.floor() as usize;
+            let fract = position - index as f64;
+            for c in self.channels.iter_mut() {
+                *c = 0.0;
+            }
+            match self.interpolation {
+                Interpolation::Linear => {
+                    let a = self.frames[index].channels();
+                    let b = self.frames[index + 1].channels();
+                    for ((c, a), b) in self.channels.iter_mut().zip(a).zip(b) {
+                        let a = util::to_f64(a);
+                        *c = a + (util::to_f64(b) - a) * fract;
+                    }
+                }
+                Interpolation::Sinc => {
+                    let mut total_weight = 0.0;
+                    for j in (index - before)..=(index + after) {
+                        let distance = position - j as f64;
+                        let weight = cutoff * sinc(cutoff * distance) * hann(distance);
+                        total_weight += weight;
+                        for (c, s) in self.channels.iter_mut().zip(self.frames[j].channels()) {
+                            *c += util::to_f64(s) * weight;
+                        }
+                    }
+                    // Normalise so that the kernel has unity gain at DC.
+                    if total_weight != 0.0 {
+                        for c in self.channels.iter_mut() {
+                            *c /= total_weight;
+                        }
+                    }
+                }
+            }
+            let channels = &self.channels;
+            *out = F::from_fn(|ch| util::from_f64(channels[ch]));
+        }
+
+        // Discard the source frames that are no longer required.
+        let consumed = (next.floor() as usize).saturating_sub(before);
+        self.frames.drain(..consumed);
+        self.position = next - consumed as f64;
+    }
+
+    /// Prepares the source at `input_hz` and discards any source frames that have been rendered
+    /// but not yet consumed.
+    fn prepare(&mut self, settings: Settings) {
+        let (before, after) = self.taps();
+        let step = if settings.sample_hz > 0.0 {
+            self.input_hz / settings.sample_hz
+        } else {
+            1.0
+        };
+        let max_frames = (settings.max_frames as f64 * step).ceil() as usize + before + after + 2;
+        self.frames.reserve(max_frames);
+        self.channels.resize(F::n_channels(), 0.0);
+        self.source
+            .prepare(Settings::new(self.input_hz, max_frames));
+        self.reset_frames();
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.source.set_seed(seed);
+    }
+
+    /// Resets the source and discards any source frames that have been rendered but not yet
+    /// consumed.
+    fn reset(&mut self) {
+        self.source.reset();
+        self.reset_frames();
+    }
+}
+
+/// The factor by which an **Oversample** raises the sample rate of the **Node** that it wraps.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OversampleFactor {
+    /// Render at twice the sample rate.
+    #[default]
+    X2,
+    /// Render at four times the sample rate.
+    X4,
+}
+
+impl OversampleFactor {
+    /// The multiple of the sample rate.
+    pub fn factor(self) -> usize {
+        match self {
+            OversampleFactor::X2 => 2,
+            OversampleFactor::X4 => 4,
+        }
+    }
+}
+
+/// A **Node** that renders the **Node** that it wraps at a multiple of the sample rate, reducing
+/// the aliasing produced by nonlinear processes such as the `Waveshaper`.
+///
+/// The buffer is upsampled, passed to the wrapped node at the raised sample rate and downsampled
+/// back again. Both conversions use polyphase windowed-sinc FIR filters that cut off just below
+/// the original Nyquist frequency, removing the images introduced by upsampling and the harmonics
+/// generated above the original Nyquist frequency before they can fold back down.
+///
+/// The filters delay the signal by `OVERSAMPLE_PHASE_TAPS - 1` frames, which is reported by
+/// `latency_samples`. For this reason the wrapped node's dry/wet mix and volume are not forwarded,
+/// as mixing the delayed signal with its undelayed input would comb filter.
+#[derive(Clone, Debug)]
+pub struct Oversample<F, N> {
+    node: N,
+    factor: OversampleFactor,
+    /// The low-pass kernel shared by the upsampling and downsampling filters.
+    kernel: Vec<f64>,
+    /// The most recent input frames, feeding the upsampling filter.
+    up: DelayLine,
+    /// The most recent oversampled frames, feeding the downsampling filter.
+    down: DelayLine,
+    /// The buffer passed to the wrapped node.
+    oversampled: Vec<F>,
+}
+
+impl<F, N> Oversample<F, N>
+where
+    F: Frame,
+{
+    /// Construct an **Oversample** that renders `node` at the given multiple of the sample rate.
+    pub fn new(node: N, factor: OversampleFactor) -> Self {
+        let mut oversample = Oversample {
+            node,
+            factor,
+            kernel: Vec::new(),
+            up: DelayLine::default(),
+            down: DelayLine::default(),
+            oversampled: Vec::new(),
+        };
+        oversample.prepare_kernel();
+        oversample
+    }
+
+    /// The multiple of the sample rate at which the wrapped node is rendered.
+    pub fn factor(&self) -> OversampleFactor {
+        self.factor
+    }
+
+    /// Set the multiple of the sample rate at which the wrapped node is rendered.
+    ///
+    /// This silences the filters, so may produce a discontinuity.
+    pub fn set_factor(&mut self, factor: OversampleFactor) {
+        if self.factor != factor {
+            self.factor = factor;
+            self.prepare_kernel();
+        }
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the **Oversample**, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+
+    /// Silence the filters without resetting the wrapped node.
+    pub fn reset_filters(&mut self) {
+        self.up.clear();
+        self.down.clear();
+    }
+
+    /// Calculate the filter kernel for the current factor and silence the filters.
+    fn prepare_kernel(&mut self) {
+        let factor = self.factor.factor();
+        let len = OVERSAMPLE_PHASE_TAPS * factor;
+        self.kernel = oversample_kernel(factor);
+        self.up = DelayLine::default();
+        self.up.resize(OVERSAMPLE_PHASE_TAPS, F::n_channels());
+        self.down = DelayLine::default();
+        self.down.resize(len, F::n_channels());
+    }
+}
+
+impl<F, N> Node<F> for Oversample<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let factor = self.factor.factor();
+        let len = buffer.len() * factor;
+        if self.oversampled.len() != len {
+            self.oversampled.resize(len, F::equilibrium());
+        }
+
+        let Oversample {
+            ref mut node,
+            ref kernel,
+            ref mut up,
+            ref mut down,
+            ref mut oversampled,
+            ..
+        } = *self;
+
+        // Upsample, computing each phase of the anti-imaging filter from the input history. The
+        // filter is scaled by the factor to make up for the energy lost to the stuffed zeros.
+        for (frame, phases) in buffer.iter().zip(oversampled.chunks_mut(factor)) {
+            for (channel, s) in frame.channels().enumerate() {
+                up.write(channel, util::to_f64(s));
+            }
+            up.advance();
+            for (phase, out) in phases.iter_mut().enumerate() {
+                *out = F::from_fn(|channel| {
+                    let sum: f64 = (0..OVERSAMPLE_PHASE_TAPS)
+                        .map(|j| kernel[phase + j * factor] * up.past(channel, j + 1))
+                        .sum();
+                    util::from_f64(sum * factor as f64)
+                });
+            }
+        }
+
+        node.audio_requested(oversampled, sample_hz * factor as f64);
+
+        // Downsample, computing the anti-aliasing filter only for the frames that are kept.
+        for (frame, phases) in buffer.iter_mut().zip(oversampled.chunks(factor)) {
+            for phase in phases {
+                for (channel, s) in phase.channels().enumerate() {
+                    down.write(channel, util::to_f64(s));
+                }
+                down.advance();
+            }
+            *frame = F::from_fn(|channel| {
+                let sum: f64 = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, h)| h * down.past(channel, k + 1))
+                    .sum();
+                util::from_f64(sum)
+            });
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        let factor = self.factor.factor();
+        let inner = (self.node.latency_samples() + factor / 2) / factor;
+        OVERSAMPLE_PHASE_TAPS - 1 + inner
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    /// Prepares the wrapped node at the oversampled rate and silences the filters.
+    fn prepare(&mut self, settings: Settings) {
+        let factor = self.factor.factor();
+        let max_frames = settings.max_frames * factor;
+        self.oversampled.clear();
+        self.oversampled.reserve(max_frames);
+        self.node.prepare(Settings::new(
+            settings.sample_hz * factor as f64,
+            max_frames,
+        ));
+        self.reset_filters();
+    }
+
+    /// Resets the wrapped node and silences the filters.
+    fn reset(&mut self) {
+        self.node.reset();
+        self.reset_filters();
+    }
+}
+
+/// The low-pass kernel of `OVERSAMPLE_PHASE_TAPS * factor` taps used to filter a signal
+/// oversampled by the given factor, with unity gain at DC.
+///
+/// Phase `p` of the polyphase upsampling filter is made of every `factor`th tap from `p`.
+pub(crate) fn oversample_kernel(factor: usize) -> Vec<f64> {
+    let len = OVERSAMPLE_PHASE_TAPS * factor;
+    // The cutoff as a fraction of the oversampled rate, doubled for the sinc.
+    let cutoff = 2.0 * OVERSAMPLE_CUTOFF / factor as f64;
+    let centre = (len - 1) as f64 / 2.0;
+    let mut kernel: Vec<f64> = (0..len)
+        .map(|k| {
+            let t = k as f64 / (len - 1) as f64;
+            let blackman = 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos();
+            cutoff * sinc(cutoff * (k as f64 - centre)) * blackman
+        })
+        .collect();
+    // Normalise so that the kernel has unity gain at DC.
+    let total: f64 = kernel.iter().sum();
+    for h in kernel.iter_mut() {
+        *h /= total;
+    }
+    kernel
+}
+
+/// Resample a whole signal from `input_hz` to `output_hz` in one go, e.g. to prepare a loaded
+/// sample for playback at the rate of the **Graph**.
+///
+/// Unlike a **Resampler**, no state is kept, so the whole signal must be given at once. The signal
+/// is treated as silent beyond either end. The output has `input.len() * output_hz / input_hz`
+/// frames, rounded up to the next frame.
+pub fn resample<F>(
+    input: &[F],
+    input_hz: f64,
+    output_hz: f64,
+    interpolation: Interpolation,
+) -> Vec<F>
+where
+    F: Frame,
+{
+    if input_hz <= 0.0 || output_hz <= 0.0 {
+        return Vec::new();
+    }
+    let channels: Vec<Vec<f64>> = (0..F::n_channels())
+        .map(|ch| {
+            let signal: Vec<f64> = input
+                .iter()
+                .map(|frame| {
+                    util::to_f64(frame.channel(ch).cloned().expect("a sample per channel"))
+                })
+                .collect();
+            resample_channel(&signal, input_hz, output_hz, interpolation)
+        })
+        .collect();
+    let len = channels.first().map_or(0, |channel| channel.len());
+    (0..len)
+        .map(|i| F::from_fn(|ch| util::from_f64(channels[ch][i])))
+        .collect()
+}
+
+/// Resample a whole signal from `input_hz` to `output_hz`, using the same windowed sinc as
+/// `Interpolation::Sinc`. The signal is treated as silent beyond either end.
+pub(crate) fn resample_signal(input: &[f64], input_hz: f64, output_hz: f64) -> Vec<f64> {
+    resample_channel(input, input_hz, output_hz, Interpolation::Sinc)
+}
+
+/// Resample a single channel of a whole signal, treating it as silent beyond either end.
+fn resample_channel(
+    input: &[f64],
+    input_hz: f64,
+    output_hz: f64,
+    interpolation: Interpolation,
+) -> Vec<f64> {
+    let step = input_hz / output_hz;
+    let cutoff = if step > 1.0 { 1.0 / step } else { 1.0 };
+    let len = (input.len() as f64 / step).ceil() as usize;
+    let sample_at = |j: isize| {
+        if j >= 0 && (j as usize) < input.len() {
+            input[j as usize]
+        } else {
+            0.0
+        }
+    };
+    let before = SINC_HALF_WIDTH as isize - 1;
+    let after = SINC_HALF_WIDTH as isize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position.floor() as isize;
+            if let Interpolation::Linear = interpolation {
+                let a = sample_at(index);
+                return a + (sample_at(index + 1) - a) * (position - index as f64);
+            }
+            let mut total_weight = 0.0;
+            let mut value = 0.0;
+            for j in (index - before)..=(index + after) {
+                let distance = position - j as f64;
+                let weight = cutoff * sinc(cutoff * distance) * hann(distance);
+                total_weight += weight;
+                value += sample_at(j) * weight;
+            }
+            if total_weight != 0.0 {
+                value / total_weight
+            } else {
+                value
+            }
+        })
+        .collect()
+}
+
+/// The normalised sinc function, `sin(πx) / πx`.
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = PI * x;
+        x.sin() / x
+    }
+}
+
+/// A Hann window spanning `±SINC_HALF_WIDTH` frames.
+fn hann(distance: f64) -> f64 {
+    let x = distance / SINC_HALF_WIDTH as f64;
+    if x.abs() >= 1.0 {
+        0.0
+    } else {
+        0.5 * (1.0 + (PI * x).cos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distortion::{TransferFunction, Waveshaper};
+    use crate::oscillator::Oscillator;
+    use crate::testing;
+
+    /// The frequency of a sine from the number of times the first channel changes sign.
+    fn frequency(buffer: &[[f32; 1]], sample_hz: f64) -> f64 {
+        let crossings = buffer
+            .windows(2)
+            .filter(|pair| (pair[0][0] < 0.0) != (pair[1][0] < 0.0))
+            .count();
+        crossings as f64 / 2.0 * sample_hz / buffer.len() as f64
+    }
+
+    #[test]
+    fn resampling_a_sine_preserves_its_frequency() {
+        for &interpolation in &[Interpolation::Linear, Interpolation::Sinc] {
+            let source = Oscillator::new(1_000.0);
+            let mut resampler = Resampler::with_interpolation(source, 44_100.0, interpolation);
+            let mut buffer = [[0.0f32; 1]; 48_000];
+            for chunk in buffer.chunks_mut(512) {
+                resampler.audio_requested(chunk, 48_000.0);
+            }
+            let hz = frequency(&buffer, 48_000.0);
+            assert!((hz - 1_000.0).abs() < 1.0, "{:?}: {} Hz", interpolation, hz);
+        }
+    }
+
+    #[test]
+    fn resampling_is_continuous_across_buffer_boundaries() {
+        let mut whole = [[0.0f32; 1]; 2_048];
+        Resampler::new(Oscillator::new(440.0), 44_100.0).audio_requested(&mut whole, 48_000.0);
+        let mut split = [[0.0f32; 1]; 2_048];
+        let mut resampler = Resampler::new(Oscillator::new(440.0), 44_100.0);
+        for chunk in split.chunks_mut(37) {
+            resampler.audio_requested(chunk, 48_000.0);
+        }
+        for (a, b) in split.iter().zip(whole.iter()) {
+            assert!((a[0] - b[0]).abs() < 1e-5, "{} != {}", a[0], b[0]);
+        }
+    }
+
+    #[test]
+    fn set_input_rate_changes_the_rate_of_the_source() {
+        let mut resampler = Resampler::new(Oscillator::new(1_000.0), 44_100.0);
+        resampler.set_input_rate(24_000.0);
+        assert_eq!(resampler.input_rate(), 24_000.0);
+        let mut buffer = [[0.0f32; 1]; 48_000];
+        resampler.audio_requested(&mut buffer, 48_000.0);
+        assert!((frequency(&buffer, 48_000.0) - 1_000.0).abs() < 1.0);
+    }
+
+    /// The power of the aliases of the odd harmonics of a hard-clipped 5 kHz sine at 48 kHz, which
+    /// would otherwise lie at 25, 35, 45, 55 and 65 kHz.
+    fn alias_power<N: Node<[f32; 1]>>(node: &mut N) -> f64 {
+        let sample_hz = 48_000.0;
+        let mut buffer = testing::sine::<[f32; 1]>(5_280, 5_000.0, 1.0, sample_hz);
+        for chunk in buffer.chunks_mut(256) {
+            node.audio_requested(chunk, sample_hz);
+        }
+        // Half a kilohertz per cycle within the window, so that every frequency fits exactly.
+        let window = &buffer[480..];
+        [23_000.0, 13_000.0, 3_000.0, 7_000.0, 17_000.0]
+            .iter()
+            .map(|&hz| testing::power_at(window, hz, sample_hz))
+            .sum()
+    }
+
+    #[test]
+    fn oversampling_a_waveshaper_reduces_aliasing() {
+        let shaper = || Waveshaper::new(10.0, TransferFunction::HardClip);
+        let plain = alias_power(&mut shaper());
+        for &factor in &[OversampleFactor::X2, OversampleFactor::X4] {
+            let oversampled = alias_power(&mut Oversample::new(shaper(), factor));
+            assert!(
+                oversampled * 10.0 < plain,
+                "{:?}: {} {}",
+                factor,
+                oversampled,
+                plain
+            );
+        }
+    }
+
+    #[test]
+    fn oversampling_passes_low_frequencies_after_its_latency() {
+        let mut node = Oversample::new(
+            Waveshaper::new(1.0, TransferFunction::HardClip),
+            OversampleFactor::X2,
+        );
+        assert_eq!(
+            Node::<[f32; 1]>::latency_samples(&node),
+            OVERSAMPLE_PHASE_TAPS - 1
+        );
+        let input = testing::sine::<[f32; 1]>(1_024, 500.0, 0.5, 48_000.0);
+        let mut output = input.clone();
+        node.audio_requested(&mut output, 48_000.0);
+        let latency = OVERSAMPLE_PHASE_TAPS - 1;
+        for (out, input) in output[latency + 256..].iter().zip(&input[256..]) {
+            assert!((out[0] - input[0]).abs() < 0.01, "{} {}", out[0], input[0]);
+        }
+    }
+
+    /// The largest difference between the buffer and a sine at `hz`, away from either end.
+    fn max_error_from_sine(buffer: &[[f32; 1]], hz: f64, sample_hz: f64) -> f32 {
+        let expected = testing::sine::<[f32; 1]>(buffer.len(), hz, 0.5, sample_hz);
+        let margin = buffer.len() / 10;
+        buffer[margin..buffer.len() - margin]
+            .iter()
+            .zip(&expected[margin..])
+            .map(|(a, b)| (a[0] - b[0]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn resampling_a_sine_scales_its_length_and_keeps_its_frequency() {
+        let input = testing::sine::<[f32; 1]>(4_410, 1_000.0, 0.5, 44_100.0);
+        for &(interpolation, tolerance) in
+            &[(Interpolation::Linear, 0.01), (Interpolation::Sinc, 3e-3)]
+        {
+            let up = resample(&input, 44_100.0, 48_000.0, interpolation);
+            assert_eq!(up.len(), 4_800);
+            let error = max_error_from_sine(&up, 1_000.0, 48_000.0);
+            assert!(error < tolerance, "{:?}: {}", interpolation, error);
+
+            let down = resample(&input, 44_100.0, 16_000.0, interpolation);
+            assert_eq!(down.len(), 1_600);
+            let error = max_error_from_sine(&down, 1_000.0, 16_000.0);
+            assert!(error < tolerance, "{:?}: {}", interpolation, error);
+        }
+        assert!(resample(&input, 0.0, 48_000.0, Interpolation::Sinc).is_empty());
+    }
+}
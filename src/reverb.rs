@@ -0,0 +1,1604 @@
+//! Reverberation and convolution nodes.
+
+use crate::delay::DelayLine;
+use crate::fft::{Complex, Fft};
+use crate::node::{Node, Settings};
+use crate::resample;
+use crate::util;
+use sample::{self, Frame, Sample};
+
+/// The number of delay lines used by `Fdn::new`.
+pub const DEFAULT_FDN_LINES: usize = 4;
+
+/// The lengths in milliseconds of the all-pass filters that diffuse the input before it enters the
+/// delay lines.
+const DIFFUSER_MS: [f64; 4] = [12.6, 10.0, 7.7, 5.1];
+
+/// The feedback coefficient of each diffusing all-pass filter.
+const DIFFUSER_GAIN: f64 = 0.5;
+
+/// The shortest delay line length in milliseconds for a `room_size` of `0.5`. The longest line
+/// is up to 1.5 times this length, with the others spaced exponentially between.
+const BASE_DELAY_MS: f64 = 45.0;
+
+/// The lengths in frames at 44.1 kHz of the parallel comb filters of a **Freeverb**.
+const FREEVERB_COMB_FRAMES: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+
+/// The lengths in frames at 44.1 kHz of the series all-pass filters of a **Freeverb**.
+const FREEVERB_ALLPASS_FRAMES: [usize; 4] = [556, 441, 341, 225];
+
+/// The number of frames at 44.1 kHz by which each filter of a **Freeverb**'s right channel is
+/// longer than that of the left, decorrelating the channels.
+const FREEVERB_STEREO_SPREAD: usize = 23;
+
+/// The gain applied to the input of a **Freeverb**, compensating for the sum of its combs.
+const FREEVERB_INPUT_GAIN: f64 = 0.03;
+
+/// The gain applied to the output of a **Freeverb**.
+const FREEVERB_OUTPUT_GAIN: f64 = 3.0;
+
+/// The length in frames of each partition of a **Convolver**'s impulse response.
+///
+/// The first partition is convolved directly, frame by frame, so impulse responses no longer than
+/// this cost nothing more. Each later partition is convolved in the frequency domain once per
+/// partition's worth of frames.
+pub const CONVOLVER_PARTITION_FRAMES: usize = 64;
+
+/// The parameters of an **Fdn**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FdnParams {
+    /// The size of the simulated room (0.0 ... 1.0), affecting both line lengths and decay time.
+    pub room_size: f64,
+    /// The amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub damping: f64,
+    /// The amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+}
+
+/// A feedback delay network reverb **Node**.
+///
+/// The input channels are averaged, smeared in time by a series of all-pass filters and fed into a
+/// number of delay lines whose lengths are mutually prime, so that their echoes rarely coincide.
+/// The output of every line is passed through a one-pole low-pass filter for damping, attenuated
+/// according to the decay time and mixed back into every line through an orthogonal Hadamard
+/// matrix, quickly building a dense, exponentially decaying tail.
+///
+/// Each output channel takes a different signed sum of the lines, decorrelating the channels of
+/// a stereo output.
+///
+/// The `room_size` scales the line lengths from half to one and a half times their base lengths
+/// and sets the time taken for the tail to decay by 60 dB from 0.3 to 5 seconds. As with the
+/// **Delay**, the `mix` is applied by the `Graph` via `Node::mix`, so the **Fdn** itself renders a
+/// fully wet signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fdn {
+    room_size: f64,
+    damping: f64,
+    mix: f32,
+    lines: Vec<DelayLine>,
+    /// The all-pass filters through which the input is passed, in order.
+    diffusers: Vec<DelayLine>,
+    /// The gain applied to the output of each line before it is fed back.
+    gains: Vec<f64>,
+    /// The state of the damping filter for each line.
+    filters: Vec<f64>,
+    /// Working space for mixing the lines through the feedback matrix.
+    feedback: Vec<f64>,
+    /// The sample rate and room size for which the line lengths and gains were last calculated.
+    prepared: Option<(f64, f64)>,
+}
+
+impl Fdn {
+    /// Construct an **Fdn** with `DEFAULT_FDN_LINES` delay lines.
+    pub fn new(room_size: f64, damping: f64) -> Self {
+        Fdn::with_lines(DEFAULT_FDN_LINES, room_size, damping)
+    }
+
+    /// Construct an **Fdn** with the given number of delay lines and a fully wet mix.
+    ///
+    /// More lines produce a denser tail at a greater cost.
+    ///
+    /// **Panics** unless `lines` is a power of two, as required by the Hadamard feedback matrix.
+    pub fn with_lines(lines: usize, room_size: f64, damping: f64) -> Self {
+        assert!(
+            lines.is_power_of_two(),
+            "the number of FDN lines must be a power of two"
+        );
+        Fdn {
+            room_size: room_size.clamp(0.0, 1.0),
+            damping: damping.clamp(0.0, 1.0),
+            mix: 1.0,
+            lines: vec![DelayLine::default(); lines],
+            diffusers: vec![DelayLine::default(); DIFFUSER_MS.len()],
+            gains: vec![0.0; lines],
+            filters: vec![0.0; lines],
+            feedback: vec![0.0; lines],
+            prepared: None,
+        }
+    }
+
+    /// The number of delay lines.
+    pub fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The size of the simulated room (0.0 ... 1.0).
+    pub fn room_size(&self) -> f64 {
+        self.room_size
+    }
+
+    /// Set the size of the simulated room (0.0 ... 1.0).
+    pub fn set_room_size(&mut self, room_size: f64) {
+        self.room_size = room_size.clamp(0.0, 1.0);
+    }
+
+    /// The amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub fn damping(&self) -> f64 {
+        self.damping
+    }
+
+    /// Set the amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    /// The amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The time in seconds taken for the tail to decay by 60 dB.
+    pub fn decay_secs(&self) -> f64 {
+        0.3 + self.room_size * 4.7
+    }
+
+    /// The parameters of the **Fdn**.
+    pub fn params(&self) -> FdnParams {
+        FdnParams {
+            room_size: self.room_size,
+            damping: self.damping,
+            mix: self.mix,
+        }
+    }
+
+    /// Apply the given parameters, leaving the audio within the delay lines untouched.
+    pub fn set_params(&mut self, params: FdnParams) {
+        self.set_room_size(params.room_size);
+        self.set_damping(params.damping);
+        self.set_mix(params.mix);
+    }
+
+    /// Silence the delay lines, diffusers and damping filters.
+    pub fn reset(&mut self) {
+        for line in self.lines.iter_mut().chain(self.diffusers.iter_mut()) {
+            line.clear();
+        }
+        for y in self.filters.iter_mut() {
+            *y = 0.0;
+        }
+    }
+
+    /// Calculate the length and feedback gain of each line for the given sample rate.
+    fn prepare_for(&mut self, sample_hz: f64) {
+        for (diffuser, &ms) in self.diffusers.iter_mut().zip(DIFFUSER_MS.iter()) {
+            let len = next_prime((ms / 1_000.0 * sample_hz).round() as usize);
+            diffuser.resize(len, 1);
+        }
+        let n = self.lines.len();
+        let scale = 0.5 + self.room_size;
+        let decay_frames = self.decay_secs() * sample_hz;
+        let mut prev_len = 0;
+        for (i, (line, gain)) in self.lines.iter_mut().zip(self.gains.iter_mut()).enumerate() {
+            let ms = BASE_DELAY_MS * scale * 1.5f64.powf(i as f64 / n as f64);
+            let target = (ms / 1_000.0 * sample_hz).round() as usize;
+            // Distinct primes are always mutually prime.
+            let len = next_prime(target.max(prev_len + 1));
+            prev_len = len;
+            line.resize(len, 1);
+            // Attenuate by 60 dB over the decay time.
+            *gain = 10f64.powf(-3.0 * len as f64 / decay_frames);
+        }
+        self.prepared = Some((sample_hz, self.room_size));
+    }
+}
+
+impl<F> Node<F> for Fdn
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        if self.prepared != Some((sample_hz, self.room_size)) {
+            self.prepare_for(sample_hz);
+        }
+
+        let n = self.lines.len();
+        let channels = F::n_channels();
+        let damping = self.damping * 0.95;
+        let Fdn {
+            ref mut lines,
+            ref mut diffusers,
+            ref gains,
+            ref mut filters,
+            ref mut feedback,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut input = frame.channels().map(util::to_f64).sum::<f64>() / channels as f64;
+            for diffuser in diffusers.iter_mut() {
+                input = allpass(diffuser, input);
+            }
+
+            // Read, damp and attenuate the output of each line.
+            for i in 0..n {
+                let out = lines[i].oldest(0);
+                filters[i] = util::flush_denormal(out + (filters[i] - out) * damping);
+                feedback[i] = filters[i] * gains[i];
+            }
+            let outputs = F::from_fn(|channel| {
+                let sum: f64 = (0..n)
+                    .map(|i| hadamard_sign(channel, i) * lines[i].oldest(0))
+                    .sum();
+                util::from_f64(sum / n as f64)
+            });
+
+            // Mix the lines through the feedback matrix and write them back along with the input.
+            hadamard(feedback);
+            for (line, &fb) in lines.iter_mut().zip(feedback.iter()) {
+                line.write(0, util::flush_denormal(input + fb));
+                line.advance();
+            }
+            outputs
+        });
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `room_size`: the size of the simulated room (0.0 ... 1.0).
+    /// - `damping`: the absorption of high frequencies (0.0 ... 1.0).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["room_size", "damping", "mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "room_size" => Some(self.room_size as f32),
+            "damping" => Some(self.damping as f32),
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "room_size" => self.set_room_size(value as f64),
+            "damping" => self.set_damping(value as f64),
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 {
+            self.prepare_for(settings.sample_hz);
+        }
+        Fdn::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Fdn::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "fdn"
+    }
+}
+
+/// Pass `x` through the Schroeder all-pass filter whose state is held within `line`.
+#[inline]
+fn allpass(line: &mut DelayLine, x: f64) -> f64 {
+    let delayed = line.oldest(0);
+    let w = util::flush_denormal(x + DIFFUSER_GAIN * delayed);
+    line.write(0, w);
+    line.advance();
+    delayed - DIFFUSER_GAIN * w
+}
+
+/// The sign of the element of the Hadamard matrix at the given row and column.
+#[inline]
+fn hadamard_sign(row: usize, column: usize) -> f64 {
+    if (row & column).count_ones() & 1 == 0 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// Multiply `values` by the normalised (and therefore orthogonal) Hadamard matrix in place, using
+/// the fast Walsh-Hadamard transform. `values.len()` must be a power of two.
+fn hadamard(values: &mut [f64]) {
+    let n = values.len();
+    let mut half = 1;
+    while half < n {
+        for start in (0..n).step_by(half * 2) {
+            for i in start..start + half {
+                let a = values[i];
+                let b = values[i + half];
+                values[i] = a + b;
+                values[i + half] = a - b;
+            }
+        }
+        half *= 2;
+    }
+    let scale = 1.0 / (n as f64).sqrt();
+    for x in values.iter_mut() {
+        *x *= scale;
+    }
+}
+
+/// The smallest prime greater than or equal to `n`.
+fn next_prime(n: usize) -> usize {
+    let is_prime = |x: usize| {
+        x >= 2
+            && (2..)
+                .take_while(|d| d * d <= x)
+                .all(|d| !x.is_multiple_of(d))
+    };
+    (n..)
+        .find(|&x| is_prime(x))
+        .expect("there is always a larger prime")
+}
+
+/// The parameters of a **Comb**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CombParams {
+    /// The delay between echoes in frames.
+    pub delay_samples: usize,
+    /// The amount of each echo fed back into the delay line.
+    pub feedback: f64,
+    /// The amount by which high frequencies are absorbed with each echo (0.0 ... 1.0).
+    pub damping: f64,
+}
+
+/// A feedback comb filter **Node**, the building block of Schroeder and Freeverb style reverbs.
+///
+/// Each channel is delayed by `delay_samples` frames, passed through a one-pole low-pass filter
+/// according to the `damping` and fed back into the delay line, so an impulse is repeated every
+/// `delay_samples` frames with each echo quieter and duller than the last. The output is the
+/// delayed signal alone, so the first echo of an impulse arrives after `delay_samples` frames.
+///
+/// Combining several **Comb**s of mutually prime lengths in parallel, followed by a series of
+/// all-pass filters, produces a classic artificial reverb.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comb {
+    delay_samples: usize,
+    feedback: f64,
+    damping: f64,
+    line: DelayLine,
+    /// The state of the damping filter for each channel.
+    filters: Vec<f64>,
+}
+
+impl Comb {
+    /// Construct a **Comb** with the given delay in frames and feedback, without damping.
+    pub fn new(delay_samples: usize, feedback: f64) -> Self {
+        let mut comb = Comb {
+            delay_samples: 1,
+            feedback: 0.0,
+            damping: 0.0,
+            line: DelayLine::default(),
+            filters: Vec::new(),
+        };
+        comb.set_delay_samples(delay_samples);
+        comb.set_feedback(feedback);
+        comb
+    }
+
+    /// The delay between echoes in frames.
+    pub fn delay_samples(&self) -> usize {
+        self.delay_samples
+    }
+
+    /// Set the delay between echoes in frames.
+    ///
+    /// Delays shorter than one frame are lengthened to one frame. Re-sizing the delay line keeps
+    /// the most recent audio.
+    pub fn set_delay_samples(&mut self, delay_samples: usize) {
+        self.delay_samples = delay_samples.max(1);
+    }
+
+    /// The amount of each echo fed back into the delay line.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Set the amount of each echo fed back into the delay line.
+    ///
+    /// Clamped to the range -1.0 ... 1.0 exclusive so that the echoes always decay.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        const MAX_FEEDBACK: f64 = 0.999;
+        self.feedback = feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+    }
+
+    /// The amount by which high frequencies are absorbed with each echo (0.0 ... 1.0).
+    pub fn damping(&self) -> f64 {
+        self.damping
+    }
+
+    /// Set the amount by which high frequencies are absorbed with each echo (0.0 ... 1.0).
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping.clamp(0.0, 1.0);
+    }
+
+    /// The parameters of the **Comb**.
+    pub fn params(&self) -> CombParams {
+        CombParams {
+            delay_samples: self.delay_samples,
+            feedback: self.feedback,
+            damping: self.damping,
+        }
+    }
+
+    /// Apply the given parameters, leaving the audio within the delay line untouched.
+    pub fn set_params(&mut self, params: CombParams) {
+        self.set_delay_samples(params.delay_samples);
+        self.set_feedback(params.feedback);
+        self.set_damping(params.damping);
+    }
+
+    /// Silence the delay line and damping filters.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        for y in self.filters.iter_mut() {
+            *y = 0.0;
+        }
+    }
+
+    /// Size the delay line and damping filters for the given number of channels.
+    fn prepare_for(&mut self, channels: usize) {
+        self.line.resize(self.delay_samples, channels);
+        self.filters.resize(channels, 0.0);
+    }
+}
+
+impl<F> Node<F> for Comb
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        self.prepare_for(F::n_channels());
+        let feedback = self.feedback;
+        let damping = self.damping;
+        let Comb {
+            ref mut line,
+            ref mut filters,
+            ..
+        } = *self;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let out = line.oldest(channel);
+                let y = &mut filters[channel];
+                *y = util::flush_denormal(out + (*y - out) * damping);
+                line.write(
+                    channel,
+                    util::flush_denormal(util::to_f64(s) + *y * feedback),
+                );
+                channel += 1;
+                util::from_f64(out)
+            });
+            line.advance();
+            frame
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `delay_samples`: the delay between echoes in frames.
+    /// - `feedback`: the amount of each echo fed back (-1.0 ... 1.0 exclusive).
+    /// - `damping`: the absorption of high frequencies (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["delay_samples", "feedback", "damping"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "delay_samples" => Some(self.delay_samples as f32),
+            "feedback" => Some(self.feedback as f32),
+            "damping" => Some(self.damping as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "delay_samples" => self.set_delay_samples(value.max(0.0).round() as usize),
+            "feedback" => self.set_feedback(value as f64),
+            "damping" => self.set_damping(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, _settings: Settings) {
+        self.prepare_for(F::n_channels());
+        Comb::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Comb::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "comb"
+    }
+}
+
+/// The parameters of an **AllPass**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllPassParams {
+    /// The length of the delay line in frames.
+    pub delay_samples: usize,
+    /// The feedback coefficient, which also sets the amount of the input passed straight through.
+    pub feedback: f64,
+}
+
+/// A Schroeder all-pass filter **Node**, used to diffuse the echoes of a reverb.
+///
+/// Each channel is delayed by `delay_samples` frames with feedback, and the input is mixed into the
+/// output inverted and scaled by the `feedback` so that every frequency passes at unity gain. An
+/// impulse is therefore smeared into a train of echoes `delay_samples` frames apart without
+/// colouring the tone, which makes a series of **AllPass**es ideal for thickening the output of
+/// parallel **Comb**s, or for diffusing a signal before it enters them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AllPass {
+    delay_samples: usize,
+    feedback: f64,
+    line: DelayLine,
+}
+
+impl AllPass {
+    /// Construct an **AllPass** with the given delay in frames and feedback coefficient.
+    pub fn new(delay_samples: usize, feedback: f64) -> Self {
+        let mut allpass = AllPass {
+            delay_samples: 1,
+            feedback: 0.0,
+            line: DelayLine::default(),
+        };
+        allpass.set_delay_samples(delay_samples);
+        allpass.set_feedback(feedback);
+        allpass
+    }
+
+    /// The length of the delay line in frames.
+    pub fn delay_samples(&self) -> usize {
+        self.delay_samples
+    }
+
+    /// Set the length of the delay line in frames.
+    ///
+    /// Delays shorter than one frame are lengthened to one frame. Re-sizing the delay line keeps
+    /// the most recent audio.
+    pub fn set_delay_samples(&mut self, delay_samples: usize) {
+        self.delay_samples = delay_samples.max(1);
+    }
+
+    /// The feedback coefficient.
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// Set the feedback coefficient.
+    ///
+    /// Clamped to the range -1.0 ... 1.0 exclusive so that the echoes always decay.
+    pub fn set_feedback(&mut self, feedback: f64) {
+        const MAX_FEEDBACK: f64 = 0.999;
+        self.feedback = feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK);
+    }
+
+    /// The parameters of the **AllPass**.
+    pub fn params(&self) -> AllPassParams {
+        AllPassParams {
+            delay_samples: self.delay_samples,
+            feedback: self.feedback,
+        }
+    }
+
+    /// Apply the given parameters, leaving the audio within the delay line untouched.
+    pub fn set_params(&mut self, params: AllPassParams) {
+        self.set_delay_samples(params.delay_samples);
+        self.set_feedback(params.feedback);
+    }
+
+    /// Silence the delay line.
+    pub fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+impl<F> Node<F> for AllPass
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        self.line.resize(self.delay_samples, F::n_channels());
+        let feedback = self.feedback;
+        let line = &mut self.line;
+        sample::slice::map_in_place(buffer, |frame| {
+            let mut channel = 0;
+            let frame = frame.map(|s| {
+                let delayed = line.oldest(channel);
+                let w = util::flush_denormal(util::to_f64(s) + feedback * delayed);
+                line.write(channel, w);
+                channel += 1;
+                util::from_f64(delayed - feedback * w)
+            });
+            line.advance();
+            frame
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `delay_samples`: the length of the delay line in frames.
+    /// - `feedback`: the feedback coefficient (-1.0 ... 1.0 exclusive).
+    fn param_names(&self) -> &[&str] {
+        &["delay_samples", "feedback"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "delay_samples" => Some(self.delay_samples as f32),
+            "feedback" => Some(self.feedback as f32),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "delay_samples" => self.set_delay_samples(value.max(0.0).round() as usize),
+            "feedback" => self.set_feedback(value as f64),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, _settings: Settings) {
+        self.line.resize(self.delay_samples, F::n_channels());
+        AllPass::reset(self);
+    }
+
+    fn reset(&mut self) {
+        AllPass::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "all_pass"
+    }
+}
+
+/// The parameters of a **Freeverb**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreeverbParams {
+    /// The size of the simulated room (0.0 ... 1.0), setting the feedback of the combs.
+    pub room_size: f64,
+    /// The amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub damping: f64,
+    /// The stereo width of the tail (0.0 ... 1.0), from mono to fully separate channels.
+    pub width: f64,
+    /// The amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub mix: f32,
+}
+
+/// The **Comb**s and **AllPass**es of a single channel of a **Freeverb**.
+#[derive(Clone, Debug, PartialEq)]
+struct FreeverbTank {
+    combs: Vec<Comb>,
+    allpasses: Vec<AllPass>,
+    /// The rendered output of the tank for the current buffer.
+    output: Vec<[f64; 1]>,
+}
+
+/// A **Node** implementing Jezar's classic Freeverb algorithm.
+///
+/// The input channels are averaged and fed into two tanks, one for each side of the stereo image.
+/// Each tank sums eight damped **Comb**s in parallel and diffuses the result through four
+/// **AllPass**es in series. The filters of the right tank are slightly longer than those of the
+/// left, so that the two sides of the tail are decorrelated. All filter lengths are scaled from
+/// their original tunings at 44.1 kHz to the rate at which audio is requested.
+///
+/// The `room_size` sets the feedback of the combs, and so the length of the tail, while the
+/// `width` blends the two tanks from a mono tail at `0.0` to fully separate sides at `1.0`. Even
+/// channels take the left side of the image and odd channels the right.
+///
+/// As with the **Fdn**, the `mix` is applied by the `Graph` via `Node::mix`, so the **Freeverb**
+/// itself renders a fully wet signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Freeverb {
+    room_size: f64,
+    damping: f64,
+    width: f64,
+    mix: f32,
+    tanks: [FreeverbTank; 2],
+    /// The averaged input for the current buffer.
+    input: Vec<[f64; 1]>,
+    /// Working space for rendering each comb.
+    scratch: Vec<[f64; 1]>,
+    /// The sample rate for which the filter lengths were last calculated.
+    prepared: Option<f64>,
+}
+
+impl FreeverbTank {
+    fn new() -> Self {
+        FreeverbTank {
+            combs: FREEVERB_COMB_FRAMES
+                .iter()
+                .map(|&frames| Comb::new(frames, 0.0))
+                .collect(),
+            allpasses: FREEVERB_ALLPASS_FRAMES
+                .iter()
+                .map(|&frames| AllPass::new(frames, DIFFUSER_GAIN))
+                .collect(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Render the tank's output for the given input into `self.output`.
+    fn render(&mut self, input: &[[f64; 1]], scratch: &mut Vec<[f64; 1]>, sample_hz: f64) {
+        self.output.clear();
+        self.output.resize(input.len(), [0.0]);
+        for comb in self.combs.iter_mut() {
+            scratch.clear();
+            scratch.extend_from_slice(input);
+            comb.audio_requested(scratch, sample_hz);
+            for (out, comb_out) in self.output.iter_mut().zip(scratch.iter()) {
+                out[0] += comb_out[0];
+            }
+        }
+        for allpass in self.allpasses.iter_mut() {
+            allpass.audio_requested(&mut self.output, sample_hz);
+        }
+    }
+
+    fn reset(&mut self) {
+        for comb in self.combs.iter_mut() {
+            comb.reset();
+        }
+        for allpass in self.allpasses.iter_mut() {
+            allpass.reset();
+        }
+    }
+}
+
+impl Freeverb {
+    /// Construct a **Freeverb** with the given room size and damping, full width and a fully wet
+    /// mix.
+    pub fn new(room_size: f64, damping: f64) -> Self {
+        let mut freeverb = Freeverb {
+            room_size: 0.0,
+            damping: 0.0,
+            width: 1.0,
+            mix: 1.0,
+            tanks: [FreeverbTank::new(), FreeverbTank::new()],
+            input: Vec::new(),
+            scratch: Vec::new(),
+            prepared: None,
+        };
+        freeverb.set_room_size(room_size);
+        freeverb.set_damping(damping);
+        freeverb
+    }
+
+    /// The size of the simulated room (0.0 ... 1.0).
+    pub fn room_size(&self) -> f64 {
+        self.room_size
+    }
+
+    /// Set the size of the simulated room (0.0 ... 1.0).
+    pub fn set_room_size(&mut self, room_size: f64) {
+        self.room_size = room_size.clamp(0.0, 1.0);
+        let feedback = 0.7 + self.room_size * 0.28;
+        for tank in self.tanks.iter_mut() {
+            for comb in tank.combs.iter_mut() {
+                comb.set_feedback(feedback);
+            }
+        }
+    }
+
+    /// The amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub fn damping(&self) -> f64 {
+        self.damping
+    }
+
+    /// Set the amount by which high frequencies are absorbed with each reflection (0.0 ... 1.0).
+    pub fn set_damping(&mut self, damping: f64) {
+        self.damping = damping.clamp(0.0, 1.0);
+        for tank in self.tanks.iter_mut() {
+            for comb in tank.combs.iter_mut() {
+                comb.set_damping(self.damping * 0.4);
+            }
+        }
+    }
+
+    /// The stereo width of the tail (0.0 ... 1.0).
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    /// Set the stereo width of the tail (0.0 ... 1.0).
+    pub fn set_width(&mut self, width: f64) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    /// The amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the reverberated (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// The parameters of the **Freeverb**.
+    pub fn params(&self) -> FreeverbParams {
+        FreeverbParams {
+            room_size: self.room_size,
+            damping: self.damping,
+            width: self.width,
+            mix: self.mix,
+        }
+    }
+
+    /// Apply the given parameters, leaving the audio within the filters untouched.
+    pub fn set_params(&mut self, params: FreeverbParams) {
+        self.set_room_size(params.room_size);
+        self.set_damping(params.damping);
+        self.set_width(params.width);
+        self.set_mix(params.mix);
+    }
+
+    /// Silence every comb and all-pass filter.
+    pub fn reset(&mut self) {
+        for tank in self.tanks.iter_mut() {
+            tank.reset();
+        }
+    }
+
+    /// Scale the length of every filter to the given sample rate.
+    fn prepare_for(&mut self, sample_hz: f64) {
+        let scale = sample_hz / 44_100.0;
+        let frames = |frames: usize| ((frames as f64 * scale).round() as usize).max(1);
+        for (side, tank) in self.tanks.iter_mut().enumerate() {
+            let spread = side * FREEVERB_STEREO_SPREAD;
+            for (comb, &len) in tank.combs.iter_mut().zip(FREEVERB_COMB_FRAMES.iter()) {
+                comb.set_delay_samples(frames(len + spread));
+            }
+            for (allpass, &len) in tank
+                .allpasses
+                .iter_mut()
+                .zip(FREEVERB_ALLPASS_FRAMES.iter())
+            {
+                allpass.set_delay_samples(frames(len + spread));
+            }
+        }
+        self.prepared = Some(sample_hz);
+    }
+}
+
+impl<F> Node<F> for Freeverb
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        if self.prepared != Some(sample_hz) {
+            self.prepare_for(sample_hz);
+        }
+
+        let channels = F::n_channels();
+        self.input.clear();
+        self.input.extend(buffer.iter().map(|frame| {
+            let sum = frame.channels().map(util::to_f64).sum::<f64>();
+            [sum / channels as f64 * FREEVERB_INPUT_GAIN]
+        }));
+        let Freeverb {
+            ref mut tanks,
+            ref input,
+            ref mut scratch,
+            width,
+            ..
+        } = *self;
+        for tank in tanks.iter_mut() {
+            tank.render(input, scratch, sample_hz);
+        }
+
+        // Blend the two sides according to the width.
+        let direct = (0.5 + width / 2.0) * FREEVERB_OUTPUT_GAIN;
+        let crossed = (1.0 - width) / 2.0 * FREEVERB_OUTPUT_GAIN;
+        let (left, right) = (&tanks[0].output, &tanks[1].output);
+        for (i, frame) in buffer.iter_mut().enumerate() {
+            let (l, r) = (left[i][0], right[i][0]);
+            let mut channel = 0;
+            *frame = frame.map(|_| {
+                let (near, far) = if channel % 2 == 0 { (l, r) } else { (r, l) };
+                channel += 1;
+                util::from_f64(near * direct + far * crossed)
+            });
+        }
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `room_size`: the size of the simulated room (0.0 ... 1.0).
+    /// - `damping`: the absorption of high frequencies (0.0 ... 1.0).
+    /// - `width`: the stereo width of the tail (0.0 ... 1.0).
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["room_size", "damping", "width", "mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "room_size" => Some(self.room_size as f32),
+            "damping" => Some(self.damping as f32),
+            "width" => Some(self.width as f32),
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "room_size" => self.set_room_size(value as f64),
+            "damping" => self.set_damping(value as f64),
+            "width" => self.set_width(value as f64),
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 {
+            self.prepare_for(settings.sample_hz);
+        }
+        self.input.reserve(settings.max_frames);
+        self.scratch.reserve(settings.max_frames);
+        for tank in self.tanks.iter_mut() {
+            tank.output.reserve(settings.max_frames);
+        }
+        Freeverb::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Freeverb::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "freeverb"
+    }
+}
+
+/// A **Node** that convolves its input with an impulse response, e.g. that of a room or of a
+/// speaker cabinet.
+///
+/// The first `CONVOLVER_PARTITION_FRAMES` frames of the impulse response are convolved directly.
+/// The remainder is split into partitions of the same length, which are convolved with blocks of
+/// the input via overlap-add FFT convolution. As each block of input is completed, its spectrum is
+/// multiplied with that of every partition, so the cost per frame grows with the length of the
+/// impulse response but remains bounded for every buffer. The partitions of the tail begin exactly
+/// where the direct convolution ends, so the **Convolver** adds no latency.
+///
+/// The same mono impulse response is applied to every channel. It is resampled to the rate at
+/// which audio is requested the first time audio is requested at that rate, scaled so that its
+/// frequency response is unchanged. Until an impulse response is loaded, the output is silent.
+///
+/// As with the **Fdn**, the `mix` is applied by the `Graph` via `Node::mix`, so the **Convolver**
+/// itself renders a fully wet signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Convolver {
+    /// The impulse response as loaded.
+    ir: Vec<f64>,
+    ir_hz: f64,
+    mix: f32,
+    /// The first partition of the resampled impulse response, reversed.
+    head: Vec<f64>,
+    /// The spectrum of each later partition of the resampled impulse response.
+    partitions: Vec<Vec<Complex>>,
+    fft: Fft,
+    channels: Vec<ConvolverChannel>,
+    /// The position of the next frame within the current block of input.
+    position: usize,
+    /// The index within each channel's `spectra` of the most recent block's spectrum.
+    newest: usize,
+    /// Working space for the spectrum of each block and its convolution.
+    spectrum: Vec<Complex>,
+    accumulated: Vec<Complex>,
+    /// The sample rate and channel count for which the partitions and state were last prepared.
+    prepared: Option<(f64, usize)>,
+}
+
+/// The state of a single channel of a **Convolver**.
+#[derive(Clone, Debug, PartialEq)]
+struct ConvolverChannel {
+    /// The two most recent blocks of input. Each frame is written at both `position` and
+    /// `position + CONVOLVER_PARTITION_FRAMES`, so the latest block is always contiguous.
+    history: Vec<f64>,
+    /// The input of the current block.
+    block: Vec<f64>,
+    /// The spectrum of each of the most recent blocks of input, one per partition.
+    spectra: Vec<Vec<Complex>>,
+    /// The output of the tail for the current block.
+    tail: Vec<f64>,
+    /// The second half of the last block's convolution, overlapping the next block.
+    overlap: Vec<f64>,
+}
+
+impl ConvolverChannel {
+    fn new(partitions: usize) -> Self {
+        let n = CONVOLVER_PARTITION_FRAMES;
+        ConvolverChannel {
+            history: vec![0.0; 2 * n],
+            block: vec![0.0; n],
+            spectra: vec![vec![Complex::default(); 2 * n]; partitions],
+            tail: vec![0.0; n],
+            overlap: vec![0.0; n],
+        }
+    }
+
+    fn reset(&mut self) {
+        for x in self
+            .history
+            .iter_mut()
+            .chain(self.block.iter_mut())
+            .chain(self.tail.iter_mut())
+            .chain(self.overlap.iter_mut())
+        {
+            *x = 0.0;
+        }
+        for spectrum in &mut self.spectra {
+            for c in spectrum.iter_mut() {
+                *c = Complex::default();
+            }
+        }
+    }
+}
+
+impl Convolver {
+    /// Construct a **Convolver** with no impulse response and a fully wet mix.
+    pub fn new() -> Self {
+        Convolver {
+            ir: Vec::new(),
+            ir_hz: 0.0,
+            mix: 1.0,
+            head: Vec::new(),
+            partitions: Vec::new(),
+            fft: Fft::new(2 * CONVOLVER_PARTITION_FRAMES),
+            channels: Vec::new(),
+            position: 0,
+            newest: 0,
+            spectrum: vec![Complex::default(); 2 * CONVOLVER_PARTITION_FRAMES],
+            accumulated: vec![Complex::default(); 2 * CONVOLVER_PARTITION_FRAMES],
+            prepared: None,
+        }
+    }
+
+    /// Load the impulse response with which the input is convolved, recorded at `ir_sample_hz`.
+    ///
+    /// The impulse response is resampled the next time audio is requested if `ir_sample_hz`
+    /// differs from the rate at which audio is requested. If `ir_sample_hz` is not positive, the
+    /// impulse response is assumed to already be at that rate.
+    ///
+    /// The state of the convolution is cleared, so any tail of the previous impulse response is
+    /// cut off.
+    pub fn load_ir<S>(&mut self, ir: &[S], ir_sample_hz: f64)
+    where
+        S: Sample,
+    {
+        self.ir.clear();
+        self.ir.extend(ir.iter().map(|&s| util::to_f64(s)));
+        self.ir_hz = ir_sample_hz;
+        self.prepared = None;
+    }
+
+    /// The length in frames of the impulse response as loaded.
+    pub fn ir_len(&self) -> usize {
+        self.ir.len()
+    }
+
+    /// The amount of the convolved (wet) signal in the output (0.0 ... 1.0).
+    pub fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Set the amount of the convolved (wet) signal in the output (0.0 ... 1.0).
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// Silence the convolution, discarding any tail still ringing out.
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+        self.position = 0;
+        self.newest = 0;
+    }
+
+    /// Resample and partition the impulse response for the given sample rate, and allocate the
+    /// state for the given number of channels.
+    fn prepare_for(&mut self, sample_hz: f64, channels: usize) {
+        let n = CONVOLVER_PARTITION_FRAMES;
+        let ir = if self.ir_hz > 0.0 && self.ir_hz != sample_hz {
+            // Scale by the ratio of the rates, so that the gain of the impulse response is
+            // independent of the number of frames it spans.
+            let scale = self.ir_hz / sample_hz;
+            let mut ir = resample::resample_signal(&self.ir, self.ir_hz, sample_hz);
+            for x in &mut ir {
+                *x *= scale;
+            }
+            ir
+        } else {
+            self.ir.clone()
+        };
+
+        let head_len = ir.len().min(n);
+        self.head = ir[..head_len].iter().rev().cloned().collect();
+        let fft = &self.fft;
+        self.partitions = ir[head_len..]
+            .chunks(n)
+            .map(|taps| {
+                let mut spectrum = vec![Complex::default(); 2 * n];
+                for (c, &tap) in spectrum.iter_mut().zip(taps) {
+                    c.re = tap;
+                }
+                fft.forward(&mut spectrum);
+                spectrum
+            })
+            .collect();
+        self.channels = vec![ConvolverChannel::new(self.partitions.len()); channels];
+        self.position = 0;
+        self.newest = 0;
+        self.prepared = Some((sample_hz, channels));
+    }
+
+    /// Convolve the block of input just completed with the tail of the impulse response, producing
+    /// the tail's output for the next block.
+    fn convolve_block(&mut self) {
+        let n = CONVOLVER_PARTITION_FRAMES;
+        let count = self.partitions.len();
+        if count == 0 {
+            return;
+        }
+        self.newest = (self.newest + 1) % count;
+        let Convolver {
+            ref partitions,
+            ref fft,
+            ref mut channels,
+            ref mut spectrum,
+            ref mut accumulated,
+            newest,
+            ..
+        } = *self;
+        for channel in channels.iter_mut() {
+            for (c, &x) in spectrum.iter_mut().zip(channel.block.iter()) {
+                *c = Complex::new(x, 0.0);
+            }
+            for c in spectrum[n..].iter_mut() {
+                *c = Complex::default();
+            }
+            fft.forward(spectrum);
+            channel.spectra[newest].copy_from_slice(spectrum);
+
+            // The block that was `p` blocks ago meets the `p`th partition.
+            for c in accumulated.iter_mut() {
+                *c = Complex::default();
+            }
+            for (p, partition) in partitions.iter().enumerate() {
+                let input = &channel.spectra[(newest + count - p) % count];
+                for ((acc, &x), &h) in accumulated.iter_mut().zip(input).zip(partition) {
+                    let y = x.mul(h);
+                    acc.re += y.re;
+                    acc.im += y.im;
+                }
+            }
+            fft.inverse(accumulated);
+
+            for (i, (tail, overlap)) in channel
+                .tail
+                .iter_mut()
+                .zip(channel.overlap.iter_mut())
+                .enumerate()
+            {
+                *tail = accumulated[i].re + *overlap;
+                *overlap = accumulated[i + n].re;
+            }
+        }
+    }
+}
+
+impl Default for Convolver {
+    fn default() -> Self {
+        Convolver::new()
+    }
+}
+
+impl<F> Node<F> for Convolver
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if sample_hz <= 0.0 {
+            return;
+        }
+        let channels = F::n_channels();
+        if self.prepared != Some((sample_hz, channels)) {
+            self.prepare_for(sample_hz, channels);
+        }
+
+        let n = CONVOLVER_PARTITION_FRAMES;
+        for frame in buffer.iter_mut() {
+            let position = self.position;
+            let Convolver {
+                ref head,
+                channels: ref mut states,
+                ..
+            } = *self;
+            let mut channel = 0;
+            *frame = frame.map(|s| {
+                let state = &mut states[channel];
+                let x = util::to_f64(s);
+                state.block[position] = x;
+                state.history[position] = x;
+                state.history[position + n] = x;
+                let start = position + n + 1 - head.len();
+                let direct = head
+                    .iter()
+                    .zip(&state.history[start..=position + n])
+                    .map(|(&h, &x)| h * x)
+                    .sum::<f64>();
+                channel += 1;
+                util::from_f64(direct + state.tail[position])
+            });
+            self.position += 1;
+            if self.position == n {
+                self.position = 0;
+                self.convolve_block();
+            }
+        }
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix
+    }
+
+    /// Exposes:
+    ///
+    /// - `mix`: the amount of the wet signal (0.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["mix"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "mix" => Some(self.mix),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "mix" => self.set_mix(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        if settings.sample_hz > 0.0 {
+            self.prepare_for(settings.sample_hz, F::n_channels());
+        }
+        Convolver::reset(self);
+    }
+
+    fn reset(&mut self) {
+        Convolver::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "convolver"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn an_fdn_impulse_produces_a_dense_decaying_tail() {
+        let sample_hz = 44_100.0;
+        let mut fdn = Fdn::new(0.3, 0.5);
+        let output: Vec<[f32; 2]> = testing::impulse_response(&mut fdn, 88_200, sample_hz);
+        let windows: Vec<f64> = output[4_410..].chunks(11_025).map(testing::rms).collect();
+        for pair in windows.windows(2) {
+            assert!(pair[1] < pair[0], "{:?}", windows);
+        }
+        assert!(
+            windows[windows.len() - 1] < windows[0] * 0.1,
+            "{:?}",
+            windows
+        );
+        // Discrete echoes would leave most frames between them silent.
+        let tail = &output[8_820..17_640];
+        let dense = tail.iter().filter(|frame| frame[0].abs() > 1e-7).count();
+        assert!(dense as f64 > tail.len() as f64 * 0.95, "{}", dense);
+        // The channels are decorrelated rather than identical.
+        assert!(tail.iter().any(|frame| frame[0] != frame[1]));
+    }
+
+    #[test]
+    fn fdn_lines_are_mutually_prime_and_the_feedback_matrix_orthogonal() {
+        let mut fdn = Fdn::with_lines(8, 0.5, 0.0);
+        fdn.prepare_for(48_000.0);
+        let lens: Vec<usize> = fdn.lines.iter().map(|line| line.len()).collect();
+        for (i, &a) in lens.iter().enumerate() {
+            for &b in &lens[i + 1..] {
+                let (mut x, mut y) = (a, b);
+                while y != 0 {
+                    let r = x % y;
+                    x = y;
+                    y = r;
+                }
+                assert_eq!(x, 1, "{:?}", lens);
+            }
+        }
+        let mut values = [0.5, -1.0, 0.25, 0.0, 2.0, 0.1, -0.3, 0.7];
+        let power: f64 = values.iter().map(|x| x * x).sum();
+        hadamard(&mut values);
+        let mixed: f64 = values.iter().map(|x| x * x).sum();
+        assert!((power - mixed).abs() < 1e-12);
+    }
+
+    #[test]
+    fn fdn_reset_silences_the_tail() {
+        let mut fdn = Fdn::new(0.8, 0.2);
+        let _: Vec<[f32; 2]> = testing::impulse_response(&mut fdn, 4_410, 44_100.0);
+        fdn.reset();
+        let mut buffer = [[0.0f32; 2]; 4_410];
+        fdn.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|&frame| frame == [0.0; 2]));
+    }
+
+    /// White noise with full scale peaks.
+    fn noise(len: usize, seed: u64) -> Vec<[f32; 1]> {
+        let mut rng = crate::noise::RngSource::new(seed);
+        (0..len).map(|_| [rng.next_bipolar() as f32]).collect()
+    }
+
+    /// Render the input through the convolver in buffers of varying length.
+    fn convolve(convolver: &mut Convolver, input: &[[f32; 1]], sample_hz: f64) -> Vec<[f32; 1]> {
+        let mut output = input.to_vec();
+        let mut start = 0;
+        for len in [1, 63, 64, 65, 200, 7].iter().cycle() {
+            if start == output.len() {
+                break;
+            }
+            let end = (start + len).min(output.len());
+            convolver.audio_requested(&mut output[start..end], sample_hz);
+            start = end;
+        }
+        output
+    }
+
+    #[test]
+    fn convolving_with_a_unit_impulse_returns_the_input_unchanged() {
+        let input = noise(1_000, 1);
+        let mut convolver = Convolver::new();
+        convolver.load_ir(&[1.0f32], 44_100.0);
+        let output = convolve(&mut convolver, &input, 44_100.0);
+        for (out, input) in output.iter().zip(&input) {
+            assert!((out[0] - input[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn a_long_impulse_response_matches_direct_convolution() {
+        // Spanning the directly convolved head and many partitions of the tail.
+        let ir: Vec<f32> = noise(1_000, 2).iter().map(|s| s[0] * 0.05).collect();
+        let input = noise(2_000, 3);
+        let mut convolver = Convolver::new();
+        convolver.load_ir(&ir, 44_100.0);
+        let output = convolve(&mut convolver, &input, 44_100.0);
+        for (n, out) in output.iter().enumerate() {
+            let expected: f64 = (0..=n.min(ir.len() - 1))
+                .map(|k| f64::from(ir[k]) * f64::from(input[n - k][0]))
+                .sum();
+            assert!((f64::from(out[0]) - expected).abs() < 1e-4, "frame {}", n);
+        }
+    }
+
+    #[test]
+    fn an_impulse_response_is_resampled_to_the_requested_rate() {
+        // A smooth impulse response at 22.05 kHz keeps its length in time and its gain at DC.
+        let ir: Vec<f32> = (0..100)
+            .map(|i| 0.02 * (::std::f32::consts::PI * i as f32 / 100.0).sin().powi(2))
+            .collect();
+        let dc_gain: f32 = ir.iter().sum();
+        let mut convolver = Convolver::new();
+        convolver.load_ir(&ir, 22_050.0);
+        let output: Vec<[f32; 1]> = testing::step_response(&mut convolver, 1_000, 44_100.0);
+        assert!(
+            (output[999][0] - dc_gain).abs() < dc_gain * 0.01,
+            "{}",
+            output[999][0]
+        );
+        let mut convolver = Convolver::new();
+        convolver.load_ir(&ir, 22_050.0);
+        let response: Vec<[f32; 1]> = testing::impulse_response(&mut convolver, 400, 44_100.0);
+        let last = response.iter().rposition(|s| s[0].abs() > 1e-6).unwrap();
+        assert!((198..210).contains(&last), "{}", last);
+    }
+
+    #[test]
+    fn a_comb_repeats_an_impulse_at_its_delay_with_decaying_echoes() {
+        let mut comb = Comb::new(10, 0.5);
+        let mut output = vec![[0.0f32; 2]; 45];
+        output[0] = [1.0, -1.0];
+        for chunk in output.chunks_mut(4) {
+            comb.audio_requested(chunk, 1_000.0);
+        }
+        for (i, frame) in output.iter().enumerate() {
+            let echo = match i {
+                10 => 1.0,
+                20 => 0.5,
+                30 => 0.25,
+                40 => 0.125,
+                _ => 0.0,
+            };
+            assert_eq!(*frame, [echo, -echo], "frame {}", i);
+        }
+
+        // Damping dulls each echo, spreading it beyond the frame at which it arrives.
+        let mut comb = Comb::new(10, 0.5);
+        comb.set_damping(0.5);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut comb, 45, 1_000.0);
+        assert_eq!(output[10], [1.0]);
+        assert!(output[20][0] < 0.5 && output[21][0] > 0.0);
+        assert!(output[30][0] < output[20][0]);
+    }
+
+    /// The magnitude at `hz` of an impulse response.
+    fn magnitude_at(response: &[[f32; 1]], hz: f64, sample_hz: f64) -> f64 {
+        let step = -2.0 * ::std::f64::consts::PI * hz / sample_hz;
+        let (re, im) = response
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (i, frame)| {
+                let (sin, cos) = (step * i as f64).sin_cos();
+                (re + frame[0] as f64 * cos, im + frame[0] as f64 * sin)
+            });
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn an_allpass_is_flat_but_spreads_an_impulse_in_time() {
+        let mut allpass = AllPass::new(7, 0.5);
+        let output: Vec<[f32; 1]> = testing::impulse_response(&mut allpass, 4_096, 48_000.0);
+        let mut hz = 20.0;
+        while hz < 24_000.0 {
+            let magnitude = magnitude_at(&output, hz, 48_000.0);
+            assert!((magnitude - 1.0).abs() < 0.01, "{} Hz: {}", hz, magnitude);
+            hz *= 1.1;
+        }
+        // The impulse is passed straight through inverted, then echoes every 7 frames.
+        assert_eq!(output[0], [-0.5]);
+        assert_eq!(output[7], [0.75]);
+        assert_eq!(output[14], [0.375]);
+        assert!(output[1..7].iter().all(|frame| frame[0] == 0.0));
+        let energy: f64 = output.iter().map(|frame| (frame[0] as f64).powi(2)).sum();
+        assert!((energy - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn a_freeverb_impulse_produces_a_smooth_dense_decaying_tail() {
+        let sample_hz = 44_100.0;
+        let mut freeverb = Freeverb::new(0.5, 0.5);
+        let output: Vec<[f32; 2]> = testing::impulse_response(&mut freeverb, 88_200, sample_hz);
+        let windows: Vec<f64> = output[4_410..].chunks(11_025).map(testing::rms).collect();
+        for pair in windows.windows(2) {
+            assert!(pair[1] < pair[0], "{:?}", windows);
+        }
+        assert!(
+            windows[windows.len() - 1] < windows[0] * 0.1,
+            "{:?}",
+            windows
+        );
+        // Discrete echoes would leave most frames between them silent.
+        let tail = &output[8_820..17_640];
+        let dense = tail.iter().filter(|frame| frame[0].abs() > 1e-7).count();
+        assert!(dense as f64 > tail.len() as f64 * 0.95, "{}", dense);
+        // The level of successive short windows changes gradually rather than in bursts.
+        let short: Vec<f64> = tail.chunks(441).map(testing::rms).collect();
+        for pair in short.windows(2) {
+            assert!(
+                pair[1] < pair[0] * 2.0 && pair[1] > pair[0] * 0.5,
+                "{:?}",
+                short
+            );
+        }
+        // The sides of the tail are decorrelated, while no width leaves them identical.
+        assert!(tail.iter().any(|frame| frame[0] != frame[1]));
+        freeverb.reset();
+        freeverb.set_width(0.0);
+        let mono: Vec<[f32; 2]> = testing::impulse_response(&mut freeverb, 8_820, sample_hz);
+        assert!(mono.iter().all(|frame| frame[0] == frame[1]));
+    }
+
+    #[test]
+    fn freeverb_reset_silences_the_tail() {
+        let mut freeverb = Freeverb::new(0.9, 0.2);
+        let _: Vec<[f32; 2]> = testing::impulse_response(&mut freeverb, 4_410, 44_100.0);
+        freeverb.reset();
+        let mut buffer = [[0.0f32; 2]; 4_410];
+        freeverb.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|&frame| frame == [0.0; 2]));
+    }
+
+    /// A source playing back a buffer, then silence.
+    struct Playback(Vec<[f32; 2]>, usize);
+
+    impl Node<[f32; 2]> for Playback {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 2]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = self.0.get(self.1).cloned().unwrap_or([0.0; 2]);
+                self.1 += 1;
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn a_freeverb_at_zero_mix_is_transparent() {
+        let input: Vec<[f32; 2]> = noise(4_410, 7).iter().map(|s| [s[0], -s[0]]).collect();
+        let mut freeverb = Freeverb::new(0.5, 0.5);
+        freeverb.set_mix(0.0);
+        let mut graph: crate::Graph<[f32; 2], Box<dyn Node<[f32; 2]>>> = crate::Graph::new();
+        let source =
+            graph.add_node(Box::new(Playback(input.clone(), 0)) as Box<dyn Node<[f32; 2]>>);
+        let (_, reverb) = graph.add_output(source, Box::new(freeverb));
+        graph.set_master(Some(reverb));
+        let mut output = vec![[0.0; 2]; 4_410];
+        for chunk in output.chunks_mut(256) {
+            graph.audio_requested(chunk, 44_100.0);
+        }
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,525 @@
+//! Playback of recorded samples.
+
+use crate::midi::MidiMessage;
+use crate::node::Node;
+use crate::util;
+use crate::Volume;
+use sample::{self, Frame, Sample};
+
+/// The parameters of a **SamplePlayer**, excluding the loaded sample and its playback position.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplePlayerParams {
+    /// The speed of playback relative to the sample's own rate (`1.0` plays at the original
+    /// pitch, `2.0` an octave higher).
+    pub playback_rate: f64,
+    /// Whether the loop is repeated (`true`) or the sample is played once to its end (`false`).
+    pub looping: bool,
+    /// The first frame of the loop.
+    pub loop_start: usize,
+    /// The frame after the last of the loop, or `None` for the end of the sample.
+    pub loop_end: Option<usize>,
+    /// The number of frames before the end of the loop over which it is crossfaded with the
+    /// frames before its start.
+    pub crossfade_frames: usize,
+    /// The volume of the player.
+    pub volume: Volume,
+}
+
+/// A source **Node** that plays back a loaded sample, either once or repeating a loop within it.
+///
+/// The sample may have any number of channels. Each output channel plays the sample's channel of
+/// the same index, wrapping around when the sample has fewer channels than the output, so a mono
+/// sample is written to every channel.
+///
+/// Playback advances by a fractional number of frames per output frame, reading between the
+/// sample's frames with linear interpolation, so the sample may be pitched by its
+/// `playback_rate`. The rate is relative to the sample rate at which the sample was recorded, so
+/// a `playback_rate` of `1.0` plays at the original pitch whatever rate audio is requested at.
+///
+/// When looping, playback runs from the current position until the end of the loop and then
+/// wraps around to its start, carrying any fractional position so that the loop repeats with a
+/// period of exactly its length. Interpolation also reads across the wraparound, so a loop whose
+/// end meets its start smoothly repeats seamlessly. For material whose loop points do not meet
+/// smoothly, the last `crossfade_frames` of the loop are crossfaded with the frames before its
+/// start, which the loop then continues from, hiding the discontinuity.
+///
+/// The **SamplePlayer** is silent until a sample is loaded and `play` is called. Any note-on
+/// message also restarts playback.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplePlayer {
+    playback_rate: f64,
+    looping: bool,
+    loop_start: usize,
+    loop_end: Option<usize>,
+    crossfade_frames: usize,
+    volume: Volume,
+    /// The frames of the sample, with the channels of each frame interleaved.
+    samples: Vec<f64>,
+    /// The number of channels within each frame of `samples`.
+    channels: usize,
+    /// The rate at which the sample was recorded.
+    sample_hz: f64,
+    /// The read position in frames within the sample.
+    position: f64,
+    playing: bool,
+}
+
+impl SamplePlayer {
+    /// Construct a full-volume, one-shot **SamplePlayer** with no sample loaded.
+    pub fn new() -> Self {
+        SamplePlayer {
+            playback_rate: 1.0,
+            looping: false,
+            loop_start: 0,
+            loop_end: None,
+            crossfade_frames: 0,
+            volume: 1.0,
+            samples: Vec::new(),
+            channels: 1,
+            sample_hz: 0.0,
+            position: 0.0,
+            playing: false,
+        }
+    }
+
+    /// Load a mono sample recorded at `sample_hz`, replacing any previously loaded.
+    ///
+    /// If `sample_hz` is not positive, the sample is assumed to be recorded at whatever rate audio
+    /// is requested. Playback is stopped and returned to the start of the sample.
+    pub fn load_sample<S>(&mut self, samples: &[S], sample_hz: f64)
+    where
+        S: Sample,
+    {
+        self.samples.clear();
+        self.samples
+            .extend(samples.iter().map(|&s| util::to_f64(s)));
+        self.channels = 1;
+        self.loaded(sample_hz);
+    }
+
+    /// Load a sample of any number of channels recorded at `sample_hz`, replacing any previously
+    /// loaded.
+    ///
+    /// If `sample_hz` is not positive, the sample is assumed to be recorded at whatever rate audio
+    /// is requested. Playback is stopped and returned to the start of the sample.
+    pub fn load_frames<G>(&mut self, frames: &[G], sample_hz: f64)
+    where
+        G: Frame,
+    {
+        self.samples.clear();
+        for frame in frames {
+            self.samples.extend(frame.channels().map(util::to_f64));
+        }
+        self.channels = G::n_channels();
+        self.loaded(sample_hz);
+    }
+
+    /// The length in frames of the sample as loaded.
+    pub fn len(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Whether or not the loaded sample is empty, as it is before any sample is loaded.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The number of channels of the sample as loaded.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The rate at which the sample was recorded, as given when it was loaded.
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_hz
+    }
+
+    /// Start playback from the start of the sample.
+    pub fn play(&mut self) {
+        self.position = 0.0;
+        self.playing = true;
+    }
+
+    /// Stop playback, leaving the position where it is.
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Whether or not the sample is playing. A one-shot sample stops once it reaches its end.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// The read position in frames within the sample.
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    /// Move the read position to the given frame within the sample, which may be fractional.
+    pub fn set_position(&mut self, position: f64) {
+        self.position = position.max(0.0);
+    }
+
+    /// The speed of playback relative to the sample's own rate.
+    pub fn playback_rate(&self) -> f64 {
+        self.playback_rate
+    }
+
+    /// Set the speed of playback relative to the sample's own rate (`0.0` or more).
+    pub fn set_playback_rate(&mut self, rate: f64) {
+        self.playback_rate = rate.max(0.0);
+    }
+
+    /// Whether the loop is repeated (`true`) or the sample is played once to its end (`false`).
+    pub fn looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Set whether the loop is repeated (`true`) or the sample is played once to its end (`false`).
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Set the loop to run from frame `start` up to, but excluding, frame `end`.
+    ///
+    /// The loop is limited to the length of the sample when played, so it may be set before the
+    /// sample is loaded. A loop shorter than one frame is ignored and the sample plays to its end.
+    pub fn set_loop(&mut self, start: usize, end: usize) {
+        self.loop_start = start;
+        self.loop_end = Some(end);
+    }
+
+    /// Loop the whole of the sample.
+    pub fn clear_loop(&mut self) {
+        self.loop_start = 0;
+        self.loop_end = None;
+    }
+
+    /// The first frame and the frame after the last of the loop, limited to the length of the
+    /// loaded sample.
+    pub fn loop_range(&self) -> (usize, usize) {
+        let len = self.len();
+        let end = self.loop_end.map_or(len, |end| end.min(len));
+        (self.loop_start.min(end), end)
+    }
+
+    /// The number of frames before the end of the loop over which it is crossfaded with the frames
+    /// before its start.
+    pub fn crossfade_frames(&self) -> usize {
+        self.crossfade_frames
+    }
+
+    /// Set the number of frames before the end of the loop over which it is crossfaded with the
+    /// frames before its start.
+    ///
+    /// The crossfade is limited to the length of the loop and to the number of frames before its
+    /// start, so a loop starting at the first frame of the sample is never crossfaded.
+    pub fn set_crossfade_frames(&mut self, frames: usize) {
+        self.crossfade_frames = frames;
+    }
+
+    /// Set the volume of the player.
+    pub fn set_vol(&mut self, vol: Volume) {
+        self.volume = vol;
+    }
+
+    /// The parameters of the **SamplePlayer**.
+    pub fn params(&self) -> SamplePlayerParams {
+        SamplePlayerParams {
+            playback_rate: self.playback_rate,
+            looping: self.looping,
+            loop_start: self.loop_start,
+            loop_end: self.loop_end,
+            crossfade_frames: self.crossfade_frames,
+            volume: self.volume,
+        }
+    }
+
+    /// Apply the given parameters, leaving the sample and playback position untouched.
+    pub fn set_params(&mut self, params: SamplePlayerParams) {
+        self.set_playback_rate(params.playback_rate);
+        self.looping = params.looping;
+        self.loop_start = params.loop_start;
+        self.loop_end = params.loop_end;
+        self.crossfade_frames = params.crossfade_frames;
+        self.volume = params.volume;
+    }
+
+    /// Stop playback and return to the start of the sample.
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.playing = false;
+    }
+
+    /// Record the rate of a newly loaded sample and return to its start.
+    fn loaded(&mut self, sample_hz: f64) {
+        self.sample_hz = sample_hz;
+        self.reset();
+    }
+
+    /// The value of the given channel of the sample at the given frame.
+    ///
+    /// When `wrap` is given as the loop's `(start, end)`, frames from the end of the loop onwards
+    /// are read from its start. Otherwise, frames beyond the end of the sample are silent.
+    fn frame(&self, idx: usize, channel: usize, wrap: Option<(usize, usize)>) -> f64 {
+        let idx = match wrap {
+            Some((start, end)) if idx >= end => start + (idx - end) % (end - start),
+            _ => idx,
+        };
+        self.samples
+            .get(idx * self.channels + channel)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// The value of the given channel of the sample at the given fractional position.
+    fn read(&self, position: f64, channel: usize, wrap: Option<(usize, usize)>) -> f64 {
+        let idx = position.floor() as usize;
+        let a = self.frame(idx, channel, wrap);
+        let b = self.frame(idx + 1, channel, wrap);
+        a + (b - a) * (position - idx as f64)
+    }
+
+    /// The value of the given channel at the current position, crossfading the end of the loop
+    /// with the frames before its start.
+    fn value(&self, channel: usize, wrap: Option<(usize, usize)>) -> f64 {
+        let value = self.read(self.position, channel, wrap);
+        let (start, end) = match wrap {
+            Some(range) => range,
+            None => return value,
+        };
+        let crossfade = self.crossfade_frames.min(start).min(end - start);
+        let fade_start = (end - crossfade) as f64;
+        if crossfade == 0 || self.position < fade_start || self.position >= end as f64 {
+            return value;
+        }
+        let t = (self.position - fade_start) / crossfade as f64;
+        let lead_in = self.read(self.position - (end - start) as f64, channel, None);
+        value + (lead_in - value) * t
+    }
+}
+
+impl Default for SamplePlayer {
+    fn default() -> Self {
+        SamplePlayer::new()
+    }
+}
+
+impl<F> Node<F> for SamplePlayer
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if !self.playing || self.samples.is_empty() {
+            sample::slice::equilibrium(buffer);
+            return;
+        }
+        let len = self.len() as f64;
+        let step = if self.sample_hz > 0.0 {
+            self.playback_rate * self.sample_hz / sample_hz
+        } else {
+            self.playback_rate
+        };
+        let (start, end) = self.loop_range();
+        let wrap = if self.looping && end > start {
+            Some((start, end))
+        } else {
+            None
+        };
+        for frame in buffer.iter_mut() {
+            if !self.playing {
+                *frame = F::equilibrium();
+                continue;
+            }
+            *frame =
+                F::from_fn(|channel| util::from_f64(self.value(channel % self.channels, wrap)));
+            self.position += step;
+            match wrap {
+                Some((start, end)) if self.position >= end as f64 => {
+                    let loop_len = (end - start) as f64;
+                    self.position = start as f64 + (self.position - start as f64) % loop_len;
+                }
+                None if self.position >= len => self.playing = false,
+                _ => (),
+            }
+        }
+    }
+
+    fn vol(&self) -> Volume {
+        self.volume
+    }
+
+    /// Exposes:
+    ///
+    /// - `playback_rate`: the speed of playback relative to the sample's own rate.
+    /// - `looping`: `1.0` to repeat the loop, `0.0` to play the sample once.
+    /// - `loop_start`: the first frame of the loop.
+    /// - `loop_end`: the frame after the last of the loop, or `-1.0` for the end of the sample.
+    /// - `crossfade_frames`: the length in frames of the crossfade at the end of the loop.
+    /// - `volume`: the volume as a linear amplitude.
+    fn param_names(&self) -> &[&str] {
+        &[
+            "playback_rate",
+            "looping",
+            "loop_start",
+            "loop_end",
+            "crossfade_frames",
+            "volume",
+        ]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "playback_rate" => Some(self.playback_rate as f32),
+            "looping" => Some(if self.looping { 1.0 } else { 0.0 }),
+            "loop_start" => Some(self.loop_start as f32),
+            "loop_end" => Some(self.loop_end.map_or(-1.0, |end| end as f32)),
+            "crossfade_frames" => Some(self.crossfade_frames as f32),
+            "volume" => Some(self.volume),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "playback_rate" => self.set_playback_rate(value as f64),
+            "looping" => self.set_looping(value >= 0.5),
+            "loop_start" => self.loop_start = value.max(0.0).round() as usize,
+            "loop_end" => {
+                self.loop_end = if value < 0.0 {
+                    None
+                } else {
+                    Some(value.round() as usize)
+                }
+            }
+            "crossfade_frames" => self.set_crossfade_frames(value.max(0.0).round() as usize),
+            "volume" => self.set_vol(value),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Restarts playback upon any note-on, whatever the note.
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        match message {
+            MidiMessage::NoteOn { velocity, .. } if velocity > 0 => {
+                self.play();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_source(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        SamplePlayer::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "sample_player"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render the given number of mono frames from the player in chunks of 64.
+    fn render(player: &mut SamplePlayer, frames: usize, sample_hz: f64) -> Vec<[f32; 1]> {
+        let mut output = vec![[0.0]; frames];
+        for chunk in output.chunks_mut(64) {
+            player.audio_requested(chunk, sample_hz);
+        }
+        output
+    }
+
+    /// A player holding a single 100 frame cycle of a sine at 44.1 kHz, ready to play.
+    fn playing_cycle() -> (SamplePlayer, Vec<f32>) {
+        let cycle: Vec<f32> = (0..100)
+            .map(|i| (2.0 * ::std::f64::consts::PI * i as f64 / 100.0).sin() as f32)
+            .collect();
+        let mut player = SamplePlayer::new();
+        player.load_sample(&cycle, 44_100.0);
+        player.play();
+        (player, cycle)
+    }
+
+    #[test]
+    fn a_looped_cycle_repeats_seamlessly() {
+        let (mut player, cycle) = playing_cycle();
+        player.set_looping(true);
+        let output = render(&mut player, 1_000, 44_100.0);
+        for (i, frame) in output.iter().enumerate() {
+            assert!((frame[0] - cycle[i % 100]).abs() < 1e-6, "{}", i);
+        }
+        // No step across the wraparound is larger than the steepest within the cycle.
+        let max_step = (2.0 * ::std::f32::consts::PI / 100.0) + 1e-6;
+        assert!(output
+            .windows(2)
+            .all(|w| (w[1][0] - w[0][0]).abs() <= max_step));
+        assert!(player.is_playing());
+    }
+
+    #[test]
+    fn a_one_shot_sample_stops_at_its_end() {
+        let (mut player, cycle) = playing_cycle();
+        let output = render(&mut player, 150, 44_100.0);
+        assert_eq!(
+            &output[..100].iter().map(|f| f[0]).collect::<Vec<_>>(),
+            &cycle
+        );
+        assert!(output[100..].iter().all(|f| f[0] == 0.0));
+        assert!(!player.is_playing());
+    }
+
+    #[test]
+    fn fractional_playback_interpolates_and_keeps_the_loop_period() {
+        let (mut player, cycle) = playing_cycle();
+        player.set_looping(true);
+        player.set_playback_rate(0.5);
+        let output = render(&mut player, 400, 44_100.0);
+        for i in 0..400 {
+            let (a, b) = (cycle[i / 2 % 100], cycle[(i / 2 + 1) % 100]);
+            let expected = if i % 2 == 0 { a } else { (a + b) * 0.5 };
+            assert!((output[i][0] - expected).abs() < 1e-6, "{}", i);
+        }
+
+        // A sample recorded at half the rate of the output plays at the same speed.
+        let (mut slow, _) = playing_cycle();
+        slow.load_sample(&cycle, 22_050.0);
+        slow.play();
+        slow.set_looping(true);
+        assert_eq!(render(&mut slow, 400, 44_100.0), output);
+    }
+
+    #[test]
+    fn a_crossfaded_loop_hides_the_discontinuity_at_its_end() {
+        // A ramp rising by 0.01 per frame, looped from 0.1 to 0.3 where its ends do not meet.
+        let ramp: Vec<f32> = (0..30).map(|i| i as f32 / 100.0).collect();
+        let mut player = SamplePlayer::new();
+        player.load_sample(&ramp, 44_100.0);
+        player.set_looping(true);
+        player.set_loop(10, 30);
+        player.play();
+        let output = render(&mut player, 200, 44_100.0);
+        let max_step = |output: &[[f32; 1]]| {
+            output
+                .windows(2)
+                .map(|w| (w[1][0] - w[0][0]).abs())
+                .fold(0.0, f32::max)
+        };
+        assert!((max_step(&output) - 0.19).abs() < 1e-6);
+
+        // Crossfading the last 10 frames with the 10 before the start leaves no step larger than
+        // those of the ramp itself.
+        player.set_crossfade_frames(10);
+        player.set_position(0.0);
+        let output = render(&mut player, 200, 44_100.0);
+        assert!(max_step(&output) < 0.01 + 1e-6, "{}", max_step(&output));
+        assert!(output[..20].iter().zip(&ramp).all(|(f, &s)| f[0] == s));
+    }
+}
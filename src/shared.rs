@@ -0,0 +1,758 @@
+//! Sharing parameters between the audio thread and other threads.
+//!
+//! A **Graph** is `Send` whenever its nodes and frames are, so it may be moved into an audio
+//! callback running on another thread. From then on the audio thread owns the **Graph**, so other
+//! threads cannot call methods like `set_vol` on its nodes directly. Instead, nodes may read their
+//! parameters from a **SharedParam**, which may be cloned and updated from any thread.
+//!
+//! Parameters are stored within atomics, so updating or reading them never blocks or allocates and
+//! the audio thread can never observe a partially written value.
+//!
+//! Any of a node's named parameters (see `Node::param_names`) may be shared in this way by
+//! wrapping it in a **SharedParams** and updating them via its **ParamHandle**.
+
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::{Frame, Panning, Sample, Volume};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// An `f32` that may be shared between threads, stored as its bits within an `AtomicU32`.
+#[derive(Debug, Default)]
+pub struct AtomicF32 {
+    bits: AtomicU32,
+}
+
+impl AtomicF32 {
+    /// Construct an **AtomicF32** holding the given value.
+    pub fn new(value: f32) -> Self {
+        AtomicF32 {
+            bits: AtomicU32::new(value.to_bits()),
+        }
+    }
+
+    /// The most recently stored value.
+    #[inline]
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    /// Store the given value.
+    #[inline]
+    pub fn store(&self, value: f32) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// A handle to an `f32` parameter that may be read and updated from any thread.
+///
+/// Cloning a **SharedParam** produces another handle to the same parameter.
+#[derive(Clone, Debug, Default)]
+pub struct SharedParam {
+    value: Arc<AtomicF32>,
+}
+
+impl SharedParam {
+    /// Construct a **SharedParam** holding the given value.
+    pub fn new(value: f32) -> Self {
+        SharedParam {
+            value: Arc::new(AtomicF32::new(value)),
+        }
+    }
+
+    /// The current value of the parameter.
+    #[inline]
+    pub fn get(&self) -> f32 {
+        self.value.load()
+    }
+
+    /// Update the value of the parameter.
+    #[inline]
+    pub fn set(&self, value: f32) {
+        self.value.store(value);
+    }
+}
+
+/// A **Node** wrapper whose volume and panning may be controlled from any thread.
+///
+/// The wrapped node's `vol` is multiplied by the shared volume, and the shared panning is added to
+/// the wrapped node's `pan`. The volume for each channel is derived from these using the default
+/// `vol_per_channel` and `vols_per_channel`, while all other **Node** methods are delegated to the
+/// wrapped node.
+///
+/// Use `vol_handle` and `pan_handle` to retrieve handles before moving the **Graph** to the audio
+/// thread.
+#[derive(Clone, Debug)]
+pub struct ThreadSafeNode<N> {
+    node: N,
+    vol: SharedParam,
+    pan: SharedParam,
+}
+
+impl<N> ThreadSafeNode<N> {
+    /// Wrap the given node at full volume and centered.
+    pub fn new(node: N) -> Self {
+        ThreadSafeNode {
+            node,
+            vol: SharedParam::new(1.0),
+            pan: SharedParam::new(0.0),
+        }
+    }
+
+    /// A handle to the volume multiplier applied to the wrapped node.
+    pub fn vol_handle(&self) -> SharedParam {
+        self.vol.clone()
+    }
+
+    /// A handle to the panning offset applied to the wrapped node (-1.0 ... 1.0).
+    pub fn pan_handle(&self) -> SharedParam {
+        self.pan.clone()
+    }
+
+    /// Set the volume multiplier applied to the wrapped node.
+    pub fn set_vol(&self, vol: Volume) {
+        self.vol.set(vol);
+    }
+
+    /// Set the panning offset applied to the wrapped node.
+    pub fn set_pan(&self, pan: Panning) {
+        self.pan.set(pan);
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<F, N> Node<F> for ThreadSafeNode<N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.node.audio_requested(buffer, sample_hz);
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol() * self.vol.get()
+    }
+
+    fn pan(&self) -> Panning {
+        (self.node.pan() + self.pan.get()).clamp(-1.0, 1.0)
+    }
+
+    /// Forwarded only while the shared panning offset is zero, as the offset cannot be applied to
+    /// the wrapped node's pans. Otherwise, the offset `pan` is applied once per buffer.
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        if self.pan.get() == 0.0 {
+            self.node.pan_per_frame()
+        } else {
+            None
+        }
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn skip_silence(&self) -> bool {
+        self.node.skip_silence()
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.node.prepare(settings);
+    }
+
+    fn reset(&mut self) {
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+
+    fn type_tag(&self) -> &str {
+        self.node.type_tag()
+    }
+}
+
+/// One of the named parameters of a **SharedParams** node.
+#[derive(Debug)]
+struct NamedParam {
+    name: String,
+    value: AtomicF32,
+}
+
+/// A handle to the named parameters of a **SharedParams** node, which may be read and updated
+/// from any thread.
+///
+/// Cloning a **ParamHandle** produces another handle to the same parameters.
+#[derive(Clone, Debug)]
+pub struct ParamHandle {
+    params: Arc<[NamedParam]>,
+}
+
+impl ParamHandle {
+    /// The names of the parameters, in the order given by the node's `param_names`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.params.iter().map(|param| &param.name[..])
+    }
+
+    /// The value most recently requested for the parameter with the given name, or `None` if the
+    /// node has no such parameter.
+    ///
+    /// The value is as requested, before the node has applied it or clamped it to its range.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.find(name).map(|param| param.value.load())
+    }
+
+    /// Request that the parameter with the given name is set to the given value, returning
+    /// `false` if the node has no such parameter.
+    ///
+    /// The value is applied by the audio thread before the node next renders.
+    pub fn set(&self, name: &str, value: f32) -> bool {
+        match self.find(name) {
+            Some(param) => {
+                param.value.store(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&NamedParam> {
+        self.params.iter().find(|param| param.name == name)
+    }
+}
+
+/// A **Node** wrapper whose named parameters may be controlled from any thread.
+///
+/// Upon construction, an atomic is created for each of the wrapped node's `param_names`, holding
+/// its current value. Any number of **ParamHandle**s may be retrieved via `param_handle` before
+/// moving the **Graph** to the audio thread. Each time audio is requested, the value of every
+/// parameter that has been updated since the last buffer is passed to the wrapped node's
+/// `set_param`, so no locks are taken and the parameters change only between buffers.
+///
+/// All other **Node** methods are delegated to the wrapped node.
+#[derive(Debug)]
+pub struct SharedParams<F, N> {
+    node: N,
+    params: Arc<[NamedParam]>,
+    /// The bits of the value of each parameter last applied to the wrapped node.
+    applied: Vec<u32>,
+    frame: PhantomData<F>,
+}
+
+impl<F, N> SharedParams<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Wrap the given node, sharing each of its named parameters.
+    pub fn new(node: N) -> Self {
+        let params: Arc<[NamedParam]> = node
+            .param_names()
+            .iter()
+            .map(|&name| NamedParam {
+                name: name.to_string(),
+                value: AtomicF32::new(node.get_param(name).unwrap_or(0.0)),
+            })
+            .collect();
+        let applied = params
+            .iter()
+            .map(|param| param.value.load().to_bits())
+            .collect();
+        SharedParams {
+            node,
+            params,
+            applied,
+            frame: PhantomData,
+        }
+    }
+
+    /// A handle to the wrapped node's named parameters.
+    pub fn param_handle(&self) -> ParamHandle {
+        ParamHandle {
+            params: self.params.clone(),
+        }
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    ///
+    /// Parameters changed directly upon the node are not reflected by its **ParamHandle**s. Use
+    /// `Node::set_param` upon the **SharedParams** instead.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+
+    /// Pass every parameter updated since they were last applied on to the wrapped node.
+    fn apply_params(&mut self) {
+        let SharedParams {
+            ref mut node,
+            ref params,
+            ref mut applied,
+            ..
+        } = *self;
+        for (param, applied) in params.iter().zip(applied.iter_mut()) {
+            let value = param.value.load();
+            if value.to_bits() != *applied {
+                node.set_param(&param.name, value);
+                *applied = value.to_bits();
+            }
+        }
+    }
+}
+
+impl<F, N> Node<F> for SharedParams<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        self.apply_params();
+        self.node.audio_requested(buffer, sample_hz);
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.node.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.node.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        self.node.vol_per_channel()
+    }
+
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        self.node.vols_per_channel(vols)
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    /// Sets the parameter upon the wrapped node immediately, and updates the value seen by its
+    /// **ParamHandle**s.
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        if !self.node.set_param(name, value) {
+            return false;
+        }
+        if let Some(i) = self.params.iter().position(|param| param.name == name) {
+            self.params[i].value.store(value);
+            self.applied[i] = value.to_bits();
+        }
+        true
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn skip_silence(&self) -> bool {
+        self.node.skip_silence()
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.node.prepare(settings);
+    }
+
+    fn reset(&mut self) {
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+
+    fn type_tag(&self) -> &str {
+        self.node.type_tag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Graph;
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    /// A full scale source.
+    struct Dc;
+
+    impl Node<[f32; 1]> for Dc {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [1.0];
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn shared_param_handles_see_the_latest_value() {
+        let param = SharedParam::new(0.5);
+        let handle = param.clone();
+        handle.set(-0.25);
+        assert_eq!(param.get(), -0.25);
+        let node = ThreadSafeNode::new(Dc);
+        node.vol_handle().set(0.5);
+        node.set_pan(2.0);
+        assert_eq!(Node::<[f32; 1]>::vol(&node), 0.5);
+        assert_eq!(Node::<[f32; 1]>::pan(&node), 1.0);
+    }
+
+    #[test]
+    fn volume_may_be_updated_while_another_thread_renders() {
+        let node = ThreadSafeNode::new(Dc);
+        let vol = node.vol_handle();
+        vol.set(0.25);
+        let mut graph = Graph::new();
+        let idx = graph.add_node(node);
+        graph.set_master(Some(idx));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let audio = {
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut buffer = [[0.0f32; 1]; 64];
+                let mut buffers = 0;
+                while !done.load(Ordering::Relaxed) || buffers < 100 {
+                    graph.audio_requested(&mut buffer, 44_100.0);
+                    for frame in buffer.iter() {
+                        assert!(frame[0] >= 0.25 && frame[0] <= 0.75, "{}", frame[0]);
+                    }
+                    buffers += 1;
+                }
+                graph
+            })
+        };
+        for i in 0..10_000 {
+            vol.set(if i % 2 == 0 { 0.25 } else { 0.75 });
+        }
+        vol.set(0.5);
+        done.store(true, Ordering::Relaxed);
+        let mut graph = audio.join().expect("the audio thread panicked");
+
+        let mut buffer = [[0.0f32; 1]; 64];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|frame| frame[0] == 0.5));
+    }
+
+    /// A source writing its `level` parameter.
+    struct Level(f32);
+
+    impl Node<[f32; 1]> for Level {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            for frame in buffer.iter_mut() {
+                *frame = [self.0];
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+
+        fn param_names(&self) -> &[&str] {
+            &["level"]
+        }
+
+        fn get_param(&self, name: &str) -> Option<f32> {
+            match name {
+                "level" => Some(self.0),
+                _ => None,
+            }
+        }
+
+        fn set_param(&mut self, name: &str, value: f32) -> bool {
+            match name {
+                "level" => self.0 = value,
+                _ => return false,
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn hammered_param_updates_are_only_ever_seen_whole() {
+        // Values whose bits differ in nearly every position, so a torn read could not pass.
+        const VALUES: [f32; 2] = [1.0, -3.0e-30];
+        let level = SharedParams::new(Level(VALUES[0]));
+        let handle = level.param_handle();
+        assert_eq!(handle.names().collect::<Vec<_>>(), vec!["level"]);
+        assert!(!handle.set("gain", 0.5));
+        let mut graph = Graph::new();
+        let idx = graph.add_node(level);
+        graph.set_master(Some(idx));
+        let done = Arc::new(AtomicBool::new(false));
+
+        let writer = {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                for i in 0..100_000 {
+                    assert!(handle.set("level", VALUES[i % 2]));
+                }
+            })
+        };
+        let reader = {
+            let handle = handle.clone();
+            let done = done.clone();
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    let value = handle.get("level").unwrap();
+                    assert!(VALUES.contains(&value), "{:e}", value);
+                }
+            })
+        };
+        let audio = {
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut buffer = [[0.0f32; 1]; 64];
+                while !done.load(Ordering::Relaxed) {
+                    graph.audio_requested(&mut buffer, 44_100.0);
+                    assert!(VALUES.contains(&buffer[0][0]), "{:e}", buffer[0][0]);
+                    assert!(buffer.iter().all(|frame| frame[0] == buffer[0][0]));
+                }
+                graph
+            })
+        };
+        writer.join().expect("the writer thread panicked");
+        done.store(true, Ordering::Relaxed);
+        reader.join().expect("the reader thread panicked");
+        let mut graph = audio.join().expect("the audio thread panicked");
+
+        // The last update is applied before the next buffer.
+        let mut buffer = [[0.0f32; 1]; 64];
+        graph.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer[0], [VALUES[1]]);
+        assert_eq!(graph[idx].get_param("level"), Some(VALUES[1]));
+    }
+}
@@ -0,0 +1,300 @@
+//! Rendering a node once for several consumers.
+//!
+//! Within a **Graph**, each node is rendered once per buffer and its output written to every one
+//! of its output connections, however many there are. Outside of a **Graph**, e.g. where a source
+//! feeds several **Graph**s or hand-written chains, a **Split** caches the source's output so that
+//! each consumer may request it without the source being rendered again.
+
+use crate::midi::MidiMessage;
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::{Frame, Panning, Sample, Volume};
+
+/// A **Node** wrapper that renders the wrapped node at most once per cycle and hands the cached
+/// output to every consumer.
+///
+/// Each consumer passes the same `cycle` (e.g. a count of buffers rendered so far) to `request`.
+/// The first request of a cycle renders the wrapped node into the cache, while later requests with
+/// the same cycle, buffer length and sample rate copy the cache without rendering. Requesting a
+/// new cycle renders the node again.
+///
+/// When used directly as a **Node**, each call to `audio_requested` begins a new cycle, after which
+/// the output may be read again via `buffer` or `request`.
+#[derive(Clone, Debug)]
+pub struct Split<F, N> {
+    node: N,
+    /// The output of the wrapped node for the current cycle.
+    buffer: Vec<F>,
+    /// The cycle and sample rate for which `buffer` was rendered, if any.
+    rendered: Option<(u64, f64)>,
+}
+
+impl<F, N> Split<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Wrap the given node. Nothing is cached until the first request.
+    pub fn new(node: N) -> Self {
+        Split {
+            node,
+            buffer: Vec::new(),
+            rendered: None,
+        }
+    }
+
+    /// Write the output of the wrapped node for the given cycle to `output`, rendering it only if
+    /// it has not yet been rendered for this cycle, buffer length and sample rate.
+    pub fn request(&mut self, cycle: u64, output: &mut [F], sample_hz: f64) {
+        let cached = self.rendered == Some((cycle, sample_hz)) && self.buffer.len() == output.len();
+        if !cached {
+            self.buffer.clear();
+            self.buffer.resize(output.len(), F::equilibrium());
+            self.node.audio_requested(&mut self.buffer, sample_hz);
+            self.rendered = Some((cycle, sample_hz));
+        }
+        sample::slice::write(output, &self.buffer);
+    }
+
+    /// The cycle for which the cache was last rendered, if any.
+    pub fn cycle(&self) -> Option<u64> {
+        self.rendered.map(|(cycle, _)| cycle)
+    }
+
+    /// The output of the wrapped node for the current cycle.
+    pub fn buffer(&self) -> &[F] {
+        &self.buffer
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    ///
+    /// Changes take effect from the next cycle.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+}
+
+impl<F, N> Node<F> for Split<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let cycle = self.cycle().map_or(0, |cycle| cycle.wrapping_add(1));
+        self.request(cycle, buffer, sample_hz);
+    }
+
+    fn combine_input(&mut self, input_idx: usize, input: &[F], output: &mut [F]) {
+        self.node.combine_input(input_idx, input, output);
+    }
+
+    fn sums_inputs(&self) -> bool {
+        self.node.sums_inputs()
+    }
+
+    fn combines_by_sum(&self) -> bool {
+        self.node.combines_by_sum()
+    }
+
+    fn combine(&self, acc: F::Sample, incoming: F::Sample) -> F::Sample {
+        self.node.combine(acc, incoming)
+    }
+
+    fn dry(&self) -> <F::Sample as Sample>::Float {
+        self.node.dry()
+    }
+
+    fn wet(&self) -> <F::Sample as Sample>::Float {
+        self.node.wet()
+    }
+
+    fn mix(&self) -> f32 {
+        self.node.mix()
+    }
+
+    fn vol(&self) -> Volume {
+        self.node.vol()
+    }
+
+    fn vol_db(&self) -> f32 {
+        self.node.vol_db()
+    }
+
+    fn pan(&self) -> Panning {
+        self.node.pan()
+    }
+
+    fn pan_per_frame(&self) -> Option<&[Panning]> {
+        self.node.pan_per_frame()
+    }
+
+    fn pan_law(&self) -> PanLaw {
+        self.node.pan_law()
+    }
+
+    fn balance(&self) -> f32 {
+        self.node.balance()
+    }
+
+    fn channel_gains(&self) -> Option<&[Volume]> {
+        self.node.channel_gains()
+    }
+
+    fn vol_per_channel(&self) -> [Volume; 2] {
+        self.node.vol_per_channel()
+    }
+
+    fn vols_per_channel(&self, vols: &mut [Volume]) {
+        self.node.vols_per_channel(vols)
+    }
+
+    fn smoothing_samples(&self) -> usize {
+        self.node.smoothing_samples()
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.node.is_bypassed()
+    }
+
+    fn is_muted(&self) -> bool {
+        self.node.is_muted()
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    fn process_inputs_parallel(&self) -> bool {
+        self.node.process_inputs_parallel()
+    }
+
+    fn is_source(&self) -> bool {
+        self.node.is_source()
+    }
+
+    fn output_channels(&self, input_channels: usize) -> usize {
+        self.node.output_channels(input_channels)
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        self.node.input_channels()
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.node.num_outputs()
+    }
+
+    fn read_output(&self, output_idx: usize, buffer: &mut [F]) {
+        self.node.read_output(output_idx, buffer)
+    }
+
+    fn param_names(&self) -> &[&str] {
+        self.node.param_names()
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        self.node.get_param(name)
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        self.node.set_param(name, value)
+    }
+
+    fn handle_midi(&mut self, message: MidiMessage) -> bool {
+        self.node.handle_midi(message)
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn skip_silence(&self) -> bool {
+        self.node.skip_silence()
+    }
+
+    fn on_buffer_processed(&mut self, output: &[F], sample_hz: f64) {
+        self.node.on_buffer_processed(output, sample_hz);
+    }
+
+    /// Prepares the wrapped node and discards the cached output, reserving space to cache up to
+    /// `max_frames` frames.
+    fn prepare(&mut self, settings: Settings) {
+        self.buffer.clear();
+        self.buffer.reserve(settings.max_frames);
+        self.rendered = None;
+        self.node.prepare(settings);
+    }
+
+    /// Resets the wrapped node and discards the cached output.
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.rendered = None;
+        self.node.reset();
+    }
+
+    fn name(&self) -> &str {
+        self.node.name()
+    }
+
+    fn type_tag(&self) -> &str {
+        self.node.type_tag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A source writing the number of buffers it has rendered.
+    struct Counter(usize);
+
+    impl Node<[f32; 1]> for Counter {
+        fn audio_requested(&mut self, buffer: &mut [[f32; 1]], _sample_hz: f64) {
+            self.0 += 1;
+            for frame in buffer.iter_mut() {
+                *frame = [self.0 as f32];
+            }
+        }
+
+        fn is_source(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn two_consumers_share_one_render_per_buffer() {
+        let mut split = Split::new(Counter(0));
+        let (mut a, mut b) = ([[0.0f32; 1]; 16], [[0.0f32; 1]; 16]);
+        for cycle in 0..3 {
+            split.request(cycle, &mut a, 44_100.0);
+            split.request(cycle, &mut b, 44_100.0);
+            assert_eq!(split.node().0, cycle as usize + 1);
+            assert_eq!(a, [[cycle as f32 + 1.0]; 16]);
+            assert_eq!(a, b);
+        }
+        assert_eq!(split.cycle(), Some(2));
+
+        // A different buffer length or sample rate within the same cycle renders again.
+        let mut longer = [[0.0f32; 1]; 32];
+        split.request(2, &mut longer, 44_100.0);
+        assert_eq!(split.node().0, 4);
+        split.request(2, &mut longer, 48_000.0);
+        assert_eq!(split.node().0, 5);
+
+        // Used as a node, every buffer is a new cycle.
+        split.audio_requested(&mut a, 48_000.0);
+        split.audio_requested(&mut a, 48_000.0);
+        assert_eq!(split.node().0, 7);
+        assert_eq!(split.buffer(), &a[..]);
+    }
+}
@@ -0,0 +1,1017 @@
+//! Nodes for processing stereo signals.
+//!
+//! Each of these **Node**s only acts on stereo frames. Buffers with any other number of channels
+//! are passed through unchanged.
+
+use crate::delay::{CompensationDelay, DelayLine};
+use crate::node::{Node, Settings};
+use crate::pan::PanLaw;
+use crate::util;
+use crate::Panning;
+use sample::{self, Frame};
+
+/// Apply `f` to the left and right samples of every frame in a stereo `buffer`.
+///
+/// Does nothing if the frames are not stereo.
+fn map_stereo_in_place<F, M>(buffer: &mut [F], mut f: M)
+where
+    F: Frame,
+    M: FnMut(f64, f64) -> (f64, f64),
+{
+    if F::n_channels() != 2 {
+        return;
+    }
+    sample::slice::map_in_place(buffer, |frame| {
+        let mut channels = frame.channels();
+        let a = util::to_f64(channels.next().expect("the left channel"));
+        let b = util::to_f64(channels.next().expect("the right channel"));
+        let (a, b) = f(a, b);
+        F::from_fn(|ch| util::from_f64(if ch == 0 { a } else { b }))
+    });
+}
+
+/// Encodes a stereo signal from left/right into mid/side.
+///
+/// The mid channel `(L + R) / 2` is written to the left channel and the side channel `(L - R) / 2`
+/// to the right channel. **MidSideDecode** is the exact inverse.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MidSideEncode;
+
+/// Decodes a stereo signal from mid/side (as produced by **MidSideEncode**) back into left/right.
+///
+/// `L = M + S` and `R = M - S`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MidSideDecode;
+
+impl<F> Node<F> for MidSideEncode
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        map_stereo_in_place(buffer, |l, r| ((l + r) * 0.5, (l - r) * 0.5));
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "mid_side_encode"
+    }
+}
+
+impl<F> Node<F> for MidSideDecode
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        map_stereo_in_place(buffer, |m, s| (m + s, m - s));
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "mid_side_decode"
+    }
+}
+
+/// The maximum width of a **StereoWidth**, avoiding runaway gain on the side channel.
+pub const MAX_STEREO_WIDTH: f32 = 4.0;
+
+/// The parameters of a **StereoWidth**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StereoWidthParams {
+    /// The width of the stereo image (0.0 ... `MAX_STEREO_WIDTH`).
+    pub width: f32,
+}
+
+/// Narrows or widens the stereo image by scaling the side channel.
+///
+/// A `width` of `1.0` leaves the signal unchanged, `0.0` collapses it to mono and anything above
+/// `1.0` widens it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoWidth {
+    width: f32,
+}
+
+impl StereoWidth {
+    /// Construct a **StereoWidth** with the given width.
+    pub fn new(width: f32) -> Self {
+        let mut stereo_width = StereoWidth { width: 1.0 };
+        stereo_width.set_width(width);
+        stereo_width
+    }
+
+    /// The width of the stereo image.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Set the width of the stereo image, clamped to `0.0 ... MAX_STEREO_WIDTH`.
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, MAX_STEREO_WIDTH);
+    }
+
+    /// The parameters of the **StereoWidth**.
+    pub fn params(&self) -> StereoWidthParams {
+        StereoWidthParams { width: self.width }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: StereoWidthParams) {
+        self.set_width(params.width);
+    }
+}
+
+impl Default for StereoWidth {
+    fn default() -> Self {
+        StereoWidth::new(1.0)
+    }
+}
+
+impl<F> Node<F> for StereoWidth
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        if self.width == 1.0 {
+            return;
+        }
+        let width = self.width as f64;
+        map_stereo_in_place(buffer, |l, r| {
+            let mid = (l + r) * 0.5;
+            let side = (l - r) * 0.5 * width;
+            (mid + side, mid - side)
+        });
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    /// Exposes:
+    ///
+    /// - `width`: the width of the stereo image (0.0 ... `MAX_STEREO_WIDTH`).
+    fn param_names(&self) -> &[&str] {
+        &["width"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "width" => Some(self.width),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "width" => self.set_width(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "stereo_width"
+    }
+}
+
+/// The parameters of a **Rotate**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotateParams {
+    /// The angle in degrees by which the stereo image is rotated.
+    pub angle_degrees: f32,
+}
+
+/// Rotates the stereo image by an angle within the plane of the left and right channels.
+///
+/// Each frame is treated as a point `(L, R)` and rotated anticlockwise by `angle_degrees`:
+///
+/// `L' = L · cos θ - R · sin θ` and `R' = L · sin θ + R · cos θ`
+///
+/// An angle of `0.0` leaves the signal unchanged, small angles nudge the image and correct
+/// imbalances between channels, and `45.0` moves a signal on the left channel alone to equal
+/// amounts on both. At `90.0`, the right channel is inverted onto the left and the left moved onto
+/// the right. Being a rotation, the power of each frame is preserved.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Rotate {
+    angle_degrees: f32,
+}
+
+impl Rotate {
+    /// Construct a **Rotate** with the given angle in degrees.
+    pub fn new(angle_degrees: f32) -> Self {
+        Rotate { angle_degrees }
+    }
+
+    /// The angle in degrees by which the stereo image is rotated.
+    pub fn angle_degrees(&self) -> f32 {
+        self.angle_degrees
+    }
+
+    /// Set the angle in degrees by which the stereo image is rotated.
+    pub fn set_angle_degrees(&mut self, angle_degrees: f32) {
+        self.angle_degrees = angle_degrees;
+    }
+
+    /// The parameters of the **Rotate**.
+    pub fn params(&self) -> RotateParams {
+        RotateParams {
+            angle_degrees: self.angle_degrees,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: RotateParams) {
+        self.set_angle_degrees(params.angle_degrees);
+    }
+}
+
+impl<F> Node<F> for Rotate
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        if self.angle_degrees == 0.0 {
+            return;
+        }
+        let (sin, cos) = (self.angle_degrees as f64).to_radians().sin_cos();
+        map_stereo_in_place(buffer, |l, r| (l * cos - r * sin, l * sin + r * cos));
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    /// Exposes:
+    ///
+    /// - `angle_degrees`: the angle in degrees by which the stereo image is rotated.
+    fn param_names(&self) -> &[&str] {
+        &["angle_degrees"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "angle_degrees" => Some(self.angle_degrees),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "angle_degrees" => self.set_angle_degrees(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "rotate"
+    }
+}
+
+/// The parameters of a **Panner**.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PannerParams {
+    /// The position of the signal (-1.0 is left, 0.0 is centred and 1.0 is right).
+    pub pan: Panning,
+    /// The law converting `pan` into a gain per channel.
+    pub pan_law: PanLaw,
+}
+
+/// Positions a stereo signal by scaling its left and right channels according to its own `pan`.
+///
+/// Unlike a **Node**'s `pan`, which the **Graph** applies when the **Node**'s output is mixed, a
+/// **Panner** may be placed anywhere within a chain. The gain of each channel follows the
+/// **Panner**'s `pan_law`, so with the default `PanLaw::Linear` a centred **Panner** leaves the
+/// signal unchanged and panning hard left silences the right channel. A mono source should be
+/// duplicated across both channels before it reaches the **Panner**.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Panner {
+    pan: Panning,
+    pan_law: PanLaw,
+}
+
+impl Panner {
+    /// Construct a **Panner** with the given position and `PanLaw::Linear`.
+    pub fn new(pan: Panning) -> Self {
+        Panner {
+            pan: pan.clamp(-1.0, 1.0),
+            pan_law: PanLaw::Linear,
+        }
+    }
+
+    /// The position of the signal.
+    pub fn pan(&self) -> Panning {
+        self.pan
+    }
+
+    /// Set the position of the signal, clamped to `-1.0 ... 1.0`.
+    pub fn set_pan(&mut self, pan: Panning) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    /// The law converting `pan` into a gain per channel.
+    pub fn pan_law(&self) -> PanLaw {
+        self.pan_law
+    }
+
+    /// Set the law converting `pan` into a gain per channel.
+    pub fn set_pan_law(&mut self, pan_law: PanLaw) {
+        self.pan_law = pan_law;
+    }
+
+    /// The parameters of the **Panner**.
+    pub fn params(&self) -> PannerParams {
+        PannerParams {
+            pan: self.pan,
+            pan_law: self.pan_law,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: PannerParams) {
+        self.set_pan(params.pan);
+        self.set_pan_law(params.pan_law);
+    }
+}
+
+impl<F> Node<F> for Panner
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], _sample_hz: f64) {
+        let [left, right] = self.pan_law.vol_per_channel(1.0, self.pan);
+        if left == 1.0 && right == 1.0 {
+            return;
+        }
+        let (left, right) = (left as f64, right as f64);
+        map_stereo_in_place(buffer, |l, r| (l * left, r * right));
+    }
+
+    fn skip_silence(&self) -> bool {
+        true
+    }
+
+    /// Exposes:
+    ///
+    /// - `pan`: the position of the signal (-1.0 ... 1.0).
+    fn param_names(&self) -> &[&str] {
+        &["pan"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "pan" => Some(self.pan),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "pan" => self.set_pan(value),
+            _ => return false,
+        }
+        true
+    }
+
+    fn type_tag(&self) -> &str {
+        "panner"
+    }
+}
+
+/// The shortest delay in milliseconds that a **Haas** applies to its delayed channel.
+pub const HAAS_MIN_DELAY_MS: f64 = 1.0;
+
+/// The longest delay in milliseconds that a **Haas** applies to its delayed channel, beyond which
+/// the delayed channel is heard as a distinct echo.
+pub const HAAS_MAX_DELAY_MS: f64 = 30.0;
+
+/// The channel delayed by a **Haas**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HaasSide {
+    /// Delay the left channel, so the image leans right.
+    Left,
+    /// Delay the right channel, so the image leans left.
+    #[default]
+    Right,
+}
+
+/// The parameters of a **Haas**, excluding its runtime state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HaasParams {
+    /// The delay in milliseconds (`HAAS_MIN_DELAY_MS ... HAAS_MAX_DELAY_MS`).
+    pub delay_ms: f64,
+    /// The channel that is delayed.
+    pub side: HaasSide,
+}
+
+/// Widens a stereo signal by delaying one of its channels by a few milliseconds.
+///
+/// Due to the precedence (or Haas) effect, the delayed channel is not heard as an echo but shifts
+/// the perceived position towards the other channel and spreads the image. The delay is rounded to
+/// the nearest whole frame.
+///
+/// Summing the output to mono adds the signal to a delayed copy of itself, which comb filters it:
+/// frequencies at odd multiples of `1 / (2 * delay)` cancel, e.g. notches at 50 Hz, 150 Hz, 250 Hz
+/// and so on with a 10 ms delay. Check the mono compatibility of any mix relying upon a **Haas**.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Haas {
+    delay_ms: f64,
+    side: HaasSide,
+    /// The most recent samples of the delayed channel.
+    line: DelayLine,
+}
+
+impl Haas {
+    /// Construct a **Haas** delaying the given channel by the given number of milliseconds.
+    pub fn new(delay_ms: f64, side: HaasSide) -> Self {
+        Haas {
+            delay_ms: delay_ms.clamp(HAAS_MIN_DELAY_MS, HAAS_MAX_DELAY_MS),
+            side,
+            line: DelayLine::default(),
+        }
+    }
+
+    /// The delay in milliseconds.
+    pub fn delay_ms(&self) -> f64 {
+        self.delay_ms
+    }
+
+    /// Set the delay in milliseconds, clamped to `HAAS_MIN_DELAY_MS ... HAAS_MAX_DELAY_MS`.
+    pub fn set_delay_ms(&mut self, delay_ms: f64) {
+        self.delay_ms = delay_ms.clamp(HAAS_MIN_DELAY_MS, HAAS_MAX_DELAY_MS);
+    }
+
+    /// The channel that is delayed.
+    pub fn side(&self) -> HaasSide {
+        self.side
+    }
+
+    /// Set the channel that is delayed.
+    pub fn set_side(&mut self, side: HaasSide) {
+        if side != self.side {
+            self.side = side;
+            self.line.clear();
+        }
+    }
+
+    /// The number of frames by which the delayed channel is delayed at the given sample rate.
+    pub fn delay_frames(&self, sample_hz: f64) -> usize {
+        (self.delay_ms / 1_000.0 * sample_hz).round() as usize
+    }
+
+    /// The parameters of the **Haas**.
+    pub fn params(&self) -> HaasParams {
+        HaasParams {
+            delay_ms: self.delay_ms,
+            side: self.side,
+        }
+    }
+
+    /// Apply the given parameters.
+    pub fn set_params(&mut self, params: HaasParams) {
+        self.set_delay_ms(params.delay_ms);
+        self.set_side(params.side);
+    }
+
+    /// Silence the delayed channel's history.
+    pub fn reset(&mut self) {
+        self.line.clear();
+    }
+}
+
+impl Default for Haas {
+    fn default() -> Self {
+        Haas::new(10.0, HaasSide::Right)
+    }
+}
+
+impl<F> Node<F> for Haas
+where
+    F: Frame,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        let frames = self.delay_frames(sample_hz);
+        if frames == 0 {
+            return;
+        }
+        self.line.resize(frames, 1);
+        let line = &mut self.line;
+        let delay_left = self.side == HaasSide::Left;
+        map_stereo_in_place(buffer, |l, r| {
+            let delayed = line.oldest(0);
+            line.write(0, if delay_left { l } else { r });
+            line.advance();
+            if delay_left {
+                (delayed, r)
+            } else {
+                (l, delayed)
+            }
+        });
+    }
+
+    /// Exposes:
+    ///
+    /// - `delay_ms`: the delay in milliseconds (`HAAS_MIN_DELAY_MS ... HAAS_MAX_DELAY_MS`).
+    /// - `side`: the delayed channel, where `0` is the left and `1` the right, rounded to the
+    ///   nearest.
+    fn param_names(&self) -> &[&str] {
+        &["delay_ms", "side"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "delay_ms" => Some(self.delay_ms as f32),
+            "side" => Some(match self.side {
+                HaasSide::Left => 0.0,
+                HaasSide::Right => 1.0,
+            }),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "delay_ms" => self.set_delay_ms(value as f64),
+            "side" => self.set_side(if value < 0.5 {
+                HaasSide::Left
+            } else {
+                HaasSide::Right
+            }),
+            _ => return false,
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        Haas::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "haas"
+    }
+}
+
+/// The component of a mid/side signal processed by an **MsProcess**.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MsComponent {
+    /// The mid channel `(L + R) / 2`, i.e. what is common to both channels.
+    Mid,
+    /// The side channel `(L - R) / 2`, i.e. the difference between the channels.
+    #[default]
+    Side,
+}
+
+/// A **Node** wrapper that processes only the mid or only the side of a stereo signal.
+///
+/// The input is encoded to mid/side as by **MidSideEncode**, the selected component is rendered
+/// through the wrapped node and the result is decoded back to left/right as by
+/// **MidSideDecode**, leaving the other component untouched. Only the change made by the wrapped
+/// node is decoded and added to the input, so with a wrapped node that leaves its input unchanged
+/// the output is identical to the input, free of the rounding of a round trip through mid/side.
+///
+/// The wrapped node is rendered over a stereo buffer holding the selected component in both
+/// channels, and the average of its two output channels is taken as the processed component, so
+/// any stereo node may be wrapped. If the wrapped node reports latency (see
+/// `Node::latency_samples`), the untouched component is delayed to match, and the **MsProcess**
+/// reports the same latency.
+#[derive(Clone, Debug)]
+pub struct MsProcess<F, N> {
+    node: N,
+    component: MsComponent,
+    /// Aligns the untouched component with the latency of the wrapped node.
+    delay: CompensationDelay,
+    /// The input encoded to mid/side, re-used between calls to `audio_requested`.
+    encoded: Vec<F>,
+    /// The selected component in both channels, re-used between calls to `audio_requested`.
+    buffer: Vec<F>,
+}
+
+impl<F, N> MsProcess<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    /// Wrap the given node, processing the given component through it.
+    pub fn new(node: N, component: MsComponent) -> Self {
+        MsProcess {
+            node,
+            component,
+            delay: CompensationDelay::new(0),
+            encoded: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// The component processed by the wrapped node.
+    pub fn component(&self) -> MsComponent {
+        self.component
+    }
+
+    /// Set the component processed by the wrapped node.
+    pub fn set_component(&mut self, component: MsComponent) {
+        self.component = component;
+    }
+
+    /// A reference to the wrapped **Node**.
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    /// A mutable reference to the wrapped **Node**.
+    pub fn node_mut(&mut self) -> &mut N {
+        &mut self.node
+    }
+
+    /// Consume the wrapper, returning the wrapped **Node**.
+    pub fn into_node(self) -> N {
+        self.node
+    }
+
+    /// Clear the delay of the untouched component and reset the wrapped node.
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.node.reset();
+    }
+}
+
+impl<F, N> Node<F> for MsProcess<F, N>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    fn audio_requested(&mut self, buffer: &mut [F], sample_hz: f64) {
+        if F::n_channels() != 2 {
+            return;
+        }
+        let channel = match self.component {
+            MsComponent::Mid => 0,
+            MsComponent::Side => 1,
+        };
+
+        // Render the selected component through the wrapped node.
+        self.encoded.clear();
+        self.encoded.extend_from_slice(buffer);
+        MidSideEncode.audio_requested(&mut self.encoded, sample_hz);
+        self.buffer.clear();
+        self.buffer.extend(self.encoded.iter().map(|frame| {
+            let s = *frame.channel(channel).expect("a stereo frame");
+            F::from_fn(|_| s)
+        }));
+        self.node.audio_requested(&mut self.buffer, sample_hz);
+
+        // Align the input with the processed component, and the component it was rendered from.
+        let latency = self.node.latency_samples();
+        if self.delay.frames() != latency {
+            self.delay = CompensationDelay::new(latency);
+        }
+        self.delay.audio_requested(buffer, sample_hz);
+        self.encoded.clear();
+        self.encoded.extend_from_slice(buffer);
+        MidSideEncode.audio_requested(&mut self.encoded, sample_hz);
+
+        // Decode only the change made to the component, so that an unchanged component leaves the
+        // input exactly as it was.
+        let sign = match self.component {
+            MsComponent::Mid => 1.0,
+            MsComponent::Side => -1.0,
+        };
+        let iter = self.buffer.iter().zip(&self.encoded);
+        for (frame, (processed, encoded)) in buffer.iter_mut().zip(iter) {
+            let mut channels = processed.channels();
+            let a = util::to_f64(channels.next().expect("the left channel"));
+            let b = util::to_f64(channels.next().expect("the right channel"));
+            let original = util::to_f64(*encoded.channel(channel).expect("a stereo frame"));
+            let change = (a + b) * 0.5 - original;
+            if change != 0.0 {
+                let mut channels = frame.channels();
+                let l = util::to_f64(channels.next().expect("the left channel"));
+                let r = util::to_f64(channels.next().expect("the right channel"));
+                let (l, r) = (l + change, r + sign * change);
+                *frame = F::from_fn(|ch| util::from_f64(if ch == 0 { l } else { r }));
+            }
+        }
+    }
+
+    fn input_channels(&self) -> Option<usize> {
+        Some(2)
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node.latency_samples()
+    }
+
+    /// Exposes:
+    ///
+    /// - `component`: the processed component, where `0` is the mid and `1` the side, rounded to
+    ///   the nearest.
+    fn param_names(&self) -> &[&str] {
+        &["component"]
+    }
+
+    fn get_param(&self, name: &str) -> Option<f32> {
+        match name {
+            "component" => Some(match self.component {
+                MsComponent::Mid => 0.0,
+                MsComponent::Side => 1.0,
+            }),
+            _ => None,
+        }
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "component" => self.set_component(if value < 0.5 {
+                MsComponent::Mid
+            } else {
+                MsComponent::Side
+            }),
+            _ => return false,
+        }
+        true
+    }
+
+    fn prepare(&mut self, settings: Settings) {
+        self.node.prepare(settings);
+        self.delay.reset();
+        for buffer in [&mut self.encoded, &mut self.buffer] {
+            buffer.clear();
+            buffer.reserve(settings.max_frames);
+        }
+    }
+
+    fn set_seed(&mut self, seed: u64) {
+        self.node.set_seed(seed);
+    }
+
+    fn reset(&mut self) {
+        MsProcess::reset(self);
+    }
+
+    fn type_tag(&self) -> &str {
+        "ms_process"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gain::Gain;
+    use crate::noise::RngSource;
+
+    /// A stereo buffer of uncorrelated noise.
+    fn noise(len: usize, seed: u64) -> Vec<[f32; 2]> {
+        let mut rng = RngSource::new(seed);
+        (0..len)
+            .map(|_| {
+                [
+                    rng.next_bipolar() as f32 * 0.5,
+                    rng.next_bipolar() as f32 * 0.5,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mid_side_decode_inverts_encode() {
+        let original = noise(512, 19);
+        let mut buffer = original.clone();
+        MidSideEncode.audio_requested(&mut buffer, 44_100.0);
+        let [l, r] = original[0];
+        assert_eq!(buffer[0], [(l + r) * 0.5, (l - r) * 0.5]);
+        MidSideDecode.audio_requested(&mut buffer, 44_100.0);
+        for (decoded, original) in buffer.iter().zip(&original) {
+            assert!((decoded[0] - original[0]).abs() < 1e-6);
+            assert!((decoded[1] - original[1]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mid_side_leaves_other_channel_counts_unchanged() {
+        let original = [[0.5f32, -0.25, 0.125]; 4];
+        let mut buffer = original;
+        MidSideEncode.audio_requested(&mut buffer, 44_100.0);
+        MidSideEncode.audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, original);
+        let mut mono = [[0.5f32]; 4];
+        MidSideDecode.audio_requested(&mut mono, 44_100.0);
+        assert_eq!(mono, [[0.5]; 4]);
+    }
+
+    #[test]
+    fn zero_width_produces_identical_channels() {
+        let mut buffer = noise(256, 20);
+        let mut width = StereoWidth::new(1.0);
+        width.set_width(0.0);
+        width.audio_requested(&mut buffer, 44_100.0);
+        assert!(buffer.iter().all(|frame| frame[0] == frame[1]));
+    }
+
+    #[test]
+    fn unit_width_is_unchanged_and_greater_widths_scale_the_side() {
+        let original = noise(256, 21);
+        let mut buffer = original.clone();
+        StereoWidth::new(1.0).audio_requested(&mut buffer, 44_100.0);
+        assert_eq!(buffer, original);
+
+        StereoWidth::new(2.0).audio_requested(&mut buffer, 44_100.0);
+        for (wide, original) in buffer.iter().zip(&original) {
+            let mid = (original[0] + original[1]) * 0.5;
+            let side = (original[0] - original[1]) * 0.5;
+            assert!(((wide[0] + wide[1]) * 0.5 - mid).abs() < 1e-6);
+            assert!(((wide[0] - wide[1]) * 0.5 - 2.0 * side).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn width_is_clamped_and_only_acts_on_stereo() {
+        assert_eq!(StereoWidth::new(100.0).width(), MAX_STEREO_WIDTH);
+        assert_eq!(StereoWidth::new(-1.0).width(), 0.0);
+        let mut mono = [[0.5f32]; 4];
+        StereoWidth::new(0.0).audio_requested(&mut mono, 44_100.0);
+        assert_eq!(mono, [[0.5]; 4]);
+    }
+
+    /// The buffer after passing through the **Panner**.
+    fn panned(panner: &mut Panner, input: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        let mut output = input.to_vec();
+        panner.audio_requested(&mut output, 44_100.0);
+        output
+    }
+
+    #[test]
+    fn panning_hard_left_zeroes_the_right_channel() {
+        let input = noise(256, 3);
+        let mut panner = Panner::new(0.0);
+        assert_eq!(panned(&mut panner, &input), input);
+
+        panner.set_pan(-1.0);
+        let output = panned(&mut panner, &input);
+        assert!(output
+            .iter()
+            .zip(&input)
+            .all(|(out, input)| out[0] == input[0] && out[1] == 0.0));
+
+        // Constant power also silences the right channel, but leaves a centred signal 3 dB down
+        // per channel.
+        panner.set_pan_law(PanLaw::ConstantPower);
+        let output = panned(&mut panner, &input);
+        assert!(output
+            .iter()
+            .zip(&input)
+            .all(|(out, input)| out[0] == input[0] && out[1] == 0.0));
+        panner.set_pan(0.0);
+        let output = panned(&mut panner, &input);
+        let expected = ::std::f32::consts::FRAC_1_SQRT_2;
+        assert!(output
+            .iter()
+            .zip(&input)
+            .all(|(out, input)| (out[0] - input[0] * expected).abs() < 1e-6));
+    }
+
+    #[test]
+    fn haas_delays_one_channel_by_the_given_number_of_frames() {
+        // 10 ms at 48 kHz is 480 frames.
+        let mut haas = Haas::new(10.0, HaasSide::Right);
+        assert_eq!(haas.delay_frames(48_000.0), 480);
+        let input = noise(2_000, 3);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(300) {
+            haas.audio_requested(chunk, 48_000.0);
+        }
+        for (i, frame) in output.iter().enumerate() {
+            assert_eq!(frame[0], input[i][0]);
+            let expected = if i < 480 { 0.0 } else { input[i - 480][1] };
+            assert_eq!(frame[1], expected, "frame {}", i);
+        }
+
+        // Delaying the other side swaps the roles of the channels.
+        haas.set_side(HaasSide::Left);
+        haas.set_delay_ms(1.0);
+        let mut output = input.clone();
+        haas.audio_requested(&mut output, 48_000.0);
+        assert!(output[..48].iter().all(|frame| frame[0] == 0.0));
+        for (i, frame) in output.iter().enumerate().skip(48) {
+            assert_eq!(*frame, [input[i - 48][0], input[i][1]]);
+        }
+    }
+
+    #[test]
+    fn haas_delay_is_clamped() {
+        let mut haas = Haas::new(0.0, HaasSide::Right);
+        assert_eq!(haas.delay_ms(), HAAS_MIN_DELAY_MS);
+        assert!(Node::<[f32; 2]>::set_param(&mut haas, "delay_ms", 100.0));
+        assert_eq!(haas.delay_ms(), HAAS_MAX_DELAY_MS);
+    }
+
+    /// The buffer rotated by the given angle in degrees.
+    fn rotated(angle_degrees: f32, input: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        let mut rotate = Rotate::new(angle_degrees);
+        let mut output = input.to_vec();
+        rotate.audio_requested(&mut output, 44_100.0);
+        output
+    }
+
+    #[test]
+    fn no_rotation_is_the_identity() {
+        let input = noise(64, 4);
+        assert_eq!(rotated(0.0, &input), input);
+    }
+
+    #[test]
+    fn rotating_by_45_degrees_spreads_one_channel_equally() {
+        let input: Vec<[f32; 2]> = noise(64, 5).iter().map(|frame| [frame[0], 0.0]).collect();
+        for (out, input) in rotated(45.0, &input).iter().zip(&input) {
+            assert!((out[0] - out[1]).abs() < 1e-6);
+            assert!((out[0] - input[0] * ::std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn rotating_by_90_degrees_swaps_and_inverts_and_preserves_power() {
+        let input = noise(64, 6);
+        for (out, input) in rotated(90.0, &input).iter().zip(&input) {
+            assert!((out[0] + input[1]).abs() < 1e-6 && (out[1] - input[0]).abs() < 1e-6);
+        }
+        for (out, input) in rotated(30.0, &input).iter().zip(&input) {
+            let power = |frame: &[f32; 2]| frame[0] * frame[0] + frame[1] * frame[1];
+            assert!((power(out) - power(input)).abs() < 1e-5);
+        }
+        let mut mono = [[0.5f32]; 4];
+        Rotate::new(45.0).audio_requested(&mut mono, 44_100.0);
+        assert_eq!(mono, [[0.5]; 4]);
+    }
+
+    /// The mid and side of a stereo frame.
+    fn mid_side(frame: [f32; 2]) -> (f32, f32) {
+        ((frame[0] + frame[1]) * 0.5, (frame[0] - frame[1]) * 0.5)
+    }
+
+    #[test]
+    fn processing_only_the_side_leaves_the_mid_untouched() {
+        let input = noise(256, 8);
+        let mut ms = MsProcess::new(Gain::new(2.0), MsComponent::Side);
+        let mut output = input.clone();
+        ms.audio_requested(&mut output, 44_100.0);
+        for (&out, &input) in output.iter().zip(&input) {
+            let ((out_mid, out_side), (mid, side)) = (mid_side(out), mid_side(input));
+            assert!((out_mid - mid).abs() < 1e-6 && (out_side - 2.0 * side).abs() < 1e-6);
+        }
+
+        // Removing the mid leaves only the side, in opposite polarity in each channel.
+        let mut ms = MsProcess::new(Gain::new(0.0), MsComponent::Mid);
+        let mut output = input.clone();
+        ms.audio_requested(&mut output, 44_100.0);
+        for (&out, &input) in output.iter().zip(&input) {
+            let side = mid_side(input).1;
+            assert!((out[0] - side).abs() < 1e-6 && (out[1] + side).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn a_transparent_inner_node_is_an_exact_identity() {
+        let input = noise(256, 9);
+        for &component in &[MsComponent::Mid, MsComponent::Side] {
+            let mut ms = MsProcess::new(Gain::new(1.0), component);
+            let mut output = input.clone();
+            ms.audio_requested(&mut output, 44_100.0);
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn the_untouched_component_is_delayed_by_the_latency_of_the_inner_node() {
+        let input = noise(64, 10);
+        let mut ms = MsProcess::new(CompensationDelay::new(4), MsComponent::Side);
+        assert_eq!(Node::<[f32; 2]>::latency_samples(&ms), 4);
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(16) {
+            ms.audio_requested(chunk, 44_100.0);
+        }
+        assert!(output[..4].iter().all(|&frame| frame == [0.0; 2]));
+        for (out, input) in output[4..].iter().zip(&input) {
+            assert!((out[0] - input[0]).abs() < 1e-6 && (out[1] - input[1]).abs() < 1e-6);
+        }
+    }
+}
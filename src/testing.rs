@@ -0,0 +1,147 @@
+//! Helpers for measuring the response of a **Node** to a synthesized input, e.g. to validate
+//! filters and effects within tests.
+//!
+//! Only available with the `testing` feature enabled, and to the tests of the crate itself.
+
+use crate::node::Node;
+use crate::util;
+use sample::Frame;
+
+/// Feed the node a unit impulse and return its response over `len` frames.
+///
+/// The impulse is a single full scale frame on every channel followed by silence. The node is
+/// rendered via `audio_requested` over a single buffer, starting from its current state, so that
+/// the response of a node holding no state describes its processing exactly.
+pub fn impulse_response<F, N>(node: &mut N, len: usize, sample_hz: f64) -> Vec<F>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    let mut buffer = vec![F::equilibrium(); len];
+    if let Some(first) = buffer.first_mut() {
+        *first = F::from_fn(|_| util::from_f64(1.0));
+    }
+    node.audio_requested(&mut buffer, sample_hz);
+    buffer
+}
+
+/// Feed the node a unit step and return its response over `len` frames.
+///
+/// The step is full scale on every channel for every frame. As with `impulse_response`, the node
+/// is rendered via `audio_requested` over a single buffer, starting from its current state.
+pub fn step_response<F, N>(node: &mut N, len: usize, sample_hz: f64) -> Vec<F>
+where
+    F: Frame,
+    N: Node<F>,
+{
+    let mut buffer = vec![F::from_fn(|_| util::from_f64(1.0)); len];
+    node.audio_requested(&mut buffer, sample_hz);
+    buffer
+}
+
+/// A sine wave of the given frequency and amplitude (as a fraction of full scale) upon every
+/// channel, starting at a phase of zero.
+pub fn sine<F>(len: usize, hz: f64, amp: f64, sample_hz: f64) -> Vec<F>
+where
+    F: Frame,
+{
+    let step = 2.0 * ::std::f64::consts::PI * hz / sample_hz;
+    (0..len)
+        .map(|i| {
+            let s = amp * (step * i as f64).sin();
+            F::from_fn(|_| util::from_f64(s))
+        })
+        .collect()
+}
+
+/// The root mean square level of the buffer (as a fraction of full scale) across every channel.
+///
+/// An empty buffer has a level of `0.0`.
+pub fn rms<F>(buffer: &[F]) -> f64
+where
+    F: Frame,
+{
+    let samples = buffer.len() * F::n_channels();
+    if samples == 0 {
+        return 0.0;
+    }
+    let sum: f64 = buffer
+        .iter()
+        .flat_map(|frame| frame.channels())
+        .map(|s| util::to_f64(s).powi(2))
+        .sum();
+    (sum / samples as f64).sqrt()
+}
+
+/// The complex response of the first channel of the buffer at the given frequency, via a single
+/// bin of a DFT.
+///
+/// Given an impulse response, this is the frequency response of the node at `hz`, from which its
+/// magnitude and phase may be found.
+pub fn response_at<F>(buffer: &[F], hz: f64, sample_hz: f64) -> (f64, f64)
+where
+    F: Frame,
+{
+    let step = -2.0 * ::std::f64::consts::PI * hz / sample_hz;
+    buffer
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(re, im), (i, frame)| {
+            let s = frame.channels().next().map_or(0.0, util::to_f64);
+            let (sin, cos) = (step * i as f64).sin_cos();
+            (re + s * cos, im + s * sin)
+        })
+}
+
+/// The power of the first channel of the buffer at the given frequency, via a single bin of a
+/// DFT, normalised by the length of the buffer.
+///
+/// A sine wave of amplitude `amp` completing a whole number of cycles has a power of
+/// `amp * amp / 4.0` at its own frequency.
+pub fn power_at<F>(buffer: &[F], hz: f64, sample_hz: f64) -> f64
+where
+    F: Frame,
+{
+    let (re, im) = response_at(buffer, hz, sample_hz);
+    (re * re + im * im) / (buffer.len() as f64).powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delay::CompensationDelay;
+
+    #[test]
+    fn the_impulse_response_of_a_pure_delay_is_a_shifted_impulse() {
+        let mut delay = CompensationDelay::new(5);
+        let output: Vec<[f32; 2]> = impulse_response(&mut delay, 16, 44_100.0);
+        for (i, frame) in output.iter().enumerate() {
+            let expected = if i == 5 { 1.0 } else { 0.0 };
+            assert_eq!(*frame, [expected; 2], "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn the_step_response_of_a_pure_delay_is_a_shifted_step() {
+        let mut delay = CompensationDelay::new(3);
+        let output: Vec<[f32; 1]> = step_response(&mut delay, 8, 44_100.0);
+        let expected: Vec<[f32; 1]> = (0..8).map(|i| [if i < 3 { 0.0 } else { 1.0 }]).collect();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn a_sine_has_its_power_at_its_own_frequency() {
+        let tone: Vec<[f32; 1]> = sine(48_000, 1_000.0, 0.5, 48_000.0);
+        assert!((power_at(&tone, 1_000.0, 48_000.0) - 0.0625).abs() < 1e-6);
+        assert!(power_at(&tone, 2_000.0, 48_000.0) < 1e-9);
+    }
+
+    #[test]
+    fn a_delayed_impulse_lags_in_phase() {
+        let mut delay = CompensationDelay::new(1);
+        let output: Vec<[f32; 1]> = impulse_response(&mut delay, 16, 16.0);
+        // A delay of one frame at a quarter of the sample rate lags by a quarter cycle.
+        let (re, im) = response_at(&output, 4.0, 16.0);
+        assert!(re.abs() < 1e-9 && (im + 1.0).abs() < 1e-9, "{} {}", re, im);
+    }
+}
@@ -0,0 +1,212 @@
+//! Helpers shared between the crate's **Node** implementations.
+
+use sample::Sample;
+
+#[cfg(feature = "fast_math")]
+pub(crate) use crate::fast_math::{exp, sin, tanh};
+
+/// `x.sin()`, approximated via `fast_math::sin` when the `fast_math` feature is enabled.
+#[cfg(not(feature = "fast_math"))]
+#[inline]
+pub(crate) fn sin(x: f64) -> f64 {
+    x.sin()
+}
+
+/// `x.tanh()`, approximated via `fast_math::tanh` when the `fast_math` feature is enabled.
+#[cfg(not(feature = "fast_math"))]
+#[inline]
+pub(crate) fn tanh(x: f64) -> f64 {
+    x.tanh()
+}
+
+/// `x.exp()`, approximated via `fast_math::exp` when the `fast_math` feature is enabled.
+#[cfg(not(feature = "fast_math"))]
+#[inline]
+pub(crate) fn exp(x: f64) -> f64 {
+    x.exp()
+}
+
+/// Convert a sample of any format to an `f64` for processing.
+#[inline]
+pub(crate) fn to_f64<S>(s: S) -> f64
+where
+    S: Sample,
+{
+    s.to_float_sample().to_sample()
+}
+
+/// Convert an `f64` produced by processing back to a sample of the given format.
+#[inline]
+pub(crate) fn from_f64<S>(x: f64) -> S
+where
+    S: Sample,
+{
+    <S::Float as Sample>::from_sample(x).to_sample()
+}
+
+/// Magnitudes below this level (roughly -400 dB) are flushed to zero by `flush_denormal`.
+const DENORMAL_THRESHOLD: f64 = 1e-20;
+
+/// Flush values too small to ever be audible to exactly zero.
+///
+/// Feedback paths such as filter and delay line state decay exponentially towards zero and, left
+/// alone, eventually reach the denormal range, where arithmetic is drastically slower on many
+/// CPUs. Flushing the state long before then keeps the cost of a decaying tail constant. A single
+/// comparison, so cheap enough to apply to every sample fed back.
+#[inline]
+pub(crate) fn flush_denormal(x: f64) -> f64 {
+    if x.abs() < DENORMAL_THRESHOLD {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// The largest magnitude (relative to full scale) at which the sum of two samples is certain not
+/// to overflow the sample's `Signed` type, allowing for the imprecision of its `Float` type.
+const EXACT_SUM_LIMIT: f64 = 0.999;
+
+/// Whether `S` is an integer sample format, which must saturate at full scale rather than
+/// exceeding it.
+#[inline]
+pub(crate) fn is_integer<S>() -> bool
+where
+    S: Sample,
+{
+    // A value far below the step of any integer format survives only in floating point formats.
+    to_f64(from_f64::<S>(1e-30)) == 0.0
+}
+
+/// Whether every sample of the format `S` survives conversion to `f64` and back.
+///
+/// This holds for every format except the 32 and 64 bit integer formats, which convert via a
+/// `Float` type too narrow to hold all of their bits.
+pub(crate) fn is_exact_in_f64<S>() -> bool
+where
+    S: Sample,
+{
+    // A sample with the top bit set along with a bit near the bottom of each of the 32 and 64 bit
+    // formats survives only where every bit of the format does.
+    let sample: S = (from_f64::<S>(0.5).to_signed_sample()
+        + from_f64::<S>(0.5f64.powi(31)).to_signed_sample()
+        + from_f64::<S>(0.5f64.powi(63)).to_signed_sample())
+    .to_sample();
+    from_f64::<S>(to_f64(sample)) == sample
+}
+
+/// Convert `x` as a fraction of full scale to the sample format `S`, saturating integer formats at
+/// full scale.
+///
+/// Converting an out of range value to the 24 and 48 bit formats wraps around rather than
+/// saturating, so for those the value is first clamped to the range of the format. Floating point
+/// formats are converted as is.
+#[inline]
+pub(crate) fn from_f64_saturating<S>(x: f64) -> S
+where
+    S: Sample,
+{
+    let wraps = is_integer::<S>() && to_f64(from_f64::<S>(2.0)) > 1.0;
+    if !wraps {
+        return from_f64(x);
+    }
+    // Conversion to an integer format truncates towards zero, so the greatest value below `1.0`
+    // of the format's `Float` type converts to the format's greatest sample.
+    let below_one = 1.0 - f64::EPSILON / 2.0;
+    let max = if to_f64(<S::Float as Sample>::from_sample(below_one)) < 1.0 {
+        below_one
+    } else {
+        1.0 - f64::from(f32::EPSILON) / 2.0
+    };
+    from_f64(x.max(-1.0).min(max))
+}
+
+/// Sum two samples without loss of precision and without integer overflow.
+///
+/// The sum is performed in the sample's `Signed` type, which is exact for every sample format.
+/// Where the sum may reach full scale, integer formats instead sum in `f64` and saturate at full
+/// scale rather than overflowing. For `f32` and `f64` both are plain addition, so floating point
+/// samples are never clipped.
+#[inline]
+pub(crate) fn add_samples<S>(a: S, b: S) -> S
+where
+    S: Sample,
+{
+    let magnitude = to_f64(a).abs() + to_f64(b).abs();
+    if magnitude <= EXACT_SUM_LIMIT {
+        (a.to_signed_sample() + b.to_signed_sample()).to_sample()
+    } else if is_integer::<S>() {
+        from_f64_saturating(to_f64(a) + to_f64(b))
+    } else {
+        (a.to_float_sample() + b.to_float_sample()).to_sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sample::types::{I24, I48};
+
+    #[test]
+    fn integer_formats_are_told_from_floating_point() {
+        assert!(is_integer::<i16>() && is_integer::<I24>() && is_integer::<I48>());
+        assert!(is_integer::<i32>() && is_integer::<u8>());
+        assert!(!is_integer::<f32>() && !is_integer::<f64>());
+    }
+
+    #[test]
+    fn formats_wider_than_their_float_type_are_not_exact() {
+        assert!(is_exact_in_f64::<i16>() && is_exact_in_f64::<I24>() && is_exact_in_f64::<I48>());
+        assert!(is_exact_in_f64::<u8>() && is_exact_in_f64::<f32>() && is_exact_in_f64::<f64>());
+        assert!(!is_exact_in_f64::<i32>() && !is_exact_in_f64::<i64>());
+        assert!(!is_exact_in_f64::<u32>());
+    }
+
+    #[test]
+    fn saturating_conversion_reaches_the_greatest_sample_of_each_format() {
+        assert_eq!(from_f64_saturating::<i16>(1.5), i16::MAX);
+        assert_eq!(from_f64_saturating::<i16>(-1.5), i16::MIN);
+        assert_eq!(from_f64_saturating::<i32>(1.5), i32::MAX);
+        assert_eq!(
+            from_f64_saturating::<I24>(1.0),
+            I24::new(8_388_607).unwrap()
+        );
+        assert_eq!(
+            from_f64_saturating::<I24>(-2.0),
+            I24::new(-8_388_608).unwrap()
+        );
+        assert_eq!(
+            from_f64_saturating::<I48>(1.25),
+            I48::new(140_737_488_355_327).unwrap()
+        );
+        assert_eq!(from_f64_saturating::<f32>(1.5), 1.5);
+    }
+
+    #[test]
+    fn sums_beyond_full_scale_saturate_rather_than_wrap() {
+        let near_max = from_f64::<I24>(0.9);
+        let sum = add_samples(near_max, near_max);
+        assert_eq!(sum, I24::new(8_388_607).unwrap());
+        let near_min = from_f64::<I48>(-0.9);
+        assert_eq!(
+            add_samples(near_min, near_min),
+            I48::new(-140_737_488_355_328).unwrap()
+        );
+        // Floating point sums are never clipped.
+        assert_eq!(add_samples(0.75f32, 0.75), 1.5);
+    }
+
+    #[test]
+    fn sums_are_exact_for_i16_and_f64() {
+        for &(a, b) in &[
+            (1i16, 2i16),
+            (-12_345, 6_789),
+            (16_000, 16_383),
+            (-16_384, -16_384),
+        ] {
+            assert_eq!(add_samples(a, b), a + b);
+        }
+        // An `f32` round trip would lose the smaller addend.
+        assert_eq!(add_samples(0.1f64, 1e-12), 0.1 + 1e-12);
+        assert_eq!(add_samples(0.75f64, 0.5), 1.25);
+    }
+}